@@ -0,0 +1,1016 @@
+use core::convert::TryFrom;
+use core::mem;
+
+use embedded_io_async::{ErrorType, Read, ReadExactError, Seek, SeekFrom, Write};
+
+use crate::boot_sector::{BiosParameterBlock, BootSector};
+use crate::dir::{
+    lfn_checksum, prepare_entry_name, split_path, LfnEntriesGenerator, LongNameBuilder, ShortNameGenerator,
+    SliceReader, SliceWriter,
+};
+use crate::dir_entry::{eq_entry_name, DirEntryData, DirFileEntryData, FileAttributes, DIR_ENTRY_SIZE, SFN_SIZE};
+use crate::error::Error;
+use crate::file::MAX_FILE_SIZE;
+use crate::fs::{FatType, FsInfoSector, FsOptions, FsStatusFlags, LossyOemCpConverter, OemCpConverter};
+use crate::io::{self as sync_io, IoBase};
+use crate::table::{read_fat, write_fat, FatValue, RESERVED_FAT_ENTRIES};
+use crate::time::{DefaultTimeProvider, TimeProvider};
+
+// Size of the boot sector and the FSInfo sector read when mounting
+const BOOT_SECTOR_SIZE: usize = 512;
+
+const ZEROS: [u8; 512] = [0_u8; 512];
+
+/// An asynchronous FAT filesystem object.
+///
+/// `AsyncFileSystem` is an async-only counterpart of `FileSystem` using storage implementing the `Read`, `Write` and
+/// `Seek` traits from the `embedded-io-async` crate. Directories and files opened from it mutably borrow the file
+/// system, so only one of them can be used at a time.
+///
+/// The async API covers a subset of the blocking one: directories can be opened and files can be created, opened,
+/// read, written, seeked and truncated. Directory iteration, removing, renaming and creating directories are not
+/// supported - use `FileSystem` for them.
+///
+/// Only the `read_only`, `update_timestamps`, `case_sensitive`, `distrust_fsinfo`, `oem_cp_converter` and
+/// `time_provider` mount options are used. The FAT is not cached. The volume is marked dirty before the first write
+/// and the flag is cleared by `unmount`.
+///
+/// Async code cannot run on drop so nothing is written back automatically: files must be flushed by
+/// `embedded_io_async::Write::flush` and the file system must be unmounted by `unmount` or flushed by `flush`.
+pub struct AsyncFileSystem<IO, TP = DefaultTimeProvider, OCC = LossyOemCpConverter> {
+    disk: IO,
+    options: FsOptions<TP, OCC>,
+    fat_type: FatType,
+    bpb: BiosParameterBlock,
+    first_data_sector: u32,
+    total_clusters: u32,
+    fs_info: FsInfoSector,
+    mount_status_flags: FsStatusFlags,
+    current_status_flags: FsStatusFlags,
+}
+
+impl<IO: Read + Write + Seek, TP, OCC> AsyncFileSystem<IO, TP, OCC> {
+    /// Creates a new filesystem object instance.
+    ///
+    /// The volume must start at the beginning of `storage`. The position of `storage` does not matter because all
+    /// accesses use absolute offsets.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::CorruptedFileSystem` will be returned if the boot sector and/or the file system information sector
+    ///   contains invalid values.
+    /// * `Error::Io` will be returned if the provided storage object returned an I/O error.
+    pub async fn new(mut storage: IO, options: FsOptions<TP, OCC>) -> Result<Self, Error<IO::Error>> {
+        trace!("AsyncFileSystem::new");
+        let mut buf = [0_u8; BOOT_SECTOR_SIZE];
+        read_exact_at(&mut storage, 0, &mut buf).await?;
+        let boot = BootSector::deserialize(&mut SliceReader::<()>::new(&buf)).map_err(convert_error)?;
+        boot.validate::<()>().map_err(convert_error)?;
+        let bpb = boot.bpb;
+
+        let first_data_sector = bpb.first_data_sector();
+        let total_clusters = bpb.total_clusters();
+        let fat_type = FatType::from_clusters(total_clusters);
+
+        // read FSInfo sector if this is FAT32
+        let mut fs_info = if fat_type == FatType::Fat32 {
+            read_exact_at(&mut storage, bpb.bytes_from_sectors(bpb.fs_info_sector()), &mut buf).await?;
+            FsInfoSector::deserialize(&mut SliceReader::<()>::new(&buf)).map_err(convert_error)?
+        } else {
+            FsInfoSector::default()
+        };
+
+        // if dirty flag is set or user does not trust FSInfo completly ignore free_cluster_count in it
+        if bpb.status_flags().dirty || options.distrust_fs_info {
+            fs_info.free_cluster_count = None;
+        }
+        fs_info.validate_and_fix(total_clusters);
+        let status_flags = bpb.status_flags();
+
+        Ok(Self {
+            disk: storage,
+            options,
+            fat_type,
+            bpb,
+            first_data_sector,
+            total_clusters,
+            fs_info,
+            mount_status_flags: status_flags,
+            current_status_flags: status_flags,
+        })
+    }
+
+    /// Returns a type of File Allocation Table (FAT) used by this filesystem.
+    pub fn fat_type(&self) -> FatType {
+        self.fat_type
+    }
+
+    /// Returns a root directory object allowing for futher penetration of a filesystem structure.
+    pub fn root_dir(&mut self) -> AsyncDir<'_, IO, TP, OCC> {
+        trace!("AsyncFileSystem::root_dir");
+        AsyncDir::new(self, None)
+    }
+
+    /// Flushes file system metadata and the underlying storage without unmounting the volume.
+    ///
+    /// Writes the `FSInfo` sector if it has been modified and flushes the storage object.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub async fn flush(&mut self) -> Result<(), Error<IO::Error>> {
+        if self.options.read_only {
+            return Ok(());
+        }
+        self.flush_fs_info().await?;
+        self.disk.flush().await.map_err(Error::Io)
+    }
+
+    /// Unmounts the filesystem.
+    ///
+    /// Updates the FS Information Sector if needed and clears the volume dirty flag set by writes.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub async fn unmount(self) -> Result<(), Error<IO::Error>> {
+        self.unmount_into_inner().await.map(|_| ())
+    }
+
+    /// Unmounts the filesystem and returns the underlying storage object.
+    ///
+    /// Works like `unmount` but the storage object is returned instead of being dropped, so it can be reused, for
+    /// example to mount the volume again.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub async fn unmount_into_inner(mut self) -> Result<IO, Error<IO::Error>> {
+        if !self.options.read_only {
+            self.flush_fs_info().await?;
+            self.set_dirty_flag(false).await?;
+            self.disk.flush().await.map_err(Error::Io)?;
+        }
+        Ok(self.disk)
+    }
+
+    fn check_writable(&self) -> Result<(), Error<IO::Error>> {
+        if self.options.read_only {
+            Err(Error::ReadOnlyFileSystem)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn cluster_size(&self) -> u32 {
+        self.bpb.cluster_size()
+    }
+
+    fn offset_from_cluster(&self, cluster: u32) -> u64 {
+        self.bpb
+            .bytes_from_sectors(self.first_data_sector + self.bpb.sectors_from_clusters(cluster - RESERVED_FAT_ENTRIES))
+    }
+
+    fn offset_from_fat(&self, fat: u32) -> u64 {
+        self.bpb
+            .bytes_from_sectors(self.bpb.reserved_sectors() + fat * self.bpb.sectors_per_fat())
+    }
+
+    async fn read_at(&mut self, pos: u64, buf: &mut [u8]) -> Result<(), Error<IO::Error>> {
+        read_exact_at(&mut self.disk, pos, buf).await
+    }
+
+    async fn write_at(&mut self, pos: u64, buf: &[u8]) -> Result<(), Error<IO::Error>> {
+        // Mark the volume 'dirty'
+        self.set_dirty_flag(true).await?;
+        self.disk.seek(SeekFrom::Start(pos)).await.map_err(Error::Io)?;
+        self.disk.write_all(buf).await.map_err(Error::Io)
+    }
+
+    async fn set_dirty_flag(&mut self, dirty: bool) -> Result<(), Error<IO::Error>> {
+        // Do not overwrite flags read from BPB on mount
+        let mut flags = self.mount_status_flags;
+        flags.dirty |= dirty;
+        if flags == self.current_status_flags {
+            return Ok(());
+        }
+        // Note: only one field is written to avoid rewriting entire boot-sector
+        let offset = if self.fat_type == FatType::Fat32 { 0x041 } else { 0x025 };
+        let mut boot_sectors = [Some(0), None];
+        if self.fat_type == FatType::Fat32 && self.bpb.backup_boot_sector() != 0 {
+            boot_sectors[1] = Some(self.bpb.bytes_from_sectors(self.bpb.backup_boot_sector()));
+        }
+        for boot_sector_pos in boot_sectors.iter().flatten() {
+            self.disk
+                .seek(SeekFrom::Start(boot_sector_pos + offset))
+                .await
+                .map_err(Error::Io)?;
+            self.disk.write_all(&[flags.encode()]).await.map_err(Error::Io)?;
+        }
+        self.current_status_flags = flags;
+        Ok(())
+    }
+
+    async fn flush_fs_info(&mut self) -> Result<(), Error<IO::Error>> {
+        if self.fat_type == FatType::Fat32 && self.fs_info.dirty {
+            let mut buf = [0_u8; BOOT_SECTOR_SIZE];
+            self.fs_info
+                .serialize(&mut SliceWriter::<Error<()>>::new(&mut buf))
+                .map_err(convert_error)?;
+            self.write_at(self.bpb.bytes_from_sectors(self.bpb.fs_info_sector()), &buf)
+                .await?;
+            self.fs_info.dirty = false;
+        }
+        Ok(())
+    }
+
+    async fn read_fat_entry(&mut self, cluster: u32) -> Result<FatValue, Error<IO::Error>> {
+        let mut entry = FatEntryBuf::new(self.fat_type, cluster);
+        let fat_offset = self.offset_from_fat(u32::from(self.bpb.active_fat()));
+        self.read_at(fat_offset + entry.offset, entry.as_mut_slice()).await?;
+        read_fat::<_, ()>(&mut entry, self.fat_type, cluster).map_err(convert_error)
+    }
+
+    async fn write_fat_entry(&mut self, cluster: u32, value: FatValue) -> Result<(), Error<IO::Error>> {
+        // FAT12 entries share bytes with their neighbours so the old bytes are needed to encode the entry
+        let mut entry = FatEntryBuf::new(self.fat_type, cluster);
+        let active_fat = u32::from(self.bpb.active_fat());
+        self.read_at(self.offset_from_fat(active_fat) + entry.offset, entry.as_mut_slice())
+            .await?;
+        write_fat::<_, ()>(&mut entry, self.fat_type, cluster, value).map_err(convert_error)?;
+        // every write is mirrored to each FAT copy unless mirroring is disabled
+        let fats = if self.bpb.mirroring_enabled() {
+            0..u32::from(self.bpb.fats)
+        } else {
+            active_fat..active_fat + 1
+        };
+        for fat in fats {
+            self.write_at(self.offset_from_fat(fat) + entry.offset, entry.as_slice())
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn next_cluster(&mut self, cluster: u32) -> Result<Option<u32>, Error<IO::Error>> {
+        match self.read_fat_entry(cluster).await? {
+            FatValue::Data(n) if n < RESERVED_FAT_ENTRIES || n >= self.total_clusters + RESERVED_FAT_ENTRIES => {
+                error!("Invalid cluster number {} in FAT", n);
+                Err(Error::CorruptedFileSystem)
+            }
+            FatValue::Data(n) => Ok(Some(n)),
+            _ => Ok(None),
+        }
+    }
+
+    async fn find_free_cluster(
+        &mut self,
+        start_cluster: u32,
+        end_cluster: u32,
+    ) -> Result<Option<u32>, Error<IO::Error>> {
+        for cluster in start_cluster..end_cluster {
+            if self.read_fat_entry(cluster).await? == FatValue::Free {
+                return Ok(Some(cluster));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn alloc_cluster(&mut self, prev_cluster: Option<u32>, zero: bool) -> Result<u32, Error<IO::Error>> {
+        trace!("alloc_cluster");
+        let end_cluster = self.total_clusters + RESERVED_FAT_ENTRIES;
+        let start_cluster = match self.fs_info.next_free_cluster {
+            Some(n) if n < end_cluster => n,
+            _ => RESERVED_FAT_ENTRIES,
+        };
+        let cluster = match self.find_free_cluster(start_cluster, end_cluster).await? {
+            Some(n) => n,
+            None => match self.find_free_cluster(RESERVED_FAT_ENTRIES, start_cluster).await? {
+                Some(n) => n,
+                None => return Err(Error::NotEnoughSpace),
+            },
+        };
+        self.write_fat_entry(cluster, FatValue::EndOfChain).await?;
+        if let Some(n) = prev_cluster {
+            self.write_fat_entry(n, FatValue::Data(cluster)).await?;
+        }
+        trace!("allocated cluster {}", cluster);
+        if zero {
+            let offset = self.offset_from_cluster(cluster);
+            let cluster_size = u64::from(self.cluster_size());
+            let mut written = 0;
+            while written < cluster_size {
+                let write_size = (cluster_size - written).min(ZEROS.len() as u64);
+                self.write_at(offset + written, &ZEROS[..write_size as usize]).await?;
+                written += write_size;
+            }
+        }
+        self.fs_info.set_next_free_cluster(cluster + 1);
+        self.fs_info.map_free_clusters(|n| n - 1);
+        Ok(cluster)
+    }
+
+    async fn free_cluster_chain(&mut self, cluster: u32) -> Result<(), Error<IO::Error>> {
+        let mut cluster_opt = Some(cluster);
+        let mut num_free = 0;
+        while let Some(n) = cluster_opt {
+            if num_free >= self.total_clusters {
+                error!("Loop detected in cluster chain");
+                return Err(Error::CorruptedFileSystem);
+            }
+            cluster_opt = self.next_cluster(n).await?;
+            self.write_fat_entry(n, FatValue::Free).await?;
+            num_free += 1;
+        }
+        self.fs_info.map_free_clusters(|n| n + num_free);
+        Ok(())
+    }
+
+    // Returns the cluster with the given index in the chain, optionally appending new clusters to the chain
+    async fn chain_cluster(
+        &mut self,
+        chain: &mut ClusterChain,
+        index: u32,
+        extend: bool,
+        zero: bool,
+    ) -> Result<Option<u32>, Error<IO::Error>> {
+        // walk from the last used cluster if possible instead of the first one
+        let (mut i, mut cluster) = match (chain.cached, chain.first) {
+            (Some((i, cluster)), _) if i <= index => (i, cluster),
+            (_, Some(first)) => (0, first),
+            (_, None) if extend => {
+                let first = self.alloc_cluster(None, zero).await?;
+                chain.first = Some(first);
+                (0, first)
+            }
+            (_, None) => return Ok(None),
+        };
+        while i < index {
+            cluster = match self.next_cluster(cluster).await? {
+                Some(n) => n,
+                None if extend => self.alloc_cluster(Some(cluster), zero).await?,
+                None => return Ok(None),
+            };
+            i += 1;
+            // a chain cannot be longer than the number of clusters so it must contain a loop
+            if i >= self.total_clusters {
+                error!("Loop detected in cluster chain");
+                return Err(Error::CorruptedFileSystem);
+            }
+        }
+        chain.cached = Some((i, cluster));
+        Ok(Some(cluster))
+    }
+}
+
+// Position in a cluster chain of a file or a directory
+#[derive(Clone, Copy, Debug)]
+struct ClusterChain {
+    first: Option<u32>,
+    // index and number of the last cluster used in the chain
+    cached: Option<(u32, u32)>,
+}
+
+impl ClusterChain {
+    fn new(first: Option<u32>) -> Self {
+        Self { first, cached: None }
+    }
+}
+
+/// An asynchronous FAT filesystem directory.
+///
+/// This struct is created by the `open_dir` method on `AsyncDir`. The root directory is returned by the `root_dir`
+/// method on `AsyncFileSystem`. Path components in arguments of `AsyncDir` methods can be separated by `/` or `\`.
+pub struct AsyncDir<'a, IO, TP, OCC> {
+    fs: &'a mut AsyncFileSystem<IO, TP, OCC>,
+    // `None` for the fixed-size root directory of a FAT12/FAT16 volume
+    chain: Option<ClusterChain>,
+}
+
+impl<'a, IO: Read + Write + Seek, TP, OCC> AsyncDir<'a, IO, TP, OCC> {
+    fn new(fs: &'a mut AsyncFileSystem<IO, TP, OCC>, first_cluster: Option<u32>) -> Self {
+        // a directory without clusters is the root directory (the parent of a root subdirectory has cluster 0)
+        let chain = match first_cluster {
+            Some(n) => Some(ClusterChain::new(Some(n))),
+            None if fs.fat_type == FatType::Fat32 => Some(ClusterChain::new(Some(fs.bpb.root_dir_first_cluster))),
+            None => None,
+        };
+        Self { fs, chain }
+    }
+
+    // Returns the position on the storage of the entry with the given index, optionally extending the directory
+    async fn entry_pos(&mut self, index: u32, extend: bool) -> Result<Option<u64>, Error<IO::Error>> {
+        let Some(offset) = index.checked_mul(DIR_ENTRY_SIZE) else {
+            return Ok(None);
+        };
+        let Some(chain) = &mut self.chain else {
+            // root directory on FAT12/FAT16 has a fixed size and cannot be extended
+            if index >= u32::from(self.fs.bpb.root_entries) {
+                return Ok(None);
+            }
+            let root_dir_sector = self.fs.bpb.reserved_sectors() + self.fs.bpb.sectors_per_all_fats();
+            return Ok(Some(
+                self.fs.bpb.bytes_from_sectors(root_dir_sector) + u64::from(offset),
+            ));
+        };
+        let cluster_size = self.fs.cluster_size();
+        let cluster_opt = self
+            .fs
+            .chain_cluster(chain, offset / cluster_size, extend, true)
+            .await?;
+        Ok(cluster_opt.map(|n| self.fs.offset_from_cluster(n) + u64::from(offset % cluster_size)))
+    }
+
+    async fn read_entry(&mut self, pos: u64) -> Result<DirEntryData, Error<IO::Error>> {
+        let mut buf = [0_u8; DIR_ENTRY_SIZE as usize];
+        self.fs.read_at(pos, &mut buf).await?;
+        DirEntryData::deserialize(&mut SliceReader::<()>::new(&buf)).map_err(convert_error)
+    }
+
+    // Opens the subdirectory `name` consuming `self` so the returned directory borrows the file system for `'a`
+    async fn into_subdir(mut self, name: &str) -> Result<Self, Error<IO::Error>>
+    where
+        OCC: OemCpConverter,
+    {
+        let Some((data, _)) = self.find_entry(name, None).await? else {
+            return Err(Error::NotFound);
+        };
+        if !data.is_dir() {
+            error!("Not a directory");
+            return Err(Error::InvalidInput);
+        }
+        let first_cluster = data.first_cluster(self.fs.fat_type);
+        Ok(Self::new(self.fs, first_cluster))
+    }
+
+    // Returns the directory containing the last component of `path` and the component itself
+    async fn open_parent<'p>(&mut self, path: &'p str) -> Result<(AsyncDir<'_, IO, TP, OCC>, &'p str), Error<IO::Error>>
+    where
+        OCC: OemCpConverter,
+    {
+        let mut dir = AsyncDir {
+            fs: &mut *self.fs,
+            chain: self.chain,
+        };
+        let (mut name, mut rest_opt) = split_path(path);
+        while let Some(rest) = rest_opt {
+            dir = dir.into_subdir(name).await?;
+            (name, rest_opt) = split_path(rest);
+        }
+        Ok((dir, name))
+    }
+
+    // Looks for an entry named `name` passing short names of other entries to the short name generator
+    async fn find_entry(
+        &mut self,
+        name: &str,
+        mut short_name_gen: Option<&mut ShortNameGenerator>,
+    ) -> Result<Option<(DirFileEntryData, u64)>, Error<IO::Error>>
+    where
+        OCC: OemCpConverter,
+    {
+        let mut lfn_builder = LongNameBuilder::new();
+        let mut index = 0;
+        while let Some(pos) = self.entry_pos(index, false).await? {
+            index += 1;
+            let raw_entry = self.read_entry(pos).await?;
+            if raw_entry.is_end() {
+                break;
+            }
+            if raw_entry.is_deleted() {
+                lfn_builder.clear();
+                continue;
+            }
+            let data = match raw_entry {
+                DirEntryData::File(data) => data,
+                DirEntryData::Lfn(data) => {
+                    lfn_builder.process(&data);
+                    continue;
+                }
+            };
+            if data.is_volume() {
+                lfn_builder.clear();
+                continue;
+            }
+            // Check if LFN checksum is valid
+            lfn_builder.validate_chksum(data.name());
+            #[cfg(feature = "lfn")]
+            let lfn_utf16 = mem::replace(&mut lfn_builder, LongNameBuilder::new()).into_buf();
+            #[cfg(feature = "lfn")]
+            let lfn = Some(lfn_utf16.as_ucs2_units()).filter(|units| !units.is_empty());
+            #[cfg(not(feature = "lfn"))]
+            let lfn = None;
+            let options = &self.fs.options;
+            if eq_entry_name(&data, lfn, name, options.case_sensitive, &options.oem_cp_converter) {
+                return Ok(Some((data, pos)));
+            }
+            // update short name generator state
+            if let Some(gen) = short_name_gen.as_deref_mut() {
+                gen.add_existing(data.name());
+            }
+        }
+        Ok(None)
+    }
+
+    // Returns index of the first entry in a run of `num_entries` free entries
+    async fn find_free_entries(&mut self, num_entries: u32) -> Result<u32, Error<IO::Error>> {
+        let mut first_free: u32 = 0;
+        let mut num_free: u32 = 0;
+        let mut index: u32 = 0;
+        loop {
+            let Some(pos) = self.entry_pos(index, false).await? else {
+                // end of the directory - it is extended when entries are written
+                if self.chain.is_none() {
+                    return Err(Error::NotEnoughSpace);
+                }
+                return Ok(if num_free == 0 { index } else { first_free });
+            };
+            let raw_entry = self.read_entry(pos).await?;
+            if raw_entry.is_end() {
+                // first unused entry - all remaining space can be used
+                let first = if num_free == 0 { index } else { first_free };
+                if self.chain.is_none() && first + num_entries > u32::from(self.fs.bpb.root_entries) {
+                    return Err(Error::NotEnoughSpace);
+                }
+                return Ok(first);
+            } else if raw_entry.is_deleted() {
+                // free entry - calculate number of free entries in a row
+                if num_free == 0 {
+                    first_free = index;
+                }
+                num_free += 1;
+                if num_free == num_entries {
+                    return Ok(first_free);
+                }
+            } else {
+                // used entry - start counting from 0
+                num_free = 0;
+            }
+            index += 1;
+        }
+    }
+
+    // Writes LFN entries and a short name entry of a new file and returns the short name entry and its position
+    async fn write_entry(
+        &mut self,
+        name: &str,
+        short_name: [u8; SFN_SIZE],
+    ) -> Result<(DirFileEntryData, u64), Error<IO::Error>>
+    where
+        TP: TimeProvider,
+        OCC: OemCpConverter,
+    {
+        trace!("AsyncDir::write_entry {}", name);
+        let mut raw_entry = DirFileEntryData::new(short_name, FileAttributes::from_bits_truncate(0));
+        let now = self.fs.options.time_provider.get_current_date_time();
+        raw_entry.set_created(now);
+        raw_entry.set_accessed(now.date);
+        raw_entry.set_modified(now);
+        let lfn_utf16 = prepare_entry_name::<(), _>(name, &mut raw_entry, &self.fs.options.oem_cp_converter)
+            .map_err(convert_error)?;
+        let lfn_iter = LfnEntriesGenerator::new(lfn_utf16.as_ucs2_units(), lfn_checksum(raw_entry.name()));
+        // find space for new entries (multiple LFN entries and 1 SFN entry)
+        let num_entries = lfn_iter.len() as u32 + 1;
+        let first_index = self.find_free_entries(num_entries).await?;
+        let mut buf = [0_u8; DIR_ENTRY_SIZE as usize];
+        // write LFN entries before SFN entry
+        for (i, lfn_entry) in (first_index..).zip(lfn_iter) {
+            lfn_entry
+                .serialize(&mut SliceWriter::<Error<()>>::new(&mut buf))
+                .map_err(convert_error)?;
+            let Some(pos) = self.entry_pos(i, true).await? else {
+                return Err(Error::NotEnoughSpace);
+            };
+            self.fs.write_at(pos, &buf).await?;
+        }
+        raw_entry
+            .serialize(&mut SliceWriter::<Error<()>>::new(&mut buf))
+            .map_err(convert_error)?;
+        let Some(pos) = self.entry_pos(first_index + num_entries - 1, true).await? else {
+            return Err(Error::NotEnoughSpace);
+        };
+        self.fs.write_at(pos, &buf).await?;
+        Ok((raw_entry, pos))
+    }
+}
+
+impl<IO: Read + Write + Seek, TP: TimeProvider, OCC: OemCpConverter> AsyncDir<'_, IO, TP, OCC> {
+    /// Opens existing subdirectory.
+    ///
+    /// `path` is a '/' separated directory path relative to self directory.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::NotFound` will be returned if `path` does not point to any existing directory entry.
+    /// * `Error::InvalidInput` will be returned if `path` points to a file that is not a directory.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub async fn open_dir(&mut self, path: &str) -> Result<AsyncDir<'_, IO, TP, OCC>, Error<IO::Error>> {
+        trace!("AsyncDir::open_dir {}", path);
+        let (dir, name) = self.open_parent(path).await?;
+        dir.into_subdir(name).await
+    }
+
+    /// Opens existing file.
+    ///
+    /// `path` is a '/' separated file path relative to self directory.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::NotFound` will be returned if `path` points to a non-existing directory entry.
+    /// * `Error::InvalidInput` will be returned if `path` points to a file that is a directory.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub async fn open_file(&mut self, path: &str) -> Result<AsyncFile<'_, IO, TP, OCC>, Error<IO::Error>> {
+        trace!("AsyncDir::open_file {}", path);
+        let (mut dir, name) = self.open_parent(path).await?;
+        let Some((data, pos)) = dir.find_entry(name, None).await? else {
+            return Err(Error::NotFound);
+        };
+        if data.is_dir() {
+            error!("Is a directory");
+            return Err(Error::InvalidInput);
+        }
+        Ok(AsyncFile::new(dir.fs, data, pos))
+    }
+
+    /// Creates new or opens existing file.
+    ///
+    /// `path` is a '/' separated file path relative to `self` directory. File is never truncated when opening. It
+    /// can be achieved by calling `AsyncFile::truncate` method after opening.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if `path` points to an existing file that is a directory.
+    /// * `Error::InvalidFileNameLength` will be returned if the file name is empty or if it is too long.
+    /// * `Error::UnsupportedFileNameCharacter` will be returned if the file name contains an invalid character.
+    /// * `Error::NotEnoughSpace` will be returned if there is not enough free space to create a new file or if
+    ///   there are not enough free entries in the fixed-size root directory of a FAT12/FAT16 volume.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub async fn create_file(&mut self, path: &str) -> Result<AsyncFile<'_, IO, TP, OCC>, Error<IO::Error>> {
+        trace!("AsyncDir::create_file {}", path);
+        self.fs.check_writable()?;
+        let (mut dir, name) = self.open_parent(path).await?;
+        let mut short_name_gen = ShortNameGenerator::new(name);
+        let short_name = loop {
+            // find matching entry
+            if let Some((data, pos)) = dir.find_entry(name, Some(&mut short_name_gen)).await? {
+                if data.is_dir() {
+                    error!("Is a directory");
+                    return Err(Error::InvalidInput);
+                }
+                return Ok(AsyncFile::new(dir.fs, data, pos));
+            }
+            // try to generate short name
+            if let Ok(short_name) = short_name_gen.generate() {
+                break short_name;
+            }
+            // there were too many collisions in short name generation
+            // try different checksum in the next iteration
+            short_name_gen.next_iteration();
+        };
+        let (data, pos) = dir.write_entry(name, short_name).await?;
+        Ok(AsyncFile::new(dir.fs, data, pos))
+    }
+}
+
+/// An asynchronous FAT filesystem file object used for reading and writing data.
+///
+/// This struct is created by the `open_file` or `create_file` methods on `AsyncDir`. Data is accessed using the
+/// `Read`, `Write` and `Seek` traits from the `embedded-io-async` crate.
+///
+/// Seeking beyond the end of the file is allowed. The gap between the end of the file and the new position is
+/// filled with zeros when data is written.
+///
+/// The directory entry of the file is updated only by `embedded_io_async::Write::flush` - it is not updated when
+/// the file is dropped.
+pub struct AsyncFile<'a, IO, TP, OCC> {
+    fs: &'a mut AsyncFileSystem<IO, TP, OCC>,
+    entry: DirFileEntryData,
+    // position of the short name entry on the storage
+    entry_pos: u64,
+    entry_dirty: bool,
+    chain: ClusterChain,
+    offset: u32,
+}
+
+impl<'a, IO: Read + Write + Seek, TP, OCC> AsyncFile<'a, IO, TP, OCC> {
+    fn new(fs: &'a mut AsyncFileSystem<IO, TP, OCC>, entry: DirFileEntryData, entry_pos: u64) -> Self {
+        let chain = ClusterChain::new(entry.first_cluster(fs.fat_type));
+        Self {
+            fs,
+            entry,
+            entry_pos,
+            entry_dirty: false,
+            chain,
+            offset: 0,
+        }
+    }
+
+    fn size(&self) -> u32 {
+        self.entry.size().unwrap_or(0)
+    }
+
+    fn check_modifiable(&self) -> Result<(), Error<IO::Error>> {
+        self.fs.check_writable()?;
+        if self.entry.is_read_only() {
+            error!("File has the read-only attribute");
+            return Err(Error::PermissionDenied);
+        }
+        Ok(())
+    }
+
+    fn set_first_cluster(&mut self, cluster: Option<u32>) {
+        self.entry.set_first_cluster(cluster, self.fs.fat_type);
+        self.entry_dirty = true;
+    }
+
+    fn update_dir_entry_after_write(&mut self, end: u32)
+    where
+        TP: TimeProvider,
+    {
+        if !self.fs.options.preserve_timestamps {
+            let now = self.fs.options.time_provider.get_current_date_time();
+            self.entry.set_modified(now);
+        }
+        if end > self.size() {
+            self.entry.set_size(end);
+        }
+        self.entry_dirty = true;
+    }
+
+    /// Truncates file size to the current position.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::PermissionDenied` will be returned if the file has the read-only attribute.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub async fn truncate(&mut self) -> Result<(), Error<IO::Error>>
+    where
+        TP: TimeProvider,
+    {
+        trace!("AsyncFile::truncate");
+        self.check_modifiable()?;
+        if self.offset >= self.size() {
+            return Ok(());
+        }
+        if self.offset == 0 {
+            if let Some(first_cluster) = self.chain.first {
+                self.fs.free_cluster_chain(first_cluster).await?;
+                self.chain = ClusterChain::new(None);
+                self.set_first_cluster(None);
+            }
+        } else {
+            let last_cluster_index = (self.offset - 1) / self.fs.cluster_size();
+            if let Some(last_cluster) = self
+                .fs
+                .chain_cluster(&mut self.chain, last_cluster_index, false, false)
+                .await?
+            {
+                if let Some(next_cluster) = self.fs.next_cluster(last_cluster).await? {
+                    self.fs.write_fat_entry(last_cluster, FatValue::EndOfChain).await?;
+                    self.fs.free_cluster_chain(next_cluster).await?;
+                }
+            }
+        }
+        self.entry.set_size(self.offset);
+        self.update_dir_entry_after_write(self.offset);
+        Ok(())
+    }
+
+    // Writes data at the given offset without crossing a cluster boundary
+    async fn write_at(&mut self, offset: u32, buf: &[u8]) -> Result<usize, Error<IO::Error>>
+    where
+        TP: TimeProvider,
+    {
+        let cluster_size = self.fs.cluster_size();
+        let offset_in_cluster = offset % cluster_size;
+        let bytes_left_in_cluster = (cluster_size - offset_in_cluster) as usize;
+        let bytes_left_until_max_file_size = (MAX_FILE_SIZE - offset) as usize;
+        let write_size = buf.len().min(bytes_left_in_cluster).min(bytes_left_until_max_file_size);
+        if write_size == 0 {
+            error!("Maximal file size exceeded");
+            return Err(Error::FileTooLarge);
+        }
+        // Get cluster for write possibly allocating new one
+        let Some(cluster) = self
+            .fs
+            .chain_cluster(&mut self.chain, offset / cluster_size, true, false)
+            .await?
+        else {
+            return Err(Error::NotEnoughSpace);
+        };
+        if self.entry.first_cluster(self.fs.fat_type) != self.chain.first {
+            self.set_first_cluster(self.chain.first);
+        }
+        trace!("write {} bytes in cluster {}", write_size, cluster);
+        let offset_in_fs = self.fs.offset_from_cluster(cluster) + u64::from(offset_in_cluster);
+        self.fs.write_at(offset_in_fs, &buf[..write_size]).await?;
+        self.update_dir_entry_after_write(offset + write_size as u32);
+        Ok(write_size)
+    }
+}
+
+impl<IO: Read + Write + Seek, TP, OCC> ErrorType for AsyncFile<'_, IO, TP, OCC> {
+    type Error = Error<IO::Error>;
+}
+
+impl<IO: Read + Write + Seek, TP, OCC> Read for AsyncFile<'_, IO, TP, OCC> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        trace!("AsyncFile::read");
+        let size = self.size();
+        if self.offset >= size || buf.is_empty() {
+            return Ok(0);
+        }
+        let cluster_size = self.fs.cluster_size();
+        let Some(cluster) = self
+            .fs
+            .chain_cluster(&mut self.chain, self.offset / cluster_size, false, false)
+            .await?
+        else {
+            // cluster chain is shorter than the file size
+            return Ok(0);
+        };
+        let offset_in_cluster = self.offset % cluster_size;
+        let bytes_left_in_cluster = (cluster_size - offset_in_cluster) as usize;
+        let bytes_left_in_file = (size - self.offset) as usize;
+        let read_size = buf.len().min(bytes_left_in_cluster).min(bytes_left_in_file);
+        trace!("read {} bytes in cluster {}", read_size, cluster);
+        let offset_in_fs = self.fs.offset_from_cluster(cluster) + u64::from(offset_in_cluster);
+        self.fs.read_at(offset_in_fs, &mut buf[..read_size]).await?;
+        self.offset += read_size as u32;
+        Ok(read_size)
+    }
+}
+
+impl<IO: Read + Write + Seek, TP: TimeProvider, OCC> Write for AsyncFile<'_, IO, TP, OCC> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        trace!("AsyncFile::write");
+        self.check_modifiable()?;
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        // fill the gap between the end of the file and the position set by seeking beyond it with zeros
+        let mut size = self.size();
+        while size < self.offset {
+            let gap_size = ((self.offset - size) as usize).min(ZEROS.len());
+            size += self.write_at(size, &ZEROS[..gap_size]).await? as u32;
+        }
+        let written_bytes = self.write_at(self.offset, buf).await?;
+        self.offset += written_bytes as u32;
+        Ok(written_bytes)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        if self.entry_dirty {
+            let mut buf = [0_u8; DIR_ENTRY_SIZE as usize];
+            self.entry
+                .serialize(&mut SliceWriter::<Error<()>>::new(&mut buf))
+                .map_err(convert_error)?;
+            self.fs.write_at(self.entry_pos, &buf).await?;
+            self.entry_dirty = false;
+        }
+        self.fs.disk.flush().await.map_err(Error::Io)
+    }
+}
+
+impl<IO: Read + Write + Seek, TP, OCC> Seek for AsyncFile<'_, IO, TP, OCC> {
+    async fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        trace!("AsyncFile::seek");
+        let new_offset_opt: Option<u32> = match pos {
+            SeekFrom::Current(x) => i64::from(self.offset)
+                .checked_add(x)
+                .and_then(|n| u32::try_from(n).ok()),
+            SeekFrom::Start(x) => u32::try_from(x).ok(),
+            SeekFrom::End(o) => i64::from(self.size())
+                .checked_add(o)
+                .and_then(|n| u32::try_from(n).ok()),
+        };
+        let Some(new_offset) = new_offset_opt else {
+            error!("Invalid seek offset");
+            return Err(Error::InvalidInput);
+        };
+        self.offset = new_offset;
+        Ok(u64::from(new_offset))
+    }
+}
+
+async fn read_exact_at<IO: Read + Seek>(disk: &mut IO, pos: u64, buf: &mut [u8]) -> Result<(), Error<IO::Error>> {
+    disk.seek(SeekFrom::Start(pos)).await.map_err(Error::Io)?;
+    disk.read_exact(buf).await.map_err(|err| match err {
+        ReadExactError::UnexpectedEof => Error::UnexpectedEof,
+        ReadExactError::Other(err) => Error::Io(err),
+    })
+}
+
+// Converts an error returned by the code shared with the sync API when it operates on in-memory buffers
+#[allow(clippy::needless_pass_by_value)]
+fn convert_error<T, E>(err: Error<T>) -> Error<E> {
+    match err {
+        // in-memory buffers never return I/O errors
+        Error::Io(_) => unreachable!(),
+        Error::UnexpectedEof => Error::UnexpectedEof,
+        Error::WriteZero => Error::WriteZero,
+        Error::InvalidInput => Error::InvalidInput,
+        Error::NotFound => Error::NotFound,
+        Error::AlreadyExists => Error::AlreadyExists,
+        Error::DirectoryIsNotEmpty => Error::DirectoryIsNotEmpty,
+        Error::CorruptedFileSystem => Error::CorruptedFileSystem,
+        Error::NotEnoughSpace => Error::NotEnoughSpace,
+        Error::InvalidFileNameLength => Error::InvalidFileNameLength,
+        Error::UnsupportedFileNameCharacter => Error::UnsupportedFileNameCharacter,
+        Error::ReadOnlyFileSystem => Error::ReadOnlyFileSystem,
+        Error::PermissionDenied => Error::PermissionDenied,
+        Error::VolumeInUse => Error::VolumeInUse,
+        Error::FileTooLarge => Error::FileTooLarge,
+    }
+}
+
+// Bytes of the FAT containing a single entry, so entries can be encoded by the code shared with the sync API
+struct FatEntryBuf {
+    // offset of the first byte in the FAT
+    offset: u64,
+    buf: [u8; 4],
+    len: usize,
+    pos: u64,
+}
+
+impl FatEntryBuf {
+    fn new(fat_type: FatType, cluster: u32) -> Self {
+        let (offset, len) = match fat_type {
+            FatType::Fat12 => (cluster + cluster / 2, 2),
+            FatType::Fat16 => (cluster * 2, 2),
+            FatType::Fat32 => (cluster * 4, 4),
+        };
+        Self {
+            offset: u64::from(offset),
+            buf: [0; 4],
+            len,
+            pos: 0,
+        }
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.buf[..self.len]
+    }
+
+    // Returns the part of the buffer starting at the current position
+    fn remaining(&mut self) -> Result<&mut [u8], ()> {
+        let start = self.pos.checked_sub(self.offset).ok_or(())?;
+        let len = self.len;
+        self.buf[..len].get_mut(start as usize..).ok_or(())
+    }
+}
+
+impl IoBase for FatEntryBuf {
+    type Error = ();
+}
+
+impl sync_io::Read for FatEntryBuf {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let src = self.remaining()?;
+        let n = buf.len().min(src.len());
+        buf[..n].copy_from_slice(&src[..n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl sync_io::Write for FatEntryBuf {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let dst = self.remaining()?;
+        let n = buf.len().min(dst.len());
+        dst[..n].copy_from_slice(&buf[..n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl sync_io::Seek for FatEntryBuf {
+    fn seek(&mut self, pos: sync_io::SeekFrom) -> Result<u64, Self::Error> {
+        match pos {
+            sync_io::SeekFrom::Start(n) => {
+                self.pos = n;
+                Ok(n)
+            }
+            _ => Err(()),
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use core::borrow::BorrowMut;
+use core::marker::PhantomData;
+
+use crate::error::IoError;
+use crate::io::{IoBase, Read, Seek, SeekFrom, Write};
+
+/// Maximal supported sector size.
+const MAX_SECTOR_SIZE: usize = 4096;
+
+// Returns the inner storage of `SectorBuffer` - a free function so other fields can be borrowed at the same time
+fn storage<B: BorrowMut<S>, S>(inner: &mut Option<B>) -> &mut S {
+    // Unwrapping is safe because the storage is taken out only when the adapter is consumed
+    inner.as_mut().unwrap().borrow_mut()
+}
+
+/// A sector-aligned IO adapter for block devices.
+///
+/// `SectorBuffer` works like a buffered stream (e.g. `fscommon::BufStream`) but with sector granularity: the inner
+/// storage only ever receives reads and writes of whole sectors at sector-aligned offsets. This makes it possible to
+/// use the filesystem directly on top of block devices (e.g. SD cards or raw disk handles) that reject unaligned
+/// access.
+///
+/// Sector-aligned requests covering whole sectors are passed directly to the inner storage. Partial sector writes
+/// are handled by a read-modify-write of a single buffered sector. The buffered sector is written back when a
+/// different sector is accessed, when `flush` is called or when the adapter is dropped.
+///
+/// Relative seeks (`SeekFrom::Current` and `SeekFrom::End`) are resolved by the inner storage so its size does not
+/// have to be a multiple of the sector size.
+pub struct SectorBuffer<B: BorrowMut<S>, S: Read + Write + Seek = B> {
+    // `None` only after the storage has been taken out by `into_inner`
+    inner: Option<B>,
+    bytes_per_sector: u16,
+    buf: [u8; MAX_SECTOR_SIZE],
+    // sector number of the data currently stored in `buf`
+    buf_sector: Option<u64>,
+    dirty: bool,
+    pos: u64,
+    // phantom is needed to add type bounds on the storage type
+    phantom: PhantomData<S>,
+}
+
+impl<B: BorrowMut<S>, S: Read + Write + Seek> SectorBuffer<B, S> {
+    /// Creates a new `SectorBuffer` wrapping the `inner` storage.
+    ///
+    /// `bytes_per_sector` is the sector size of the underlying device. Only operations of whole sectors at offsets
+    /// being a multiple of this value are issued to the inner storage.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes_per_sector` is not a power of two or is greater than 4096.
+    pub fn new(inner: B, bytes_per_sector: u16) -> Self {
+        assert!(
+            bytes_per_sector.is_power_of_two() && usize::from(bytes_per_sector) <= MAX_SECTOR_SIZE,
+            "Invalid bytes_per_sector"
+        );
+        Self {
+            inner: Some(inner),
+            bytes_per_sector,
+            buf: [0_u8; MAX_SECTOR_SIZE],
+            buf_sector: None,
+            dirty: false,
+            pos: 0,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the sector size used by this adapter.
+    #[must_use]
+    pub fn bytes_per_sector(&self) -> u16 {
+        self.bytes_per_sector
+    }
+
+    /// Returns a reference to the inner storage.
+    ///
+    /// Note: buffered changes that were not flushed yet are not visible in the inner storage.
+    pub fn get_ref(&self) -> &B {
+        match &self.inner {
+            Some(inner) => inner,
+            // the storage is taken out only when the adapter is consumed
+            None => unreachable!(),
+        }
+    }
+
+    /// Returns a mutable reference to the inner storage.
+    ///
+    /// Note: buffered changes that were not flushed yet are not visible in the inner storage. Modifying the inner
+    /// storage directly while a sector is buffered can make the buffered data stale, so call `flush` first.
+    pub fn get_mut(&mut self) -> &mut B {
+        match &mut self.inner {
+            Some(inner) => inner,
+            // the storage is taken out only when the adapter is consumed
+            None => unreachable!(),
+        }
+    }
+
+    /// Flushes the buffered sector and returns the inner storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing back the buffered sector or flushing the inner storage failed. The inner storage
+    /// is dropped in that case.
+    pub fn into_inner(mut self) -> Result<B, S::Error> {
+        self.flush()?;
+        match self.inner.take() {
+            Some(inner) => Ok(inner),
+            // the storage is taken out only once
+            None => unreachable!(),
+        }
+    }
+
+    fn sector_size(&self) -> u64 {
+        u64::from(self.bytes_per_sector)
+    }
+
+    fn flush_buf(&mut self) -> Result<(), S::Error> {
+        if let (true, Some(sector)) = (self.dirty, self.buf_sector) {
+            let sector_size = self.sector_size();
+            let inner = storage(&mut self.inner);
+            inner.seek(SeekFrom::Start(sector * sector_size))?;
+            inner.write_all(&self.buf[..usize::from(self.bytes_per_sector)])?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+
+    // Loads a sector into the buffer. If `zero_fill` is set data beyond the end of the storage is read as zeros, so
+    // partial sector writes can extend empty or growable storage.
+    fn load_buf(&mut self, sector: u64, zero_fill: bool) -> Result<(), S::Error> {
+        if self.buf_sector == Some(sector) {
+            return Ok(());
+        }
+        self.flush_buf()?;
+        // invalidate buffer before reading so a failed read does not leave stale data
+        self.buf_sector = None;
+        let sector_size = self.sector_size();
+        let len = usize::from(self.bytes_per_sector);
+        let inner = storage(&mut self.inner);
+        inner.seek(SeekFrom::Start(sector * sector_size))?;
+        if zero_fill {
+            let mut filled = 0;
+            while filled < len {
+                match inner.read(&mut self.buf[filled..len]) {
+                    Ok(0) => break,
+                    Ok(n) => filled += n,
+                    Err(ref e) if e.is_interrupted() => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            self.buf[filled..len].fill(0);
+        } else {
+            inner.read_exact(&mut self.buf[..len])?;
+        }
+        self.buf_sector = Some(sector);
+        Ok(())
+    }
+
+    // Returns number of whole sectors that can be transferred directly from the current position
+    fn whole_sectors(&self, len: usize) -> u64 {
+        if self.pos % self.sector_size() == 0 {
+            len as u64 / self.sector_size()
+        } else {
+            0
+        }
+    }
+
+    fn invalidate_buf_in_range(&mut self, first_sector: u64, sectors: u64) {
+        if let Some(sector) = self.buf_sector {
+            if (first_sector..first_sector + sectors).contains(&sector) {
+                self.buf_sector = None;
+                self.dirty = false;
+            }
+        }
+    }
+}
+
+impl<B: BorrowMut<S>, S: Read + Write + Seek> IoBase for SectorBuffer<B, S> {
+    type Error = S::Error;
+}
+
+impl<B: BorrowMut<S>, S: Read + Write + Seek> Read for SectorBuffer<B, S> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let sector_size = self.sector_size();
+        let first_sector = self.pos / sector_size;
+        let sectors = self.whole_sectors(buf.len());
+        if sectors > 0 {
+            // make sure buffered changes are visible to the direct read
+            self.flush_buf()?;
+            let len = (sectors * sector_size) as usize;
+            let inner = storage(&mut self.inner);
+            inner.seek(SeekFrom::Start(self.pos))?;
+            inner.read_exact(&mut buf[..len])?;
+            self.pos += len as u64;
+            return Ok(len);
+        }
+        self.load_buf(first_sector, false)?;
+        let offset_in_sector = (self.pos % sector_size) as usize;
+        let len = buf.len().min(usize::from(self.bytes_per_sector) - offset_in_sector);
+        buf[..len].copy_from_slice(&self.buf[offset_in_sector..offset_in_sector + len]);
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+impl<B: BorrowMut<S>, S: Read + Write + Seek> Write for SectorBuffer<B, S> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let sector_size = self.sector_size();
+        let first_sector = self.pos / sector_size;
+        let sectors = self.whole_sectors(buf.len());
+        if sectors > 0 {
+            // buffered sector is going to be overwritten - drop it
+            self.invalidate_buf_in_range(first_sector, sectors);
+            let len = (sectors * sector_size) as usize;
+            let inner = storage(&mut self.inner);
+            inner.seek(SeekFrom::Start(self.pos))?;
+            inner.write_all(&buf[..len])?;
+            self.pos += len as u64;
+            return Ok(len);
+        }
+        self.load_buf(first_sector, true)?;
+        let offset_in_sector = (self.pos % sector_size) as usize;
+        let len = buf.len().min(usize::from(self.bytes_per_sector) - offset_in_sector);
+        self.buf[offset_in_sector..offset_in_sector + len].copy_from_slice(&buf[..len]);
+        self.dirty = true;
+        self.pos += len as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.flush_buf()?;
+        storage(&mut self.inner).flush()
+    }
+}
+
+impl<B: BorrowMut<S>, S: Read + Write + Seek> Seek for SectorBuffer<B, S> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        self.pos = match pos {
+            SeekFrom::Start(n) => n,
+            // position relative to the current one or the end can be resolved by the inner storage
+            SeekFrom::Current(n) => {
+                let inner = storage(&mut self.inner);
+                inner.seek(SeekFrom::Start(self.pos))?;
+                inner.seek(SeekFrom::Current(n))?
+            }
+            SeekFrom::End(n) => storage(&mut self.inner).seek(SeekFrom::End(n))?,
+        };
+        Ok(self.pos)
+    }
+}
+
+/// `Drop` implementation tries to write back the buffered sector when dropping.
+impl<B: BorrowMut<S>, S: Read + Write + Seek> Drop for SectorBuffer<B, S> {
+    fn drop(&mut self) {
+        if self.inner.is_none() {
+            // the storage has been taken out by `into_inner` after flushing
+            return;
+        }
+        if let Err(err) = self.flush_buf() {
+            error!("flush failed {:?}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SectorBuffer;
+    use crate::io::{Read, Seek, SeekFrom, StdIoWrapper, Write};
+    use std::io::Cursor;
+
+    type Storage = StdIoWrapper<Cursor<Vec<u8>>>;
+
+    fn new_storage(len: usize) -> Storage {
+        StdIoWrapper::new(Cursor::new(vec![0_u8; len]))
+    }
+
+    #[test]
+    fn test_partial_sector_write() {
+        let mut storage = new_storage(2048);
+        let mut buf = SectorBuffer::<_, Storage>::new(&mut storage, 512);
+        buf.seek(SeekFrom::Start(510)).unwrap();
+        buf.write_all(&[1, 2, 3, 4]).unwrap();
+        buf.flush().unwrap();
+        drop(buf);
+        let data = storage.into_inner().into_inner();
+        assert_eq!(data[508..516], [0, 0, 1, 2, 3, 4, 0, 0]);
+    }
+
+    #[test]
+    fn test_partial_sector_write_beyond_end() {
+        let mut buf = SectorBuffer::new(new_storage(700), 512);
+        buf.seek(SeekFrom::Start(1030)).unwrap();
+        buf.write_all(&[1, 2]).unwrap();
+        let data = buf.into_inner().unwrap().into_inner().into_inner();
+        // the last partial sector is padded and the new sector is zero-filled
+        assert_eq!(data.len(), 1536);
+        assert!(data[..1030].iter().all(|b| *b == 0));
+        assert_eq!(data[1030..1034], [1, 2, 0, 0]);
+    }
+
+    #[test]
+    fn test_read_after_write() {
+        let mut buf = SectorBuffer::new(new_storage(2048), 512);
+        buf.seek(SeekFrom::Start(100)).unwrap();
+        buf.write_all(&[0xAA; 1000]).unwrap();
+        buf.seek(SeekFrom::Start(0)).unwrap();
+        let mut data = [0_u8; 2048];
+        buf.read_exact(&mut data).unwrap();
+        assert!(data[..100].iter().all(|b| *b == 0));
+        assert!(data[100..1100].iter().all(|b| *b == 0xAA));
+        assert!(data[1100..].iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn test_flush_on_drop() {
+        let mut storage = new_storage(1024);
+        {
+            let mut buf = SectorBuffer::<_, Storage>::new(&mut storage, 512);
+            buf.seek(SeekFrom::Start(1)).unwrap();
+            buf.write_all(&[0x55]).unwrap();
+        }
+        assert_eq!(storage.into_inner().into_inner()[..2], [0, 0x55]);
+    }
+
+    #[test]
+    fn test_into_inner() {
+        let mut buf = SectorBuffer::new(new_storage(1024), 512);
+        buf.seek(SeekFrom::Start(513)).unwrap();
+        buf.write_all(&[0x55]).unwrap();
+        // the change is not visible in the inner storage until flushed
+        let mut byte = [0xFF_u8];
+        buf.get_mut().seek(SeekFrom::Start(513)).unwrap();
+        buf.get_mut().read_exact(&mut byte).unwrap();
+        assert_eq!(byte, [0]);
+        let data = buf.into_inner().unwrap().into_inner().into_inner();
+        assert_eq!(data[512..515], [0, 0x55, 0]);
+    }
+
+    #[test]
+    fn test_seek_relative() {
+        let mut buf = SectorBuffer::new(new_storage(1000), 512);
+        assert_eq!(buf.seek(SeekFrom::End(-10)).unwrap(), 990);
+        assert_eq!(buf.seek(SeekFrom::Current(-90)).unwrap(), 900);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid bytes_per_sector")]
+    fn test_invalid_sector_size() {
+        let _ = SectorBuffer::new(new_storage(0), 1000);
+    }
+}
@@ -12,6 +12,10 @@ const KB_64: u64 = 1024;
 const MB_64: u64 = KB_64 * 1024;
 const GB_64: u64 = MB_64 * 1024;
 
+// Locations of FAT32 special sectors used when formatting
+const FAT32_FS_INFO_SECTOR: u16 = 1;
+const FAT32_BACKUP_BOOT_SECTOR: u16 = 6;
+
 #[derive(Default, Debug, Clone)]
 pub(crate) struct BiosParameterBlock {
     pub(crate) bytes_per_sector: u16,
@@ -205,6 +209,14 @@ impl BiosParameterBlock {
                 self.fats
             );
         }
+        if u16::from(self.fats) <= self.active_fat() {
+            error!(
+                "invalid active FAT in BPB: {} (number of FATs is {})",
+                self.active_fat(),
+                self.fats
+            );
+            return Err(Error::CorruptedFileSystem);
+        }
         Ok(())
     }
 
@@ -584,17 +596,27 @@ fn determine_sectors_per_fat(
 }
 
 fn try_fs_layout(
+    options: &FormatVolumeOptions,
     total_sectors: u32,
-    bytes_per_sector: u16,
     sectors_per_cluster: u8,
     fat_type: FatType,
     root_dir_sectors: u32,
-    fats: u8,
 ) -> Result<(u16, u32), Error<()>> {
+    let bytes_per_sector = options.bytes_per_sector;
+    let fats = options.fats;
     // Note: most of implementations use 32 reserved sectors for FAT32 but it's wasting of space
-    // This implementation uses only 8. This is enough to fit in two boot sectors (main and backup) with additional
-    // bootstrap code and one FSInfo sector. It also makes FAT alligned to 4096 which is a nice number.
-    let reserved_sectors: u16 = if fat_type == FatType::Fat32 { 8 } else { 1 };
+    // This implementation uses only 8 by default. This is enough to fit in two boot sectors (main and backup) with
+    // additional bootstrap code and one FSInfo sector. It also makes FAT alligned to 4096 which is a nice number.
+    let reserved_sectors: u16 = options
+        .reserved_sectors
+        .unwrap_or(if fat_type == FatType::Fat32 { 8 } else { 1 });
+    if fat_type == FatType::Fat32 && reserved_sectors <= FAT32_BACKUP_BOOT_SECTOR {
+        error!(
+            "FAT32 requires more than {} reserved sectors to fit FSInfo and backup boot sectors",
+            FAT32_BACKUP_BOOT_SECTOR
+        );
+        return Err(Error::InvalidInput);
+    }
 
     // Check if volume has enough space to accomodate reserved sectors, FAT, root directory and some data space
     // Having less than 8 sectors for FAT and data would make a little sense
@@ -603,16 +625,43 @@ fn try_fs_layout(
         return Err(Error::InvalidInput);
     }
 
-    // calculate File Allocation Table size
-    let sectors_per_fat = determine_sectors_per_fat(
-        total_sectors,
-        bytes_per_sector,
-        sectors_per_cluster,
-        fat_type,
-        reserved_sectors,
-        root_dir_sectors,
-        fats,
-    );
+    // calculate File Allocation Table size unless it has been provided explicitly
+    let sectors_per_fat = if let Some(sectors_per_fat) = options.sectors_per_fat {
+        let fat_area_sectors = u64::from(sectors_per_fat) * u64::from(fats);
+        if u64::from(total_sectors) <= u64::from(reserved_sectors) + u64::from(root_dir_sectors) + fat_area_sectors {
+            error!("Volume is too small for the requested FAT size");
+            return Err(Error::InvalidInput);
+        }
+        sectors_per_fat
+    } else {
+        determine_sectors_per_fat(
+            total_sectors,
+            bytes_per_sector,
+            sectors_per_cluster,
+            fat_type,
+            reserved_sectors,
+            root_dir_sectors,
+            fats,
+        )
+    };
+
+    // Add padding to reserved sectors so the data region starts on the requested boundary. FAT size is not reduced
+    // by the padding so it can only be slightly bigger than necessary.
+    let reserved_sectors = if let Some(alignment) = options.data_alignment_sectors {
+        let first_data_sector = u32::from(reserved_sectors) + sectors_per_fat * u32::from(fats) + root_dir_sectors;
+        let padding = (alignment - first_data_sector % alignment) % alignment;
+        let Ok(aligned_reserved_sectors) = u16::try_from(u32::from(reserved_sectors) + padding) else {
+            error!("Data alignment requires too many reserved sectors");
+            return Err(Error::InvalidInput);
+        };
+        if total_sectors <= u32::from(aligned_reserved_sectors) + root_dir_sectors + sectors_per_fat * u32::from(fats) {
+            error!("Volume is too small for the requested data alignment");
+            return Err(Error::InvalidInput);
+        }
+        aligned_reserved_sectors
+    } else {
+        reserved_sectors
+    };
 
     let data_sectors =
         total_sectors - u32::from(reserved_sectors) - root_dir_sectors - sectors_per_fat * u32::from(fats);
@@ -631,6 +680,13 @@ fn try_fs_layout(
         error!("Too many clusters");
         return Err(Error::InvalidInput);
     }
+    let total_fat_entries = u64::from(sectors_per_fat) * u64::from(bytes_per_sector) * u64::from(BITS_PER_BYTE)
+        / u64::from(fat_type.bits_per_fat_entry());
+    if total_fat_entries < u64::from(total_clusters) + u64::from(RESERVED_FAT_ENTRIES) {
+        // Note: it can happen only if sectors_per_fat has been provided explicitly
+        error!("FAT is too small for {} clusters", total_clusters);
+        return Err(Error::InvalidInput);
+    }
 
     Ok((reserved_sectors, sectors_per_fat))
 }
@@ -673,14 +729,7 @@ fn determine_fs_layout<E: IoError>(options: &FormatVolumeOptions, total_sectors:
     for &fat_type in allowed_fat_types {
         let root_dir_sectors =
             determine_root_dir_sectors(options.max_root_dir_entries, options.bytes_per_sector, fat_type);
-        let result = try_fs_layout(
-            total_sectors,
-            options.bytes_per_sector,
-            sectors_per_cluster,
-            fat_type,
-            root_dir_sectors,
-            options.fats,
-        );
+        let result = try_fs_layout(options, total_sectors, sectors_per_cluster, fat_type, root_dir_sectors);
         if let Ok((reserved_sectors, sectors_per_fat)) = result {
             return Ok(FsLayout {
                 fat_type,
@@ -756,8 +805,8 @@ fn format_bpb<E: IoError>(
         extended_flags: 0, // mirroring enabled
         fs_version: 0,
         root_dir_first_cluster: if is_fat32 { 2 } else { 0 },
-        fs_info_sector: if is_fat32 { 1 } else { 0 },
-        backup_boot_sector: if is_fat32 { 6 } else { 0 },
+        fs_info_sector: if is_fat32 { FAT32_FS_INFO_SECTOR } else { 0 },
+        backup_boot_sector: if is_fat32 { FAT32_BACKUP_BOOT_SECTOR } else { 0 },
         reserved_0: [0_u8; 12],
         // FAT32 fields end
         drive_num,
@@ -784,7 +833,7 @@ pub(crate) fn format_boot_sector<E: IoError>(
     let mut boot = BootSector::default();
     let (bpb, fat_type) = format_bpb(options, total_sectors)?;
     boot.bpb = bpb;
-    boot.oem_name.copy_from_slice(b"MSWIN4.1");
+    boot.oem_name = options.oem_name;
     // Boot code copied from FAT32 boot sector initialized by mkfs.fat
     boot.bootjmp = [0xEB, 0x58, 0x90];
     let boot_code: [u8; 129] = [
@@ -882,7 +931,7 @@ mod tests {
         root_dir_entries: u32,
     ) {
         let total_sectors = total_bytes / u64::from(bytes_per_sector);
-        debug_assert!(total_sectors <= u64::from(u32::MAX), "{:x}", total_sectors);
+        debug_assert!(u32::try_from(total_sectors).is_ok(), "{:x}", total_sectors);
         let total_sectors = total_sectors as u32;
 
         let sectors_per_cluster = (bytes_per_cluster / u32::from(bytes_per_sector)) as u8;
@@ -973,6 +1022,54 @@ mod tests {
         test_determine_sectors_per_fat_for_multiple_sizes(4096, FatType::Fat32, 32, 2, 0);
     }
 
+    #[test]
+    fn test_determine_sectors_per_fat_near_cluster_count_limits() {
+        init();
+
+        // FAT type is selected by the number of clusters so check every volume size close to the limits
+        let cases = [
+            (FatType::Fat12, 1, 1, 512),
+            (FatType::Fat12, FatType::Fat12.max_clusters(), 1, 512),
+            (FatType::Fat16, FatType::Fat16.min_clusters(), 1, 512),
+            (FatType::Fat16, FatType::Fat16.max_clusters(), 1, 512),
+            (FatType::Fat32, FatType::Fat32.min_clusters(), 32, 0),
+            (FatType::Fat32, FatType::Fat32.max_clusters(), 32, 0),
+        ];
+        for (fat_type, clusters, reserved_sectors, root_dir_entries) in cases {
+            for bytes_per_sector in [512_u16, 4096] {
+                let mut bytes_per_cluster = u32::from(bytes_per_sector);
+                while bytes_per_cluster <= 64 * KB_32 {
+                    let sectors_per_cluster = u64::from(bytes_per_cluster / u32::from(bytes_per_sector));
+                    let fat_sectors = (u64::from(clusters) + 2) * u64::from(fat_type.bits_per_fat_entry())
+                        / (u64::from(bytes_per_sector) * u64::from(BITS_PER_BYTE))
+                        + 1;
+                    let root_dir_sectors = u64::from(root_dir_entries * DIR_ENTRY_SIZE) / u64::from(bytes_per_sector);
+                    let approx_total_sectors = u64::from(reserved_sectors)
+                        + root_dir_sectors
+                        + 2 * fat_sectors
+                        + u64::from(clusters) * sectors_per_cluster;
+                    let min_total_sectors = approx_total_sectors
+                        .saturating_sub(4 * sectors_per_cluster + 4)
+                        .max(u64::from(reserved_sectors) + root_dir_sectors + 9);
+                    let max_total_sectors =
+                        (approx_total_sectors + 4 * sectors_per_cluster + 4).min(u64::from(u32::MAX));
+                    for total_sectors in min_total_sectors..=max_total_sectors {
+                        test_determine_sectors_per_fat_single(
+                            total_sectors * u64::from(bytes_per_sector),
+                            bytes_per_sector,
+                            bytes_per_cluster,
+                            fat_type,
+                            reserved_sectors,
+                            2,
+                            root_dir_entries,
+                        );
+                    }
+                    bytes_per_cluster *= 2;
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_format_boot_sector() {
         init();
@@ -1014,6 +1111,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_format_boot_sector_data_alignment() {
+        init();
+
+        for (fat_type, total_sectors) in [
+            (FatType::Fat12, 8192),
+            (FatType::Fat16, 65536),
+            (FatType::Fat32, 262_144),
+        ] {
+            for alignment in [1, 8, 64, 2048] {
+                let options = FormatVolumeOptions::new()
+                    .fat_type(fat_type)
+                    .data_alignment_sectors(alignment);
+                let (boot, _) = format_boot_sector::<()>(&options, total_sectors).expect("format_boot_sector");
+                boot.validate::<()>().expect("validate");
+                assert_eq!(boot.bpb.first_data_sector() % alignment, 0);
+            }
+        }
+        // padding does not fit in the volume
+        let options = FormatVolumeOptions::new()
+            .fat_type(FatType::Fat12)
+            .data_alignment_sectors(4096);
+        assert!(format_boot_sector::<()>(&options, 2048).is_err());
+    }
+
     #[test]
     fn test_determine_fs_layout_fat12() {
         // approximately: 21 KB - 127 MB
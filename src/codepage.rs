@@ -0,0 +1,230 @@
+//! Converters for common OEM code pages.
+//!
+//! Short file names are stored using an OEM code page. These converters can be passed to
+//! `FsOptions::oem_cp_converter` to properly decode and encode non-ASCII characters in short names.
+
+use crate::fs::OemCpConverter;
+
+// Characters in range 0x00-0x7F are the same as in ASCII so only the upper half of a code page is stored
+fn decode_high(table: &[char; 128], oem_char: u8) -> char {
+    if oem_char <= 0x7F {
+        char::from(oem_char)
+    } else {
+        table[usize::from(oem_char - 0x80)]
+    }
+}
+
+// `table` is sorted by characters so binary search can be used
+fn encode_high(table: &[(char, u8); 128], uni_char: char) -> Option<u8> {
+    if uni_char <= '\x7F' {
+        Some(uni_char as u8) // safe cast: value is in range [0, 0x7F]
+    } else {
+        table
+            .binary_search_by_key(&uni_char, |&(c, _)| c)
+            .ok()
+            .map(|index| table[index].1)
+    }
+}
+
+/// `OemCpConverter` implementation for code page 437 (the original IBM PC code page).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cp437Converter {
+    _dummy: (),
+}
+
+impl Cp437Converter {
+    #[must_use]
+    pub fn new() -> Self {
+        Self { _dummy: () }
+    }
+}
+
+impl OemCpConverter for Cp437Converter {
+    fn decode(&self, oem_char: u8) -> char {
+        decode_high(&CP437_DECODE, oem_char)
+    }
+
+    fn encode(&self, uni_char: char) -> Option<u8> {
+        encode_high(&CP437_ENCODE, uni_char)
+    }
+}
+
+/// `OemCpConverter` implementation for code page 850 (DOS Latin-1 used in Western Europe).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cp850Converter {
+    _dummy: (),
+}
+
+impl Cp850Converter {
+    #[must_use]
+    pub fn new() -> Self {
+        Self { _dummy: () }
+    }
+}
+
+impl OemCpConverter for Cp850Converter {
+    fn decode(&self, oem_char: u8) -> char {
+        decode_high(&CP850_DECODE, oem_char)
+    }
+
+    fn encode(&self, uni_char: char) -> Option<u8> {
+        encode_high(&CP850_ENCODE, uni_char)
+    }
+}
+
+#[rustfmt::skip]
+const CP437_DECODE: [char; 128] = [
+    '\u{00C7}', '\u{00FC}', '\u{00E9}', '\u{00E2}', '\u{00E4}', '\u{00E0}', '\u{00E5}', '\u{00E7}',
+    '\u{00EA}', '\u{00EB}', '\u{00E8}', '\u{00EF}', '\u{00EE}', '\u{00EC}', '\u{00C4}', '\u{00C5}',
+    '\u{00C9}', '\u{00E6}', '\u{00C6}', '\u{00F4}', '\u{00F6}', '\u{00F2}', '\u{00FB}', '\u{00F9}',
+    '\u{00FF}', '\u{00D6}', '\u{00DC}', '\u{00A2}', '\u{00A3}', '\u{00A5}', '\u{20A7}', '\u{0192}',
+    '\u{00E1}', '\u{00ED}', '\u{00F3}', '\u{00FA}', '\u{00F1}', '\u{00D1}', '\u{00AA}', '\u{00BA}',
+    '\u{00BF}', '\u{2310}', '\u{00AC}', '\u{00BD}', '\u{00BC}', '\u{00A1}', '\u{00AB}', '\u{00BB}',
+    '\u{2591}', '\u{2592}', '\u{2593}', '\u{2502}', '\u{2524}', '\u{2561}', '\u{2562}', '\u{2556}',
+    '\u{2555}', '\u{2563}', '\u{2551}', '\u{2557}', '\u{255D}', '\u{255C}', '\u{255B}', '\u{2510}',
+    '\u{2514}', '\u{2534}', '\u{252C}', '\u{251C}', '\u{2500}', '\u{253C}', '\u{255E}', '\u{255F}',
+    '\u{255A}', '\u{2554}', '\u{2569}', '\u{2566}', '\u{2560}', '\u{2550}', '\u{256C}', '\u{2567}',
+    '\u{2568}', '\u{2564}', '\u{2565}', '\u{2559}', '\u{2558}', '\u{2552}', '\u{2553}', '\u{256B}',
+    '\u{256A}', '\u{2518}', '\u{250C}', '\u{2588}', '\u{2584}', '\u{258C}', '\u{2590}', '\u{2580}',
+    '\u{03B1}', '\u{00DF}', '\u{0393}', '\u{03C0}', '\u{03A3}', '\u{03C3}', '\u{00B5}', '\u{03C4}',
+    '\u{03A6}', '\u{0398}', '\u{03A9}', '\u{03B4}', '\u{221E}', '\u{03C6}', '\u{03B5}', '\u{2229}',
+    '\u{2261}', '\u{00B1}', '\u{2265}', '\u{2264}', '\u{2320}', '\u{2321}', '\u{00F7}', '\u{2248}',
+    '\u{00B0}', '\u{2219}', '\u{00B7}', '\u{221A}', '\u{207F}', '\u{00B2}', '\u{25A0}', '\u{00A0}',
+];
+
+#[rustfmt::skip]
+const CP437_ENCODE: [(char, u8); 128] = [
+    ('\u{00A0}', 0xFF), ('\u{00A1}', 0xAD), ('\u{00A2}', 0x9B), ('\u{00A3}', 0x9C),
+    ('\u{00A5}', 0x9D), ('\u{00AA}', 0xA6), ('\u{00AB}', 0xAE), ('\u{00AC}', 0xAA),
+    ('\u{00B0}', 0xF8), ('\u{00B1}', 0xF1), ('\u{00B2}', 0xFD), ('\u{00B5}', 0xE6),
+    ('\u{00B7}', 0xFA), ('\u{00BA}', 0xA7), ('\u{00BB}', 0xAF), ('\u{00BC}', 0xAC),
+    ('\u{00BD}', 0xAB), ('\u{00BF}', 0xA8), ('\u{00C4}', 0x8E), ('\u{00C5}', 0x8F),
+    ('\u{00C6}', 0x92), ('\u{00C7}', 0x80), ('\u{00C9}', 0x90), ('\u{00D1}', 0xA5),
+    ('\u{00D6}', 0x99), ('\u{00DC}', 0x9A), ('\u{00DF}', 0xE1), ('\u{00E0}', 0x85),
+    ('\u{00E1}', 0xA0), ('\u{00E2}', 0x83), ('\u{00E4}', 0x84), ('\u{00E5}', 0x86),
+    ('\u{00E6}', 0x91), ('\u{00E7}', 0x87), ('\u{00E8}', 0x8A), ('\u{00E9}', 0x82),
+    ('\u{00EA}', 0x88), ('\u{00EB}', 0x89), ('\u{00EC}', 0x8D), ('\u{00ED}', 0xA1),
+    ('\u{00EE}', 0x8C), ('\u{00EF}', 0x8B), ('\u{00F1}', 0xA4), ('\u{00F2}', 0x95),
+    ('\u{00F3}', 0xA2), ('\u{00F4}', 0x93), ('\u{00F6}', 0x94), ('\u{00F7}', 0xF6),
+    ('\u{00F9}', 0x97), ('\u{00FA}', 0xA3), ('\u{00FB}', 0x96), ('\u{00FC}', 0x81),
+    ('\u{00FF}', 0x98), ('\u{0192}', 0x9F), ('\u{0393}', 0xE2), ('\u{0398}', 0xE9),
+    ('\u{03A3}', 0xE4), ('\u{03A6}', 0xE8), ('\u{03A9}', 0xEA), ('\u{03B1}', 0xE0),
+    ('\u{03B4}', 0xEB), ('\u{03B5}', 0xEE), ('\u{03C0}', 0xE3), ('\u{03C3}', 0xE5),
+    ('\u{03C4}', 0xE7), ('\u{03C6}', 0xED), ('\u{207F}', 0xFC), ('\u{20A7}', 0x9E),
+    ('\u{2219}', 0xF9), ('\u{221A}', 0xFB), ('\u{221E}', 0xEC), ('\u{2229}', 0xEF),
+    ('\u{2248}', 0xF7), ('\u{2261}', 0xF0), ('\u{2264}', 0xF3), ('\u{2265}', 0xF2),
+    ('\u{2310}', 0xA9), ('\u{2320}', 0xF4), ('\u{2321}', 0xF5), ('\u{2500}', 0xC4),
+    ('\u{2502}', 0xB3), ('\u{250C}', 0xDA), ('\u{2510}', 0xBF), ('\u{2514}', 0xC0),
+    ('\u{2518}', 0xD9), ('\u{251C}', 0xC3), ('\u{2524}', 0xB4), ('\u{252C}', 0xC2),
+    ('\u{2534}', 0xC1), ('\u{253C}', 0xC5), ('\u{2550}', 0xCD), ('\u{2551}', 0xBA),
+    ('\u{2552}', 0xD5), ('\u{2553}', 0xD6), ('\u{2554}', 0xC9), ('\u{2555}', 0xB8),
+    ('\u{2556}', 0xB7), ('\u{2557}', 0xBB), ('\u{2558}', 0xD4), ('\u{2559}', 0xD3),
+    ('\u{255A}', 0xC8), ('\u{255B}', 0xBE), ('\u{255C}', 0xBD), ('\u{255D}', 0xBC),
+    ('\u{255E}', 0xC6), ('\u{255F}', 0xC7), ('\u{2560}', 0xCC), ('\u{2561}', 0xB5),
+    ('\u{2562}', 0xB6), ('\u{2563}', 0xB9), ('\u{2564}', 0xD1), ('\u{2565}', 0xD2),
+    ('\u{2566}', 0xCB), ('\u{2567}', 0xCF), ('\u{2568}', 0xD0), ('\u{2569}', 0xCA),
+    ('\u{256A}', 0xD8), ('\u{256B}', 0xD7), ('\u{256C}', 0xCE), ('\u{2580}', 0xDF),
+    ('\u{2584}', 0xDC), ('\u{2588}', 0xDB), ('\u{258C}', 0xDD), ('\u{2590}', 0xDE),
+    ('\u{2591}', 0xB0), ('\u{2592}', 0xB1), ('\u{2593}', 0xB2), ('\u{25A0}', 0xFE),
+];
+
+#[rustfmt::skip]
+const CP850_DECODE: [char; 128] = [
+    '\u{00C7}', '\u{00FC}', '\u{00E9}', '\u{00E2}', '\u{00E4}', '\u{00E0}', '\u{00E5}', '\u{00E7}',
+    '\u{00EA}', '\u{00EB}', '\u{00E8}', '\u{00EF}', '\u{00EE}', '\u{00EC}', '\u{00C4}', '\u{00C5}',
+    '\u{00C9}', '\u{00E6}', '\u{00C6}', '\u{00F4}', '\u{00F6}', '\u{00F2}', '\u{00FB}', '\u{00F9}',
+    '\u{00FF}', '\u{00D6}', '\u{00DC}', '\u{00F8}', '\u{00A3}', '\u{00D8}', '\u{00D7}', '\u{0192}',
+    '\u{00E1}', '\u{00ED}', '\u{00F3}', '\u{00FA}', '\u{00F1}', '\u{00D1}', '\u{00AA}', '\u{00BA}',
+    '\u{00BF}', '\u{00AE}', '\u{00AC}', '\u{00BD}', '\u{00BC}', '\u{00A1}', '\u{00AB}', '\u{00BB}',
+    '\u{2591}', '\u{2592}', '\u{2593}', '\u{2502}', '\u{2524}', '\u{00C1}', '\u{00C2}', '\u{00C0}',
+    '\u{00A9}', '\u{2563}', '\u{2551}', '\u{2557}', '\u{255D}', '\u{00A2}', '\u{00A5}', '\u{2510}',
+    '\u{2514}', '\u{2534}', '\u{252C}', '\u{251C}', '\u{2500}', '\u{253C}', '\u{00E3}', '\u{00C3}',
+    '\u{255A}', '\u{2554}', '\u{2569}', '\u{2566}', '\u{2560}', '\u{2550}', '\u{256C}', '\u{00A4}',
+    '\u{00F0}', '\u{00D0}', '\u{00CA}', '\u{00CB}', '\u{00C8}', '\u{0131}', '\u{00CD}', '\u{00CE}',
+    '\u{00CF}', '\u{2518}', '\u{250C}', '\u{2588}', '\u{2584}', '\u{00A6}', '\u{00CC}', '\u{2580}',
+    '\u{00D3}', '\u{00DF}', '\u{00D4}', '\u{00D2}', '\u{00F5}', '\u{00D5}', '\u{00B5}', '\u{00FE}',
+    '\u{00DE}', '\u{00DA}', '\u{00DB}', '\u{00D9}', '\u{00FD}', '\u{00DD}', '\u{00AF}', '\u{00B4}',
+    '\u{00AD}', '\u{00B1}', '\u{2017}', '\u{00BE}', '\u{00B6}', '\u{00A7}', '\u{00F7}', '\u{00B8}',
+    '\u{00B0}', '\u{00A8}', '\u{00B7}', '\u{00B9}', '\u{00B3}', '\u{00B2}', '\u{25A0}', '\u{00A0}',
+];
+
+#[rustfmt::skip]
+const CP850_ENCODE: [(char, u8); 128] = [
+    ('\u{00A0}', 0xFF), ('\u{00A1}', 0xAD), ('\u{00A2}', 0xBD), ('\u{00A3}', 0x9C),
+    ('\u{00A4}', 0xCF), ('\u{00A5}', 0xBE), ('\u{00A6}', 0xDD), ('\u{00A7}', 0xF5),
+    ('\u{00A8}', 0xF9), ('\u{00A9}', 0xB8), ('\u{00AA}', 0xA6), ('\u{00AB}', 0xAE),
+    ('\u{00AC}', 0xAA), ('\u{00AD}', 0xF0), ('\u{00AE}', 0xA9), ('\u{00AF}', 0xEE),
+    ('\u{00B0}', 0xF8), ('\u{00B1}', 0xF1), ('\u{00B2}', 0xFD), ('\u{00B3}', 0xFC),
+    ('\u{00B4}', 0xEF), ('\u{00B5}', 0xE6), ('\u{00B6}', 0xF4), ('\u{00B7}', 0xFA),
+    ('\u{00B8}', 0xF7), ('\u{00B9}', 0xFB), ('\u{00BA}', 0xA7), ('\u{00BB}', 0xAF),
+    ('\u{00BC}', 0xAC), ('\u{00BD}', 0xAB), ('\u{00BE}', 0xF3), ('\u{00BF}', 0xA8),
+    ('\u{00C0}', 0xB7), ('\u{00C1}', 0xB5), ('\u{00C2}', 0xB6), ('\u{00C3}', 0xC7),
+    ('\u{00C4}', 0x8E), ('\u{00C5}', 0x8F), ('\u{00C6}', 0x92), ('\u{00C7}', 0x80),
+    ('\u{00C8}', 0xD4), ('\u{00C9}', 0x90), ('\u{00CA}', 0xD2), ('\u{00CB}', 0xD3),
+    ('\u{00CC}', 0xDE), ('\u{00CD}', 0xD6), ('\u{00CE}', 0xD7), ('\u{00CF}', 0xD8),
+    ('\u{00D0}', 0xD1), ('\u{00D1}', 0xA5), ('\u{00D2}', 0xE3), ('\u{00D3}', 0xE0),
+    ('\u{00D4}', 0xE2), ('\u{00D5}', 0xE5), ('\u{00D6}', 0x99), ('\u{00D7}', 0x9E),
+    ('\u{00D8}', 0x9D), ('\u{00D9}', 0xEB), ('\u{00DA}', 0xE9), ('\u{00DB}', 0xEA),
+    ('\u{00DC}', 0x9A), ('\u{00DD}', 0xED), ('\u{00DE}', 0xE8), ('\u{00DF}', 0xE1),
+    ('\u{00E0}', 0x85), ('\u{00E1}', 0xA0), ('\u{00E2}', 0x83), ('\u{00E3}', 0xC6),
+    ('\u{00E4}', 0x84), ('\u{00E5}', 0x86), ('\u{00E6}', 0x91), ('\u{00E7}', 0x87),
+    ('\u{00E8}', 0x8A), ('\u{00E9}', 0x82), ('\u{00EA}', 0x88), ('\u{00EB}', 0x89),
+    ('\u{00EC}', 0x8D), ('\u{00ED}', 0xA1), ('\u{00EE}', 0x8C), ('\u{00EF}', 0x8B),
+    ('\u{00F0}', 0xD0), ('\u{00F1}', 0xA4), ('\u{00F2}', 0x95), ('\u{00F3}', 0xA2),
+    ('\u{00F4}', 0x93), ('\u{00F5}', 0xE4), ('\u{00F6}', 0x94), ('\u{00F7}', 0xF6),
+    ('\u{00F8}', 0x9B), ('\u{00F9}', 0x97), ('\u{00FA}', 0xA3), ('\u{00FB}', 0x96),
+    ('\u{00FC}', 0x81), ('\u{00FD}', 0xEC), ('\u{00FE}', 0xE7), ('\u{00FF}', 0x98),
+    ('\u{0131}', 0xD5), ('\u{0192}', 0x9F), ('\u{2017}', 0xF2), ('\u{2500}', 0xC4),
+    ('\u{2502}', 0xB3), ('\u{250C}', 0xDA), ('\u{2510}', 0xBF), ('\u{2514}', 0xC0),
+    ('\u{2518}', 0xD9), ('\u{251C}', 0xC3), ('\u{2524}', 0xB4), ('\u{252C}', 0xC2),
+    ('\u{2534}', 0xC1), ('\u{253C}', 0xC5), ('\u{2550}', 0xCD), ('\u{2551}', 0xBA),
+    ('\u{2554}', 0xC9), ('\u{2557}', 0xBB), ('\u{255A}', 0xC8), ('\u{255D}', 0xBC),
+    ('\u{2560}', 0xCC), ('\u{2563}', 0xB9), ('\u{2566}', 0xCB), ('\u{2569}', 0xCA),
+    ('\u{256C}', 0xCE), ('\u{2580}', 0xDF), ('\u{2584}', 0xDC), ('\u{2588}', 0xDB),
+    ('\u{2591}', 0xB0), ('\u{2592}', 0xB1), ('\u{2593}', 0xB2), ('\u{25A0}', 0xFE),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cp437_decode() {
+        let conv = Cp437Converter::new();
+        assert_eq!(conv.decode(b'A'), 'A');
+        assert_eq!(conv.decode(0x82), '\u{E9}');
+        assert_eq!(conv.decode(0x9A), '\u{DC}');
+        assert_eq!(conv.decode(0xC9), '\u{2554}');
+        assert_eq!(conv.decode(0xE1), '\u{DF}');
+        assert_eq!(conv.decode(0xFF), '\u{A0}');
+    }
+
+    #[test]
+    fn test_cp437_round_trip() {
+        let conv = Cp437Converter::new();
+        for oem_char in 0..=255_u8 {
+            let uni_char = conv.decode(oem_char);
+            assert_ne!(uni_char, '\u{FFFD}');
+            assert_eq!(conv.encode(uni_char), Some(oem_char));
+        }
+        assert_eq!(conv.encode('\u{E9}'), Some(0x82));
+        assert_eq!(conv.encode('\u{20AC}'), None);
+        assert_eq!(conv.encode('\u{FFFD}'), None);
+        assert_eq!(conv.encode('\u{1F600}'), None);
+    }
+
+    #[test]
+    fn test_cp850_round_trip() {
+        let conv = Cp850Converter::new();
+        for oem_char in 0..=255_u8 {
+            let uni_char = conv.decode(oem_char);
+            assert_ne!(uni_char, '\u{FFFD}');
+            assert_eq!(conv.encode(uni_char), Some(oem_char));
+        }
+        assert_eq!(conv.decode(0x9B), '\u{F8}');
+        assert_eq!(conv.decode(0xD5), '\u{131}');
+        assert_eq!(conv.encode('\u{20AC}'), None);
+        // not available in CP850 unlike CP437
+        assert_eq!(conv.encode('\u{20A7}'), None);
+    }
+}
@@ -1,24 +1,33 @@
-#[cfg(all(not(feature = "std"), feature = "alloc", feature = "lfn"))]
-use alloc::vec::Vec;
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::{format, string::String, vec, vec::Vec};
+use core::marker::PhantomData;
+use core::mem;
 use core::num;
 use core::str;
 #[cfg(feature = "lfn")]
 use core::{iter, slice};
 
 use crate::dir_entry::{
-    DirEntry, DirEntryData, DirFileEntryData, DirLfnEntryData, FileAttributes, ShortName, DIR_ENTRY_SIZE,
+    encode_short_name, DeletedDirEntry, DirEntry, DirEntryData, DirFileEntryData, DirLfnEntryData, FileAttributes,
+    Metadata, ShortName, DIR_ENTRY_SIZE,
 };
 #[cfg(feature = "lfn")]
 use crate::dir_entry::{LFN_ENTRY_LAST_FLAG, LFN_PART_LEN};
 use crate::dir_entry::{SFN_PADDING, SFN_SIZE};
 use crate::error::{Error, IoError};
-use crate::file::File;
+use crate::file::{Extent, File};
 use crate::fs::{DiskSlice, FileSystem, FsIoAdapter, OemCpConverter, ReadWriteSeek};
 use crate::io::{self, IoBase, Read, Seek, SeekFrom, Write};
-use crate::time::TimeProvider;
+use crate::time::{DateTime, TimeProvider};
 
 const LFN_PADDING: u16 = 0xFFFF;
 
+// Size of the buffer used when copying file content
+const COPY_BUFFER_SIZE: usize = 512;
+
+// Size of the buffer used by `DirIter` for reading directory entries ahead
+const DIR_READ_AHEAD_SIZE: usize = 512;
+
 pub(crate) enum DirRawStream<'a, IO: ReadWriteSeek, TP, OCC> {
     File(File<'a, IO, TP, OCC>),
     Root(DiskSlice<FsIoAdapter<'a, IO, TP, OCC>, FsIoAdapter<'a, IO, TP, OCC>>),
@@ -94,11 +103,16 @@ impl<IO: ReadWriteSeek, TP, OCC> Seek for DirRawStream<'_, IO, TP, OCC> {
     }
 }
 
-fn split_path(path: &str) -> (&str, Option<&str>) {
-    let trimmed_path = path.trim_matches('/');
-    trimmed_path.find('/').map_or((trimmed_path, None), |n| {
-        (&trimmed_path[..n], Some(&trimmed_path[n + 1..]))
-    })
+// Both slash and backslash separate path components
+const PATH_SEPARATORS: [char; 2] = ['/', '\\'];
+
+pub(crate) fn split_path(path: &str) -> (&str, Option<&str>) {
+    let trimmed_path = path.trim_matches(&PATH_SEPARATORS[..]);
+    trimmed_path
+        .find(&PATH_SEPARATORS[..])
+        .map_or((trimmed_path, None), |n| {
+            (&trimmed_path[..n], Some(&trimmed_path[n + 1..]))
+        })
 }
 
 enum DirEntryOrShortName<'a, IO: ReadWriteSeek, TP, OCC> {
@@ -110,25 +124,233 @@ enum DirEntryOrShortName<'a, IO: ReadWriteSeek, TP, OCC> {
 ///
 /// This struct is created by the `open_dir` or `create_dir` methods on `Dir`.
 /// The root directory is returned by the `root_dir` method on `FileSystem`.
+/// Path components in arguments of `Dir` methods can be separated by `/` or `\`.
+///
+/// Without the `lfn` feature only short (8.3) names are stored. Creating or renaming an entry using a name that is
+/// not a valid short name fails with `Error::InvalidFileNameLength` or `Error::UnsupportedFileNameCharacter`, and
+/// letter case of the name is not preserved.
 pub struct Dir<'a, IO: ReadWriteSeek, TP, OCC> {
     stream: DirRawStream<'a, IO, TP, OCC>,
     fs: &'a FileSystem<IO, TP, OCC>,
+    // absolute path of this directory without the trailing slash (empty for the root directory)
+    #[cfg(feature = "alloc")]
+    path: String,
 }
 
 impl<'a, IO: ReadWriteSeek, TP, OCC> Dir<'a, IO, TP, OCC> {
     pub(crate) fn new(stream: DirRawStream<'a, IO, TP, OCC>, fs: &'a FileSystem<IO, TP, OCC>) -> Self {
-        Dir { stream, fs }
+        Dir {
+            stream,
+            fs,
+            #[cfg(feature = "alloc")]
+            path: String::new(),
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    pub(crate) fn with_path(self, path: String) -> Self {
+        Dir { path, ..self }
     }
 
     /// Creates directory entries iterator.
     #[must_use]
     #[allow(clippy::iter_not_returning_iterator)]
     pub fn iter(&self) -> DirIter<'a, IO, TP, OCC> {
-        DirIter::new(self.stream.clone(), self.fs, true)
+        DirIter::new(self, true)
+    }
+
+    /// Get the extents of a directory on disk.
+    ///
+    /// This returns an iterator over the byte ranges on-disk occupied by this directory. See `File::extents`.
+    /// For the FAT12/FAT16 root directory a single extent covering the root directory region is returned.
+    pub fn extents(&self) -> impl Iterator<Item = Result<Extent, Error<IO::Error>>> + 'a {
+        let (file_extents, root_extent) = match &self.stream {
+            DirRawStream::File(file) => (Some(file.extents()), None),
+            DirRawStream::Root(slice) => (None, Some(Ok(slice.extent()))),
+        };
+        file_extents.into_iter().flatten().chain(root_extent)
     }
 }
 
 impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, TP, OCC> {
+    /// Creates an iterator over directory entries that are files.
+    ///
+    /// Works like `iter` but skips directories. Errors are passed through and end the iteration.
+    pub fn iter_files(&self) -> impl Iterator<Item = Result<DirEntry<'a, IO, TP, OCC>, Error<IO::Error>>> + 'a {
+        self.iter().filter(|r| r.as_ref().map_or(true, DirEntry::is_file))
+    }
+
+    /// Creates an iterator over directory entries that are directories.
+    ///
+    /// Works like `iter` but skips files. Note: `.` and `..` entries are included if present.
+    /// Errors are passed through and end the iteration.
+    pub fn iter_dirs(&self) -> impl Iterator<Item = Result<DirEntry<'a, IO, TP, OCC>, Error<IO::Error>>> + 'a {
+        self.iter().filter(|r| r.as_ref().map_or(true, DirEntry::is_dir))
+    }
+
+    /// Creates an iterator over deleted entries in this directory.
+    ///
+    /// Only short name entries are returned - long names of deleted entries are not restored. Recovery of deleted
+    /// files is best-effort: see `DeletedDirEntry`. An error ends the iteration.
+    pub fn iter_deleted(&self) -> impl Iterator<Item = Result<DeletedDirEntry, Error<IO::Error>>> + 'a {
+        let mut stream = self.stream.clone();
+        let fat_type = self.fs.fat_type();
+        let mut offset = 0;
+        let mut done = false;
+        core::iter::from_fn(move || {
+            while !done {
+                let raw_entry = match DirEntryData::deserialize(&mut stream) {
+                    Ok(raw_entry) => raw_entry,
+                    Err(err) => {
+                        done = true;
+                        return Some(Err(err));
+                    }
+                };
+                let entry_offset = offset;
+                offset += u64::from(DIR_ENTRY_SIZE);
+                match raw_entry {
+                    _ if raw_entry.is_end() => done = true,
+                    DirEntryData::File(data) if data.is_deleted() && !data.is_volume() => {
+                        return Some(Ok(DeletedDirEntry::new(data, fat_type, entry_offset)));
+                    }
+                    _ => {}
+                }
+            }
+            None
+        })
+    }
+
+    /// Returns the number of free 32-byte entry slots in a directory that has a fixed size.
+    ///
+    /// Only the root directory on FAT12/FAT16 volumes has a fixed size (the maximal number of root directory
+    /// entries is set when formatting the volume). Slots after the last used entry and slots of deleted entries are
+    /// counted as free. Note that a file with a long name needs multiple slots in a row (one for every 13 characters
+    /// of the name plus one for the short name). `None` is returned for other directories because they are extended
+    /// by allocating new clusters when needed.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn free_entry_slots(&self) -> Result<Option<usize>, Error<IO::Error>> {
+        let mut stream = self.stream.clone();
+        let total_slots = match &mut stream {
+            DirRawStream::Root(slice) => slice.seek(SeekFrom::End(0))? / u64::from(DIR_ENTRY_SIZE),
+            DirRawStream::File(_) => return Ok(None),
+        };
+        stream.seek(SeekFrom::Start(0))?;
+        let mut used_slots = 0;
+        for _ in 0..total_slots {
+            let raw_entry = DirEntryData::deserialize(&mut stream)?;
+            if raw_entry.is_end() {
+                break;
+            }
+            if !raw_entry.is_deleted() {
+                used_slots += 1;
+            }
+        }
+        // Note: the root directory size is limited by a 16-bit field
+        #[allow(clippy::cast_possible_truncation)]
+        Ok(Some((total_slots - used_slots) as usize))
+    }
+
+    /// Creates an iterator walking recursively over all entries in this directory and its subdirectories.
+    ///
+    /// Traversal is depth-first and a directory entry is returned before the content of the directory. Items are
+    /// pairs of an entry path relative to this directory (components are separated by `/`) and the entry itself.
+    /// `.` and `..` entries are skipped. An error ends iteration of the directory that caused it and the traversal
+    /// continues in the parent directory. Every directory is entered at most once so a corrupted tree containing a
+    /// loop is not followed infinitely. A subdirectory entry without a cluster is reported as
+    /// `Error::CorruptedFileSystem` and it is not entered.
+    #[cfg(feature = "alloc")]
+    #[allow(clippy::type_complexity)]
+    pub fn walk(&self) -> impl Iterator<Item = Result<(String, DirEntry<'a, IO, TP, OCC>), Error<IO::Error>>> + 'a {
+        // first clusters of visited directories kept sorted for binary search
+        let mut visited: Vec<u32> = self.stream.first_cluster().into_iter().collect();
+        let mut stack = vec![(String::new(), self.iter())];
+        core::iter::from_fn(move || loop {
+            let (prefix, dir_iter) = stack.last_mut()?;
+            let e = match dir_iter.next() {
+                Some(Ok(e)) => e,
+                Some(Err(err)) => return Some(Err(err)),
+                None => {
+                    stack.pop();
+                    continue;
+                }
+            };
+            let name = e.file_name();
+            if name == "." || name == ".." {
+                continue;
+            }
+            let path = if prefix.is_empty() {
+                name
+            } else {
+                format!("{}/{}", prefix, name)
+            };
+            if e.is_dir() {
+                let Some(cluster) = e.first_cluster() else {
+                    // a subdirectory without a cluster would resolve to the root directory
+                    error!("Directory entry without a cluster in directory tree");
+                    return Some(Err(Error::CorruptedFileSystem));
+                };
+                if let Err(pos) = visited.binary_search(&cluster) {
+                    visited.insert(pos, cluster);
+                    stack.push((path.clone(), e.to_dir().iter()));
+                } else {
+                    warn!("Directory loop detected at cluster {}", cluster);
+                }
+            }
+            return Some(Ok((path, e)));
+        })
+    }
+
+    /// Returns the total size of all files in this directory and its subdirectories.
+    ///
+    /// File sizes are summed recursively. Directories themselves do not add to the result and `.` and `..` entries
+    /// are skipped. Every directory is visited at most once so a corrupted tree containing a loop is not followed
+    /// infinitely. Note: the result is the sum of file lengths, not the space allocated for them on the volume.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::CorruptedFileSystem` will be returned if a subdirectory entry has no cluster or a cluster chain is
+    ///   invalid.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg(feature = "alloc")]
+    pub fn total_size(&self) -> Result<u64, Error<IO::Error>> {
+        trace!("Dir::total_size");
+        let mut total_size = 0;
+        // first clusters of visited directories kept sorted for binary search
+        let mut visited: Vec<u32> = self.stream.first_cluster().into_iter().collect();
+        // explicit stack is used instead of recursion so deep trees cannot overflow the call stack
+        let mut stack = vec![self.iter()];
+        while let Some(dir_iter) = stack.pop() {
+            for r in dir_iter {
+                let e = r?;
+                if !e.is_dir() {
+                    total_size += e.len();
+                    continue;
+                }
+                let name = e.short_file_name_as_bytes();
+                if name == b"." || name == b".." {
+                    continue;
+                }
+                let Some(cluster) = e.first_cluster() else {
+                    // a subdirectory without a cluster would resolve to the root directory
+                    error!("Directory entry without a cluster in directory tree");
+                    return Err(Error::CorruptedFileSystem);
+                };
+                if let Err(pos) = visited.binary_search(&cluster) {
+                    visited.insert(pos, cluster);
+                    stack.push(e.to_dir().iter());
+                } else {
+                    warn!("Directory loop detected at cluster {}", cluster);
+                }
+            }
+        }
+        Ok(total_size)
+    }
+
     fn find_entry(
         &self,
         name: &str,
@@ -160,7 +382,7 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
 
     #[allow(clippy::type_complexity)]
     pub(crate) fn find_volume_entry(&self) -> Result<Option<DirEntry<'a, IO, TP, OCC>>, Error<IO::Error>> {
-        for r in DirIter::new(self.stream.clone(), self.fs, false) {
+        for r in DirIter::new(self, false) {
             let e = r?;
             if e.data.is_volume() {
                 return Ok(Some(e));
@@ -185,7 +407,7 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
                 Err(err) => return Err(err),
                 // directory already exists - return it
                 Ok(e) => return Ok(DirEntryOrShortName::DirEntry(e)),
-            };
+            }
             // try to generate short name
             if let Ok(name) = short_name_gen.generate() {
                 return Ok(DirEntryOrShortName::ShortName(name));
@@ -196,6 +418,49 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
         }
     }
 
+    fn find_entry_by_path(&self, path: &str) -> Result<DirEntry<'a, IO, TP, OCC>, Error<IO::Error>> {
+        let (name, rest_opt) = split_path(path);
+        let e = self.find_entry(name, None, None)?;
+        match rest_opt {
+            Some(rest) if e.is_dir() => e.to_dir().find_entry_by_path(rest),
+            // intermediate path component is a file
+            Some(_) => Err(Error::NotFound),
+            None => Ok(e),
+        }
+    }
+
+    /// Checks if a file or a directory exists.
+    ///
+    /// `path` is a '/' separated path relative to self directory. `false` is returned if any component of the path
+    /// does not exist or is not a directory.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn exists(&self, path: &str) -> Result<bool, Error<IO::Error>> {
+        trace!("Dir::exists {}", path);
+        match self.find_entry_by_path(path) {
+            Ok(_) => Ok(true),
+            Err(Error::NotFound) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns metadata of a file or a directory.
+    ///
+    /// `path` is a '/' separated path relative to self directory.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::NotFound` will be returned if `path` does not point to any existing directory entry.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn metadata(&self, path: &str) -> Result<Metadata, Error<IO::Error>> {
+        trace!("Dir::metadata {}", path);
+        Ok(self.find_entry_by_path(path)?.metadata())
+    }
+
     /// Opens existing subdirectory.
     ///
     /// `path` is a '/' separated directory path relative to self directory.
@@ -220,6 +485,8 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     /// Opens existing file.
     ///
     /// `path` is a '/' separated file path relative to self directory.
+    /// If the file has the read-only attribute, writing to it or truncating it fails with
+    /// `Error::PermissionDenied`. Use `open_file_ignore_readonly` to modify such file anyway.
     ///
     /// # Errors
     ///
@@ -230,17 +497,48 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
     pub fn open_file(&self, path: &str) -> Result<File<'a, IO, TP, OCC>, Error<IO::Error>> {
         trace!("Dir::open_file {}", path);
+        self.open_options().read(true).write(true).open(path)
+    }
+
+    fn open_existing_file(&self, path: &str) -> Result<File<'a, IO, TP, OCC>, Error<IO::Error>> {
         // traverse path
         let (name, rest_opt) = split_path(path);
         if let Some(rest) = rest_opt {
             let e = self.find_entry(name, Some(true), None)?;
-            return e.to_dir().open_file(rest);
+            return e.to_dir().open_existing_file(rest);
         }
         // convert entry to a file
         let e = self.find_entry(name, Some(false), None)?;
         Ok(e.to_file())
     }
 
+    /// Returns options for opening a file.
+    ///
+    /// The returned builder works like `std::fs::OpenOptions`. All options are initially set to `false`.
+    #[must_use]
+    pub fn open_options(&self) -> OpenOptions<'_, 'a, IO, TP, OCC> {
+        OpenOptions::new(self)
+    }
+
+    /// Opens existing file ignoring its read-only attribute.
+    ///
+    /// Works like `open_file` but the returned file can be written to and truncated even if it has the read-only
+    /// attribute. The attribute itself is not changed.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::NotFound` will be returned if `path` points to a non-existing directory entry.
+    /// * `Error::InvalidInput` will be returned if `path` points to a file that is a directory.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn open_file_ignore_readonly(&self, path: &str) -> Result<File<'a, IO, TP, OCC>, Error<IO::Error>> {
+        trace!("Dir::open_file_ignore_readonly {}", path);
+        let mut file = self.open_file(path)?;
+        file.set_ignore_read_only(true);
+        Ok(file)
+    }
+
     /// Creates new or opens existing file=.
     ///
     /// `path` is a '/' separated file path relative to `self` directory.
@@ -253,17 +551,51 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     /// * `Error::InvalidInput` will be returned if `path` points to an existing file that is a directory.
     /// * `Error::InvalidFileNameLength` will be returned if the file name is empty or if it is too long.
     /// * `Error::UnsupportedFileNameCharacter` will be returned if the file name contains an invalid character.
-    /// * `Error::NotEnoughSpace` will be returned if there is not enough free space to create a new file.
+    /// * `Error::NotEnoughSpace` will be returned if there is not enough free space to create a new file or if
+    ///   there are not enough free entries in the fixed-size root directory of a FAT12/FAT16 volume.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
     /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
     pub fn create_file(&self, path: &str) -> Result<File<'a, IO, TP, OCC>, Error<IO::Error>> {
         trace!("Dir::create_file {}", path);
+        self.open_options().read(true).write(true).create(true).open(path)
+    }
+
+    /// Creates new file and fails if it already exists.
+    ///
+    /// `path` is a '/' separated file path relative to `self` directory. All directories in `path` except the last
+    /// one must already exist. Only the last component is created. Checking for existence and creating the file is
+    /// done in one call so it can be used for lock files or to avoid overwriting existing files.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::AlreadyExists` will be returned if `path` points to an existing directory entry.
+    /// * `Error::NotFound` will be returned if a directory in `path` other than the last one does not exist.
+    /// * `Error::InvalidFileNameLength` will be returned if the file name is empty or if it is too long.
+    /// * `Error::UnsupportedFileNameCharacter` will be returned if the file name contains an invalid character.
+    /// * `Error::NotEnoughSpace` will be returned if there is not enough free space to create a new file or if
+    ///   there are not enough free entries in the fixed-size root directory of a FAT12/FAT16 volume.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn create_new_file(&self, path: &str) -> Result<File<'a, IO, TP, OCC>, Error<IO::Error>> {
+        trace!("Dir::create_new_file {}", path);
+        self.open_options().read(true).write(true).create_new(true).open(path)
+    }
+
+    fn create_file_internal(&self, path: &str, open_existing: bool) -> Result<File<'a, IO, TP, OCC>, Error<IO::Error>> {
+        self.fs.check_writable()?;
         // traverse path
         let (name, rest_opt) = split_path(path);
         if let Some(rest) = rest_opt {
-            return self.find_entry(name, Some(true), None)?.to_dir().create_file(rest);
+            return self
+                .find_entry(name, Some(true), None)?
+                .to_dir()
+                .create_file_internal(rest, open_existing);
         }
         // this is final filename in the path
-        let r = self.check_for_existence(name, Some(false))?;
+        let is_dir = if open_existing { Some(false) } else { None };
+        let r = self.check_for_existence(name, is_dir)?;
         match r {
             // file does not exist - create it
             DirEntryOrShortName::ShortName(short_name) => {
@@ -271,32 +603,125 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
                 Ok(self.write_entry(name, sfn_entry)?.to_file())
             }
             // file already exists - return it
-            DirEntryOrShortName::DirEntry(e) => Ok(e.to_file()),
+            DirEntryOrShortName::DirEntry(e) if open_existing => Ok(e.to_file()),
+            DirEntryOrShortName::DirEntry(_) => Err(Error::AlreadyExists),
         }
     }
 
-    /// Creates new directory or opens existing.
+    /// Creates new file with an explicitly provided short (8.3) name.
     ///
-    /// `path` is a '/' separated path relative to self directory.
+    /// `path` is a '/' separated file path relative to `self` directory. Its last component is used as the long
+    /// name of the new file. `short_name` is written as is instead of a generated one, so it must be in the raw
+    /// on-disk format: upper-case, 8 characters of the base name followed by 3 characters of the extension,
+    /// both padded with spaces (e.g. `*b"README  TXT"`). Characters are encoded in the OEM codepage. A leading 0xE5
+    /// byte is stored as 0x05 because 0xE5 marks deleted entries.
     ///
     /// # Errors
     ///
     /// Errors that can be returned:
     ///
-    /// * `Error::InvalidInput` will be returned if `path` points to an existing file that is not a directory.
+    /// * `Error::AlreadyExists` will be returned if an entry with the same long or short name already exists.
+    /// * `Error::InvalidInput` will be returned if `attrs` contains the `DIRECTORY` or `VOLUME_ID` attribute.
+    /// * `Error::InvalidFileNameLength` will be returned if the long name is empty or if it is too long or if the
+    ///   short name has an empty base name.
+    /// * `Error::UnsupportedFileNameCharacter` will be returned if the long name or the short name contains an
+    ///   invalid character.
+    /// * `Error::NotEnoughSpace` will be returned if there is not enough free space to create a new file or if
+    ///   there are not enough free entries in the fixed-size root directory of a FAT12/FAT16 volume.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn create_file_with_short_name(
+        &self,
+        path: &str,
+        short_name: [u8; SFN_SIZE],
+        attrs: FileAttributes,
+    ) -> Result<File<'a, IO, TP, OCC>, Error<IO::Error>> {
+        trace!("Dir::create_file_with_short_name {} {:?}", path, short_name);
+        self.fs.check_writable()?;
+        // traverse path
+        let (name, rest_opt) = split_path(path);
+        if let Some(rest) = rest_opt {
+            return self
+                .find_entry(name, Some(true), None)?
+                .to_dir()
+                .create_file_with_short_name(rest, short_name, attrs);
+        }
+        if attrs.intersects(FileAttributes::DIRECTORY | FileAttributes::VOLUME_ID) {
+            return Err(Error::InvalidInput);
+        }
+        validate_long_name(name)?;
+        validate_short_name(&short_name)?;
+        // make sure neither name is used by an existing entry
+        let raw_short_name = encode_short_name(short_name);
+        for r in self.iter() {
+            let e = r?;
+            if e.eq_name(name) || e.raw_short_name() == &raw_short_name {
+                return Err(Error::AlreadyExists);
+            }
+        }
+        let sfn_entry = self.create_sfn_entry(short_name, attrs, None);
+        Ok(self.write_entry(name, sfn_entry)?.to_file())
+    }
+
+    /// Creates new directory.
+    ///
+    /// `path` is a '/' separated path relative to self directory. All directories in `path` except the last one
+    /// must already exist. Like `std::fs::create_dir` this function fails if the last component of `path` already
+    /// exists. Use `open_or_create_dir` to open an existing directory instead.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::AlreadyExists` will be returned if `path` points to an existing directory entry.
+    /// * `Error::NotFound` will be returned if a directory in `path` other than the last one does not exist.
     /// * `Error::InvalidFileNameLength` will be returned if the file name is empty or if it is too long.
     /// * `Error::UnsupportedFileNameCharacter` will be returned if the file name contains an invalid character.
-    /// * `Error::NotEnoughSpace` will be returned if there is not enough free space to create a new directory.
+    /// * `Error::NotEnoughSpace` will be returned if there is not enough free space to create a new directory or if
+    ///   there are not enough free entries in the fixed-size root directory of a FAT12/FAT16 volume.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
     /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
     pub fn create_dir(&self, path: &str) -> Result<Self, Error<IO::Error>> {
         trace!("Dir::create_dir {}", path);
+        self.create_dir_internal(path, false)
+    }
+
+    /// Opens existing directory or creates new one.
+    ///
+    /// `path` is a '/' separated path relative to self directory. All directories in `path` except the last one
+    /// must already exist. This function is idempotent: calling it again with the same `path` opens the directory
+    /// created by the first call.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if `path` points to an existing file that is not a directory.
+    /// * `Error::NotFound` will be returned if a directory in `path` other than the last one does not exist.
+    /// * `Error::InvalidFileNameLength` will be returned if the file name is empty or if it is too long.
+    /// * `Error::UnsupportedFileNameCharacter` will be returned if the file name contains an invalid character.
+    /// * `Error::NotEnoughSpace` will be returned if there is not enough free space to create a new directory or if
+    ///   there are not enough free entries in the fixed-size root directory of a FAT12/FAT16 volume.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn open_or_create_dir(&self, path: &str) -> Result<Self, Error<IO::Error>> {
+        trace!("Dir::open_or_create_dir {}", path);
+        self.create_dir_internal(path, true)
+    }
+
+    fn create_dir_internal(&self, path: &str, open_existing: bool) -> Result<Self, Error<IO::Error>> {
+        self.fs.check_writable()?;
         // traverse path
         let (name, rest_opt) = split_path(path);
         if let Some(rest) = rest_opt {
-            return self.find_entry(name, Some(true), None)?.to_dir().create_dir(rest);
+            return self
+                .find_entry(name, Some(true), None)?
+                .to_dir()
+                .create_dir_internal(rest, open_existing);
         }
         // this is final filename in the path
-        let r = self.check_for_existence(name, Some(true))?;
+        let is_dir = if open_existing { Some(true) } else { None };
+        let r = self.check_for_existence(name, is_dir)?;
         match r {
             // directory does not exist - create it
             DirEntryOrShortName::ShortName(short_name) => {
@@ -304,7 +729,14 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
                 let cluster = self.fs.alloc_cluster(None, true)?;
                 // create entry in parent directory
                 let sfn_entry = self.create_sfn_entry(short_name, FileAttributes::DIRECTORY, Some(cluster));
-                let entry = self.write_entry(name, sfn_entry)?;
+                let entry = match self.write_entry(name, sfn_entry) {
+                    Ok(e) => e,
+                    Err(err) => {
+                        // do not leak the directory cluster if there is no space for its entry
+                        self.fs.free_cluster_chain(cluster)?;
+                        return Err(err);
+                    }
+                };
                 let dir = entry.to_dir();
                 // create special entries "." and ".."
                 let dot_sfn = ShortNameGenerator::generate_dot();
@@ -322,11 +754,22 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
                 Ok(dir)
             }
             // directory already exists - return it
-            DirEntryOrShortName::DirEntry(e) => Ok(e.to_dir()),
+            DirEntryOrShortName::DirEntry(e) if open_existing => Ok(e.to_dir()),
+            DirEntryOrShortName::DirEntry(_) => Err(Error::AlreadyExists),
         }
     }
 
-    fn is_empty(&self) -> Result<bool, Error<IO::Error>> {
+    /// Checks if this directory contains no files or subdirectories.
+    ///
+    /// Special entries `.` and `..` are ignored so a newly created directory is empty.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::CorruptedFileSystem` will be returned if the directory cluster chain is invalid.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn is_empty(&self) -> Result<bool, Error<IO::Error>> {
         trace!("Dir::is_empty");
         // check if directory contains no files
         for r in self.iter() {
@@ -353,9 +796,11 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     /// * `Error::NotFound` will be returned if `path` points to a non-existing directory entry.
     /// * `Error::InvalidInput` will be returned if `path` points to a file that is not a directory.
     /// * `Error::DirectoryIsNotEmpty` will be returned if the specified directory is not empty.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
     /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
     pub fn remove(&self, path: &str) -> Result<(), Error<IO::Error>> {
         trace!("Dir::remove {}", path);
+        self.fs.check_writable()?;
         // traverse path
         let (name, rest_opt) = split_path(path);
         if let Some(rest) = rest_opt {
@@ -367,10 +812,78 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
         if e.is_dir() && !e.to_dir().is_empty()? {
             return Err(Error::DirectoryIsNotEmpty);
         }
-        // free data
+        self.remove_entry(&e)
+    }
+
+    /// Removes existing directory together with all of its content.
+    ///
+    /// `path` is a '/' separated directory path relative to self directory.
+    /// Make sure there is no reference to any file or directory in the removed subtree (no File or Dir instance) or
+    /// filesystem corruption can happen.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::NotFound` will be returned if `path` points to a non-existing directory entry.
+    /// * `Error::InvalidInput` will be returned if `path` points to a file that is not a directory or if its last
+    ///   component is `.` or `..`.
+    /// * `Error::CorruptedFileSystem` will be returned if a directory entry in the removed tree has no cluster.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn remove_dir_all(&self, path: &str) -> Result<(), Error<IO::Error>> {
+        trace!("Dir::remove_dir_all {}", path);
+        self.fs.check_writable()?;
+        // traverse path
+        let (name, rest_opt) = split_path(path);
+        if let Some(rest) = rest_opt {
+            let e = self.find_entry(name, Some(true), None)?;
+            return e.to_dir().remove_dir_all(rest);
+        }
+        if name == "." || name == ".." {
+            error!("Cannot remove a directory using a self or parent link");
+            return Err(Error::InvalidInput);
+        }
+        let e = self.find_entry(name, Some(true), None)?;
+        if e.first_cluster().is_none() {
+            error!("Directory entry without a cluster");
+            return Err(Error::CorruptedFileSystem);
+        }
+        e.to_dir().remove_contents()?;
+        self.remove_entry(&e)
+    }
+
+    fn remove_contents(&self) -> Result<(), Error<IO::Error>> {
+        trace!("Dir::remove_contents");
+        for r in self.iter() {
+            let e = r?;
+            let name = e.short_file_name_as_bytes();
+            // ignore special entries "." and ".." so parent directories are never visited
+            if name == b"." || name == b".." {
+                continue;
+            }
+            if e.is_dir() {
+                if e.first_cluster().is_none() {
+                    // a subdirectory without a cluster would resolve to the root directory
+                    error!("Directory entry without a cluster in directory tree");
+                    return Err(Error::CorruptedFileSystem);
+                }
+                e.to_dir().remove_contents()?;
+            }
+            self.remove_entry(&e)?;
+        }
+        Ok(())
+    }
+
+    fn remove_entry(&self, e: &DirEntry<'a, IO, TP, OCC>) -> Result<(), Error<IO::Error>> {
+        // free data before the entry so clusters are never left unreferenced but still allocated
         if let Some(n) = e.first_cluster() {
             self.fs.free_cluster_chain(n)?;
         }
+        self.mark_entry_slots_deleted(e)
+    }
+
+    fn mark_entry_slots_deleted(&self, e: &DirEntry<'a, IO, TP, OCC>) -> Result<(), Error<IO::Error>> {
         // free long and short name entries
         let mut stream = self.stream.clone();
         stream.seek(SeekFrom::Start(e.offset_range.0))?;
@@ -400,9 +913,14 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     /// * `Error::NotFound` will be returned if `src_path` points to a non-existing directory entry or if `dst_path`
     ///   stripped from the last component does not point to an existing directory.
     /// * `Error::AlreadyExists` will be returned if `dst_path` points to an existing directory entry.
+    /// * `Error::InvalidFileNameLength` will be returned if the destination file name is empty or if it is too long.
+    /// * `Error::UnsupportedFileNameCharacter` will be returned if the destination file name contains an invalid
+    ///   character.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
     /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
     pub fn rename(&self, src_path: &str, dst_dir: &Dir<IO, TP, OCC>, dst_path: &str) -> Result<(), Error<IO::Error>> {
         trace!("Dir::rename {} {}", src_path, dst_path);
+        self.fs.check_writable()?;
         // traverse source path
         let (src_name, src_rest_opt) = split_path(src_path);
         if let Some(rest) = src_rest_opt {
@@ -419,6 +937,34 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
         self.rename_internal(src_path, dst_dir, dst_path)
     }
 
+    /// Renames existing file or directory without moving it to another directory.
+    ///
+    /// `old_name` and `new_name` are names of entries in this directory. It works like `rename` with `self` passed as
+    /// the destination directory: new long and short name entries are created for `new_name` and the first cluster,
+    /// size, attributes and timestamps are preserved. Nothing is changed if `new_name` refers to the same entry as
+    /// `old_name`.
+    /// Make sure there is no reference to this file (no File instance) or filesystem corruption
+    /// can happen.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::NotFound` will be returned if `old_name` does not point to an existing directory entry.
+    /// * `Error::AlreadyExists` will be returned if `new_name` points to an existing directory entry.
+    /// * `Error::InvalidFileNameLength` will be returned if `new_name` is empty or if it is too long.
+    /// * `Error::UnsupportedFileNameCharacter` will be returned if `new_name` contains an invalid character (including
+    ///   a path separator).
+    /// * `Error::NotEnoughSpace` will be returned if there is not enough free space to create the new directory
+    ///   entries.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn rename_within(&self, old_name: &str, new_name: &str) -> Result<(), Error<IO::Error>> {
+        trace!("Dir::rename_within {} {}", old_name, new_name);
+        self.fs.check_writable()?;
+        self.rename_internal(old_name, self, new_name)
+    }
+
     fn rename_internal(
         &self,
         src_name: &str,
@@ -426,6 +972,8 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
         dst_name: &str,
     ) -> Result<(), Error<IO::Error>> {
         trace!("Dir::rename_internal {} {}", src_name, dst_name);
+        // validate the new name before the source entry is removed so an invalid name does not orphan the file
+        validate_long_name(dst_name)?;
         // find existing file
         let e = self.find_entry(src_name, None, None)?;
         // check if destionation filename is unused
@@ -441,26 +989,188 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
                 // destination file exists and it is not the same as source file - fail
                 return Err(Error::AlreadyExists);
             }
-            // destionation file does not exist, short name has been generated
-            DirEntryOrShortName::ShortName(short_name) => short_name,
-        };
-        // free long and short name entries
-        let mut stream = self.stream.clone();
-        stream.seek(SeekFrom::Start(e.offset_range.0))?;
-        let num = ((e.offset_range.1 - e.offset_range.0) / u64::from(DIR_ENTRY_SIZE)) as usize;
-        for _ in 0..num {
-            let mut data = DirEntryData::deserialize(&mut stream)?;
-            trace!("removing LFN entry {:?}", data);
-            data.set_deleted();
-            stream.seek(SeekFrom::Current(-i64::from(DIR_ENTRY_SIZE)))?;
-            data.serialize(&mut stream)?;
+            // destionation file does not exist, short name has been generated
+            DirEntryOrShortName::ShortName(short_name) => short_name,
+        };
+        #[cfg(not(feature = "lfn"))]
+        validate_name_stored_as_short_name(dst_name, &short_name, &self.fs.options.oem_cp_converter)?;
+        self.mark_entry_slots_deleted(&e)?;
+        // save new directory entry
+        let sfn_entry = e.data.renamed(short_name);
+        dst_dir.write_entry(dst_name, sfn_entry)?;
+        Ok(())
+    }
+
+    /// Renames or moves existing file or directory replacing the destination if it exists.
+    ///
+    /// `src_path` is a '/' separated source file path relative to self directory.
+    /// `dst_path` is a '/' separated destination file path relative to `dst_dir`.
+    /// If the destination does not exist this method behaves like `rename`. Otherwise the destination directory
+    /// entry is overwritten in place with the source entry (keeping the destination name) and the destination data
+    /// is freed afterwards, so no new directory entries or clusters have to be allocated. A file can only replace
+    /// a file and a directory can only replace an empty directory.
+    /// Make sure there is no reference to the source or destination file (no File instance) or filesystem
+    /// corruption can happen.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::NotFound` will be returned if `src_path` points to a non-existing directory entry or if `dst_path`
+    ///   stripped from the last component does not point to an existing directory.
+    /// * `Error::InvalidInput` will be returned if the source is a file and the destination is a directory or vice
+    ///   versa.
+    /// * `Error::DirectoryIsNotEmpty` will be returned if the destination is a directory that is not empty.
+    /// * `Error::PermissionDenied` will be returned if the destination is a file with the read-only attribute.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn rename_overwrite(
+        &self,
+        src_path: &str,
+        dst_dir: &Dir<IO, TP, OCC>,
+        dst_path: &str,
+    ) -> Result<(), Error<IO::Error>> {
+        trace!("Dir::rename_overwrite {} {}", src_path, dst_path);
+        self.fs.check_writable()?;
+        // traverse source path
+        let (src_name, src_rest_opt) = split_path(src_path);
+        if let Some(rest) = src_rest_opt {
+            let e = self.find_entry(src_name, Some(true), None)?;
+            return e.to_dir().rename_overwrite(rest, dst_dir, dst_path);
+        }
+        // traverse destination path
+        let (dst_name, dst_rest_opt) = split_path(dst_path);
+        if let Some(rest) = dst_rest_opt {
+            let e = dst_dir.find_entry(dst_name, Some(true), None)?;
+            return self.rename_overwrite(src_path, &e.to_dir(), rest);
+        }
+        self.rename_overwrite_internal(src_path, dst_dir, dst_path)
+    }
+
+    fn rename_overwrite_internal(
+        &self,
+        src_name: &str,
+        dst_dir: &Dir<IO, TP, OCC>,
+        dst_name: &str,
+    ) -> Result<(), Error<IO::Error>> {
+        trace!("Dir::rename_overwrite_internal {} {}", src_name, dst_name);
+        let e = self.find_entry(src_name, None, None)?;
+        let dst_e = match dst_dir.check_for_existence(dst_name, None)? {
+            DirEntryOrShortName::DirEntry(dst_e) => dst_e,
+            DirEntryOrShortName::ShortName(_) => return self.rename_internal(src_name, dst_dir, dst_name),
+        };
+        if e.is_same_entry(&dst_e) {
+            // nothing to do
+            return Ok(());
+        }
+        // validate everything before modifying anything
+        match (e.is_dir(), dst_e.is_dir()) {
+            (false, false) => {
+                if dst_e.attributes().contains(FileAttributes::READ_ONLY) {
+                    return Err(Error::PermissionDenied);
+                }
+            }
+            (true, true) => {
+                if dst_e.first_cluster().is_none() {
+                    error!("Directory entry without a cluster");
+                    return Err(Error::CorruptedFileSystem);
+                }
+                if !dst_e.to_dir().is_empty()? {
+                    return Err(Error::DirectoryIsNotEmpty);
+                }
+            }
+            _ => return Err(Error::InvalidInput),
+        }
+        // reuse destination entry, then remove the source entry and free old destination data
+        dst_e.replace_data(&e.data)?;
+        self.mark_entry_slots_deleted(&e)?;
+        if let Some(n) = dst_e.first_cluster() {
+            self.fs.free_cluster_chain(n)?;
+        }
+        Ok(())
+    }
+
+    /// Copies existing file.
+    ///
+    /// `src_path` is a '/' separated source file path relative to self directory.
+    /// `dst_path` is a '/' separated destination file path relative to `dst_dir`.
+    /// File content is copied in small chunks so memory usage does not depend on the file size. Attributes and
+    /// timestamps of the new file are copied from the source file.
+    /// If copying fails the partially written destination file is removed.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::NotFound` will be returned if `src_path` points to a non-existing directory entry or if `dst_path`
+    ///   stripped from the last component does not point to an existing directory.
+    /// * `Error::InvalidInput` will be returned if `src_path` points to a directory.
+    /// * `Error::AlreadyExists` will be returned if `dst_path` points to an existing directory entry.
+    /// * `Error::InvalidFileNameLength` will be returned if the destination file name is empty or if it is too long.
+    /// * `Error::UnsupportedFileNameCharacter` will be returned if the destination file name contains an invalid
+    ///   character.
+    /// * `Error::NotEnoughSpace` will be returned if there is not enough free space to copy the file.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn copy_file(
+        &self,
+        src_path: &str,
+        dst_dir: &Dir<IO, TP, OCC>,
+        dst_path: &str,
+    ) -> Result<(), Error<IO::Error>> {
+        trace!("Dir::copy_file {} {}", src_path, dst_path);
+        self.fs.check_writable()?;
+        // traverse source path
+        let (src_name, src_rest_opt) = split_path(src_path);
+        if let Some(rest) = src_rest_opt {
+            let e = self.find_entry(src_name, Some(true), None)?;
+            return e.to_dir().copy_file(rest, dst_dir, dst_path);
+        }
+        // traverse destination path
+        let (dst_name, dst_rest_opt) = split_path(dst_path);
+        if let Some(rest) = dst_rest_opt {
+            let e = dst_dir.find_entry(dst_name, Some(true), None)?;
+            return self.copy_file(src_path, &e.to_dir(), rest);
+        }
+        // find source file
+        let src_entry = self.find_entry(src_name, Some(false), None)?;
+        // check if destination filename is unused
+        match dst_dir.find_entry(dst_name, None, None) {
+            Err(Error::NotFound) => {}
+            Err(err) => return Err(err),
+            Ok(_) => return Err(Error::AlreadyExists),
+        }
+        let mut src_file = src_entry.to_file();
+        let mut dst_file = dst_dir.create_file(dst_name)?;
+        if let Err(err) = Self::copy_file_content(&mut src_file, &mut dst_file, &src_entry.data) {
+            // remove partially written file so its clusters are not leaked
+            drop(dst_file);
+            if let Err(remove_err) = dst_dir.remove(dst_name) {
+                error!("failed to remove partially copied file: {:?}", remove_err);
+            }
+            return Err(err);
         }
-        // save new directory entry
-        let sfn_entry = e.data.renamed(short_name);
-        dst_dir.write_entry(dst_name, sfn_entry)?;
         Ok(())
     }
 
+    fn copy_file_content(
+        src_file: &mut File<'_, IO, TP, OCC>,
+        dst_file: &mut File<'_, IO, TP, OCC>,
+        src_data: &DirFileEntryData,
+    ) -> Result<(), Error<IO::Error>> {
+        let mut buf = [0_u8; COPY_BUFFER_SIZE];
+        loop {
+            let n = src_file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            dst_file.write_all(&buf[..n])?;
+        }
+        // timestamps must be set after writing data because every write updates the modification time
+        dst_file.copy_metadata_from(src_data);
+        dst_file.flush()
+    }
+
     fn find_free_entries(&self, num_entries: u32) -> Result<DirRawStream<'a, IO, TP, OCC>, Error<IO::Error>> {
         let mut stream = self.stream.clone();
         let mut first_free: u32 = 0;
@@ -474,6 +1184,13 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
                     first_free = i;
                 }
                 let pos = u64::from(first_free * DIR_ENTRY_SIZE);
+                if let DirRawStream::Root(slice) = &mut stream {
+                    // root directory on FAT12/FAT16 has a fixed size and cannot be extended
+                    let root_dir_size = slice.seek(io::SeekFrom::End(0))?;
+                    if pos + u64::from(num_entries * DIR_ENTRY_SIZE) > root_dir_size {
+                        return Err(Error::NotEnoughSpace);
+                    }
+                }
                 stream.seek(io::SeekFrom::Start(pos))?;
                 return Ok(stream);
             } else if raw_entry.is_deleted() {
@@ -511,15 +1228,6 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
         raw_entry
     }
 
-    #[cfg(feature = "lfn")]
-    fn encode_lfn_utf16(name: &str) -> LfnBuffer {
-        LfnBuffer::from_ucs2_units(name.encode_utf16())
-    }
-    #[cfg(not(feature = "lfn"))]
-    fn encode_lfn_utf16(_name: &str) -> LfnBuffer {
-        LfnBuffer {}
-    }
-
     #[allow(clippy::type_complexity)]
     fn alloc_and_write_lfn_entries(
         &self,
@@ -541,6 +1249,7 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
         Ok((stream, start_pos))
     }
 
+    #[allow(clippy::type_complexity)]
     fn alloc_sfn_entry(&self) -> Result<(DirRawStream<'a, IO, TP, OCC>, u64), Error<IO::Error>> {
         let mut stream = self.find_free_entries(1)?;
         let start_pos = stream.seek(io::SeekFrom::Current(0))?;
@@ -550,19 +1259,16 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     fn write_entry(
         &self,
         name: &str,
-        raw_entry: DirFileEntryData,
+        mut raw_entry: DirFileEntryData,
     ) -> Result<DirEntry<'a, IO, TP, OCC>, Error<IO::Error>> {
         trace!("Dir::write_entry {}", name);
-        // check if name doesn't contain unsupported characters
-        validate_long_name(name)?;
-        // convert long name to UTF-16
-        let lfn_utf16 = Self::encode_lfn_utf16(name);
-        // write LFN entries, except for . and .., which need to be at
-        // the first two slots and don't need LFNs anyway
-        let (mut stream, start_pos) = if name == "." || name == ".." {
-            self.alloc_sfn_entry()?
-        } else {
+        let lfn_utf16 = prepare_entry_name(name, &mut raw_entry, &self.fs.options.oem_cp_converter)?;
+        let needs_lfn = !lfn_utf16.as_ucs2_units().is_empty();
+        // write LFN entries if needed
+        let (mut stream, start_pos) = if needs_lfn {
             self.alloc_and_write_lfn_entries(&lfn_utf16, raw_entry.name())?
+        } else {
+            self.alloc_sfn_entry()?
         };
         // write short name entry
         raw_entry.serialize(&mut stream)?;
@@ -584,6 +1290,8 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
             #[cfg(feature = "lfn")]
             lfn_utf16,
             fs: self.fs,
+            #[cfg(feature = "alloc")]
+            dir_path: self.path.clone(),
             entry_pos: start_abs_pos,
             offset_range: (start_pos, end_pos),
         })
@@ -596,29 +1304,208 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Clone for Dir<'_,
         Self {
             stream: self.stream.clone(),
             fs: self.fs,
+            #[cfg(feature = "alloc")]
+            path: self.path.clone(),
+        }
+    }
+}
+
+/// Options for opening a file.
+///
+/// This struct implements a builder pattern similar to `std::fs::OpenOptions`. It is created by the `open_options`
+/// method on `Dir` and the file is opened by calling `open`.
+#[allow(clippy::struct_excessive_bools)]
+pub struct OpenOptions<'b, 'a, IO: ReadWriteSeek, TP, OCC> {
+    dir: &'b Dir<'a, IO, TP, OCC>,
+    read: bool,
+    write: bool,
+    append: bool,
+    truncate: bool,
+    create: bool,
+    create_new: bool,
+}
+
+impl<'b, 'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> OpenOptions<'b, 'a, IO, TP, OCC> {
+    fn new(dir: &'b Dir<'a, IO, TP, OCC>) -> Self {
+        Self {
+            dir,
+            read: false,
+            write: false,
+            append: false,
+            truncate: false,
+            create: false,
+            create_new: false,
+        }
+    }
+
+    /// Sets the option for read access.
+    ///
+    /// If not set reading from the file returns `Error::PermissionDenied`.
+    #[must_use]
+    pub fn read(mut self, read: bool) -> Self {
+        self.read = read;
+        self
+    }
+
+    /// Sets the option for write access.
+    ///
+    /// If neither this option nor `append` is set writing to the file returns `Error::PermissionDenied`.
+    #[must_use]
+    pub fn write(mut self, write: bool) -> Self {
+        self.write = write;
+        self
+    }
+
+    /// Sets the option for the append mode.
+    ///
+    /// In the append mode every write is done at the end of the file regardless of the current position. It implies
+    /// write access.
+    #[must_use]
+    pub fn append(mut self, append: bool) -> Self {
+        self.append = append;
+        self
+    }
+
+    /// Sets the option for truncating an existing file to 0 length when it is opened.
+    ///
+    /// Requires write access.
+    #[must_use]
+    pub fn truncate(mut self, truncate: bool) -> Self {
+        self.truncate = truncate;
+        self
+    }
+
+    /// Sets the option to create a new file if it does not exist or open it if it does.
+    ///
+    /// Requires write or append access.
+    #[must_use]
+    pub fn create(mut self, create: bool) -> Self {
+        self.create = create;
+        self
+    }
+
+    /// Sets the option to create a new file and fail if it already exists.
+    ///
+    /// If set `create` and `truncate` options are ignored. Requires write or append access.
+    #[must_use]
+    pub fn create_new(mut self, create_new: bool) -> Self {
+        self.create_new = create_new;
+        self
+    }
+
+    /// Opens a file at `path` with the options specified by `self`.
+    ///
+    /// `path` is a '/' separated file path relative to the directory. All directories in `path` except the last one
+    /// must already exist.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if the combination of options is invalid (no access mode is set,
+    ///   creating or truncating without write access, truncating in the append mode) or if `path` points to a
+    ///   directory.
+    /// * `Error::NotFound` will be returned if `path` points to a non-existing directory entry and the file is not
+    ///   created.
+    /// * `Error::AlreadyExists` will be returned if `create_new` is set and `path` points to an existing entry.
+    /// * `Error::InvalidFileNameLength` will be returned if the file name is empty or if it is too long.
+    /// * `Error::UnsupportedFileNameCharacter` will be returned if the file name contains an invalid character.
+    /// * `Error::NotEnoughSpace` will be returned if there is not enough free space to create a new file or if
+    ///   there are not enough free entries in the fixed-size root directory of a FAT12/FAT16 volume.
+    /// * `Error::ReadOnlyFileSystem` will be returned if a file is created or truncated and the file system has been
+    ///   mounted in read-only mode.
+    /// * `Error::PermissionDenied` will be returned if a file that has the read-only attribute is truncated.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn open(&self, path: &str) -> Result<File<'a, IO, TP, OCC>, Error<IO::Error>> {
+        trace!("OpenOptions::open {}", path);
+        let writable = self.write || self.append;
+        if !self.read && !writable {
+            error!("No access mode set");
+            return Err(Error::InvalidInput);
+        }
+        let truncate = self.truncate && !self.create_new;
+        if !writable && (truncate || self.create || self.create_new) {
+            error!("Creating or truncating a file requires write access");
+            return Err(Error::InvalidInput);
+        }
+        if truncate && self.append {
+            error!("Truncating a file in append mode is not allowed");
+            return Err(Error::InvalidInput);
+        }
+        let mut file = if self.create_new {
+            self.dir.create_file_internal(path, false)?
+        } else if self.create {
+            self.dir.create_file_internal(path, true)?
+        } else {
+            self.dir.open_existing_file(path)?
+        };
+        file.set_access_mode(self.read, writable, self.append);
+        if truncate {
+            file.truncate()?;
         }
+        Ok(file)
     }
 }
 
 /// An iterator over the directory entries.
 ///
 /// This struct is created by the `iter` method on `Dir`.
+///
+/// The iterator can be paused and resumed later: the current position returned by `position` or an entry offset
+/// returned by `DirEntry::offset` can be stored and passed to `seek_to`.
 pub struct DirIter<'a, IO: ReadWriteSeek, TP, OCC> {
     stream: DirRawStream<'a, IO, TP, OCC>,
     fs: &'a FileSystem<IO, TP, OCC>,
+    #[cfg(feature = "alloc")]
+    dir_path: String,
+    offset: u64,
+    read_ahead: DirReadAhead,
     skip_volume: bool,
     err: bool,
 }
 
 impl<'a, IO: ReadWriteSeek, TP, OCC> DirIter<'a, IO, TP, OCC> {
-    fn new(stream: DirRawStream<'a, IO, TP, OCC>, fs: &'a FileSystem<IO, TP, OCC>, skip_volume: bool) -> Self {
+    fn new(dir: &Dir<'a, IO, TP, OCC>, skip_volume: bool) -> Self {
         DirIter {
-            stream,
-            fs,
+            stream: dir.stream.clone(),
+            fs: dir.fs,
+            #[cfg(feature = "alloc")]
+            dir_path: dir.path.clone(),
+            offset: 0,
+            read_ahead: DirReadAhead::new(),
             skip_volume,
             err: false,
         }
     }
+
+    /// Returns the byte offset in the directory of the entry that will be returned next.
+    #[must_use]
+    pub fn position(&self) -> u64 {
+        self.offset
+    }
+
+    /// Moves the iterator to the byte `offset` in the directory.
+    ///
+    /// `offset` should be a value returned by `position` or `DirEntry::offset` for the same directory. Iteration
+    /// continues from the entry starting at that offset, including its long name entries.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if `offset` is not a multiple of the directory entry size.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn seek_to(&mut self, offset: u64) -> Result<(), Error<IO::Error>> {
+        trace!("DirIter::seek_to {}", offset);
+        if offset % u64::from(DIR_ENTRY_SIZE) != 0 {
+            return Err(Error::InvalidInput);
+        }
+        self.stream.seek(SeekFrom::Start(offset))?;
+        self.offset = offset;
+        self.read_ahead.clear();
+        self.err = false;
+        Ok(())
+    }
 }
 
 impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC> DirIter<'a, IO, TP, OCC> {
@@ -636,13 +1523,20 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC> DirIter<'a, IO, TP, OCC> {
     fn read_dir_entry(&mut self) -> Result<Option<DirEntry<'a, IO, TP, OCC>>, Error<IO::Error>> {
         trace!("DirIter::read_dir_entry");
         let mut lfn_builder = LongNameBuilder::new();
-        let mut offset = self.stream.seek(SeekFrom::Current(0))?;
+        let mut offset = self.offset;
         let mut begin_offset = offset;
         loop {
-            let raw_entry = DirEntryData::deserialize(&mut self.stream)?;
+            if self.read_ahead.is_empty() {
+                self.read_ahead.fill(&mut self.stream)?;
+            }
+            let (raw_entry, abs_pos) = self.read_ahead.next_entry()?;
             offset += u64::from(DIR_ENTRY_SIZE);
             // Check if this is end of dir
             if raw_entry.is_end() {
+                // stay at the end marker so the position does not point past the end of directory and entries
+                // created after reaching the end are returned by the next call
+                self.stream.seek(SeekFrom::Start(self.offset))?;
+                self.read_ahead.clear();
                 return Ok(None);
             }
             // Check if this is deleted or volume ID entry
@@ -654,25 +1548,20 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC> DirIter<'a, IO, TP, OCC> {
             }
             match raw_entry {
                 DirEntryData::File(data) => {
-                    // Get current absolute position on the storage
-                    // Unwrapping is safe because abs_pos() returns None only if stream is at position 0. This is not
-                    // the case because an entry was just read
-                    // Note: if current position is on the cluster boundary then a position in the cluster containing the entry is
-                    // returned
-                    let end_abs_pos = self.stream.abs_pos().unwrap();
-                    // Calculate SFN entry start position on the storage
-                    let abs_pos = end_abs_pos - u64::from(DIR_ENTRY_SIZE);
                     // Check if LFN checksum is valid
                     lfn_builder.validate_chksum(data.name());
                     // Return directory entry
                     let short_name = ShortName::new(data.name());
                     trace!("file entry {:?}", data.name());
+                    self.offset = offset;
                     return Ok(Some(DirEntry {
                         data,
                         short_name,
                         #[cfg(feature = "lfn")]
                         lfn_utf16: lfn_builder.into_buf(),
                         fs: self.fs,
+                        #[cfg(feature = "alloc")]
+                        dir_path: self.dir_path.clone(),
                         entry_pos: abs_pos,
                         offset_range: (begin_offset, offset),
                     }));
@@ -687,12 +1576,29 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC> DirIter<'a, IO, TP, OCC> {
     }
 }
 
+impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> DirIter<'a, IO, TP, OCC> {
+    /// Returns an iterator over files modified after the given date and time.
+    ///
+    /// Entries with a modification time equal to or earlier than `date_time` are skipped. Directories (including the
+    /// `.` and `..` entries) are skipped too. Errors are passed through.
+    pub fn modified_since(self, date_time: DateTime) -> impl Iterator<Item = <Self as Iterator>::Item> + 'a {
+        self.filter(move |r| match r {
+            Ok(e) => !e.is_dir() && e.modified() > date_time,
+            Err(_) => true,
+        })
+    }
+}
+
 // Note: derive cannot be used because of invalid bounds. See: https://github.com/rust-lang/rust/issues/26925
 impl<IO: ReadWriteSeek, TP, OCC> Clone for DirIter<'_, IO, TP, OCC> {
     fn clone(&self) -> Self {
         Self {
             stream: self.stream.clone(),
             fs: self.fs,
+            #[cfg(feature = "alloc")]
+            dir_path: self.dir_path.clone(),
+            offset: self.offset,
+            read_ahead: self.read_ahead.clone(),
             err: self.err,
             skip_volume: self.skip_volume,
         }
@@ -718,6 +1624,138 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC> Iterator for DirIter<'a, IO,
     }
 }
 
+// Buffer of raw directory entries read ahead by `DirIter`
+//
+// Data in the buffer is always physically contiguous on the storage because a single `read` call on a directory
+// stream never crosses a boundary between non-contiguous clusters.
+#[derive(Clone)]
+struct DirReadAhead {
+    buf: [u8; DIR_READ_AHEAD_SIZE],
+    pos: usize,
+    len: usize,
+    // absolute position on the storage of the first byte in the buffer
+    abs_pos: u64,
+}
+
+impl DirReadAhead {
+    fn new() -> Self {
+        Self {
+            buf: [0; DIR_READ_AHEAD_SIZE],
+            pos: 0,
+            len: 0,
+            abs_pos: 0,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos == self.len
+    }
+
+    fn clear(&mut self) {
+        self.pos = 0;
+        self.len = 0;
+    }
+
+    fn fill<IO: ReadWriteSeek, TP: TimeProvider, OCC>(
+        &mut self,
+        stream: &mut DirRawStream<IO, TP, OCC>,
+    ) -> Result<(), Error<IO::Error>> {
+        trace!("DirReadAhead::fill");
+        self.clear();
+        let mut len = stream.read(&mut self.buf)?;
+        // complete a partially read entry (entries never cross a cluster boundary)
+        while len % DIR_ENTRY_SIZE as usize != 0 {
+            let n = stream.read(&mut self.buf[len..])?;
+            if n == 0 {
+                break;
+            }
+            len += n;
+        }
+        if len > 0 {
+            // Unwrapping is safe because abs_pos() returns None only if stream is at position 0. This is not the case
+            // because data was just read
+            // Note: if current position is on the cluster boundary then a position in the cluster containing the last
+            // byte is returned
+            self.abs_pos = stream.abs_pos().unwrap() - len as u64;
+        }
+        self.len = len;
+        Ok(())
+    }
+
+    // Returns the next raw entry and its absolute position on the storage
+    //
+    // An empty entry marking the end of directory is returned if there is no complete entry in the buffer.
+    fn next_entry<E: IoError>(&mut self) -> Result<(DirEntryData, u64), Error<E>> {
+        let abs_pos = self.abs_pos + self.pos as u64;
+        let end = (self.pos + DIR_ENTRY_SIZE as usize).min(self.len);
+        let mut rdr = SliceReader::<E>::new(&self.buf[self.pos..end]);
+        self.pos = end;
+        let raw_entry = DirEntryData::deserialize(&mut rdr)?;
+        Ok((raw_entry, abs_pos))
+    }
+}
+
+// Reader of raw directory entries buffered in memory
+pub(crate) struct SliceReader<'b, E> {
+    buf: &'b [u8],
+    _marker: PhantomData<E>,
+}
+
+impl<'b, E> SliceReader<'b, E> {
+    pub(crate) fn new(buf: &'b [u8]) -> Self {
+        Self {
+            buf,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<E: IoError> IoBase for SliceReader<'_, E> {
+    type Error = Error<E>;
+}
+
+impl<E: IoError> Read for SliceReader<'_, E> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let n = buf.len().min(self.buf.len());
+        buf[..n].copy_from_slice(&self.buf[..n]);
+        self.buf = &self.buf[n..];
+        Ok(n)
+    }
+}
+
+// Writer of raw structures serialized in memory
+pub(crate) struct SliceWriter<'b, E> {
+    buf: &'b mut [u8],
+    _marker: PhantomData<E>,
+}
+
+impl<'b, E> SliceWriter<'b, E> {
+    pub(crate) fn new(buf: &'b mut [u8]) -> Self {
+        Self {
+            buf,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<E: IoError> IoBase for SliceWriter<'_, E> {
+    type Error = E;
+}
+
+impl<E: IoError> Write for SliceWriter<'_, E> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let n = buf.len().min(self.buf.len());
+        let (dst, rest) = mem::take(&mut self.buf).split_at_mut(n);
+        dst.copy_from_slice(&buf[..n]);
+        self.buf = rest;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
 #[rustfmt::skip]
 fn validate_long_name<E: IoError>(name: &str) -> Result<(), Error<E>> {
     // check if length is valid
@@ -740,7 +1778,124 @@ fn validate_long_name<E: IoError>(name: &str) -> Result<(), Error<E>> {
     Ok(())
 }
 
-fn lfn_checksum(short_name: &[u8; SFN_SIZE]) -> u8 {
+// Returns lower-case flags for the basename and the extension if `name` is the same as `short_name` except for
+// letter case and each part is either all lower-case or all upper-case, so no LFN entries are needed
+fn short_name_case_flags(name: &str, short_name: &[u8; SFN_SIZE]) -> Option<(bool, bool)> {
+    if !short_name.is_ascii() {
+        return None;
+    }
+    let basename_len = short_name[..8]
+        .iter()
+        .rposition(|b| *b != SFN_PADDING)
+        .map_or(0, |n| n + 1);
+    let ext_len = short_name[8..]
+        .iter()
+        .rposition(|b| *b != SFN_PADDING)
+        .map_or(0, |n| n + 1);
+    let name = name.as_bytes();
+    let (basename, ext) = if ext_len == 0 {
+        (name, &name[name.len()..])
+    } else if name.len() == basename_len + 1 + ext_len && name[basename_len] == b'.' {
+        (&name[..basename_len], &name[basename_len + 1..])
+    } else {
+        return None;
+    };
+    if !basename.eq_ignore_ascii_case(&short_name[..basename_len])
+        || !ext.eq_ignore_ascii_case(&short_name[8..8 + ext_len])
+    {
+        return None;
+    }
+    let is_lowercase = |part: &[u8]| {
+        let has_lowercase = part.iter().any(u8::is_ascii_lowercase);
+        let has_uppercase = part.iter().any(u8::is_ascii_uppercase);
+        // mixed case cannot be represented by the flags
+        if has_lowercase && has_uppercase {
+            None
+        } else {
+            Some(has_lowercase)
+        }
+    };
+    Some((is_lowercase(basename)?, is_lowercase(ext)?))
+}
+
+#[cfg(feature = "lfn")]
+fn encode_lfn_utf16(name: &str) -> LfnBuffer {
+    LfnBuffer::from_ucs2_units(name.encode_utf16())
+}
+#[cfg(not(feature = "lfn"))]
+fn encode_lfn_utf16(_name: &str) -> LfnBuffer {
+    LfnBuffer {}
+}
+
+// Without LFN support only the short name is stored so the name must be equal to it except for letter case,
+// otherwise the entry could not be found by the name used to create it
+#[cfg(not(feature = "lfn"))]
+fn validate_name_stored_as_short_name<E: IoError, OCC: OemCpConverter>(
+    name: &str,
+    short_name: &[u8; SFN_SIZE],
+    oem_cp_converter: &OCC,
+) -> Result<(), Error<E>> {
+    if ShortName::new(short_name).eq_ignore_case(name, oem_cp_converter) {
+        return Ok(());
+    }
+    error!("Name {} cannot be stored without LFN support", name);
+    let (basename, ext) = name.rsplit_once('.').unwrap_or((name, ""));
+    if basename.chars().count() > 8 || ext.chars().count() > 3 {
+        Err(Error::InvalidFileNameLength)
+    } else {
+        Err(Error::UnsupportedFileNameCharacter)
+    }
+}
+
+// Validates the name of a new entry and sets letter case flags in its short name entry. Returns the name encoded
+// for LFN entries - it is empty if the name can be stored in the short name entry alone.
+#[allow(unused_variables)]
+pub(crate) fn prepare_entry_name<E: IoError, OCC: OemCpConverter>(
+    name: &str,
+    raw_entry: &mut DirFileEntryData,
+    oem_cp_converter: &OCC,
+) -> Result<LfnBuffer, Error<E>> {
+    // check if name doesn't contain unsupported characters
+    validate_long_name(name)?;
+    #[cfg(not(feature = "lfn"))]
+    validate_name_stored_as_short_name(name, raw_entry.name(), oem_cp_converter)?;
+    // . and .. need to be at the first two slots and don't need LFNs anyway
+    let is_dot_entry = name == "." || name == "..";
+    // names that differ from the short name only by letter case are stored using the case flags
+    let case_flags = if is_dot_entry {
+        None
+    } else {
+        short_name_case_flags(name, raw_entry.name())
+    };
+    let (lowercase_basename, lowercase_ext) = case_flags.unwrap_or((false, false));
+    raw_entry.set_lowercase_flags(lowercase_basename, lowercase_ext);
+    let needs_lfn = !is_dot_entry && case_flags.is_none();
+    // convert long name to UTF-16
+    Ok(encode_lfn_utf16(if needs_lfn { name } else { "" }))
+}
+
+#[rustfmt::skip]
+fn validate_short_name<E: IoError>(short_name: &[u8; SFN_SIZE]) -> Result<(), Error<E>> {
+    // base name cannot be empty
+    if short_name[0] == b' ' {
+        return Err(Error::InvalidFileNameLength);
+    }
+    for part in [&short_name[..8], &short_name[8..]] {
+        // spaces are only allowed as padding at the end of each part
+        let len = part.iter().rposition(|b| *b != b' ').map_or(0, |n| n + 1);
+        for b in &part[..len] {
+            match b {
+                b'A'..=b'Z' | b'0'..=b'9' | 0x80..=0xFF
+                | b'!' | b'#' | b'$' | b'%' | b'&' | b'\'' | b'(' | b')' | b'-' | b'@' | b'^' | b'_' | b'`' | b'{'
+                | b'}' | b'~' => {},
+                _ => return Err(Error::UnsupportedFileNameCharacter),
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn lfn_checksum(short_name: &[u8; SFN_SIZE]) -> u8 {
     let mut chksum = num::Wrapping(0_u8);
     for b in short_name {
         chksum = (chksum << 7) + (chksum >> 1) + num::Wrapping(*b);
@@ -851,7 +2006,7 @@ impl LfnBuffer {
 }
 
 #[cfg(feature = "lfn")]
-struct LongNameBuilder {
+pub(crate) struct LongNameBuilder {
     buf: LfnBuffer,
     chksum: u8,
     index: u8,
@@ -859,7 +2014,7 @@ struct LongNameBuilder {
 
 #[cfg(feature = "lfn")]
 impl LongNameBuilder {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self {
             buf: LfnBuffer::new(),
             chksum: 0,
@@ -867,12 +2022,12 @@ impl LongNameBuilder {
         }
     }
 
-    fn clear(&mut self) {
+    pub(crate) fn clear(&mut self) {
         self.buf.clear();
         self.index = 0;
     }
 
-    fn into_buf(mut self) -> LfnBuffer {
+    pub(crate) fn into_buf(mut self) -> LfnBuffer {
         // Check if last processed entry had index 1
         if self.index == 1 {
             self.truncate();
@@ -899,7 +2054,7 @@ impl LongNameBuilder {
         self.index == 0
     }
 
-    fn process(&mut self, data: &DirLfnEntryData) {
+    pub(crate) fn process(&mut self, data: &DirLfnEntryData) {
         let is_last = (data.order() & LFN_ENTRY_LAST_FLAG) != 0;
         let index = data.order() & 0x1F;
         if index == 0 || usize::from(index) > MAX_LONG_DIR_ENTRIES {
@@ -933,7 +2088,7 @@ impl LongNameBuilder {
         data.copy_name_to_slice(&mut self.buf.ucs2_units[pos..pos + 13]);
     }
 
-    fn validate_chksum(&mut self, short_name: &[u8; SFN_SIZE]) {
+    pub(crate) fn validate_chksum(&mut self, short_name: &[u8; SFN_SIZE]) {
         if self.is_empty() {
             // Nothing to validate - no LFN entries has been processed
             return;
@@ -948,21 +2103,21 @@ impl LongNameBuilder {
 
 // Dummy implementation for non-alloc build
 #[cfg(not(feature = "lfn"))]
-struct LongNameBuilder {}
+pub(crate) struct LongNameBuilder {}
 #[cfg(not(feature = "lfn"))]
 impl LongNameBuilder {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         LongNameBuilder {}
     }
-    fn clear(&mut self) {}
+    pub(crate) fn clear(&mut self) {}
     fn into_vec(self) {}
     fn truncate(&mut self) {}
-    fn process(&mut self, _data: &DirLfnEntryData) {}
-    fn validate_chksum(&mut self, _short_name: &[u8; SFN_SIZE]) {}
+    pub(crate) fn process(&mut self, _data: &DirLfnEntryData) {}
+    pub(crate) fn validate_chksum(&mut self, _short_name: &[u8; SFN_SIZE]) {}
 }
 
 #[cfg(feature = "lfn")]
-struct LfnEntriesGenerator<'a> {
+pub(crate) struct LfnEntriesGenerator<'a> {
     name_parts_iter: iter::Rev<slice::Chunks<'a, u16>>,
     checksum: u8,
     index: usize,
@@ -972,7 +2127,7 @@ struct LfnEntriesGenerator<'a> {
 
 #[cfg(feature = "lfn")]
 impl<'a> LfnEntriesGenerator<'a> {
-    fn new(name_utf16: &'a [u16], checksum: u8) -> Self {
+    pub(crate) fn new(name_utf16: &'a [u16], checksum: u8) -> Self {
         let num_entries = (name_utf16.len() + LFN_PART_LEN - 1) / LFN_PART_LEN;
         // create generator using reverse iterator over chunks - first chunk can be shorter
         LfnEntriesGenerator {
@@ -1032,10 +2187,10 @@ impl ExactSizeIterator for LfnEntriesGenerator<'_> {}
 
 // Dummy implementation for non-alloc build
 #[cfg(not(feature = "lfn"))]
-struct LfnEntriesGenerator {}
+pub(crate) struct LfnEntriesGenerator {}
 #[cfg(not(feature = "lfn"))]
 impl LfnEntriesGenerator {
-    fn new(_name_utf16: &[u16], _checksum: u8) -> Self {
+    pub(crate) fn new(_name_utf16: &[u16], _checksum: u8) -> Self {
         LfnEntriesGenerator {}
     }
 }
@@ -1054,10 +2209,19 @@ impl Iterator for LfnEntriesGenerator {
 #[cfg(not(feature = "lfn"))]
 impl ExactSizeIterator for LfnEntriesGenerator {}
 
+// Biggest numeric tail that fits into a short name (with 1 character prefix)
+const MAX_NUMERIC_TAIL: u32 = 999_999;
+// Number of long prefix numeric tails tracked by the search window of the short name generator
+const TAIL_WINDOW_SIZE: u32 = 128;
+
 #[derive(Default, Debug, Clone)]
-struct ShortNameGenerator {
+pub(crate) struct ShortNameGenerator {
     chksum: u16,
     long_prefix_bitmap: u16,
+    max_long_prefix_num: u32,
+    // used long prefix numeric tails in range starting at `tail_window_start` (used when the biggest tail is taken)
+    tail_window_start: u32,
+    tail_window_bitmap: u128,
     prefix_chksum_bitmap: u16,
     name_fits: bool,
     lossy_conv: bool,
@@ -1067,12 +2231,13 @@ struct ShortNameGenerator {
 }
 
 impl ShortNameGenerator {
-    fn new(name: &str) -> Self {
+    pub(crate) fn new(name: &str) -> Self {
         // padded by ' '
         let mut short_name = [SFN_PADDING; SFN_SIZE];
         // find extension after last dot
         // Note: short file name cannot start with the extension
-        let dot_index_opt = name[1..].rfind('.').map(|index| index + 1);
+        let first_char_len = name.chars().next().map_or(0, char::len_utf8);
+        let dot_index_opt = name[first_char_len..].rfind('.').map(|index| index + first_char_len);
         // copy basename (part of filename before a dot)
         let basename_src = dot_index_opt.map_or(name, |dot_index| &name[..dot_index]);
         let (basename_len, basename_fits, basename_lossy) =
@@ -1138,7 +2303,7 @@ impl ShortNameGenerator {
         (dst_pos, true, lossy_conv)
     }
 
-    fn add_existing(&mut self, short_name: &[u8; SFN_SIZE]) {
+    pub(crate) fn add_existing(&mut self, short_name: &[u8; SFN_SIZE]) {
         // check for exact match collision
         if short_name == &self.short_name {
             self.exact_match = true;
@@ -1151,18 +2316,34 @@ impl ShortNameGenerator {
     }
 
     fn check_for_long_prefix_collision(&mut self, short_name: &[u8; SFN_SIZE]) {
-        // check for long prefix form collision (TEXTFI~1.TXT)
-        let long_prefix_len = 6.min(self.basename_len);
-        if short_name[long_prefix_len] != b'~' {
+        // check for long prefix form collision (TEXTFI~1.TXT, TEXTF~10.TXT, ...)
+        if short_name[8..] != self.short_name[8..] {
             return;
         }
-        if let Some(num_suffix) = char::from(short_name[long_prefix_len + 1]).to_digit(10) {
-            let long_prefix_matches = short_name[..long_prefix_len] == self.short_name[..long_prefix_len];
-            let ext_matches = short_name[8..] == self.short_name[8..];
-            if long_prefix_matches && ext_matches {
-                self.long_prefix_bitmap |= 1 << num_suffix;
-            }
+        let Some(tilde_pos) = short_name[..8].iter().position(|b| *b == b'~') else {
+            return;
+        };
+        let tail = &short_name[tilde_pos + 1..8];
+        let num_len = tail.iter().take_while(|b| b.is_ascii_digit()).count();
+        if num_len == 0 || tail[num_len..].iter().any(|b| *b != SFN_PADDING) {
+            return;
+        }
+        // prefix is shortened when the numeric tail grows
+        let long_prefix_len = (7 - num_len).min(self.basename_len);
+        if tilde_pos != long_prefix_len || short_name[..long_prefix_len] != self.short_name[..long_prefix_len] {
+            return;
         }
+        let num_suffix = tail[..num_len].iter().fold(0, |acc, b| acc * 10 + u32::from(b - b'0'));
+        if num_suffix < 16 {
+            self.long_prefix_bitmap |= 1 << num_suffix;
+        }
+        if let Some(bit) = num_suffix
+            .checked_sub(self.tail_window_start)
+            .filter(|n| *n < TAIL_WINDOW_SIZE)
+        {
+            self.tail_window_bitmap |= 1 << bit;
+        }
+        self.max_long_prefix_num = self.max_long_prefix_num.max(num_suffix);
     }
 
     fn check_for_short_prefix_collision(&mut self, short_name: &[u8; SFN_SIZE]) {
@@ -1193,7 +2374,7 @@ impl ShortNameGenerator {
         chksum.0
     }
 
-    fn generate(&self) -> Result<[u8; SFN_SIZE], Error<()>> {
+    pub(crate) fn generate(&self) -> Result<[u8; SFN_SIZE], Error<()>> {
         if !self.lossy_conv && self.name_fits && !self.exact_match {
             // If there was no lossy conversion and name fits into
             // 8.3 convention and there is no collision return it as is
@@ -1211,16 +2392,33 @@ impl ShortNameGenerator {
                 return Ok(self.build_prefixed_name(i, true));
             }
         }
+        // Try long prefix with a numeric tail bigger than any existing one (prefix is shortened as the number grows)
+        let num = self.max_long_prefix_num.max(4) + 1;
+        if num <= MAX_NUMERIC_TAIL {
+            return Ok(self.build_prefixed_name(num, false));
+        }
+        // The biggest numeric tail is taken - look for an unused one in the search window
+        let free_num = (0..TAIL_WINDOW_SIZE)
+            .filter(|bit| self.tail_window_bitmap & (1 << bit) == 0)
+            .map(|bit| self.tail_window_start + bit)
+            .find(|n| (1..=MAX_NUMERIC_TAIL).contains(n));
+        if let Some(num) = free_num {
+            return Ok(self.build_prefixed_name(num, false));
+        }
         // Too many collisions - fail
         Err(Error::AlreadyExists)
     }
 
-    fn next_iteration(&mut self) {
+    pub(crate) fn next_iteration(&mut self) {
         // Try different checksum in next iteration
         self.chksum = (num::Wrapping(self.chksum) + num::Wrapping(1)).0;
         // Zero bitmaps
         self.long_prefix_bitmap = 0;
+        self.max_long_prefix_num = 0;
         self.prefix_chksum_bitmap = 0;
+        // Search the next range of numeric tails
+        self.tail_window_start = self.tail_window_start.saturating_add(TAIL_WINDOW_SIZE);
+        self.tail_window_bitmap = 0;
     }
 
     fn build_prefixed_name(&self, num: u32, with_chksum: bool) -> [u8; SFN_SIZE] {
@@ -1231,16 +2429,31 @@ impl ShortNameGenerator {
             buf[prefix_len..prefix_len + 4].copy_from_slice(&Self::u16_to_hex(self.chksum));
             prefix_len + 4
         } else {
-            let prefix_len = 6.min(self.basename_len);
+            let prefix_len = (7 - Self::num_len(num)).min(self.basename_len);
             buf[..prefix_len].copy_from_slice(&self.short_name[..prefix_len]);
             prefix_len
         };
         buf[prefix_len] = b'~';
-        buf[prefix_len + 1] = char::from_digit(num, 10).unwrap() as u8; // SAFE: num is in range [1, 9]
+        let num_len = Self::num_len(num);
+        let mut n = num;
+        for i in (0..num_len).rev() {
+            buf[prefix_len + 1 + i] = b'0' + (n % 10) as u8;
+            n /= 10;
+        }
         buf[8..].copy_from_slice(&self.short_name[8..]);
         buf
     }
 
+    fn num_len(num: u32) -> usize {
+        let mut len = 1;
+        let mut n = num / 10;
+        while n > 0 {
+            len += 1;
+            n /= 10;
+        }
+        len
+    }
+
     fn u16_to_hex(x: u16) -> [u8; 4] {
         // Unwrapping below is safe because each line takes 4 bits of `x` and shifts them to the right so they form
         // a number in range [0, 15]
@@ -1265,6 +2478,59 @@ mod tests {
         assert_eq!(split_path("aaa/bbb/ccc"), ("aaa", Some("bbb/ccc")));
         assert_eq!(split_path("aaa/bbb"), ("aaa", Some("bbb")));
         assert_eq!(split_path("aaa"), ("aaa", None));
+        assert_eq!(split_path("aaa\\bbb\\ccc"), ("aaa", Some("bbb\\ccc")));
+        assert_eq!(split_path("aaa\\bbb/ccc"), ("aaa", Some("bbb/ccc")));
+        assert_eq!(split_path("aaa/bbb\\ccc"), ("aaa", Some("bbb\\ccc")));
+        assert_eq!(split_path("\\/aaa\\/"), ("aaa", None));
+        assert_eq!(split_path("/\\aaa\\..\\"), ("aaa", Some("..")));
+        assert_eq!(split_path("\\"), ("", None));
+    }
+
+    #[test]
+    fn test_short_name_case_flags() {
+        assert_eq!(short_name_case_flags("readme.txt", b"README  TXT"), Some((true, true)));
+        assert_eq!(short_name_case_flags("readme.TXT", b"README  TXT"), Some((true, false)));
+        assert_eq!(short_name_case_flags("README.txt", b"README  TXT"), Some((false, true)));
+        assert_eq!(
+            short_name_case_flags("README.TXT", b"README  TXT"),
+            Some((false, false))
+        );
+        assert_eq!(short_name_case_flags("makefile", b"MAKEFILE   "), Some((true, false)));
+        assert_eq!(short_name_case_flags("a-1_~2.rs", b"A-1_~2  RS "), Some((true, true)));
+        assert_eq!(short_name_case_flags("ReadMe.txt", b"README  TXT"), None);
+        assert_eq!(short_name_case_flags("readme.Txt", b"README  TXT"), None);
+        assert_eq!(short_name_case_flags("readme", b"README  TXT"), None);
+        assert_eq!(short_name_case_flags("readme.txt.bak", b"README~1BAK"), None);
+        assert_eq!(short_name_case_flags("long file.txt", b"LONGFI~1TXT"), None);
+    }
+
+    #[test]
+    fn test_validate_short_name() {
+        assert!(validate_short_name::<()>(b"README  TXT").is_ok());
+        assert!(validate_short_name::<()>(b"A~1        ").is_ok());
+        assert!(validate_short_name::<()>(b"\x80$!{}   ~~ ").is_ok());
+        assert!(matches!(
+            validate_short_name::<()>(b"        TXT"),
+            Err(Error::InvalidFileNameLength)
+        ));
+        assert!(matches!(
+            validate_short_name::<()>(b"readme  TXT"),
+            Err(Error::UnsupportedFileNameCharacter)
+        ));
+        assert!(matches!(
+            validate_short_name::<()>(b"READ ME TXT"),
+            Err(Error::UnsupportedFileNameCharacter)
+        ));
+        assert!(matches!(
+            validate_short_name::<()>(b"README  T T"),
+            Err(Error::UnsupportedFileNameCharacter)
+        ));
+        assert!(matches!(
+            validate_short_name::<()>(b"READ.ME TXT"),
+            Err(Error::UnsupportedFileNameCharacter)
+        ));
+        // 0xE5 is encoded as 0x05 when written
+        assert!(validate_short_name::<()>(b"\xE5EADME  TXT").is_ok());
     }
 
     #[test]
@@ -1288,6 +2554,10 @@ mod tests {
             Some(*b"BASHRC~1SWP")
         );
         assert_eq!(ShortNameGenerator::new(".foo").generate().ok(), Some(*b"FOO~1      "));
+        assert_eq!(
+            ShortNameGenerator::new("\u{DC}ber.txt").generate().ok(),
+            Some(*b"_BER~1  TXT")
+        );
     }
 
     #[test]
@@ -1327,7 +2597,8 @@ mod tests {
             assert_eq!(&buf, format!("TE527D~{}TXT", i).as_bytes());
         }
         gen.add_existing(&buf);
-        assert!(gen.generate().is_err());
+        buf = gen.generate().unwrap();
+        assert_eq!(&buf, b"TEXTFI~5TXT");
         gen.next_iteration();
         for _i in 0..4 {
             buf = gen.generate().unwrap();
@@ -1337,6 +2608,65 @@ mod tests {
         assert_eq!(&buf, b"TE527E~1TXT");
     }
 
+    #[test]
+    fn test_generate_short_name_numeric_tails() {
+        let mut buf: [u8; SFN_SIZE];
+        let mut gen = ShortNameGenerator::new("TextFile.Mine.txt");
+        for i in 1..10 {
+            gen.add_existing(format!("TEXTFI~{}TXT", i).as_bytes().try_into().unwrap());
+            gen.add_existing(format!("TE527D~{}TXT", i).as_bytes().try_into().unwrap());
+        }
+        buf = gen.generate().unwrap();
+        assert_eq!(&buf, b"TEXTF~10TXT");
+        gen.add_existing(&buf);
+        buf = gen.generate().unwrap();
+        assert_eq!(&buf, b"TEXTF~11TXT");
+        // numbers with a different prefix length are ignored
+        gen.add_existing(b"TEXT~99 TXT");
+        gen.add_existing(b"TEXTF~9 TXT");
+        gen.add_existing(b"TEXT~999TXT");
+        buf = gen.generate().unwrap();
+        assert_eq!(&buf, b"TEX~1000TXT");
+        // the lowest unused number is taken when the biggest one is used
+        gen.add_existing(b"T~999999TXT");
+        buf = gen.generate().unwrap();
+        assert_eq!(&buf, b"TEXTF~11TXT");
+
+        let mut gen = ShortNameGenerator::new("x.txt");
+        gen.add_existing(b"X       TXT");
+        gen.add_existing(b"X~12    TXT");
+        gen.add_existing(b"X~999999TXT");
+        for i in 1..10 {
+            gen.add_existing(format!("X40DA~{} TXT", i).as_bytes().try_into().unwrap());
+        }
+        buf = gen.generate().unwrap();
+        assert_eq!(&buf, b"X~1     TXT");
+        for i in 1..5 {
+            gen.add_existing(format!("X~{}     TXT", i).as_bytes().try_into().unwrap());
+        }
+        buf = gen.generate().unwrap();
+        assert_eq!(&buf, b"X~5     TXT");
+        // all numbers in the search window are used
+        for i in 5..128 {
+            gen.add_existing(format!("X~{:<6}TXT", i).as_bytes().try_into().unwrap());
+        }
+        assert!(gen.generate().is_err());
+        // the next iteration searches the next range of numbers
+        let existing = (1..=128)
+            .chain([999_999])
+            .map(|i| format!("X~{:<6}TXT", i))
+            .collect::<Vec<_>>();
+        gen.next_iteration();
+        for i in 1..10 {
+            gen.add_existing(format!("X40DB~{} TXT", i).as_bytes().try_into().unwrap());
+        }
+        for name in &existing {
+            gen.add_existing(name.as_bytes().try_into().unwrap());
+        }
+        buf = gen.generate().unwrap();
+        assert_eq!(&buf, b"X~129   TXT");
+    }
+
     #[test]
     fn test_generate_short_name_collisions_short() {
         let mut buf: [u8; SFN_SIZE];
@@ -1,11 +1,12 @@
 #[cfg(all(not(feature = "std"), feature = "alloc"))]
-use alloc::string::String;
+use alloc::{format, string::String};
 use bitflags::bitflags;
 use core::char;
 use core::convert::TryInto;
 use core::fmt;
 #[cfg(not(feature = "unicode"))]
 use core::iter;
+use core::str;
 
 #[cfg(feature = "lfn")]
 use crate::dir::LfnBuffer;
@@ -13,7 +14,7 @@ use crate::dir::{Dir, DirRawStream};
 use crate::error::{Error, IoError};
 use crate::file::File;
 use crate::fs::{FatType, FileSystem, OemCpConverter, ReadWriteSeek};
-use crate::io::{self, Read, ReadLeExt, Write, WriteLeExt};
+use crate::io::{self, Read, ReadLeExt, Seek, Write, WriteLeExt};
 use crate::time::{Date, DateTime};
 
 bitflags! {
@@ -115,7 +116,7 @@ impl ShortName {
             .collect()
     }
 
-    fn eq_ignore_case<OCC: OemCpConverter>(&self, name: &str, oem_cp_converter: &OCC) -> bool {
+    pub(crate) fn eq_ignore_case<OCC: OemCpConverter>(&self, name: &str, oem_cp_converter: &OCC) -> bool {
         // Convert name to UTF-8 character iterator
         let byte_iter = self.as_bytes().iter().copied();
         let char_iter = byte_iter.map(|c| oem_cp_converter.decode(c));
@@ -123,8 +124,30 @@ impl ShortName {
         let uppercase_char_iter = char_iter.flat_map(char_to_uppercase);
         uppercase_char_iter.eq(name.chars().flat_map(char_to_uppercase))
     }
+
+    fn eq_exact<OCC: OemCpConverter>(&self, name: &str, oem_cp_converter: &OCC) -> bool {
+        let char_iter = self.as_bytes().iter().map(|c| oem_cp_converter.decode(*c));
+        char_iter.eq(name.chars())
+    }
 }
 
+// Encodes characters in UTF-8 into `buf` stopping at the first character that does not fit
+fn encode_chars_to_buf(char_iter: impl Iterator<Item = char>, buf: &mut [u8]) -> &str {
+    let mut len = 0;
+    for c in char_iter {
+        if len + c.len_utf8() > buf.len() {
+            break;
+        }
+        len += c.encode_utf8(&mut buf[len..]).len();
+    }
+    // Unwrapping is safe because only complete UTF-8 sequences have been written
+    str::from_utf8(&buf[..len]).unwrap()
+}
+
+// Windows NT flags in the reserved byte used for names that fit 8.3 format except for lower-case letters
+const LOWERCASE_BASENAME: u8 = 1 << 3;
+const LOWERCASE_EXT: u8 = 1 << 4;
+
 #[allow(dead_code)]
 #[derive(Clone, Debug, Default)]
 pub(crate) struct DirFileEntryData {
@@ -142,10 +165,19 @@ pub(crate) struct DirFileEntryData {
     size: u32,
 }
 
+// Converts a short name to the on-disk format
+pub(crate) fn encode_short_name(mut name: [u8; SFN_SIZE]) -> [u8; SFN_SIZE] {
+    // FAT encodes character 0xE5 as 0x05 because 0xE5 marks deleted files
+    if name[0] == DIR_ENTRY_DELETED_FLAG {
+        name[0] = DIR_ENTRY_REALLY_E5_FLAG;
+    }
+    name
+}
+
 impl DirFileEntryData {
     pub(crate) fn new(name: [u8; SFN_SIZE], attrs: FileAttributes) -> Self {
         Self {
-            name,
+            name: encode_short_name(name),
             attrs,
             ..Self::default()
         }
@@ -153,7 +185,7 @@ impl DirFileEntryData {
 
     pub(crate) fn renamed(&self, new_name: [u8; SFN_SIZE]) -> Self {
         let mut sfn_entry = self.clone();
-        sfn_entry.name = new_name;
+        sfn_entry.name = encode_short_name(new_name);
         sfn_entry
     }
 
@@ -161,7 +193,6 @@ impl DirFileEntryData {
         &self.name
     }
 
-    #[cfg(feature = "alloc")]
     fn lowercase_name(&self) -> ShortName {
         let mut name_copy: [u8; SFN_SIZE] = self.name;
         if self.lowercase_basename() {
@@ -203,7 +234,7 @@ impl DirFileEntryData {
         }
     }
 
-    fn set_size(&mut self, size: u32) {
+    pub(crate) fn set_size(&mut self, size: u32) {
         self.size = size;
     }
 
@@ -211,16 +242,30 @@ impl DirFileEntryData {
         self.attrs.contains(FileAttributes::DIRECTORY)
     }
 
+    pub(crate) fn is_read_only(&self) -> bool {
+        self.attrs.contains(FileAttributes::READ_ONLY)
+    }
+
     fn is_file(&self) -> bool {
         !self.is_dir()
     }
 
     fn lowercase_basename(&self) -> bool {
-        self.reserved_0 & (1 << 3) != 0
+        self.reserved_0 & LOWERCASE_BASENAME != 0
     }
 
     fn lowercase_ext(&self) -> bool {
-        self.reserved_0 & (1 << 4) != 0
+        self.reserved_0 & LOWERCASE_EXT != 0
+    }
+
+    pub(crate) fn set_lowercase_flags(&mut self, lowercase_basename: bool, lowercase_ext: bool) {
+        self.reserved_0 &= !(LOWERCASE_BASENAME | LOWERCASE_EXT);
+        if lowercase_basename {
+            self.reserved_0 |= LOWERCASE_BASENAME;
+        }
+        if lowercase_ext {
+            self.reserved_0 |= LOWERCASE_EXT;
+        }
     }
 
     fn created(&self) -> DateTime {
@@ -512,8 +557,33 @@ impl DirEntryEditor {
         }
     }
 
-    pub(crate) fn flush<IO: ReadWriteSeek, TP, OCC>(&mut self, fs: &FileSystem<IO, TP, OCC>) -> Result<(), IO::Error> {
+    pub(crate) fn set_attributes(&mut self, attrs: FileAttributes) {
+        if attrs != self.data.attrs {
+            self.data.attrs = attrs;
+            self.dirty = true;
+        }
+    }
+
+    pub(crate) fn copy_metadata_from(&mut self, src: &DirFileEntryData) {
+        if src.attrs != self.data.attrs {
+            self.data.attrs = src.attrs;
+            self.dirty = true;
+        }
+        self.set_created(src.created());
+        self.set_accessed(src.accessed());
+        self.set_modified(src.modified());
+    }
+
+    pub(crate) fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub(crate) fn flush<IO: ReadWriteSeek, TP, OCC>(
+        &mut self,
+        fs: &FileSystem<IO, TP, OCC>,
+    ) -> Result<(), Error<IO::Error>> {
         if self.dirty {
+            fs.check_writable()?;
             self.write(fs)?;
             self.dirty = false;
         }
@@ -527,6 +597,148 @@ impl DirEntryEditor {
     }
 }
 
+/// Metadata of a file or a directory.
+///
+/// This struct is returned by the `metadata` method on `DirEntry` or `Dir`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Metadata {
+    len: u64,
+    attributes: FileAttributes,
+    created: DateTime,
+    accessed: Date,
+    modified: DateTime,
+}
+
+impl Metadata {
+    /// Returns file size or 0 for directory.
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if file size is 0.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Checks if metadata belongs to directory.
+    #[must_use]
+    pub fn is_dir(&self) -> bool {
+        self.attributes.contains(FileAttributes::DIRECTORY)
+    }
+
+    /// Checks if metadata belongs to regular file.
+    #[must_use]
+    pub fn is_file(&self) -> bool {
+        !self.is_dir()
+    }
+
+    /// Returns file attributes.
+    #[must_use]
+    pub fn attributes(&self) -> FileAttributes {
+        self.attributes
+    }
+
+    /// Returns file creation date and time.
+    #[must_use]
+    pub fn created(&self) -> DateTime {
+        self.created
+    }
+
+    /// Returns file last access date.
+    #[must_use]
+    pub fn accessed(&self) -> Date {
+        self.accessed
+    }
+
+    /// Returns file last modification date and time.
+    #[must_use]
+    pub fn modified(&self) -> DateTime {
+        self.modified
+    }
+}
+
+/// A deleted directory entry.
+///
+/// This struct is returned by the `iter_deleted` method on `Dir`. FAT keeps most of the deleted entry content intact
+/// until it is overwritten, so the first cluster and size can be used to attempt data recovery (see
+/// `FileSystem::recover_deleted`). Note that clusters of a deleted file are marked as free and may have been reused by
+/// other files.
+#[derive(Clone, Debug)]
+pub struct DeletedDirEntry {
+    data: DirFileEntryData,
+    short_name: ShortName,
+    first_cluster: Option<u32>,
+    offset: u64,
+}
+
+impl DeletedDirEntry {
+    pub(crate) fn new(data: DirFileEntryData, fat_type: FatType, offset: u64) -> Self {
+        Self {
+            short_name: ShortName::new(data.name()),
+            first_cluster: data.first_cluster(fat_type),
+            data,
+            offset,
+        }
+    }
+
+    /// Returns short file name as byte array slice.
+    ///
+    /// Characters are encoded in the OEM codepage. The first character of a deleted entry name is lost - it is
+    /// replaced by the 0xE5 byte.
+    #[must_use]
+    pub fn short_file_name_as_bytes(&self) -> &[u8] {
+        self.short_name.as_bytes()
+    }
+
+    /// Returns the first cluster of the deleted file or `None` if the file was empty.
+    #[must_use]
+    pub fn first_cluster(&self) -> Option<u32> {
+        self.first_cluster
+    }
+
+    /// Returns file size or 0 for directory.
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        u64::from(self.data.size)
+    }
+
+    /// Returns `true` if file size is 0.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.data.size == 0
+    }
+
+    pub(crate) fn size(&self) -> u32 {
+        self.data.size
+    }
+
+    /// Checks if this entry was a directory.
+    #[must_use]
+    pub fn is_dir(&self) -> bool {
+        self.data.is_dir()
+    }
+
+    /// Returns the byte offset of this entry in the parent directory.
+    #[must_use]
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns metadata of this entry.
+    #[must_use]
+    pub fn metadata(&self) -> Metadata {
+        Metadata {
+            len: self.len(),
+            attributes: self.data.attrs,
+            created: self.data.created(),
+            accessed: self.data.accessed(),
+            modified: self.data.modified(),
+        }
+    }
+}
+
 /// A FAT directory entry.
 ///
 /// `DirEntry` is returned by `DirIter` when reading a directory.
@@ -539,19 +751,33 @@ pub struct DirEntry<'a, IO: ReadWriteSeek, TP, OCC> {
     pub(crate) entry_pos: u64,
     pub(crate) offset_range: (u64, u64),
     pub(crate) fs: &'a FileSystem<IO, TP, OCC>,
+    // absolute path of the directory containing this entry (empty for the root directory)
+    #[cfg(feature = "alloc")]
+    pub(crate) dir_path: String,
 }
 
 #[allow(clippy::len_without_is_empty)]
 impl<'a, IO: ReadWriteSeek, TP, OCC: OemCpConverter> DirEntry<'a, IO, TP, OCC> {
     /// Returns short file name.
     ///
-    /// Non-ASCII characters are replaced by the replacement character (U+FFFD).
+    /// Non-ASCII characters are decoded using the OEM codepage converter set by `FsOptions::oem_cp_converter`.
     #[cfg(feature = "alloc")]
     #[must_use]
     pub fn short_file_name(&self) -> String {
         self.short_name.to_string(&self.fs.options.oem_cp_converter)
     }
 
+    /// Returns short file name with the letter case flags applied.
+    ///
+    /// Unlike `short_file_name` this method lowercases the base name and/or the extension if the entry has the
+    /// case flags set (as done by Windows NT). The flags are applied even if the entry has a long file name.
+    /// Non-ASCII characters are decoded using the OEM codepage converter set by `FsOptions::oem_cp_converter`.
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn short_name_cased(&self) -> String {
+        self.data.lowercase_name().to_string(&self.fs.options.oem_cp_converter)
+    }
+
     /// Returns short file name as byte array slice.
     ///
     /// Characters are encoded in the OEM codepage.
@@ -588,12 +814,91 @@ impl<'a, IO: ReadWriteSeek, TP, OCC: OemCpConverter> DirEntry<'a, IO, TP, OCC> {
         self.data.lowercase_name().to_string(&self.fs.options.oem_cp_converter)
     }
 
+    /// Writes long file name or if it doesn't exist short file name into `buf` and returns it as a string slice.
+    ///
+    /// The name is the same as the one returned by `file_name` but this method does not need the `alloc` feature.
+    /// If `buf` is too small the name is truncated on a character boundary. A buffer of 765 bytes is always big
+    /// enough because a long name has at most 255 UTF-16 code units and each of them takes at most 3 bytes in UTF-8.
+    #[must_use]
+    pub fn file_name_to_buf<'b>(&self, buf: &'b mut [u8]) -> &'b str {
+        #[cfg(feature = "lfn")]
+        {
+            if let Some(lfn) = self.long_file_name_as_ucs2_units() {
+                let char_iter =
+                    char::decode_utf16(lfn.iter().copied()).map(|r| r.unwrap_or(char::REPLACEMENT_CHARACTER));
+                return encode_chars_to_buf(char_iter, buf);
+            }
+        }
+
+        let short_name = self.data.lowercase_name();
+        let char_iter = short_name
+            .as_bytes()
+            .iter()
+            .map(|c| self.fs.options.oem_cp_converter.decode(*c));
+        encode_chars_to_buf(char_iter, buf)
+    }
+
+    /// Returns an absolute path of this entry.
+    ///
+    /// Path components are separated by `/` and the root directory path is `/`. Names are obtained by the
+    /// `file_name` method. The `.` entry path is the path of the directory containing it and the `..` entry path is
+    /// the path of the parent of that directory.
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn path(&self) -> String {
+        let path = self.path_without_root_slash();
+        if path.is_empty() {
+            String::from("/")
+        } else {
+            path
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    fn path_without_root_slash(&self) -> String {
+        let name = self.file_name();
+        match name.as_str() {
+            "." => self.dir_path.clone(),
+            ".." => self
+                .dir_path
+                .rsplit_once('/')
+                .map_or_else(String::new, |(parent, _)| parent.into()),
+            _ => format!("{}/{}", self.dir_path, name),
+        }
+    }
+
     /// Returns file attributes.
     #[must_use]
     pub fn attributes(&self) -> FileAttributes {
         self.data.attrs
     }
 
+    /// Changes file attributes.
+    ///
+    /// Only `READ_ONLY`, `HIDDEN`, `SYSTEM` and `ARCHIVE` attributes can be changed. The entry is updated on the
+    /// storage immediately.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if `attrs` changes the `DIRECTORY` or `VOLUME_ID` attribute.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn set_attributes(&mut self, attrs: FileAttributes) -> Result<(), Error<IO::Error>> {
+        trace!("DirEntry::set_attributes {:?}", attrs);
+        let protected = FileAttributes::DIRECTORY | FileAttributes::VOLUME_ID;
+        if (attrs ^ self.data.attrs).intersects(protected) {
+            error!("Directory and volume attributes cannot be changed");
+            return Err(Error::InvalidInput);
+        }
+        let mut editor = self.editor();
+        editor.set_attributes(attrs);
+        editor.flush(self.fs)?;
+        self.data.attrs = attrs;
+        Ok(())
+    }
+
     /// Checks if entry belongs to directory.
     #[must_use]
     pub fn is_dir(&self) -> bool {
@@ -606,7 +911,43 @@ impl<'a, IO: ReadWriteSeek, TP, OCC: OemCpConverter> DirEntry<'a, IO, TP, OCC> {
         self.data.is_file()
     }
 
-    pub(crate) fn first_cluster(&self) -> Option<u32> {
+    /// Returns the byte offset of this entry in the parent directory.
+    ///
+    /// The offset points to the first long name entry if the entry has a long name. It can be passed to
+    /// `DirIter::seek_to` to resume iteration at this entry.
+    #[must_use]
+    pub fn offset(&self) -> u64 {
+        self.offset_range.0
+    }
+
+    /// Returns a byte offset of the short name entry from the beginning of the volume.
+    ///
+    /// Like `FileSystem::offset_from_cluster` the offset is relative to the beginning of the volume (not the
+    /// beginning of the storage if the volume is stored in a partition). The 32-byte entry at this offset contains
+    /// the short name, attributes, timestamps, first cluster and size.
+    #[must_use]
+    pub fn offset_on_disk(&self) -> u64 {
+        self.entry_pos
+    }
+
+    /// Returns the byte range occupied by this entry in the parent directory.
+    ///
+    /// The range starts at the first long name entry (or the short name entry if there is no long name) and ends
+    /// after the short name entry. Offsets are relative to the beginning of the directory content, like the value
+    /// returned by `offset`. Directory content is not necessarily contiguous on the disk - use `Dir::extents` to map
+    /// directory offsets to the disk.
+    #[must_use]
+    pub fn entry_span(&self) -> (u64, u64) {
+        self.offset_range
+    }
+
+    /// Returns the first cluster of the file or directory content.
+    ///
+    /// `None` is returned for empty files and directories without an allocated cluster. The `..` entry of
+    /// a directory placed in the root directory returns `None` too, because the root directory is referenced by
+    /// cluster 0. Following clusters can be found in the FAT, for example using `FileSystem::fat_entries`.
+    #[must_use]
+    pub fn first_cluster(&self) -> Option<u32> {
         self.data.first_cluster(self.fs.fat_type())
     }
 
@@ -614,6 +955,15 @@ impl<'a, IO: ReadWriteSeek, TP, OCC: OemCpConverter> DirEntry<'a, IO, TP, OCC> {
         DirEntryEditor::new(self.data.clone(), self.entry_pos)
     }
 
+    // Overwrites this entry with `data` keeping the short name and its case (so LFN entries stay valid)
+    pub(crate) fn replace_data(&self, data: &DirFileEntryData) -> Result<(), Error<IO::Error>> {
+        let mut new_data = data.renamed(*self.data.name());
+        new_data.set_lowercase_flags(self.data.lowercase_basename(), self.data.lowercase_ext());
+        let mut editor = DirEntryEditor::new(new_data, self.entry_pos);
+        editor.dirty = true;
+        editor.flush(self.fs)
+    }
+
     pub(crate) fn is_same_entry(&self, other: &DirEntry<IO, TP, OCC>) -> bool {
         self.entry_pos == other.entry_pos
     }
@@ -640,7 +990,10 @@ impl<'a, IO: ReadWriteSeek, TP, OCC: OemCpConverter> DirEntry<'a, IO, TP, OCC> {
         match self.first_cluster() {
             Some(n) => {
                 let file = File::new(Some(n), Some(self.editor()), self.fs);
-                Dir::new(DirRawStream::File(file), self.fs)
+                let dir = Dir::new(DirRawStream::File(file), self.fs);
+                #[cfg(feature = "alloc")]
+                let dir = dir.with_path(self.path_without_root_slash());
+                dir
             }
             None => self.fs.root_dir(),
         }
@@ -674,46 +1027,87 @@ impl<'a, IO: ReadWriteSeek, TP, OCC: OemCpConverter> DirEntry<'a, IO, TP, OCC> {
         self.data.modified()
     }
 
+    /// Returns metadata of this entry.
+    #[must_use]
+    pub fn metadata(&self) -> Metadata {
+        Metadata {
+            len: self.len(),
+            attributes: self.attributes(),
+            created: self.created(),
+            accessed: self.accessed(),
+            modified: self.modified(),
+        }
+    }
+
     pub(crate) fn raw_short_name(&self) -> &[u8; SFN_SIZE] {
         &self.data.name
     }
 
-    #[cfg(feature = "lfn")]
-    fn eq_name_lfn(&self, name: &str) -> bool {
-        if let Some(lfn) = self.long_file_name_as_ucs2_units() {
-            let self_decode_iter = char::decode_utf16(lfn.iter().copied());
-            let mut other_uppercase_iter = name.chars().flat_map(char_to_uppercase);
-            for decode_result in self_decode_iter {
-                if let Ok(self_char) = decode_result {
-                    for self_uppercase_char in char_to_uppercase(self_char) {
-                        // compare each character in uppercase
-                        if Some(self_uppercase_char) != other_uppercase_iter.next() {
-                            return false;
-                        }
-                    }
-                } else {
-                    // decoding failed
+    pub(crate) fn eq_name(&self, name: &str) -> bool {
+        #[cfg(feature = "lfn")]
+        let lfn = self.long_file_name_as_ucs2_units();
+        #[cfg(not(feature = "lfn"))]
+        let lfn = None;
+        let options = &self.fs.options;
+        eq_entry_name(&self.data, lfn, name, options.case_sensitive, &options.oem_cp_converter)
+    }
+}
+
+#[cfg(feature = "lfn")]
+fn eq_name_lfn(lfn: &[u16], name: &str) -> bool {
+    let self_decode_iter = char::decode_utf16(lfn.iter().copied());
+    let mut other_uppercase_iter = name.chars().flat_map(char_to_uppercase);
+    for decode_result in self_decode_iter {
+        if let Ok(self_char) = decode_result {
+            for self_uppercase_char in char_to_uppercase(self_char) {
+                // compare each character in uppercase
+                if Some(self_uppercase_char) != other_uppercase_iter.next() {
                     return false;
                 }
             }
-            // both iterators should be at the end here
-            other_uppercase_iter.next().is_none()
         } else {
-            // entry has no long name
-            false
+            // decoding failed
+            return false;
         }
     }
+    // both iterators should be at the end here
+    other_uppercase_iter.next().is_none()
+}
 
-    pub(crate) fn eq_name(&self, name: &str) -> bool {
-        #[cfg(feature = "lfn")]
-        {
-            if self.eq_name_lfn(name) {
-                return true;
-            }
-        }
+fn eq_name_case_sensitive<OCC: OemCpConverter>(
+    data: &DirFileEntryData,
+    lfn: Option<&[u16]>,
+    name: &str,
+    oem_cp_converter: &OCC,
+) -> bool {
+    if let Some(lfn) = lfn {
+        // short name is not matched because it is usually an upper case variant of the long name
+        let lfn_decode_iter = char::decode_utf16(lfn.iter().copied()).map(Result::ok);
+        return lfn_decode_iter.eq(name.chars().map(Some));
+    }
+    data.lowercase_name().eq_exact(name, oem_cp_converter)
+}
 
-        self.short_name.eq_ignore_case(name, &self.fs.options.oem_cp_converter)
+// Checks if `name` matches an entry consisting of the short name entry `data` and the long name `lfn`
+pub(crate) fn eq_entry_name<OCC: OemCpConverter>(
+    data: &DirFileEntryData,
+    lfn: Option<&[u16]>,
+    name: &str,
+    case_sensitive: bool,
+    oem_cp_converter: &OCC,
+) -> bool {
+    if case_sensitive {
+        return eq_name_case_sensitive(data, lfn, name, oem_cp_converter);
+    }
+
+    #[cfg(feature = "lfn")]
+    {
+        if lfn.map_or(false, |lfn| eq_name_lfn(lfn, name)) {
+            return true;
+        }
     }
+
+    ShortName::new(data.name()).eq_ignore_case(name, oem_cp_converter)
 }
 
 impl<IO: ReadWriteSeek, TP, OCC> fmt::Debug for DirEntry<'_, IO, TP, OCC> {
@@ -722,6 +1116,41 @@ impl<IO: ReadWriteSeek, TP, OCC> fmt::Debug for DirEntry<'_, IO, TP, OCC> {
     }
 }
 
+/// Formats the entry as a line similar to the `ls -l` output.
+///
+/// The line contains a mode string, the file size, the modification date and time and the file name, e.g.
+/// `-rw--a        1234  2023-05-01 10:22  readme.txt`. The mode string consists of the entry type (`d` or `-`),
+/// the `r` letter, `w` if the entry is not read-only, and `h`, `s`, `a` letters for the hidden, system and archive
+/// attributes (`-` is used for attributes that are not set).
+#[cfg(feature = "alloc")]
+impl<IO: ReadWriteSeek, TP, OCC: OemCpConverter> fmt::Display for DirEntry<'_, IO, TP, OCC> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let attrs = self.attributes();
+        let flag = |attr: FileAttributes, c: char| if attrs.contains(attr) { c } else { '-' };
+        let modified = self.modified();
+        write!(
+            f,
+            "{}r{}{}{}{}  {:>10}  {:04}-{:02}-{:02} {:02}:{:02}  {}",
+            flag(FileAttributes::DIRECTORY, 'd'),
+            if attrs.contains(FileAttributes::READ_ONLY) {
+                '-'
+            } else {
+                'w'
+            },
+            flag(FileAttributes::HIDDEN, 'h'),
+            flag(FileAttributes::SYSTEM, 's'),
+            flag(FileAttributes::ARCHIVE, 'a'),
+            self.len(),
+            modified.date.year,
+            modified.date.month,
+            modified.date.day,
+            modified.time.hour,
+            modified.time.min,
+            self.file_name(),
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -754,6 +1183,17 @@ mod tests {
         assert!(ShortName::new(raw_short_name).eq_ignore_case("\u{FFFD}ook AT.m \u{FFFD}", &oem_cp_conv));
     }
 
+    #[test]
+    #[cfg(feature = "oem-codepages")]
+    fn short_name_cp850() {
+        let oem_cp_conv = crate::codepage::Cp850Converter::new();
+        let raw_short_name: &[u8; SFN_SIZE] = b"\x82\xA4\x81     TXT";
+        assert_eq!(
+            ShortName::new(raw_short_name).to_string(&oem_cp_conv),
+            "\u{E9}\u{F1}\u{FC}.TXT"
+        );
+    }
+
     #[test]
     fn short_name_05_changed_to_e5() {
         let raw_short_name = [0x05; SFN_SIZE];
@@ -26,6 +26,14 @@ pub enum Error<T> {
     InvalidFileNameLength,
     /// The provided file name contains an invalid character.
     UnsupportedFileNameCharacter,
+    /// A write operation cannot be performed because the file system has been mounted in read-only mode.
+    ReadOnlyFileSystem,
+    /// A write operation cannot be performed because the file has the read-only attribute.
+    PermissionDenied,
+    /// The volume cannot be mounted in the exclusive mode because it is marked as being in use.
+    VolumeInUse,
+    /// A write operation cannot be completed because the file would exceed the maximal file size (4 GiB - 1 byte).
+    FileTooLarge,
 }
 
 impl<T: IoError> From<T> for Error<T> {
@@ -48,6 +56,30 @@ impl From<Error<std::io::Error>> for std::io::Error {
             Error::NotFound => Self::new(std::io::ErrorKind::NotFound, error),
             Error::AlreadyExists => Self::new(std::io::ErrorKind::AlreadyExists, error),
             Error::CorruptedFileSystem => Self::new(std::io::ErrorKind::InvalidData, error),
+            Error::ReadOnlyFileSystem | Error::PermissionDenied => {
+                Self::new(std::io::ErrorKind::PermissionDenied, error)
+            }
+            Error::VolumeInUse | Error::FileTooLarge => Self::new(std::io::ErrorKind::Other, error),
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T: embedded_io_async::Error> embedded_io_async::Error for Error<T> {
+    fn kind(&self) -> embedded_io_async::ErrorKind {
+        use embedded_io_async::ErrorKind;
+        match self {
+            Error::Io(io_error) => io_error.kind(),
+            Error::WriteZero => ErrorKind::WriteZero,
+            Error::InvalidInput
+            | Error::InvalidFileNameLength
+            | Error::UnsupportedFileNameCharacter
+            | Error::DirectoryIsNotEmpty => ErrorKind::InvalidInput,
+            Error::NotFound => ErrorKind::NotFound,
+            Error::AlreadyExists => ErrorKind::AlreadyExists,
+            Error::CorruptedFileSystem => ErrorKind::InvalidData,
+            Error::ReadOnlyFileSystem | Error::PermissionDenied => ErrorKind::PermissionDenied,
+            Error::UnexpectedEof | Error::NotEnoughSpace | Error::VolumeInUse | Error::FileTooLarge => ErrorKind::Other,
         }
     }
 }
@@ -66,6 +98,10 @@ impl<T: core::fmt::Display> core::fmt::Display for Error<T> {
             Error::NotFound => write!(f, "No such file or directory"),
             Error::AlreadyExists => write!(f, "File or directory already exists"),
             Error::CorruptedFileSystem => write!(f, "Corrupted file system"),
+            Error::ReadOnlyFileSystem => write!(f, "Read-only file system"),
+            Error::PermissionDenied => write!(f, "Permission denied"),
+            Error::VolumeInUse => write!(f, "Volume is in use"),
+            Error::FileTooLarge => write!(f, "File too large"),
         }
     }
 }
@@ -0,0 +1,376 @@
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::cell::RefCell;
+
+use crate::io::{IoBase, Read, Seek, SeekFrom, Write};
+
+#[cfg(feature = "alloc")]
+struct CachedSector {
+    sector: u32,
+    data: Box<[u8]>,
+    // sector has been modified and not written to the storage yet
+    dirty: bool,
+}
+
+/// In-memory cache of recently used FAT sectors.
+///
+/// Sectors are ordered from the least recently used to the most recently used one. By default the cache is
+/// write-through: writes are always passed to the storage and only update sectors that are already cached.
+/// In write-back mode writes only modify cached sectors and modified sectors are written to the storage when they are
+/// evicted from the cache or when the cache is flushed.
+pub(crate) struct FatCache {
+    #[cfg(feature = "alloc")]
+    sectors: Vec<CachedSector>,
+    #[cfg(feature = "alloc")]
+    capacity: usize,
+    #[cfg(feature = "alloc")]
+    write_back: bool,
+}
+
+// without `alloc` the cache is always empty and methods do not use `self`
+#[cfg_attr(not(feature = "alloc"), allow(clippy::unused_self))]
+impl FatCache {
+    #[cfg(feature = "alloc")]
+    pub(crate) fn new(capacity: usize, write_back: bool) -> Self {
+        Self {
+            sectors: Vec::new(),
+            capacity,
+            write_back,
+        }
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    pub(crate) fn new() -> Self {
+        Self {}
+    }
+
+    fn get(&mut self, sector: u32) -> Option<&[u8]> {
+        #[cfg(feature = "alloc")]
+        {
+            let index = self.sectors.iter().position(|e| e.sector == sector)?;
+            // mark the sector as the most recently used one
+            let entry = self.sectors.remove(index);
+            self.sectors.push(entry);
+            self.sectors.last().map(|e| &e.data[..])
+        }
+        #[cfg(not(feature = "alloc"))]
+        {
+            let _ = sector;
+            None
+        }
+    }
+
+    fn is_write_back(&self) -> bool {
+        #[cfg(feature = "alloc")]
+        {
+            self.write_back && self.capacity > 0
+        }
+        #[cfg(not(feature = "alloc"))]
+        {
+            false
+        }
+    }
+
+    // Returns the least recently used sector if it is modified and it has to be evicted to insert a new sector
+    fn dirty_victim(&self) -> Option<(u32, &[u8])> {
+        #[cfg(feature = "alloc")]
+        {
+            if self.sectors.len() < self.capacity {
+                return None;
+            }
+            self.sectors
+                .first()
+                .filter(|e| e.dirty)
+                .map(|e| (e.sector, &e.data[..]))
+        }
+        #[cfg(not(feature = "alloc"))]
+        {
+            None
+        }
+    }
+
+    // Returns the first modified sector
+    fn first_dirty(&self) -> Option<(u32, &[u8])> {
+        #[cfg(feature = "alloc")]
+        {
+            self.sectors.iter().find(|e| e.dirty).map(|e| (e.sector, &e.data[..]))
+        }
+        #[cfg(not(feature = "alloc"))]
+        {
+            None
+        }
+    }
+
+    // Returns a modified sector that has not been written to the storage yet
+    pub(crate) fn dirty_sector(&self, sector: u32) -> Option<&[u8]> {
+        #[cfg(feature = "alloc")]
+        {
+            self.sectors
+                .iter()
+                .find(|e| e.sector == sector && e.dirty)
+                .map(|e| &e.data[..])
+        }
+        #[cfg(not(feature = "alloc"))]
+        {
+            let _ = sector;
+            None
+        }
+    }
+
+    fn mark_clean(&mut self, sector: u32) {
+        #[cfg(feature = "alloc")]
+        if let Some(e) = self.sectors.iter_mut().find(|e| e.sector == sector) {
+            e.dirty = false;
+        }
+        #[cfg(not(feature = "alloc"))]
+        let _ = sector;
+    }
+
+    // Returns a buffer for a new cached sector or `None` if caching is disabled
+    // Note: the least recently used sector is dropped if the cache is full so it must not be dirty
+    fn insert(&mut self, sector: u32, sector_size: usize) -> Option<&mut [u8]> {
+        #[cfg(feature = "alloc")]
+        {
+            if self.capacity == 0 {
+                return None;
+            }
+            let data = if self.sectors.len() >= self.capacity {
+                // reuse buffer of the least recently used sector
+                let entry = self.sectors.remove(0);
+                debug_assert!(!entry.dirty);
+                entry.data
+            } else {
+                vec![0_u8; sector_size].into_boxed_slice()
+            };
+            self.sectors.push(CachedSector {
+                sector,
+                data,
+                dirty: false,
+            });
+            self.sectors.last_mut().map(|e| &mut e.data[..])
+        }
+        #[cfg(not(feature = "alloc"))]
+        {
+            let _ = (sector, sector_size);
+            None
+        }
+    }
+
+    // Drops all cached sectors without writing them back
+    pub(crate) fn clear(&mut self) {
+        #[cfg(feature = "alloc")]
+        self.sectors.clear();
+    }
+
+    fn remove(&mut self, sector: u32) {
+        #[cfg(feature = "alloc")]
+        self.sectors.retain(|e| e.sector != sector);
+        #[cfg(not(feature = "alloc"))]
+        let _ = sector;
+    }
+
+    // Updates a cached sector and returns `false` if the sector is not cached
+    fn update(&mut self, sector: u32, offset: usize, buf: &[u8], dirty: bool) -> bool {
+        #[cfg(feature = "alloc")]
+        if let Some(e) = self.sectors.iter_mut().find(|e| e.sector == sector) {
+            e.data[offset..offset + buf.len()].copy_from_slice(buf);
+            e.dirty |= dirty;
+            return true;
+        }
+        #[cfg(not(feature = "alloc"))]
+        let _ = (sector, offset, buf, dirty);
+        false
+    }
+}
+
+/// FAT stream that serves reads from the FAT cache and writes through to the inner stream or to the cache in
+/// write-back mode.
+pub(crate) struct FatCacheIo<'a, S> {
+    inner: S,
+    cache: &'a RefCell<FatCache>,
+    sector_size: u64,
+    pos: u64,
+}
+
+impl<'a, S: Read + Write + Seek> FatCacheIo<'a, S> {
+    pub(crate) fn new(inner: S, cache: &'a RefCell<FatCache>, bytes_per_sector: u16) -> Self {
+        Self {
+            inner,
+            cache,
+            sector_size: u64::from(bytes_per_sector),
+            pos: 0,
+        }
+    }
+
+    fn write_sector(&mut self, sector: u32, data: &[u8]) -> Result<(), S::Error> {
+        self.inner.seek(SeekFrom::Start(u64::from(sector) * self.sector_size))?;
+        self.inner.write_all(data)
+    }
+
+    // Makes space for a new sector in the cache by writing back the least recently used sector if needed
+    fn evict(&mut self, cache: &mut FatCache) -> Result<(), S::Error> {
+        if let Some((sector, data)) = cache.dirty_victim() {
+            self.write_sector(sector, data)?;
+            cache.mark_clean(sector);
+        }
+        Ok(())
+    }
+
+    // Loads a sector into the cache
+    fn load(&mut self, cache: &mut FatCache, sector: u32) -> Result<bool, S::Error> {
+        self.evict(cache)?;
+        let Some(data) = cache.insert(sector, self.sector_size as usize) else {
+            return Ok(false);
+        };
+        self.inner.seek(SeekFrom::Start(u64::from(sector) * self.sector_size))?;
+        if let Err(err) = self.inner.read_exact(data) {
+            cache.remove(sector);
+            return Err(err);
+        }
+        Ok(true)
+    }
+
+    /// Writes all modified sectors to the inner stream.
+    pub(crate) fn write_back(&mut self) -> Result<(), S::Error> {
+        let cache = self.cache;
+        let mut cache = cache.borrow_mut();
+        while let Some((sector, data)) = cache.first_dirty() {
+            self.write_sector(sector, data)?;
+            cache.mark_clean(sector);
+        }
+        Ok(())
+    }
+}
+
+impl<S: IoBase> IoBase for FatCacheIo<'_, S> {
+    type Error = S::Error;
+}
+
+impl<S: Read + Write + Seek> Read for FatCacheIo<'_, S> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        // safe cast: FAT size is limited to 2^32 sectors
+        let sector = (self.pos / self.sector_size) as u32;
+        let offset_in_sector = (self.pos % self.sector_size) as usize;
+        let len = buf.len().min(self.sector_size as usize - offset_in_sector);
+        let cache = self.cache;
+        let mut cache = cache.borrow_mut();
+        if cache.get(sector).is_some() || self.load(&mut cache, sector)? {
+            let data = cache.get(sector).unwrap();
+            buf[..len].copy_from_slice(&data[offset_in_sector..offset_in_sector + len]);
+            self.pos += len as u64;
+            return Ok(len);
+        }
+        // caching is disabled
+        self.inner.seek(SeekFrom::Start(self.pos))?;
+        let size = self.inner.read(&mut buf[..len])?;
+        self.pos += size as u64;
+        Ok(size)
+    }
+}
+
+impl<S: Read + Write + Seek> Write for FatCacheIo<'_, S> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        // safe cast: FAT size is limited to 2^32 sectors
+        let sector = (self.pos / self.sector_size) as u32;
+        let offset_in_sector = (self.pos % self.sector_size) as usize;
+        let len = buf.len().min(self.sector_size as usize - offset_in_sector);
+        let cache = self.cache;
+        let mut cache = cache.borrow_mut();
+        if cache.is_write_back() {
+            let cached = cache.get(sector).is_some() || self.load(&mut cache, sector)?;
+            if cached && cache.update(sector, offset_in_sector, &buf[..len], true) {
+                self.pos += len as u64;
+                return Ok(len);
+            }
+        }
+        self.inner.seek(SeekFrom::Start(self.pos))?;
+        let size = self.inner.write(&buf[..len])?;
+        cache.update(sector, offset_in_sector, &buf[..size], false);
+        self.pos += size as u64;
+        Ok(size)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.write_back()?;
+        self.inner.flush()
+    }
+}
+
+impl<S: Seek> Seek for FatCacheIo<'_, S> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        self.pos = match pos {
+            SeekFrom::Start(n) => n,
+            SeekFrom::Current(n) => {
+                self.inner.seek(SeekFrom::Start(self.pos))?;
+                self.inner.seek(SeekFrom::Current(n))?
+            }
+            SeekFrom::End(n) => self.inner.seek(SeekFrom::End(n))?,
+        };
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FatCache, FatCacheIo};
+    use crate::io::{Read, Seek, SeekFrom, StdIoWrapper, Write};
+    use core::cell::RefCell;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_cache_write_through_and_eviction() {
+        let mut data = (0..4096_u32).map(|i| (i / 512) as u8).collect::<Vec<_>>();
+        let cache = RefCell::new(FatCache::new(2, false));
+        let mut io = FatCacheIo::new(StdIoWrapper::new(Cursor::new(&mut data)), &cache, 512);
+        let mut buf = [0_u8; 2];
+        for sector in [0_u8, 1, 2, 0] {
+            io.seek(SeekFrom::Start(u64::from(sector) * 512 + 511)).unwrap();
+            io.read_exact(&mut buf).unwrap();
+            assert_eq!(buf, [sector, sector + 1]);
+        }
+        // only the two most recently used sectors are kept
+        let cached = cache.borrow().sectors.iter().map(|e| e.sector).collect::<Vec<_>>();
+        assert_eq!(cached, [0, 1]);
+        // writes update cached sectors and the storage
+        io.seek(SeekFrom::Start(510)).unwrap();
+        io.write_all(&[0xAA, 0xBB, 0xCC]).unwrap();
+        io.seek(SeekFrom::Start(510)).unwrap();
+        let mut buf = [0_u8; 3];
+        io.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [0xAA, 0xBB, 0xCC]);
+        assert_eq!(data[510..513], [0xAA, 0xBB, 0xCC]);
+    }
+
+    #[test]
+    fn test_cache_write_back() {
+        let mut data = vec![0_u8; 4096];
+        let cache = RefCell::new(FatCache::new(2, true));
+        {
+            // writes are kept in the cache
+            let mut io = FatCacheIo::new(StdIoWrapper::new(Cursor::new(&mut data)), &cache, 512);
+            io.seek(SeekFrom::Start(510)).unwrap();
+            io.write_all(&[1, 2]).unwrap();
+            io.seek(SeekFrom::Start(1024)).unwrap();
+            io.write_all(&[3]).unwrap();
+            io.seek(SeekFrom::Start(510)).unwrap();
+            let mut buf = [0_u8; 2];
+            io.read_exact(&mut buf).unwrap();
+            assert_eq!(buf, [1, 2]);
+        }
+        assert!(data.iter().all(|b| *b == 0));
+        {
+            // loading a new sector writes back the least recently used one (sector 0 has been read recently)
+            let mut io = FatCacheIo::new(StdIoWrapper::new(Cursor::new(&mut data)), &cache, 512);
+            io.seek(SeekFrom::Start(2048)).unwrap();
+            io.read_exact(&mut [0_u8; 2]).unwrap();
+        }
+        assert_eq!(data[1024], 3);
+        assert_eq!(data[510..512], [0, 0]);
+        // flush writes back all modified sectors
+        FatCacheIo::new(StdIoWrapper::new(Cursor::new(&mut data)), &cache, 512)
+            .flush()
+            .unwrap();
+        assert_eq!(data[510..512], [1, 2]);
+        assert!(cache.borrow().sectors.iter().all(|e| !e.dirty));
+    }
+}
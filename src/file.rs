@@ -1,16 +1,25 @@
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::vec::Vec;
 use core::convert::TryFrom;
 
-use crate::dir_entry::DirEntryEditor;
+use crate::dir_entry::{DirEntryEditor, DirFileEntryData};
 use crate::error::Error;
 use crate::fs::{FileSystem, ReadWriteSeek};
 use crate::io::{IoBase, Read, Seek, SeekFrom, Write};
 use crate::time::{Date, DateTime, TimeProvider};
 
-const MAX_FILE_SIZE: u32 = u32::MAX;
+pub(crate) const MAX_FILE_SIZE: u32 = u32::MAX;
 
 /// A FAT filesystem file object used for reading and writing data.
 ///
 /// This struct is created by the `open_file` or `create_file` methods on `Dir`.
+///
+/// Seeking beyond the end of the file is allowed. The gap between the end of the file and the new position is
+/// filled with zeros when data is written. Seeking alone does not change the file size.
+///
+/// The size of a file is limited to 4 GiB - 1 byte. Writing beyond this limit fails with `Error::FileTooLarge` and
+/// seeking beyond it fails with `Error::InvalidInput`.
+#[allow(clippy::struct_excessive_bools)]
 pub struct File<'a, IO: ReadWriteSeek, TP, OCC> {
     // Note first_cluster is None if file is empty
     first_cluster: Option<u32>,
@@ -18,20 +27,47 @@ pub struct File<'a, IO: ReadWriteSeek, TP, OCC> {
     current_cluster: Option<u32>,
     // current position in this file
     offset: u32,
-    // file dir entry editor - None for root dir
+    // position set by seeking beyond the end of the file - offset stays at the end of the file until the next write
+    pending_offset: Option<u32>,
+    // file dir entry editor - None for root dir and detached files
     entry: Option<DirEntryEditor>,
+    // file opened by its first cluster without a directory entry - it cannot be modified
+    detached: bool,
+    // known size of a detached file
+    detached_size: Option<u32>,
+    // follow physically next clusters if the cluster chain is broken (recovery of deleted files)
+    assume_contiguous: bool,
+    // access mode set when opening the file
+    readable: bool,
+    writable: bool,
+    append: bool,
     // file-system reference
     fs: &'a FileSystem<IO, TP, OCC>,
+    // allow modifying the file even if it has the read-only attribute
+    ignore_read_only: bool,
+    // timestamps set explicitly by the user are not updated automatically
+    keep_accessed: bool,
+    keep_modified: bool,
+    // data has been written since the last flush
+    written: bool,
 }
 
 /// An extent containing a file's data on disk.
 ///
-/// This is created by the `extents` method on `File`, and represents
-/// a byte range on the disk that contains a file's data. All values
-/// are in bytes.
+/// This is created by the `extents` method on `File` or `Dir`, and represents
+/// a byte range on the disk that contains a file's data. Physically contiguous
+/// clusters are merged into a single extent. All values are in bytes.
+///
+/// `offset` is always a multiple of the sector size, so the device sector can
+/// be computed by dividing it by `FileSystem::bytes_per_sector`.
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub struct Extent {
+    /// Offset of the extent data relative to the beginning of the file
+    pub file_offset: u32,
+    /// Offset of the extent data relative to the beginning of the volume
     pub offset: u64,
+    /// Size of the extent data
     pub size: u32,
 }
 
@@ -47,6 +83,68 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
             fs,
             current_cluster: None, // cluster before first one
             offset: 0,
+            pending_offset: None,
+            detached: false,
+            detached_size: None,
+            assume_contiguous: false,
+            readable: true,
+            writable: true,
+            append: false,
+            ignore_read_only: false,
+            keep_accessed: false,
+            keep_modified: false,
+            written: false,
+        }
+    }
+
+    pub(crate) fn new_detached(first_cluster: Option<u32>, size: Option<u32>, fs: &'a FileSystem<IO, TP, OCC>) -> Self {
+        let mut file = Self::new(first_cluster, None, fs);
+        file.detached = true;
+        file.detached_size = size;
+        file
+    }
+
+    pub(crate) fn new_recovered(first_cluster: Option<u32>, size: u32, fs: &'a FileSystem<IO, TP, OCC>) -> Self {
+        let mut file = Self::new_detached(first_cluster, Some(size), fs);
+        file.assume_contiguous = true;
+        file
+    }
+
+    // Iterates over clusters following the given one taking the recovery mode into account
+    fn next_clusters(&self, cluster: u32) -> impl Iterator<Item = Result<u32, Error<IO::Error>>> + 'a {
+        let iter = self.fs.cluster_iter(cluster);
+        if self.assume_contiguous {
+            iter.assume_contiguous()
+        } else {
+            iter
+        }
+    }
+
+    pub(crate) fn set_access_mode(&mut self, readable: bool, writable: bool, append: bool) {
+        self.readable = readable;
+        self.writable = writable;
+        self.append = append;
+    }
+
+    pub(crate) fn set_ignore_read_only(&mut self, ignore_read_only: bool) {
+        self.ignore_read_only = ignore_read_only;
+    }
+
+    fn check_modifiable(&self) -> Result<(), Error<IO::Error>> {
+        self.fs.check_writable()?;
+        if self.detached {
+            error!("File opened without a directory entry cannot be modified");
+            return Err(Error::PermissionDenied);
+        }
+        if !self.writable {
+            error!("File has not been opened for writing");
+            return Err(Error::PermissionDenied);
+        }
+        match self.entry {
+            Some(ref e) if !self.ignore_read_only && !e.inner().is_dir() && e.inner().is_read_only() => {
+                Err(Error::PermissionDenied)
+            }
+            _ => Ok(()),
         }
     }
 
@@ -54,13 +152,16 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
     ///
     /// # Errors
     ///
-    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
+    /// * `Error::PermissionDenied` will be returned if the file has the read-only attribute.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
     ///
     /// # Panics
     ///
     /// Will panic if this is the root directory.
     pub fn truncate(&mut self) -> Result<(), Error<IO::Error>> {
         trace!("File::truncate");
+        self.check_modifiable()?;
         if let Some(ref mut e) = self.entry {
             e.set_size(self.offset);
             if self.offset == 0 {
@@ -84,37 +185,257 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
         }
     }
 
+    /// Preallocate clusters so the file can grow to `len` bytes without further allocations.
+    ///
+    /// Missing clusters are allocated as a single contiguous run if possible (preferably directly after the last
+    /// cluster of the file) to avoid fragmentation. If there is no contiguous run of free clusters that is big
+    /// enough, clusters are allocated one by one. The file size and position are not changed. Clusters allocated
+    /// beyond the end of the file are released when the file is truncated at its end.
+    ///
+    /// Until then the cluster chain is longer than the file size requires. `FileSystem::check` accepts such files but
+    /// other implementations may not: for example `fsck.fat` reports them and shortens their chains.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if `len` exceeds the maximal file size.
+    /// * `Error::NotEnoughSpace` will be returned if there are not enough free clusters.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
+    /// * `Error::PermissionDenied` will be returned if the file has the read-only attribute.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn preallocate(&mut self, len: u64) -> Result<(), Error<IO::Error>> {
+        trace!("File::preallocate {}", len);
+        self.check_modifiable()?;
+        if len > u64::from(MAX_FILE_SIZE) {
+            return Err(Error::InvalidInput);
+        }
+        let required_clusters = self.fs.clusters_from_bytes(len);
+        let mut allocated_clusters = 0;
+        let mut last_cluster = None;
+        for r in self.cluster_chain() {
+            last_cluster = Some(r?);
+            allocated_clusters += 1;
+        }
+        if allocated_clusters >= required_clusters {
+            return Ok(());
+        }
+        let missing_clusters = required_clusters - allocated_clusters;
+        self.fs.set_dirty_flag(true)?;
+        let hint = last_cluster.map(|n| n + 1);
+        let is_dir = self.is_dir();
+        if let Some(n) = self
+            .fs
+            .alloc_contiguous_run(last_cluster, missing_clusters, hint, is_dir)?
+        {
+            if self.first_cluster.is_none() {
+                self.set_first_cluster(n);
+            }
+            return Ok(());
+        }
+        debug!("no contiguous run of {} free clusters", missing_clusters);
+        let mut prev_cluster = last_cluster;
+        for _ in 0..missing_clusters {
+            let new_cluster = self.fs.alloc_cluster(prev_cluster, is_dir)?;
+            if self.first_cluster.is_none() {
+                self.set_first_cluster(new_cluster);
+            }
+            prev_cluster = Some(new_cluster);
+        }
+        Ok(())
+    }
+
+    /// Get the cluster chain of a file.
+    ///
+    /// This returns an iterator over the numbers of clusters occupied by this file, starting from the first cluster.
+    /// Use the `offset_from_cluster` method on `FileSystem` to translate a cluster number into a byte offset.
+    /// Iteration stops after the end of the chain is reached. If the chain contains an invalid cluster number
+    /// `Error::CorruptedFileSystem` is returned and iteration stops.
+    pub fn cluster_chain(&self) -> impl Iterator<Item = Result<u32, Error<IO::Error>>> + 'a {
+        let fs = self.fs;
+        self.first_cluster
+            .map(|first| core::iter::once(Ok(first)).chain(fs.cluster_iter(first)))
+            .into_iter()
+            .flatten()
+    }
+
     /// Get the extents of a file on disk.
     ///
     /// This returns an iterator over the byte ranges on-disk occupied by
-    /// this file.
-    pub fn extents(&mut self) -> impl Iterator<Item = Result<Extent, Error<IO::Error>>> + 'a {
+    /// this file. Extents are returned in ascending order of `file_offset` and
+    /// physically contiguous clusters are merged into a single extent. For
+    /// directories the whole allocated clusters are included.
+    pub fn extents(&self) -> impl Iterator<Item = Result<Extent, Error<IO::Error>>> + 'a {
         let fs = self.fs;
         let cluster_size = fs.cluster_size();
-        let Some(mut bytes_left) = self.size() else {
-            return None.into_iter().flatten();
+        let mut clusters = self.cluster_chain();
+        // None means that the whole clusters are used (directories)
+        let mut bytes_left = self.size();
+        let mut file_offset = 0_u32;
+        let mut pending: Option<Result<u32, Error<IO::Error>>> = None;
+        let mut take_cluster = move || match bytes_left {
+            Some(ref mut n) => {
+                let size = cluster_size.min(*n);
+                *n -= size;
+                size
+            }
+            None => cluster_size,
         };
-        let Some(first) = self.first_cluster else {
-            return None.into_iter().flatten();
+        core::iter::from_fn(move || {
+            let first_cluster = match pending.take().or_else(|| clusters.next())? {
+                Ok(n) => n,
+                Err(e) => return Some(Err(e)),
+            };
+            let mut size = take_cluster();
+            if size == 0 {
+                return None;
+            }
+            let mut last_cluster = first_cluster;
+            while size % cluster_size == 0 {
+                match clusters.next() {
+                    Some(Ok(n)) if n == last_cluster + 1 => {
+                        let cluster_bytes = take_cluster();
+                        if cluster_bytes == 0 {
+                            break;
+                        }
+                        size += cluster_bytes;
+                        last_cluster = n;
+                    }
+                    None => break,
+                    r => {
+                        pending = r;
+                        break;
+                    }
+                }
+            }
+            let extent = Extent {
+                file_offset,
+                offset: fs.offset_from_cluster(first_cluster),
+                size,
+            };
+            file_offset += size;
+            Some(Ok(extent))
+        })
+    }
+
+    /// Checks if all clusters of the file are physically contiguous.
+    ///
+    /// Only the FAT is read, the file data is not accessed. An empty file is considered contiguous.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::CorruptedFileSystem` will be returned if the cluster chain of the file is invalid.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn is_contiguous(&self) -> Result<bool, Error<IO::Error>> {
+        let mut prev_cluster: Option<u32> = None;
+        // Note: the chain iterator stops on loops and invalid cluster numbers
+        for r in self.cluster_chain() {
+            let cluster = r?;
+            if prev_cluster.map_or(false, |n| cluster != n + 1) {
+                return Ok(false);
+            }
+            prev_cluster = Some(cluster);
+        }
+        Ok(true)
+    }
+
+    /// Relocates the file data so all clusters of the file are physically contiguous.
+    ///
+    /// A contiguous run of free clusters is allocated and the file data is copied into it. The directory entry is
+    /// switched to the new clusters only after the copy has been flushed to the storage and the old clusters are
+    /// freed at the end, so the file is not corrupted if the operation is interrupted (at worst the new clusters are
+    /// leaked). The file position is preserved.
+    ///
+    /// Returns `true` if the file has been relocated and `false` if it was already contiguous or there is no
+    /// contiguous run of free clusters that is big enough.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if this is a directory.
+    /// * `Error::CorruptedFileSystem` will be returned if the cluster chain of the file is invalid.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
+    /// * `Error::PermissionDenied` will be returned if the file has the read-only attribute.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn defragment(&mut self) -> Result<bool, Error<IO::Error>> {
+        trace!("File::defragment");
+        self.check_modifiable()?;
+        if self.is_dir() {
+            error!("Directories cannot be defragmented");
+            return Err(Error::InvalidInput);
+        }
+        if self.is_contiguous()? {
+            return Ok(false);
+        }
+        let Some(old_first_cluster) = self.first_cluster else {
+            return Ok(false);
         };
+        let mut num_clusters = 0;
+        for r in self.cluster_chain() {
+            r?;
+            num_clusters += 1;
+        }
+        self.fs.set_dirty_flag(true)?;
+        let Some(new_first_cluster) = self.fs.alloc_contiguous_run(None, num_clusters, None, false)? else {
+            debug!("no contiguous run of {} free clusters", num_clusters);
+            return Ok(false);
+        };
+        let mut new_current_cluster = None;
+        let mut copy_result = Ok(());
+        for (r, new_cluster) in self.cluster_chain().zip(new_first_cluster..) {
+            copy_result = r.and_then(|old_cluster| {
+                if self.current_cluster == Some(old_cluster) {
+                    new_current_cluster = Some(new_cluster);
+                }
+                self.fs.copy_cluster(old_cluster, new_cluster)
+            });
+            if copy_result.is_err() {
+                break;
+            }
+        }
+        if let Err(e) = copy_result.and_then(|()| self.fs.flush()) {
+            // the file is still using the old clusters
+            self.fs.free_cluster_chain(new_first_cluster)?;
+            return Err(e);
+        }
+        // switch the directory entry to the new clusters
+        self.set_first_cluster(new_first_cluster);
+        self.current_cluster = new_current_cluster;
+        self.flush_dir_entry()?;
+        self.fs.flush()?;
+        self.fs.free_cluster_chain(old_first_cluster)?;
+        Ok(true)
+    }
 
-        Some(
-            core::iter::once(Ok(first))
-                .chain(fs.cluster_iter(first))
-                .map(move |cluster_err| match cluster_err {
-                    Ok(cluster) => {
-                        let size = cluster_size.min(bytes_left);
-                        bytes_left -= size;
-                        Ok(Extent {
-                            offset: fs.offset_from_cluster(cluster),
-                            size,
-                        })
-                    }
-                    Err(e) => Err(e),
-                }),
-        )
-        .into_iter()
-        .flatten()
+    /// Returns the current position in the file.
+    ///
+    /// Unlike `seek(SeekFrom::Current(0))` it only reads the position stored in this object and never accesses the
+    /// storage. The returned position can be beyond the end of the file if the file has been seeked past its end.
+    ///
+    /// # Errors
+    ///
+    /// This method never fails. `Result` is returned for compatibility with `std::io::Seek::stream_position`.
+    pub fn stream_position(&mut self) -> Result<u64, Error<IO::Error>> {
+        Ok(u64::from(self.pending_offset.unwrap_or(self.offset)))
+    }
+
+    /// Seeks relative to the current position.
+    ///
+    /// It is equivalent to `seek(SeekFrom::Current(offset))` but the new position is not returned. Seeking within
+    /// the current cluster does not access the storage.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if the new position is negative or exceeds the maximal file size.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn seek_relative(&mut self, offset: i64) -> Result<(), Error<IO::Error>> {
+        self.seek(SeekFrom::Current(offset))?;
+        Ok(())
     }
 
     pub(crate) fn abs_pos(&self) -> Option<u64> {
@@ -149,46 +470,131 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
     ///
     /// Note: it is set to a value from the `TimeProvider` when creating a file.
     /// Deprecated: if needed implement a custom `TimeProvider`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidInput` will be returned if `date_time` cannot be stored in a directory entry. Supported values
+    /// have a year in the range [1980, 2107] and valid month, day, hour, minute, second and millisecond numbers.
     #[deprecated]
-    pub fn set_created(&mut self, date_time: DateTime) {
+    pub fn set_created(&mut self, date_time: DateTime) -> Result<(), Error<IO::Error>> {
+        if !date_time.is_valid() {
+            error!("Invalid date and time {:?}", date_time);
+            return Err(Error::InvalidInput);
+        }
         if let Some(ref mut e) = self.entry {
             e.set_created(date_time);
         }
+        Ok(())
     }
 
     /// Sets date of last access for this file.
     ///
     /// Note: it is overwritten by a value from the `TimeProvider` on every file read operation.
     /// Deprecated: if needed implement a custom `TimeProvider`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidInput` will be returned if `date` cannot be stored in a directory entry. Supported values
+    /// have a year in the range [1980, 2107] and valid month and day numbers.
     #[deprecated]
-    pub fn set_accessed(&mut self, date: Date) {
+    pub fn set_accessed(&mut self, date: Date) -> Result<(), Error<IO::Error>> {
+        if !date.is_valid() {
+            error!("Invalid date {:?}", date);
+            return Err(Error::InvalidInput);
+        }
         if let Some(ref mut e) = self.entry {
             e.set_accessed(date);
         }
+        Ok(())
     }
 
     /// Sets date and time of last modification for this file.
     ///
     /// Note: it is overwritten by a value from the `TimeProvider` on every file write operation.
+    /// The time is stored with a resolution of 2s.
     /// Deprecated: if needed implement a custom `TimeProvider`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidInput` will be returned if `date_time` cannot be stored in a directory entry. Supported values
+    /// have a year in the range [1980, 2107] and valid month, day, hour, minute, second and millisecond numbers.
     #[deprecated]
-    pub fn set_modified(&mut self, date_time: DateTime) {
+    pub fn set_modified(&mut self, date_time: DateTime) -> Result<(), Error<IO::Error>> {
+        if !date_time.is_valid() {
+            error!("Invalid date and time {:?}", date_time);
+            return Err(Error::InvalidInput);
+        }
         if let Some(ref mut e) = self.entry {
             e.set_modified(date_time);
         }
+        Ok(())
+    }
+
+    /// Sets the date and time of creation, last access and last modification for this file at once.
+    ///
+    /// Only timestamps passed as `Some` are changed. The directory entry is updated in a single write when the file
+    /// is flushed. Timestamps of last access and last modification set by this method are not overwritten by
+    /// subsequent read and write operations on this `File` object, so original timestamps can be restored exactly.
+    /// Note that the creation time is stored with a resolution of 1/100s but the modification time is stored with a
+    /// resolution of 2s (odd seconds and milliseconds are dropped) because a FAT directory entry has no field for
+    /// a finer modification time.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if any of the timestamps cannot be stored in a directory entry.
+    ///   Supported values have a year in the range [1980, 2107] and valid month, day, hour, minute, second and
+    ///   millisecond numbers. No timestamp is changed in that case.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
+    pub fn set_times(
+        &mut self,
+        created: Option<DateTime>,
+        accessed: Option<Date>,
+        modified: Option<DateTime>,
+    ) -> Result<(), Error<IO::Error>> {
+        trace!("File::set_times {:?} {:?} {:?}", created, accessed, modified);
+        self.fs.check_writable()?;
+        if !created.map_or(true, DateTime::is_valid)
+            || !accessed.map_or(true, Date::is_valid)
+            || !modified.map_or(true, DateTime::is_valid)
+        {
+            error!("Invalid date and time {:?} {:?} {:?}", created, accessed, modified);
+            return Err(Error::InvalidInput);
+        }
+        if let Some(ref mut e) = self.entry {
+            if let Some(date_time) = created {
+                e.set_created(date_time);
+            }
+            if let Some(date) = accessed {
+                e.set_accessed(date);
+                self.keep_accessed = true;
+            }
+            if let Some(date_time) = modified {
+                e.set_modified(date_time);
+                self.keep_modified = true;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn copy_metadata_from(&mut self, src: &DirFileEntryData) {
+        if let Some(ref mut e) = self.entry {
+            e.copy_metadata_from(src);
+        }
     }
 
     fn size(&self) -> Option<u32> {
         match self.entry {
             Some(ref e) => e.inner().size(),
-            None => None,
+            None => self.detached_size,
         }
     }
 
     fn is_dir(&self) -> bool {
         match self.entry {
             Some(ref e) => e.inner().is_dir(),
-            None => true, // root directory
+            None => !self.detached, // root directory
         }
     }
 
@@ -209,23 +615,84 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
     }
 
     fn flush(&mut self) -> Result<(), Error<IO::Error>> {
+        // Do not touch the storage if nothing has changed so dropping a file opened for reading causes no writes
+        let entry_dirty = self.entry.as_ref().map_or(false, DirEntryEditor::is_dirty);
+        if !self.written && !entry_dirty {
+            return Ok(());
+        }
         self.flush_dir_entry()?;
         let mut disk = self.fs.disk.borrow_mut();
         disk.flush()?;
+        self.written = false;
         Ok(())
     }
 
     pub(crate) fn is_root_dir(&self) -> bool {
-        self.entry.is_none()
+        self.entry.is_none() && !self.detached
     }
 }
 
 impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> File<'_, IO, TP, OCC> {
+    /// Reads all bytes from the current position until the end of the file and appends them to `buf`.
+    ///
+    /// The file size is known from its directory entry so the buffer is grown only once, to the exact size of the
+    /// remaining data, instead of being reallocated multiple times. Directories have no size so their buffer is
+    /// grown incrementally, one cluster at a time. Returns the number of bytes read.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::CorruptedFileSystem` will be returned if the cluster chain of the file is invalid.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    ///
+    /// If an error is returned `buf` contains all data read before the error.
+    #[cfg(feature = "alloc")]
+    pub fn read_all_into(&mut self, buf: &mut Vec<u8>) -> Result<usize, Error<IO::Error>> {
+        trace!("File::read_all_into");
+        let start_len = buf.len();
+        loop {
+            let len = buf.len();
+            match self.bytes_left_in_file() {
+                Some(0) => break,
+                Some(n) => {
+                    buf.reserve_exact(n);
+                    buf.resize(len + n, 0);
+                }
+                None => buf.resize(len + self.fs.cluster_size() as usize, 0),
+            }
+            let read_result = self.read(&mut buf[len..]);
+            buf.truncate(len + read_result.as_ref().map_or(0, |n| *n));
+            if read_result? == 0 {
+                // cluster chain is shorter than the file size or this is the end of a directory
+                break;
+            }
+        }
+        Ok(buf.len() - start_len)
+    }
+
+    // Fills the gap between the end of the file and the position set by seeking beyond it with zeros
+    fn fill_gap(&mut self, end: u32) -> Result<(), Error<IO::Error>> {
+        const ZEROS: [u8; 512] = [0_u8; 512];
+        self.pending_offset = None;
+        while self.offset < end {
+            let write_size = ((end - self.offset) as usize).min(ZEROS.len());
+            if let Err(err) = self.write_all(&ZEROS[..write_size]) {
+                // keep the position requested by the user
+                self.pending_offset = Some(end);
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
     fn update_dir_entry_after_write(&mut self) {
         let offset = self.offset;
         if let Some(ref mut e) = self.entry {
-            let now = self.fs.options.time_provider.get_current_date_time();
-            e.set_modified(now);
+            if !self.keep_modified && !self.fs.options.preserve_timestamps {
+                let now = self.fs.options.time_provider.get_current_date_time();
+                e.set_modified(now);
+            }
             if e.inner().size().map_or(false, |s| offset > s) {
                 e.set_size(offset);
             }
@@ -248,8 +715,19 @@ impl<IO: ReadWriteSeek, TP, OCC> Clone for File<'_, IO, TP, OCC> {
             first_cluster: self.first_cluster,
             current_cluster: self.current_cluster,
             offset: self.offset,
+            pending_offset: self.pending_offset,
             entry: self.entry.clone(),
+            detached: self.detached,
+            detached_size: self.detached_size,
+            assume_contiguous: self.assume_contiguous,
+            readable: self.readable,
+            writable: self.writable,
+            append: self.append,
             fs: self.fs,
+            ignore_read_only: self.ignore_read_only,
+            keep_accessed: self.keep_accessed,
+            keep_modified: self.keep_modified,
+            written: self.written,
         }
     }
 }
@@ -261,16 +739,28 @@ impl<IO: ReadWriteSeek, TP, OCC> IoBase for File<'_, IO, TP, OCC> {
 impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Read for File<'_, IO, TP, OCC> {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
         trace!("File::read");
+        if !self.readable {
+            error!("File has not been opened for reading");
+            return Err(Error::PermissionDenied);
+        }
         let cluster_size = self.fs.cluster_size();
         let current_cluster_opt = if self.offset % cluster_size == 0 {
             // next cluster
             match self.current_cluster {
                 None => self.first_cluster,
                 Some(n) => {
-                    let r = self.fs.cluster_iter(n).next();
+                    let r = self.next_clusters(n).next();
                     match r {
                         Some(Err(err)) => return Err(err),
-                        Some(Ok(n)) => Some(n),
+                        Some(Ok(n)) => {
+                            // a chain cannot be longer than the number of clusters (directories have no size
+                            // limiting it)
+                            if self.fs.clusters_from_bytes(u64::from(self.offset)) >= self.fs.total_clusters() {
+                                error!("Loop detected in cluster chain of a file");
+                                return Err(Error::CorruptedFileSystem);
+                            }
+                            Some(n)
+                        }
                         None => None,
                     }
                 }
@@ -284,11 +774,27 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Read for File<'_, IO, TP, OCC> {
         let offset_in_cluster = self.offset % cluster_size;
         let bytes_left_in_cluster = (cluster_size - offset_in_cluster) as usize;
         let bytes_left_in_file = self.bytes_left_in_file().unwrap_or(bytes_left_in_cluster);
-        let read_size = buf.len().min(bytes_left_in_cluster).min(bytes_left_in_file);
+        let max_read_size = buf.len().min(bytes_left_in_file);
+        let mut read_size = max_read_size.min(bytes_left_in_cluster);
         if read_size == 0 {
             return Ok(0);
         }
-        trace!("read {} bytes in cluster {}", read_size, current_cluster);
+        // extend the read over following clusters if they are physically contiguous
+        if read_size < max_read_size {
+            let mut last_cluster = current_cluster;
+            for r in self.next_clusters(current_cluster) {
+                let n = r?;
+                if n != last_cluster + 1 {
+                    break;
+                }
+                last_cluster = n;
+                read_size = max_read_size.min(read_size + cluster_size as usize);
+                if read_size == max_read_size {
+                    break;
+                }
+            }
+        }
+        trace!("read {} bytes starting in cluster {}", read_size, current_cluster);
         let offset_in_fs = self.fs.offset_from_cluster(current_cluster) + u64::from(offset_in_cluster);
         let read_bytes = {
             let mut disk = self.fs.disk.borrow_mut();
@@ -299,10 +805,14 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Read for File<'_, IO, TP, OCC> {
             return Ok(0);
         }
         self.offset += read_bytes as u32;
-        self.current_cluster = Some(current_cluster);
+        // clusters are contiguous so the cluster containing the last read byte can be computed directly
+        let clusters_read = (offset_in_cluster as usize + read_bytes - 1) / cluster_size as usize;
+        self.current_cluster = Some(current_cluster + clusters_read as u32);
 
         if let Some(ref mut e) = self.entry {
-            if self.fs.options.update_accessed_date {
+            let options = &self.fs.options;
+            if options.update_accessed_date && !options.preserve_timestamps && !options.read_only && !self.keep_accessed
+            {
                 let now = self.fs.options.time_provider.get_current_date();
                 e.set_accessed(now);
             }
@@ -324,14 +834,29 @@ where
 impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Write for File<'_, IO, TP, OCC> {
     fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
         trace!("File::write");
+        self.check_modifiable()?;
+        if self.append {
+            self.seek(SeekFrom::End(0))?;
+        }
+        if let Some(end) = self.pending_offset {
+            if buf.is_empty() {
+                return Ok(0);
+            }
+            self.fill_gap(end)?;
+        }
         let cluster_size = self.fs.cluster_size();
         let offset_in_cluster = self.offset % cluster_size;
         let bytes_left_in_cluster = (cluster_size - offset_in_cluster) as usize;
         let bytes_left_until_max_file_size = (MAX_FILE_SIZE - self.offset) as usize;
-        let write_size = buf.len().min(bytes_left_in_cluster).min(bytes_left_until_max_file_size);
+        let max_write_size = buf.len().min(bytes_left_until_max_file_size);
+        let mut write_size = max_write_size.min(bytes_left_in_cluster);
         // Exit early if we are going to write no data
         if write_size == 0 {
-            return Ok(0);
+            if buf.is_empty() {
+                return Ok(0);
+            }
+            error!("Maximal file size exceeded");
+            return Err(Error::FileTooLarge);
         }
         // Mark the volume 'dirty'
         self.fs.set_dirty_flag(true)?;
@@ -367,8 +892,23 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Write for File<'_, IO, TP, OCC> {
                 None => panic!("Offset inside cluster but no cluster allocated"),
             }
         };
-        trace!("write {} bytes in cluster {}", write_size, current_cluster);
+        // extend the write over following clusters if they are physically contiguous - at the end of the chain
+        // the physically next cluster is allocated if it is free
+        let mut last_cluster = current_cluster;
+        while write_size < max_write_size {
+            let next_cluster = match self.fs.cluster_iter(last_cluster).next() {
+                Some(r) => Some(r?),
+                None => self.fs.alloc_next_cluster_if_free(last_cluster, self.is_dir())?,
+            };
+            if next_cluster != Some(last_cluster + 1) {
+                break;
+            }
+            last_cluster += 1;
+            write_size = max_write_size.min(write_size + cluster_size as usize);
+        }
+        trace!("write {} bytes starting in cluster {}", write_size, current_cluster);
         let offset_in_fs = self.fs.offset_from_cluster(current_cluster) + u64::from(offset_in_cluster);
+        self.written = true;
         let written_bytes = {
             let mut disk = self.fs.disk.borrow_mut();
             disk.seek(SeekFrom::Start(offset_in_fs))?;
@@ -379,7 +919,9 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Write for File<'_, IO, TP, OCC> {
         }
         // some bytes were writter - update position and optionally size
         self.offset += written_bytes as u32;
-        self.current_cluster = Some(current_cluster);
+        // clusters are contiguous so the cluster containing the last written byte can be computed directly
+        let clusters_written = (offset_in_cluster as usize + written_bytes - 1) / cluster_size as usize;
+        self.current_cluster = Some(current_cluster + clusters_written as u32);
         self.update_dir_entry_after_write();
         Ok(written_bytes)
     }
@@ -411,10 +953,9 @@ impl<IO: ReadWriteSeek, TP, OCC> Seek for File<'_, IO, TP, OCC> {
     fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
         trace!("File::seek");
         let size_opt = self.size();
+        let position = self.pending_offset.unwrap_or(self.offset);
         let new_offset_opt: Option<u32> = match pos {
-            SeekFrom::Current(x) => i64::from(self.offset)
-                .checked_add(x)
-                .and_then(|n| u32::try_from(n).ok()),
+            SeekFrom::Current(x) => i64::from(position).checked_add(x).and_then(|n| u32::try_from(n).ok()),
             SeekFrom::Start(x) => u32::try_from(x).ok(),
             SeekFrom::End(o) => size_opt
                 .and_then(|s| i64::from(s).checked_add(o))
@@ -424,16 +965,18 @@ impl<IO: ReadWriteSeek, TP, OCC> Seek for File<'_, IO, TP, OCC> {
             error!("Invalid seek offset");
             return Err(Error::InvalidInput);
         };
+        trace!("file seek {} -> {} - entry {:?}", position, new_offset, self.entry);
+        self.pending_offset = None;
         if let Some(size) = size_opt {
             if new_offset > size {
-                warn!("Seek beyond the end of the file");
+                // stay at the end of the file - the gap is filled on the next write
+                self.pending_offset = Some(new_offset);
                 new_offset = size;
             }
         }
-        trace!("file seek {} -> {} - entry {:?}", self.offset, new_offset, self.entry);
         if new_offset == self.offset {
-            // position is the same - nothing to do
-            return Ok(u64::from(self.offset));
+            // position in the cluster chain is the same - nothing to do
+            return Ok(u64::from(self.pending_offset.unwrap_or(self.offset)));
         }
         let new_offset_in_clusters = self.fs.clusters_from_bytes(u64::from(new_offset));
         let old_offset_in_clusters = self.fs.clusters_from_bytes(u64::from(self.offset));
@@ -446,15 +989,23 @@ impl<IO: ReadWriteSeek, TP, OCC> Seek for File<'_, IO, TP, OCC> {
             // return the previous cluster if the offset points to the cluster boundary
             // Note: new_offset_in_clusters cannot be 0 here because new_offset is not 0
             debug_assert!(new_offset_in_clusters > 0);
-            let clusters_to_skip = new_offset_in_clusters - 1;
-            let mut cluster = first_cluster;
-            let mut iter = self.fs.cluster_iter(first_cluster);
+            // when seeking forward continue walking from the current cluster instead of the first one
+            // Note: current_cluster has index old_offset_in_clusters - 1 if offset is not 0
+            let (start_index, start_cluster) = match self.current_cluster {
+                Some(current_cluster) if self.offset > 0 && new_offset_in_clusters > old_offset_in_clusters => {
+                    (old_offset_in_clusters - 1, current_cluster)
+                }
+                _ => (0, first_cluster),
+            };
+            let clusters_to_skip = new_offset_in_clusters - 1 - start_index;
+            let mut cluster = start_cluster;
+            let mut iter = self.next_clusters(start_cluster);
             for i in 0..clusters_to_skip {
                 cluster = if let Some(r) = iter.next() {
                     r?
                 } else {
                     // cluster chain ends before the new position - seek to the end of the last cluster
-                    new_offset = self.fs.bytes_from_clusters(i + 1) as u32;
+                    new_offset = self.fs.bytes_from_clusters(start_index + i + 1) as u32;
                     break;
                 };
             }
@@ -466,7 +1017,7 @@ impl<IO: ReadWriteSeek, TP, OCC> Seek for File<'_, IO, TP, OCC> {
         };
         self.offset = new_offset;
         self.current_cluster = new_cluster;
-        Ok(u64::from(self.offset))
+        Ok(u64::from(self.pending_offset.unwrap_or(self.offset)))
     }
 }
 
@@ -6,14 +6,21 @@ use core::convert::TryFrom;
 use core::fmt::Debug;
 use core::marker::PhantomData;
 
+use crate::block::SectorBuffer;
 use crate::boot_sector::{format_boot_sector, BiosParameterBlock, BootSector};
-use crate::dir::{Dir, DirRawStream};
-use crate::dir_entry::{DirFileEntryData, FileAttributes, SFN_PADDING, SFN_SIZE};
+use crate::dir::{Dir, DirRawStream, SliceWriter};
+use crate::dir_entry::{DeletedDirEntry, DirFileEntryData, FileAttributes, SFN_PADDING, SFN_SIZE};
 use crate::error::Error;
-use crate::file::File;
+use crate::fat_cache::{FatCache, FatCacheIo};
+use crate::file::{Extent, File};
+#[cfg(feature = "alloc")]
+use crate::fsck::{self, FsckReport};
 use crate::io::{self, IoBase, Read, ReadLeExt, Seek, SeekFrom, Write, WriteLeExt};
+#[cfg(feature = "mbr")]
+use crate::mbr::{read_mbr_partitions, MBR_SECTOR_SIZE};
 use crate::table::{
-    alloc_cluster, count_free_clusters, format_fat, read_fat_flags, ClusterIterator, RESERVED_FAT_ENTRIES,
+    alloc_cluster, alloc_clusters_contiguous, count_free_clusters, format_fat, read_fat, read_fat_flags, write_fat,
+    ClusterIterator, FatValue, RESERVED_FAT_ENTRIES,
 };
 use crate::time::{DefaultTimeProvider, TimeProvider};
 
@@ -21,6 +28,12 @@ use crate::time::{DefaultTimeProvider, TimeProvider};
 //   http://wiki.osdev.org/FAT
 //   https://www.win.tue.nl/~aeb/linux/fs/fat/fat-1.html
 
+// Backup boot sector is usually stored in sector 6
+const DEFAULT_BACKUP_BOOT_SECTOR_OFFSET: u64 = 6 * 512;
+
+#[cfg(feature = "alloc")]
+const DEFAULT_FAT_CACHE_SECTORS: usize = 0;
+
 /// A type of FAT filesystem.
 ///
 /// `FatType` values are based on the size of File Allocation Table entry.
@@ -96,7 +109,7 @@ impl FsStatusFlags {
         self.io_error
     }
 
-    fn encode(self) -> u8 {
+    pub(crate) fn encode(self) -> u8 {
         let mut res = 0_u8;
         if self.dirty {
             res |= 1;
@@ -124,10 +137,10 @@ pub trait ReadWriteSeek: Read + Write + Seek {}
 impl<T: Read + Write + Seek> ReadWriteSeek for T {}
 
 #[derive(Clone, Default, Debug)]
-struct FsInfoSector {
-    free_cluster_count: Option<u32>,
-    next_free_cluster: Option<u32>,
-    dirty: bool,
+pub(crate) struct FsInfoSector {
+    pub(crate) free_cluster_count: Option<u32>,
+    pub(crate) next_free_cluster: Option<u32>,
+    pub(crate) dirty: bool,
 }
 
 impl FsInfoSector {
@@ -135,7 +148,7 @@ impl FsInfoSector {
     const STRUC_SIG: u32 = 0x6141_7272;
     const TRAIL_SIG: u32 = 0xAA55_0000;
 
-    fn deserialize<R: Read>(rdr: &mut R) -> Result<Self, Error<R::Error>> {
+    pub(crate) fn deserialize<R: Read>(rdr: &mut R) -> Result<Self, Error<R::Error>> {
         let lead_sig = rdr.read_u32_le()?;
         if lead_sig != Self::LEAD_SIG {
             error!("invalid lead_sig in FsInfo sector: {}", lead_sig);
@@ -176,7 +189,7 @@ impl FsInfoSector {
         })
     }
 
-    fn serialize<W: Write>(&self, wrt: &mut W) -> Result<(), Error<W::Error>> {
+    pub(crate) fn serialize<W: Write>(&self, wrt: &mut W) -> Result<(), Error<W::Error>> {
         wrt.write_u32_le(Self::LEAD_SIG)?;
         let reserved = [0_u8; 480];
         wrt.write_all(&reserved)?;
@@ -189,7 +202,7 @@ impl FsInfoSector {
         Ok(())
     }
 
-    fn validate_and_fix(&mut self, total_clusters: u32) {
+    pub(crate) fn validate_and_fix(&mut self, total_clusters: u32) {
         let max_valid_cluster_number = total_clusters + RESERVED_FAT_ENTRIES;
         if let Some(n) = self.free_cluster_count {
             if n > total_clusters {
@@ -211,14 +224,14 @@ impl FsInfoSector {
         }
     }
 
-    fn map_free_clusters(&mut self, map_fn: impl Fn(u32) -> u32) {
+    pub(crate) fn map_free_clusters(&mut self, map_fn: impl Fn(u32) -> u32) {
         if let Some(n) = self.free_cluster_count {
             self.free_cluster_count = Some(map_fn(n));
             self.dirty = true;
         }
     }
 
-    fn set_next_free_cluster(&mut self, cluster: u32) {
+    pub(crate) fn set_next_free_cluster(&mut self, cluster: u32) {
         self.next_free_cluster = Some(cluster);
         self.dirty = true;
     }
@@ -233,8 +246,20 @@ impl FsInfoSector {
 ///
 /// Options are specified as an argument for `FileSystem::new` method.
 #[derive(Copy, Clone, Debug, Default)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct FsOptions<TP, OCC> {
     pub(crate) update_accessed_date: bool,
+    pub(crate) preserve_timestamps: bool,
+    pub(crate) read_only: bool,
+    pub(crate) use_backup_boot_sector: bool,
+    pub(crate) exclusive: bool,
+    pub(crate) zero_freed_clusters: bool,
+    pub(crate) distrust_fs_info: bool,
+    pub(crate) case_sensitive: bool,
+    #[cfg(feature = "alloc")]
+    pub(crate) fat_cache_sectors: usize,
+    #[cfg(feature = "alloc")]
+    pub(crate) fat_write_back: bool,
     pub(crate) oem_cp_converter: OCC,
     pub(crate) time_provider: TP,
 }
@@ -245,6 +270,17 @@ impl FsOptions<DefaultTimeProvider, LossyOemCpConverter> {
     pub fn new() -> Self {
         Self {
             update_accessed_date: false,
+            preserve_timestamps: false,
+            read_only: false,
+            use_backup_boot_sector: false,
+            exclusive: false,
+            zero_freed_clusters: false,
+            distrust_fs_info: false,
+            case_sensitive: false,
+            #[cfg(feature = "alloc")]
+            fat_cache_sectors: DEFAULT_FAT_CACHE_SECTORS,
+            #[cfg(feature = "alloc")]
+            fat_write_back: false,
             oem_cp_converter: LossyOemCpConverter::new(),
             time_provider: DefaultTimeProvider::new(),
         }
@@ -253,16 +289,146 @@ impl FsOptions<DefaultTimeProvider, LossyOemCpConverter> {
 
 impl<TP: TimeProvider, OCC: OemCpConverter> FsOptions<TP, OCC> {
     /// If enabled accessed date field in directory entry is updated when reading or writing a file.
+    ///
+    /// It has no effect if timestamp updates are disabled by `update_timestamps`.
     #[must_use]
     pub fn update_accessed_date(mut self, enabled: bool) -> Self {
         self.update_accessed_date = enabled;
         self
     }
 
+    /// If enabled timestamps in directory entries are updated automatically when files are read or written.
+    ///
+    /// If disabled reading, writing and truncating a file leaves its creation, access and modification timestamps
+    /// unchanged, so they can be set explicitly, e.g. by `File::set_times`. New entries still get timestamps from
+    /// the `TimeProvider`. Default is `true`.
+    #[must_use]
+    pub fn update_timestamps(mut self, enabled: bool) -> Self {
+        self.preserve_timestamps = !enabled;
+        self
+    }
+
+    /// If enabled the file system never writes to the storage.
+    ///
+    /// All operations that would modify the volume return `Error::ReadOnlyFileSystem` and the dirty flag and the
+    /// `FSInfo` sector are not updated when unmounting. Default is `false`.
+    #[must_use]
+    pub fn read_only(mut self, enabled: bool) -> Self {
+        self.read_only = enabled;
+        self
+    }
+
+    /// If enabled the backup boot sector is used when the primary boot sector is invalid.
+    ///
+    /// Only FAT32 volumes have a backup boot sector. A warning is logged when the backup is used. The primary boot
+    /// sector is not repaired. Default is `false`.
+    #[must_use]
+    pub fn use_backup_boot_sector_on_error(mut self, enabled: bool) -> Self {
+        self.use_backup_boot_sector = enabled;
+        self
+    }
+
+    /// If enabled the volume is marked as being in use for the whole time it is mounted.
+    ///
+    /// Normally the dirty flag in the Boot Sector is set before the first write and cleared when the file system is
+    /// unmounted. In the exclusive mode the flag is set right away when mounting and mounting fails with
+    /// `Error::VolumeInUse` if the flag is already set. This prevents mounting the same storage twice by
+    /// cooperating users of this option. In read-only mode the flag is checked but not set.
+    ///
+    /// Note that the flag is also left set after an unclean unmount (e.g. a power loss or a `FileSystem` leaked
+    /// without being dropped) and a flag found set when mounting is never cleared by unmounting. Such a volume must
+    /// be mounted without this option, preferably checked, and marked as clean by `FileSystem::clear_dirty_flag`
+    /// before it can be mounted in the exclusive mode again. Default is `false`.
+    #[must_use]
+    pub fn exclusive(mut self, enabled: bool) -> Self {
+        self.exclusive = enabled;
+        self
+    }
+
+    /// If enabled clusters are overwritten with zeros before they are freed.
+    ///
+    /// It applies to clusters freed by truncating or removing files and directories, so their old content cannot be
+    /// recovered from the storage. Zeros are written and the storage is flushed before the clusters are marked as
+    /// free in the FAT. It makes freeing clusters much slower. Default is `false`.
+    #[must_use]
+    pub fn zero_freed_clusters(mut self, enabled: bool) -> Self {
+        self.zero_freed_clusters = enabled;
+        self
+    }
+
+    /// If enabled the number of free clusters stored in the FAT32 `FSInfo` sector is ignored when mounting.
+    ///
+    /// The number is then computed by scanning the FAT on the first call to `FileSystem::stats`, which makes the call
+    /// slower on big volumes but the result is correct even if the `FSInfo` sector has been left inconsistent by
+    /// another implementation. The corrected number is written back to the `FSInfo` sector when unmounting unless
+    /// the file system has been mounted in read-only mode. Free clusters are always counted if the volume is marked
+    /// dirty. Default is `false`.
+    #[must_use]
+    pub fn distrust_fsinfo(mut self, enabled: bool) -> Self {
+        self.distrust_fs_info = enabled;
+        self
+    }
+
+    /// If enabled file names are compared case-sensitively when looking up entries in directories.
+    ///
+    /// FAT file systems are case-insensitive so by default letter case is ignored when a path is resolved. In the
+    /// case-sensitive mode a name must be exactly equal to the name returned by `DirEntry::file_name`, that is the
+    /// long name of an entry or, if the entry has no long name, its short name with the letter case flags applied.
+    /// Short names of entries having a long name are not matched at all. It makes entries whose names differ only
+    /// by letter case individually addressable (some FAT volumes contain such entries) and allows creating new
+    /// ones, but note that such entries may confuse other FAT implementations. Default is `false`.
+    #[must_use]
+    pub fn case_sensitive(mut self, enabled: bool) -> Self {
+        self.case_sensitive = enabled;
+        self
+    }
+
+    /// Sets the number of FAT sectors cached in memory.
+    ///
+    /// Cached sectors are used when traversing cluster chains so reading big fragmented files does not require
+    /// reading the FAT from the storage again and again. The cache is write-through so changes in the FAT are always
+    /// written to the storage immediately, unless `fat_write_back` is enabled. Each cached sector takes
+    /// `bytes_per_sector` bytes of memory. Value 0 disables the cache. Default is 0.
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn fat_cache_sectors(mut self, sectors: usize) -> Self {
+        self.fat_cache_sectors = sectors;
+        self
+    }
+
+    /// If enabled the FAT cache works in write-back mode.
+    ///
+    /// Changes in the FAT are kept in cached sectors and written to the storage (to all FAT copies) when a sector is
+    /// evicted from the cache, when `FileSystem::flush` is called or when the file system is unmounted. It greatly
+    /// reduces the number of small writes to the storage when many clusters are allocated or freed, but changes not
+    /// written yet are lost if the file system is not unmounted properly. The cache size is set by
+    /// `fat_cache_sectors` and this option has no effect if the cache is disabled, which is the default. Default is
+    /// `false`.
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn fat_write_back(mut self, enabled: bool) -> Self {
+        self.fat_write_back = enabled;
+        self
+    }
+
     /// Changes default OEM code page encoder-decoder.
+    ///
+    /// Built-in converters for common code pages (`Cp437Converter` and `Cp850Converter`) are available if
+    /// `oem-codepages` feature is enabled.
     pub fn oem_cp_converter<OCC2: OemCpConverter>(self, oem_cp_converter: OCC2) -> FsOptions<TP, OCC2> {
         FsOptions::<TP, OCC2> {
             update_accessed_date: self.update_accessed_date,
+            preserve_timestamps: self.preserve_timestamps,
+            read_only: self.read_only,
+            use_backup_boot_sector: self.use_backup_boot_sector,
+            exclusive: self.exclusive,
+            zero_freed_clusters: self.zero_freed_clusters,
+            distrust_fs_info: self.distrust_fs_info,
+            case_sensitive: self.case_sensitive,
+            #[cfg(feature = "alloc")]
+            fat_cache_sectors: self.fat_cache_sectors,
+            #[cfg(feature = "alloc")]
+            fat_write_back: self.fat_write_back,
             oem_cp_converter,
             time_provider: self.time_provider,
         }
@@ -272,6 +438,17 @@ impl<TP: TimeProvider, OCC: OemCpConverter> FsOptions<TP, OCC> {
     pub fn time_provider<TP2: TimeProvider>(self, time_provider: TP2) -> FsOptions<TP2, OCC> {
         FsOptions::<TP2, OCC> {
             update_accessed_date: self.update_accessed_date,
+            preserve_timestamps: self.preserve_timestamps,
+            read_only: self.read_only,
+            use_backup_boot_sector: self.use_backup_boot_sector,
+            exclusive: self.exclusive,
+            zero_freed_clusters: self.zero_freed_clusters,
+            distrust_fs_info: self.distrust_fs_info,
+            case_sensitive: self.case_sensitive,
+            #[cfg(feature = "alloc")]
+            fat_cache_sectors: self.fat_cache_sectors,
+            #[cfg(feature = "alloc")]
+            fat_write_back: self.fat_write_back,
             oem_cp_converter: self.oem_cp_converter,
             time_provider,
         }
@@ -304,13 +481,31 @@ impl FileSystemStats {
     pub fn free_clusters(&self) -> u32 {
         self.free_clusters
     }
+
+    /// Total size of space usable for file allocation in bytes
+    #[must_use]
+    pub fn total_space(&self) -> u64 {
+        u64::from(self.total_clusters) * u64::from(self.cluster_size)
+    }
+
+    /// Size of free space in bytes
+    #[must_use]
+    pub fn free_space(&self) -> u64 {
+        u64::from(self.free_clusters) * u64::from(self.cluster_size)
+    }
+
+    /// Size of used space in bytes
+    #[must_use]
+    pub fn used_space(&self) -> u64 {
+        self.total_space().saturating_sub(self.free_space())
+    }
 }
 
 /// A FAT filesystem object.
 ///
 /// `FileSystem` struct is representing a state of a mounted FAT volume.
 pub struct FileSystem<IO: ReadWriteSeek, TP = DefaultTimeProvider, OCC = LossyOemCpConverter> {
-    pub(crate) disk: RefCell<IO>,
+    pub(crate) disk: RefCell<PartitionIo<IO>>,
     pub(crate) options: FsOptions<TP, OCC>,
     fat_type: FatType,
     bpb: BiosParameterBlock,
@@ -318,6 +513,9 @@ pub struct FileSystem<IO: ReadWriteSeek, TP = DefaultTimeProvider, OCC = LossyOe
     root_dir_sectors: u32,
     total_clusters: u32,
     fs_info: RefCell<FsInfoSector>,
+    fat_cache: RefCell<FatCache>,
+    // status flags read on mount - the dirty flag is kept set on unmount unless it is cleared by `clear_dirty_flag`
+    mount_status_flags: Cell<FsStatusFlags>,
     current_status_flags: Cell<FsStatusFlags>,
 }
 
@@ -342,11 +540,10 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
     /// Creates a new filesystem object instance.
     ///
     /// Supplied `storage` parameter cannot be seeked. If there is a need to read a fragment of disk
-    /// image (e.g. partition) library user should wrap the file struct in a struct limiting
-    /// access to partition bytes only e.g. `fscommon::StreamSlice`.
+    /// image (e.g. partition) use `new_in_partition`.
     ///
     /// Note: creating multiple filesystem objects with a single underlying storage can
-    /// cause a filesystem corruption.
+    /// cause a filesystem corruption. See `FsOptions::exclusive` for a way to detect it.
     ///
     /// # Errors
     ///
@@ -354,23 +551,112 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
     ///
     /// * `Error::CorruptedFileSystem` will be returned if the boot sector and/or the file system information sector
     ///   contains invalid values.
+    /// * `Error::VolumeInUse` will be returned if the exclusive mode is enabled and the volume is marked as being in
+    ///   use.
     /// * `Error::Io` will be returned if the provided storage object returned an I/O error.
     ///
     /// # Panics
     ///
     /// Panics in non-optimized build if `storage` position returned by `seek` is not zero.
     pub fn new<T: IntoStorage<IO>>(storage: T, options: FsOptions<TP, OCC>) -> Result<Self, Error<IO::Error>> {
-        // Make sure given image is not seeked
-        let mut disk = storage.into_storage();
         trace!("FileSystem::new");
+        Self::new_internal(PartitionIo::new(storage.into_storage(), 0, None), options)
+    }
+
+    /// Creates a new filesystem object instance for a volume stored in a partition.
+    ///
+    /// The volume starts `start` bytes from the beginning of `storage` and spans `len` bytes. All accesses are
+    /// offset by `start` and accesses beyond `len` bytes fail, so there is no need to wrap `storage` in a type
+    /// limiting access to the partition bytes. The position of `storage` is changed before mounting so it does not
+    /// need to be seeked by the caller.
+    /// The `hidden_sectors` field of the BPB is not used to compute the offset - see `new_in_mbr_partition`.
+    ///
+    /// Note: creating multiple filesystem objects with a single underlying storage can
+    /// cause a filesystem corruption. See `FsOptions::exclusive` for a way to detect it.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::CorruptedFileSystem` will be returned if the boot sector and/or the file system information sector
+    ///   contains invalid values or if the volume does not fit in the partition.
+    /// * `Error::VolumeInUse` will be returned if the exclusive mode is enabled and the volume is marked as being in
+    ///   use.
+    /// * `Error::Io` will be returned if the provided storage object returned an I/O error.
+    pub fn new_in_partition<T: IntoStorage<IO>>(
+        storage: T,
+        start: u64,
+        len: u64,
+        options: FsOptions<TP, OCC>,
+    ) -> Result<Self, Error<IO::Error>> {
+        trace!("FileSystem::new_in_partition {} {}", start, len);
+        let mut disk = storage.into_storage();
+        disk.seek(SeekFrom::Start(start))?;
+        Self::new_internal(PartitionIo::new(disk, start, Some(len)), options)
+    }
+
+    /// Creates a new filesystem object instance for a volume stored in a primary partition of an MBR partitioned disk.
+    ///
+    /// The MBR (Master Boot Record) partition table is read from the beginning of `storage` and the volume is mounted
+    /// like in `new_in_partition` using the start and the size of the partition entry with the given `index`
+    /// (0-3). Partition entries use 512-byte sectors. The `hidden_sectors` field of the volume BPB is checked against
+    /// the partition start unless it is 0 - many tools do not set it.
+    ///
+    /// Note: creating multiple filesystem objects with a single underlying storage can
+    /// cause a filesystem corruption. See `FsOptions::exclusive` for a way to detect it.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if `index` is greater than 3 or if the partition entry is unused.
+    /// * `Error::CorruptedFileSystem` will be returned if the MBR signature is invalid, if the boot sector and/or the
+    ///   file system information sector contains invalid values, if the volume does not fit in the partition or if
+    ///   the `hidden_sectors` field does not match the partition start.
+    /// * `Error::VolumeInUse` will be returned if the exclusive mode is enabled and the volume is marked as being in
+    ///   use.
+    /// * `Error::Io` will be returned if the provided storage object returned an I/O error.
+    #[cfg(feature = "mbr")]
+    pub fn new_in_mbr_partition<T: IntoStorage<IO>>(
+        storage: T,
+        index: usize,
+        options: FsOptions<TP, OCC>,
+    ) -> Result<Self, Error<IO::Error>> {
+        trace!("FileSystem::new_in_mbr_partition {}", index);
+        let mut disk = storage.into_storage();
+        let partitions = read_mbr_partitions(&mut disk)?;
+        let Some(partition) = partitions.get(index).filter(|p| !p.is_empty()) else {
+            error!("Invalid partition index {}", index);
+            return Err(Error::InvalidInput);
+        };
+        let start = u64::from(partition.start_lba()) * MBR_SECTOR_SIZE;
+        let len = u64::from(partition.sector_count()) * MBR_SECTOR_SIZE;
+        let fs = Self::new_in_partition(disk, start, len, options)?;
+        let hidden_sectors = fs.hidden_sectors();
+        if hidden_sectors != 0 && hidden_sectors != partition.start_lba() {
+            error!(
+                "Hidden sectors {} do not match partition start {}",
+                hidden_sectors,
+                partition.start_lba()
+            );
+            return Err(Error::CorruptedFileSystem);
+        }
+        Ok(fs)
+    }
+
+    fn new_internal(mut disk: PartitionIo<IO>, options: FsOptions<TP, OCC>) -> Result<Self, Error<IO::Error>> {
+        // Make sure given image is not seeked (position is relative to the partition start)
         debug_assert!(disk.seek(SeekFrom::Current(0))? == 0);
 
         // read boot sector
-        let bpb = {
-            let boot = BootSector::deserialize(&mut disk)?;
-            boot.validate()?;
-            boot.bpb
-        };
+        let bpb = read_boot_sector(&mut disk, options.use_backup_boot_sector)?.bpb;
+
+        if let Some(len) = disk.len {
+            if bpb.bytes_from_sectors(bpb.total_sectors()) > len {
+                error!("Volume does not fit in the partition");
+                return Err(Error::CorruptedFileSystem);
+            }
+        }
 
         let root_dir_sectors = bpb.root_dir_sectors();
         let first_data_sector = bpb.first_data_sector();
@@ -385,8 +671,8 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
             FsInfoSector::default()
         };
 
-        // if dirty flag is set completly ignore free_cluster_count in FSInfo
-        if bpb.status_flags().dirty {
+        // if dirty flag is set or user does not trust FSInfo completly ignore free_cluster_count in it
+        if bpb.status_flags().dirty || options.distrust_fs_info {
             fs_info.free_cluster_count = None;
         }
 
@@ -395,8 +681,11 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
 
         // return FileSystem struct
         let status_flags = bpb.status_flags();
-        trace!("FileSystem::new end");
-        Ok(Self {
+        #[cfg(feature = "alloc")]
+        let fat_cache = FatCache::new(options.fat_cache_sectors, options.fat_write_back && !options.read_only);
+        #[cfg(not(feature = "alloc"))]
+        let fat_cache = FatCache::new();
+        let fs = Self {
             disk: RefCell::new(disk),
             options,
             fat_type,
@@ -405,8 +694,23 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
             root_dir_sectors,
             total_clusters,
             fs_info: RefCell::new(fs_info),
+            fat_cache: RefCell::new(fat_cache),
+            mount_status_flags: Cell::new(status_flags),
             current_status_flags: Cell::new(status_flags),
-        })
+        };
+        if fs.options.exclusive {
+            if status_flags.dirty {
+                error!("Volume is marked as being in use");
+                return Err(Error::VolumeInUse);
+            }
+            if !fs.options.read_only {
+                fs.set_dirty_flag(true)?;
+                // make the flag visible to other users of the storage
+                fs.disk.borrow_mut().flush()?;
+            }
+        }
+        trace!("FileSystem::new_internal end");
+        Ok(fs)
     }
 
     /// Returns a type of File Allocation Table (FAT) used by this filesystem.
@@ -414,11 +718,67 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
         self.fat_type
     }
 
+    /// Returns `true` if the file system has been mounted in read-only mode.
+    pub fn is_read_only(&self) -> bool {
+        self.options.read_only
+    }
+
+    pub(crate) fn check_writable(&self) -> Result<(), Error<IO::Error>> {
+        if self.options.read_only {
+            debug!("write operation rejected on read-only file system");
+            return Err(Error::ReadOnlyFileSystem);
+        }
+        Ok(())
+    }
+
     /// Returns a volume identifier read from BPB in the Boot Sector.
     pub fn volume_id(&self) -> u32 {
         self.bpb.volume_id
     }
 
+    /// Returns a number of hidden sectors read from BPB in the Boot Sector.
+    ///
+    /// It is the number of sectors preceding the volume on the disk, i.e. the first sector of the partition
+    /// containing the volume. It is informational only: the BPB is stored at the beginning of the volume, so the
+    /// volume must be found first, e.g. using `read_mbr_partitions` and `new_in_partition`. The value can be used to
+    /// verify that the partition start matches the volume - `new_in_mbr_partition` does it. Many tools set it to 0
+    /// for volumes that are not placed in a partition.
+    #[must_use]
+    pub fn hidden_sectors(&self) -> u32 {
+        self.bpb.hidden_sectors
+    }
+
+    /// Changes the volume identifier (serial number).
+    ///
+    /// The identifier is written to the BPB in the Boot Sector and, on FAT32 volumes, to the backup Boot Sector.
+    /// Only the identifier field is written, the rest of the Boot Sector is left untouched.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if the Boot Sector has no extended BPB.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn set_volume_id(&mut self, volume_id: u32) -> Result<(), Error<IO::Error>> {
+        trace!("FileSystem::set_volume_id {:08X}", volume_id);
+        self.check_writable()?;
+        if self.bpb.ext_sig != 0x29 {
+            error!("Boot Sector has no extended BPB - cannot set volume ID");
+            return Err(Error::InvalidInput);
+        }
+        let offset = if self.fat_type == FatType::Fat32 { 0x043 } else { 0x027 };
+        {
+            let mut disk = self.disk.borrow_mut();
+            for boot_sector_pos in self.boot_sector_offsets().iter().flatten() {
+                disk.seek(SeekFrom::Start(boot_sector_pos + offset))?;
+                disk.write_u32_le(volume_id)?;
+            }
+        }
+        self.bpb.volume_id = volume_id;
+        Ok(())
+    }
+
     /// Returns a volume label from BPB in the Boot Sector as byte array slice.
     ///
     /// Label is encoded in the OEM codepage.
@@ -437,16 +797,61 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
         self.bpb.bytes_from_sectors(sector)
     }
 
-    fn sector_from_cluster(&self, cluster: u32) -> u32 {
-        self.first_data_sector + self.bpb.sectors_from_clusters(cluster - RESERVED_FAT_ENTRIES)
+    /// Returns a sector size in bytes.
+    #[must_use]
+    pub fn bytes_per_sector(&self) -> u16 {
+        self.bpb.bytes_per_sector
     }
 
     pub fn cluster_size(&self) -> u32 {
         self.bpb.cluster_size()
     }
 
-    pub(crate) fn offset_from_cluster(&self, cluster: u32) -> u64 {
-        self.offset_from_sector(self.sector_from_cluster(cluster))
+    /// Returns a byte offset of the cluster data from the beginning of the volume.
+    ///
+    /// Valid cluster numbers start from 2 and end at `total_clusters() + 1`. Cluster numbers can be obtained by
+    /// the `cluster_chain` method on `File`.
+    ///
+    /// # Panics
+    ///
+    /// Will panic in debug builds if `cluster` is lower than 2.
+    pub fn offset_from_cluster(&self, cluster: u32) -> u64 {
+        self.offset_from_sector(self.first_sector_of_cluster(cluster))
+    }
+
+    /// Returns a number of sectors in a cluster.
+    #[must_use]
+    pub fn sectors_per_cluster(&self) -> u8 {
+        self.bpb.sectors_per_cluster
+    }
+
+    /// Returns a number of the first sector of the cluster data.
+    ///
+    /// Sector numbers are relative to the beginning of the volume, like in `read_sector`. Valid cluster numbers start
+    /// from 2 and end at `total_clusters() + 1`.
+    ///
+    /// # Panics
+    ///
+    /// Will panic in debug builds if `cluster` is lower than 2.
+    #[must_use]
+    pub fn first_sector_of_cluster(&self, cluster: u32) -> u32 {
+        self.first_data_sector + self.bpb.sectors_from_clusters(cluster - RESERVED_FAT_ENTRIES)
+    }
+
+    /// Returns a number of the cluster containing the sector.
+    ///
+    /// It is the inverse of `first_sector_of_cluster` extended to all sectors of the cluster. `None` is returned for
+    /// sectors placed before the data region (reserved sectors, FATs and the FAT12/FAT16 root directory) and for
+    /// sectors after the last cluster.
+    #[must_use]
+    pub fn cluster_containing_sector(&self, sector: u32) -> Option<u32> {
+        let data_sector = sector.checked_sub(self.first_data_sector)?;
+        let cluster_index = data_sector / u32::from(self.bpb.sectors_per_cluster);
+        if cluster_index < self.total_clusters {
+            Some(cluster_index + RESERVED_FAT_ENTRIES)
+        } else {
+            None
+        }
     }
 
     pub(crate) fn bytes_from_clusters(&self, clusters: u32) -> u64 {
@@ -457,9 +862,17 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
         self.bpb.clusters_from_bytes(bytes)
     }
 
-    fn fat_slice(&self) -> impl ReadWriteSeek<Error = Error<IO::Error>> + '_ {
+    pub(crate) fn total_clusters(&self) -> u32 {
+        self.total_clusters
+    }
+
+    pub(crate) fn root_dir_first_cluster(&self) -> u32 {
+        self.bpb.root_dir_first_cluster
+    }
+
+    pub(crate) fn fat_slice(&self) -> impl ReadWriteSeek<Error = Error<IO::Error>> + '_ {
         let io = FsIoAdapter { fs: self };
-        fat_slice(io, &self.bpb)
+        FatCacheIo::new(fat_slice(io, &self.bpb), &self.fat_cache, self.bpb.bytes_per_sector)
     }
 
     pub(crate) fn cluster_iter(
@@ -467,10 +880,30 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
         cluster: u32,
     ) -> ClusterIterator<impl ReadWriteSeek<Error = Error<IO::Error>> + '_, IO::Error> {
         let disk_slice = self.fat_slice();
-        ClusterIterator::new(disk_slice, self.fat_type, cluster)
+        ClusterIterator::new(disk_slice, self.fat_type, cluster, self.total_clusters)
+    }
+
+    // Overwrites clusters that are going to be freed with zeros if enabled in options
+    fn scrub_clusters(
+        &self,
+        clusters: impl Iterator<Item = Result<u32, Error<IO::Error>>>,
+    ) -> Result<(), Error<IO::Error>> {
+        if !self.options.zero_freed_clusters {
+            return Ok(());
+        }
+        for r in clusters {
+            let cluster = r?;
+            let mut disk = self.disk.borrow_mut();
+            disk.seek(SeekFrom::Start(self.offset_from_cluster(cluster)))?;
+            write_zeros(&mut *disk, u64::from(self.cluster_size()))?;
+        }
+        // make sure data is overwritten before the FAT is updated
+        self.disk.borrow_mut().flush()?;
+        Ok(())
     }
 
     pub(crate) fn truncate_cluster_chain(&self, cluster: u32) -> Result<(), Error<IO::Error>> {
+        self.scrub_clusters(self.cluster_iter(cluster))?;
         let mut iter = self.cluster_iter(cluster);
         let num_free = iter.truncate()?;
         let mut fs_info = self.fs_info.borrow_mut();
@@ -479,6 +912,7 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
     }
 
     pub(crate) fn free_cluster_chain(&self, cluster: u32) -> Result<(), Error<IO::Error>> {
+        self.scrub_clusters(core::iter::once(Ok(cluster)).chain(self.cluster_iter(cluster)))?;
         let mut iter = self.cluster_iter(cluster);
         let num_free = iter.free()?;
         let mut fs_info = self.fs_info.borrow_mut();
@@ -504,13 +938,103 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
         Ok(cluster)
     }
 
+    // Allocates the cluster physically following `prev_cluster` and appends it to the chain if it is free
+    pub(crate) fn alloc_next_cluster_if_free(
+        &self,
+        prev_cluster: u32,
+        zero: bool,
+    ) -> Result<Option<u32>, Error<IO::Error>> {
+        let cluster = prev_cluster + 1;
+        if cluster >= self.total_clusters + RESERVED_FAT_ENTRIES
+            || read_fat(&mut self.fat_slice(), self.fat_type, cluster)? != FatValue::Free
+        {
+            return Ok(None);
+        }
+        self.alloc_contiguous_run(Some(prev_cluster), 1, Some(cluster), zero)
+    }
+
+    pub(crate) fn copy_cluster(&self, src_cluster: u32, dst_cluster: u32) -> Result<(), Error<IO::Error>> {
+        trace!("copy_cluster {} -> {}", src_cluster, dst_cluster);
+        // Note: buffer size is equal to the maximal sector size
+        let mut buf = [0_u8; 4096];
+        let mut disk = self.disk.borrow_mut();
+        let cluster_size = u64::from(self.cluster_size());
+        let mut copied = 0;
+        while copied < cluster_size {
+            let len = (cluster_size - copied).min(buf.len() as u64);
+            let chunk = &mut buf[..len as usize];
+            disk.seek(SeekFrom::Start(self.offset_from_cluster(src_cluster) + copied))?;
+            disk.read_exact(chunk)?;
+            disk.seek(SeekFrom::Start(self.offset_from_cluster(dst_cluster) + copied))?;
+            disk.write_all(chunk)?;
+            copied += len;
+        }
+        Ok(())
+    }
+
+    /// Allocates a chain of `count` physically contiguous clusters.
+    ///
+    /// The FAT is scanned for a run of `count` free clusters starting from `hint` (or from the next free cluster
+    /// hint stored in `FSInfo` if `hint` is `None`) and wrapping around to the beginning of the data area. The
+    /// clusters are linked into a chain terminated by the end-of-chain marker and the free clusters count is
+    /// decreased by `count`. Returns the first cluster of the chain or `None` if there is no contiguous run of free
+    /// clusters that is long enough - the FAT is not modified in that case.
+    ///
+    /// The chain is not referenced by any directory entry, so it is reported as lost by `check` until it is
+    /// attached to a file, for example by writing its first cluster into a directory entry.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if `count` is zero.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn alloc_clusters_contiguous(&self, count: u32, hint: Option<u32>) -> Result<Option<u32>, Error<IO::Error>> {
+        trace!("alloc_clusters_contiguous {} {:?}", count, hint);
+        if count == 0 {
+            return Err(Error::InvalidInput);
+        }
+        self.check_writable()?;
+        self.set_dirty_flag(true)?;
+        self.alloc_contiguous_run(None, count, hint, false)
+    }
+
+    // Allocates a run of contiguous clusters appending it to the chain ending at `prev_cluster`
+    pub(crate) fn alloc_contiguous_run(
+        &self,
+        prev_cluster: Option<u32>,
+        count: u32,
+        hint: Option<u32>,
+        zero: bool,
+    ) -> Result<Option<u32>, Error<IO::Error>> {
+        trace!("alloc_contiguous_run {}", count);
+        let hint = hint.or(self.fs_info.borrow().next_free_cluster);
+        let first_cluster = {
+            let mut fat = self.fat_slice();
+            alloc_clusters_contiguous(&mut fat, self.fat_type, prev_cluster, count, hint, self.total_clusters)?
+        };
+        let Some(first_cluster) = first_cluster else {
+            return Ok(None);
+        };
+        if zero {
+            let mut disk = self.disk.borrow_mut();
+            disk.seek(SeekFrom::Start(self.offset_from_cluster(first_cluster)))?;
+            write_zeros(&mut *disk, self.bytes_from_clusters(count))?;
+        }
+        let mut fs_info = self.fs_info.borrow_mut();
+        fs_info.set_next_free_cluster(first_cluster + count);
+        fs_info.map_free_clusters(|n| n - count);
+        Ok(Some(first_cluster))
+    }
+
     /// Returns status flags for this volume.
     ///
     /// # Errors
     ///
     /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
     pub fn read_status_flags(&self) -> Result<FsStatusFlags, Error<IO::Error>> {
-        let bpb_status = self.bpb.status_flags();
+        let bpb_status = self.mount_status_flags.get();
         let fat_status = read_fat_flags(&mut self.fat_slice(), self.fat_type)?;
         Ok(FsStatusFlags {
             dirty: bpb_status.dirty || fat_status.dirty,
@@ -518,9 +1042,237 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
         })
     }
 
+    fn check_fat_entry_cluster(&self, cluster: u32) -> Result<(), Error<IO::Error>> {
+        if (RESERVED_FAT_ENTRIES..self.total_clusters + RESERVED_FAT_ENTRIES).contains(&cluster) {
+            Ok(())
+        } else {
+            error!("invalid cluster number {}", cluster);
+            Err(Error::InvalidInput)
+        }
+    }
+
+    /// Reads an entry of the File Allocation Table.
+    ///
+    /// Valid cluster numbers start from 2 and end at `total_clusters() + 1`.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if `cluster` is not a valid cluster number.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn read_fat_entry(&self, cluster: u32) -> Result<FatValue, Error<IO::Error>> {
+        self.check_fat_entry_cluster(cluster)?;
+        read_fat(&mut self.fat_slice(), self.fat_type, cluster)
+    }
+
+    /// Reads an entry from the given copy of the File Allocation Table.
+    ///
+    /// Unlike `read_fat_entry` this method ignores the mirroring flag and the active FAT stored in the boot sector.
+    /// It can be used to compare FAT copies during a repair. Valid FAT indices start from 0 and end at the number of
+    /// FATs minus 1. Valid cluster numbers start from 2 and end at `total_clusters() + 1`.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if `fat_index` is not a valid FAT index or `cluster` is not a valid
+    ///   cluster number.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn read_fat_entry_from(&self, fat_index: u8, cluster: u32) -> Result<FatValue, Error<IO::Error>> {
+        if fat_index >= self.bpb.fats {
+            error!("invalid FAT index {}", fat_index);
+            return Err(Error::InvalidInput);
+        }
+        self.check_fat_entry_cluster(cluster)?;
+        // make sure cached changes are visible in all FAT copies
+        self.flush_fat()?;
+        let sectors_per_fat = self.bpb.sectors_per_fat();
+        let fat_first_sector = self.bpb.reserved_sectors() + u32::from(fat_index) * sectors_per_fat;
+        let io = FsIoAdapter { fs: self };
+        let mut fat: DiskSlice<_, FsIoAdapter<IO, TP, OCC>> =
+            DiskSlice::from_sectors(fat_first_sector, sectors_per_fat, 1, &self.bpb, io);
+        read_fat(&mut fat, self.fat_type, cluster)
+    }
+
+    /// Reads a raw sector of the volume.
+    ///
+    /// `sector` is a sector number relative to the beginning of the volume. `buf` must be exactly `bytes_per_sector`
+    /// bytes long. Changes to the File Allocation Table and the FS Information Sector that are still cached in memory
+    /// are copied to `buf`, so the returned data reflects the current state of the volume. This method never writes
+    /// to the storage - cached changes are written when the file system is flushed or unmounted.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if `sector` is not lower than the total number of sectors in the volume
+    ///   or if the length of `buf` is not equal to the sector size.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn read_sector(&self, sector: u32, buf: &mut [u8]) -> Result<(), Error<IO::Error>> {
+        trace!("FileSystem::read_sector {}", sector);
+        self.check_raw_sector(sector, buf.len())?;
+        let mut disk = self.disk.borrow_mut();
+        disk.seek(SeekFrom::Start(self.offset_from_sector(sector)))?;
+        disk.read_exact(buf)?;
+        // overlay changes that have not been written back yet
+        if let Some(fat_sector) = self.fat_sector_index(sector) {
+            if let Some(data) = self.fat_cache.borrow().dirty_sector(fat_sector) {
+                buf.copy_from_slice(data);
+            }
+        }
+        let fs_info = self.fs_info.borrow();
+        if self.fat_type == FatType::Fat32 && fs_info.dirty && sector == u32::from(self.bpb.fs_info_sector) {
+            fs_info.serialize(&mut SliceWriter::<IO::Error>::new(buf))?;
+        }
+        Ok(())
+    }
+
+    // Returns the sector index in the FAT cache of the given volume sector if it belongs to a FAT copy that is
+    // accessed through the cache
+    fn fat_sector_index(&self, sector: u32) -> Option<u32> {
+        let sectors_per_fat = self.bpb.sectors_per_fat();
+        let first_fat_sector = |fat: u32| self.bpb.reserved_sectors() + fat * sectors_per_fat;
+        // with mirroring enabled cached sectors are written to all FAT copies
+        let fats = if self.bpb.mirroring_enabled() {
+            0..u32::from(self.bpb.fats)
+        } else {
+            let active_fat = u32::from(self.bpb.active_fat());
+            active_fat..active_fat + 1
+        };
+        fats.map(first_fat_sector)
+            .find(|first| (*first..*first + sectors_per_fat).contains(&sector))
+            .map(|first| sector - first)
+    }
+
+    /// Writes a raw sector of the volume.
+    ///
+    /// `sector` is a sector number relative to the beginning of the volume. `buf` must be exactly `bytes_per_sector`
+    /// bytes long. Cached FAT sectors are dropped after writing so the new content is used by later operations.
+    /// Note: other file system metadata (for example the boot sector, the FS Information Sector or directory entries
+    /// of open files) is kept in memory and is not reloaded, so overwriting sectors used by the file system can
+    /// cause a filesystem corruption.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if `sector` is not lower than the total number of sectors in the volume
+    ///   or if the length of `buf` is not equal to the sector size.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn write_sector(&self, sector: u32, buf: &[u8]) -> Result<(), Error<IO::Error>> {
+        trace!("FileSystem::write_sector {}", sector);
+        self.check_writable()?;
+        self.check_raw_sector(sector, buf.len())?;
+        // write back cached FAT changes so they do not overwrite the new sector content later
+        self.flush_fat()?;
+        {
+            let mut disk = self.disk.borrow_mut();
+            disk.seek(SeekFrom::Start(self.offset_from_sector(sector)))?;
+            disk.write_all(buf)?;
+        }
+        self.fat_cache.borrow_mut().clear();
+        Ok(())
+    }
+
+    fn check_raw_sector(&self, sector: u32, buf_len: usize) -> Result<(), Error<IO::Error>> {
+        if sector >= self.bpb.total_sectors() {
+            error!("invalid sector number {}", sector);
+            return Err(Error::InvalidInput);
+        }
+        if buf_len != usize::from(self.bpb.bytes_per_sector) {
+            error!("invalid sector buffer length {}", buf_len);
+            return Err(Error::InvalidInput);
+        }
+        Ok(())
+    }
+
+    /// Returns an iterator over all entries of the File Allocation Table.
+    ///
+    /// Items are pairs of a cluster number and the value of its FAT entry. Cluster numbers start from 2 and end at
+    /// `total_clusters() + 1`. Iteration stops after the first error.
+    pub fn fat_entries(&self) -> impl Iterator<Item = Result<(u32, FatValue), Error<IO::Error>>> + '_ {
+        let mut fat = self.fat_slice();
+        let fat_type = self.fat_type;
+        let mut failed = false;
+        (RESERVED_FAT_ENTRIES..self.total_clusters + RESERVED_FAT_ENTRIES).map_while(move |cluster| {
+            if failed {
+                return None;
+            }
+            let result = read_fat(&mut fat, fat_type, cluster);
+            failed = result.is_err();
+            Some(result.map(|value| (cluster, value)))
+        })
+    }
+
+    /// Writes an entry of the File Allocation Table.
+    ///
+    /// The entry is written to all FAT copies if mirroring is enabled. The free clusters count is updated if the
+    /// entry changes from free to used or the other way round. No other consistency checks are made so this function
+    /// can easily corrupt the filesystem - it is intended for recovery tools.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if `cluster` or a cluster number stored in `value` is not a valid
+    ///   cluster number.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn write_fat_entry(&self, cluster: u32, value: FatValue) -> Result<(), Error<IO::Error>> {
+        trace!("write_fat_entry {} {:?}", cluster, value);
+        self.check_fat_entry_cluster(cluster)?;
+        if let FatValue::Data(next_cluster) = value {
+            self.check_fat_entry_cluster(next_cluster)?;
+        }
+        self.check_writable()?;
+        let mut fat = self.fat_slice();
+        let old_value = read_fat(&mut fat, self.fat_type, cluster)?;
+        write_fat(&mut fat, self.fat_type, cluster, value)?;
+        let mut fs_info = self.fs_info.borrow_mut();
+        match (old_value == FatValue::Free, value == FatValue::Free) {
+            (true, false) => fs_info.map_free_clusters(|n| n - 1),
+            (false, true) => fs_info.map_free_clusters(|n| n + 1),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Marks a free cluster as bad so it is never allocated.
+    ///
+    /// Marking a cluster that is already marked as bad does nothing. The free clusters count is updated.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if `cluster` is not a valid cluster number or the cluster is used by
+    ///   a file or a directory.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn mark_cluster_bad(&self, cluster: u32) -> Result<(), Error<IO::Error>> {
+        trace!("mark_cluster_bad {}", cluster);
+        self.check_fat_entry_cluster(cluster)?;
+        self.check_writable()?;
+        let mut fat = self.fat_slice();
+        match read_fat(&mut fat, self.fat_type, cluster)? {
+            FatValue::Free => {}
+            FatValue::Bad => return Ok(()),
+            _ => {
+                error!("cluster {} is in use", cluster);
+                return Err(Error::InvalidInput);
+            }
+        }
+        write_fat(&mut fat, self.fat_type, cluster, FatValue::Bad)?;
+        self.fs_info.borrow_mut().map_free_clusters(|n| n - 1);
+        Ok(())
+    }
+
     /// Returns filesystem statistics like number of total and free clusters.
     ///
-    /// For FAT32 volumes number of free clusters from the FS Information Sector is returned (may be incorrect).
+    /// For FAT32 volumes number of free clusters from the FS Information Sector is returned (may be incorrect, use
+    /// `recalc_free_clusters` to get the correct value).
     /// For other FAT variants number is computed on the first call to this method and cached for later use.
     ///
     /// # Errors
@@ -531,7 +1283,7 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
         let free_clusters = if let Some(n) = free_clusters_option {
             n
         } else {
-            self.recalc_free_clusters()?
+            self.update_free_cluster_count()?
         };
         Ok(FileSystemStats {
             cluster_size: self.cluster_size(),
@@ -541,13 +1293,55 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
     }
 
     /// Forces free clusters recalculation.
-    fn recalc_free_clusters(&self) -> Result<u32, Error<IO::Error>> {
+    ///
+    /// The FAT is scanned to count free clusters. The result is cached for `stats` and written to the FS Information
+    /// Sector (FAT32 only) unless the file system has been mounted in read-only mode. It can be used to get
+    /// trustworthy statistics after mounting a volume that was not cleanly unmounted.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn recalc_free_clusters(&self) -> Result<u32, Error<IO::Error>> {
+        trace!("FileSystem::recalc_free_clusters");
+        let free_cluster_count = self.update_free_cluster_count()?;
+        self.flush()?;
+        Ok(free_cluster_count)
+    }
+
+    fn update_free_cluster_count(&self) -> Result<u32, Error<IO::Error>> {
         let mut fat = self.fat_slice();
         let free_cluster_count = count_free_clusters(&mut fat, self.fat_type, self.total_clusters)?;
         self.fs_info.borrow_mut().set_free_cluster_count(free_cluster_count);
         Ok(free_cluster_count)
     }
 
+    /// Returns the next free cluster hint used by the cluster allocator.
+    ///
+    /// The search for a free cluster starts at the hinted cluster. On FAT32 volumes the hint is loaded from the FS
+    /// Information Sector. `None` is returned if the hint is unknown, for example on FAT12/FAT16 volumes before the
+    /// first cluster has been allocated.
+    #[must_use]
+    pub fn next_free_cluster_hint(&self) -> Option<u32> {
+        self.fs_info.borrow().next_free_cluster
+    }
+
+    /// Sets the next free cluster hint used by the cluster allocator.
+    ///
+    /// Subsequent cluster allocations start searching for a free cluster at `cluster`. It can be used to control
+    /// the layout of newly allocated clusters. On FAT32 volumes the hint is written to the FS Information Sector
+    /// when the file system is flushed or unmounted.
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidInput` will be returned if `cluster` is not a valid data cluster number.
+    pub fn set_next_free_cluster_hint(&self, cluster: u32) -> Result<(), Error<IO::Error>> {
+        if cluster < RESERVED_FAT_ENTRIES || cluster >= self.total_clusters + RESERVED_FAT_ENTRIES {
+            return Err(Error::InvalidInput);
+        }
+        self.fs_info.borrow_mut().set_next_free_cluster(cluster);
+        Ok(())
+    }
+
     /// Unmounts the filesystem.
     ///
     /// Updates the FS Information Sector if needed.
@@ -559,12 +1353,87 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
         self.unmount_internal()
     }
 
+    /// Unmounts the filesystem and returns the underlying storage object.
+    ///
+    /// Works like `unmount` but the storage object is returned instead of being dropped, so it can be reused, for
+    /// example to mount the volume again. The storage object is flushed and rewound to the position `0` before it is
+    /// returned, so it can be passed to `new` directly. If the file system has been created by `new_in_partition`
+    /// or `new_in_mbr_partition` the storage object containing the whole partition table is returned.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error. The storage object is
+    /// dropped in that case.
+    pub fn unmount_into_inner(mut self) -> Result<IO, Error<IO::Error>> {
+        self.unmount_internal()?;
+        let disk = self.disk.get_mut();
+        disk.flush()?;
+        disk.inner_mut().seek(SeekFrom::Start(0))?;
+        Ok(disk.take_inner())
+    }
+
+    /// Flushes file system metadata and the underlying storage without unmounting the volume.
+    ///
+    /// Writes the `FSInfo` sector if it has been modified and flushes the storage object. The volume dirty flag is
+    /// left as-is, so the volume is still marked as being in use. Directory entries of open files are updated when
+    /// the files are flushed or dropped.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn flush(&self) -> Result<(), Error<IO::Error>> {
+        if self.options.read_only {
+            return Ok(());
+        }
+        self.flush_fat()?;
+        self.flush_fs_info()?;
+        self.disk.borrow_mut().flush()?;
+        Ok(())
+    }
+
+    /// Clears the volume dirty flag that was set when the file system was mounted.
+    ///
+    /// The dirty flag left by an unclean unmount (e.g. a power loss) is normally preserved, so the volume keeps
+    /// being reported as dirty. This method should be called after making sure the volume is consistent (e.g. by
+    /// the `check` method) to mark it as clean again. The FAT and the `FSInfo` sector are flushed and the flag is
+    /// cleared right away, unless the file system has been mounted in the exclusive mode - in that case the flag is
+    /// cleared on unmount. The flag is set again by the next write to the volume.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn clear_dirty_flag(&self) -> Result<(), Error<IO::Error>> {
+        if self.options.read_only {
+            return Err(Error::ReadOnlyFileSystem);
+        }
+        let mut flags = self.mount_status_flags.get();
+        flags.dirty = false;
+        self.mount_status_flags.set(flags);
+        self.flush_fat()?;
+        self.flush_fs_info()?;
+        self.set_dirty_flag(self.options.exclusive)?;
+        self.disk.borrow_mut().flush()?;
+        Ok(())
+    }
+
     fn unmount_internal(&self) -> Result<(), Error<IO::Error>> {
+        if self.options.read_only {
+            return Ok(());
+        }
+        self.flush_fat()?;
         self.flush_fs_info()?;
         self.set_dirty_flag(false)?;
         Ok(())
     }
 
+    fn flush_fat(&self) -> Result<(), Error<IO::Error>> {
+        let io = FsIoAdapter { fs: self };
+        FatCacheIo::new(fat_slice(io, &self.bpb), &self.fat_cache, self.bpb.bytes_per_sector).write_back()
+    }
+
     fn flush_fs_info(&self) -> Result<(), Error<IO::Error>> {
         let mut fs_info = self.fs_info.borrow_mut();
         if self.fat_type == FatType::Fat32 && fs_info.dirty {
@@ -579,7 +1448,7 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
 
     pub(crate) fn set_dirty_flag(&self, dirty: bool) -> Result<(), IO::Error> {
         // Do not overwrite flags read from BPB on mount
-        let mut flags = self.bpb.status_flags();
+        let mut flags = self.mount_status_flags.get();
         flags.dirty |= dirty;
         // Check if flags has changed
         let current_flags = self.current_status_flags.get();
@@ -596,14 +1465,26 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
             0x025
         };
         let mut disk = self.disk.borrow_mut();
-        disk.seek(io::SeekFrom::Start(offset))?;
-        disk.write_u8(encoded)?;
+        // keep the backup boot sector consistent with the primary one
+        for boot_sector_pos in self.boot_sector_offsets().iter().flatten() {
+            disk.seek(io::SeekFrom::Start(boot_sector_pos + offset))?;
+            disk.write_u8(encoded)?;
+        }
         self.current_status_flags.set(flags);
         Ok(())
     }
 
+    // Returns offsets of the primary and the backup (FAT32 only) boot sector
+    fn boot_sector_offsets(&self) -> [Option<u64>; 2] {
+        let mut boot_sectors = [Some(0), None];
+        if self.fat_type == FatType::Fat32 && self.bpb.backup_boot_sector() != 0 {
+            boot_sectors[1] = Some(self.offset_from_sector(self.bpb.backup_boot_sector()));
+        }
+        boot_sectors
+    }
+
     /// Returns a root directory object allowing for futher penetration of a filesystem structure.
-    pub fn root_dir(&self) -> Dir<IO, TP, OCC> {
+    pub fn root_dir(&self) -> Dir<'_, IO, TP, OCC> {
         trace!("root_dir");
         let root_rdr = {
             match self.fat_type {
@@ -619,6 +1500,57 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
         };
         Dir::new(root_rdr, self)
     }
+
+    /// Opens a file using its first cluster number instead of a directory entry.
+    ///
+    /// It is intended for data recovery when the directory entry of a file is damaged but its first cluster is known.
+    /// If `size` is provided reading stops at this position. Otherwise the file is read until the end of its cluster
+    /// chain. The returned file has no directory entry so it cannot be modified: writing and truncating it returns
+    /// `Error::PermissionDenied`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidInput` will be returned if `first_cluster` is not a valid data cluster number.
+    pub fn open_file_from_cluster(
+        &self,
+        first_cluster: u32,
+        size: Option<u32>,
+    ) -> Result<File<'_, IO, TP, OCC>, Error<IO::Error>> {
+        trace!("open_file_from_cluster {}", first_cluster);
+        if !(RESERVED_FAT_ENTRIES..self.total_clusters + RESERVED_FAT_ENTRIES).contains(&first_cluster) {
+            error!("Invalid cluster number {}", first_cluster);
+            return Err(Error::InvalidInput);
+        }
+        Ok(File::new_detached(Some(first_cluster), size, self))
+    }
+
+    /// Opens a deleted file for reading in order to recover its content.
+    ///
+    /// The cluster chain is followed starting from the first cluster of the deleted entry up to the recorded size.
+    /// Clusters of deleted files are usually marked as free in the FAT, so if the chain is broken the file is assumed
+    /// to be allocated contiguously. This is a heuristic: the returned data may be stale or belong to other files if
+    /// the clusters have been reused or the file was fragmented. The returned file cannot be modified.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if `entry` is a directory or its first cluster is not a valid data
+    ///   cluster number.
+    pub fn recover_deleted(&self, entry: &DeletedDirEntry) -> Result<File<'_, IO, TP, OCC>, Error<IO::Error>> {
+        trace!("recover_deleted {:?}", entry.first_cluster());
+        if entry.is_dir() {
+            error!("Recovery of deleted directories is not supported");
+            return Err(Error::InvalidInput);
+        }
+        if let Some(n) = entry.first_cluster() {
+            if !(RESERVED_FAT_ENTRIES..self.total_clusters + RESERVED_FAT_ENTRIES).contains(&n) {
+                error!("Invalid cluster number {}", n);
+                return Err(Error::InvalidInput);
+            }
+        }
+        Ok(File::new_recovered(entry.first_cluster(), entry.size(), self))
+    }
 }
 
 impl<IO: ReadWriteSeek, TP, OCC: OemCpConverter> FileSystem<IO, TP, OCC> {
@@ -677,11 +1609,84 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> FileSystem<IO, TP
         let entry_opt = self.root_dir().find_volume_entry()?;
         Ok(entry_opt.map(|e| *e.raw_short_name()))
     }
+
+    /// Checks the file system consistency.
+    ///
+    /// The whole directory tree is traversed and cluster chains of all entries are compared with the FAT. Clusters
+    /// used by more than one entry, clusters allocated in the FAT but not used by any entry, broken cluster chains and
+    /// file sizes not matching the cluster chain length are reported. The file system is not modified, problems
+    /// are only reported. The check reads every directory and the entire FAT so it can take a long time on big volumes.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg(feature = "alloc")]
+    pub fn check(&self) -> Result<FsckReport, Error<IO::Error>> {
+        trace!("FileSystem::check");
+        fsck::check(self)
+    }
+
+    /// Changes the volume label.
+    ///
+    /// The label is encoded in the OEM codepage, padded with spaces and written to the BPB in the Boot Sector and,
+    /// on FAT32 volumes, to the backup Boot Sector. If the root directory contains an entry with `VOLUME_ID` attribute
+    /// its name is updated too.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if the encoded label is longer than 11 bytes, if it contains
+    ///   a character that cannot be encoded in the OEM codepage or if the Boot Sector has no extended BPB.
+    /// * `Error::ReadOnlyFileSystem` will be returned if the file system has been mounted in read-only mode.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn set_volume_label(&mut self, label: &str) -> Result<(), Error<IO::Error>> {
+        trace!("FileSystem::set_volume_label {}", label);
+        self.check_writable()?;
+        let mut volume_label = [SFN_PADDING; SFN_SIZE];
+        for (i, c) in label.chars().enumerate() {
+            let Some(b) = self.options.oem_cp_converter.encode(c) else {
+                error!("Volume label character cannot be encoded: {}", c);
+                return Err(Error::InvalidInput);
+            };
+            if i == SFN_SIZE {
+                error!("Volume label is too long: {}", label);
+                return Err(Error::InvalidInput);
+            }
+            volume_label[i] = b;
+        }
+        if self.bpb.ext_sig != 0x29 {
+            error!("Boot Sector has no extended BPB - cannot set volume label");
+            return Err(Error::InvalidInput);
+        }
+        // update root directory entry first so BPB is not modified if it fails
+        if let Some(e) = self.root_dir().find_volume_entry()? {
+            let data = e.data.renamed(volume_label);
+            let mut disk = self.disk.borrow_mut();
+            disk.seek(SeekFrom::Start(e.entry_pos))?;
+            data.serialize(&mut *disk)?;
+        }
+        // Note: only one field is written to avoid rewriting entire boot-sector which could be dangerous
+        let offset = if self.fat_type == FatType::Fat32 { 0x047 } else { 0x02B };
+        {
+            let mut disk = self.disk.borrow_mut();
+            for boot_sector_pos in self.boot_sector_offsets().iter().flatten() {
+                disk.seek(SeekFrom::Start(boot_sector_pos + offset))?;
+                disk.write_all(&volume_label)?;
+            }
+        }
+        self.bpb.volume_label = volume_label;
+        Ok(())
+    }
 }
 
 /// `Drop` implementation tries to unmount the filesystem when dropping.
 impl<IO: ReadWriteSeek, TP, OCC> Drop for FileSystem<IO, TP, OCC> {
     fn drop(&mut self) {
+        if self.disk.get_mut().inner.is_none() {
+            // the storage has been taken out by `unmount_into_inner` after unmounting
+            return;
+        }
         if let Err(err) = self.unmount_internal() {
             error!("unmount failed {:?}", err);
         }
@@ -729,6 +1734,129 @@ impl<IO: ReadWriteSeek, TP, OCC> Clone for FsIoAdapter<'_, IO, TP, OCC> {
     }
 }
 
+fn read_boot_sector<S: ReadWriteSeek>(disk: &mut S, use_backup: bool) -> Result<BootSector, Error<S::Error>> {
+    let boot = BootSector::deserialize(disk)?;
+    let err = match boot.validate() {
+        Ok(()) => return Ok(boot),
+        Err(err) => err,
+    };
+    if !use_backup {
+        return Err(err);
+    }
+    // BPB fields may be corrupted so use the usual location of the backup if they are not reasonable
+    let bpb = &boot.bpb;
+    let backup_offset = if bpb.bytes_per_sector.is_power_of_two()
+        && (512..=4096).contains(&bpb.bytes_per_sector)
+        && bpb.backup_boot_sector != 0
+        && bpb.backup_boot_sector != 0xFFFF
+    {
+        u64::from(bpb.backup_boot_sector) * u64::from(bpb.bytes_per_sector)
+    } else {
+        DEFAULT_BACKUP_BOOT_SECTOR_OFFSET
+    };
+    match read_backup_boot_sector(disk, backup_offset) {
+        Ok(backup) if backup.bpb.is_fat32() => {
+            warn!("Primary boot sector is invalid - using backup boot sector");
+            Ok(backup)
+        }
+        // backup boot sector is also invalid - report the original error
+        _ => Err(err),
+    }
+}
+
+fn read_backup_boot_sector<S: ReadWriteSeek>(disk: &mut S, offset: u64) -> Result<BootSector, Error<S::Error>> {
+    disk.seek(SeekFrom::Start(offset))?;
+    let boot = BootSector::deserialize(disk)?;
+    boot.validate()?;
+    Ok(boot)
+}
+
+// Storage adapter offsetting all accesses by the partition start and limiting them to the partition size
+pub(crate) struct PartitionIo<IO> {
+    // `None` only after the storage has been taken out by `FileSystem::unmount_into_inner`
+    inner: Option<IO>,
+    start: u64,
+    len: Option<u64>,
+    pos: u64,
+}
+
+impl<IO> PartitionIo<IO> {
+    fn new(inner: IO, start: u64, len: Option<u64>) -> Self {
+        Self {
+            inner: Some(inner),
+            start,
+            len,
+            pos: 0,
+        }
+    }
+
+    fn inner_mut(&mut self) -> &mut IO {
+        // Unwrapping is safe because the storage is taken out only when the file system is consumed
+        self.inner.as_mut().unwrap()
+    }
+
+    fn take_inner(&mut self) -> IO {
+        // Unwrapping is safe because the storage is taken out only once
+        self.inner.take().unwrap()
+    }
+
+    fn bytes_left(&self, buf_len: usize) -> usize {
+        match self.len {
+            Some(len) => (buf_len as u64).min(len.saturating_sub(self.pos)) as usize,
+            None => buf_len,
+        }
+    }
+}
+
+impl<IO: IoBase> IoBase for PartitionIo<IO> {
+    type Error = IO::Error;
+}
+
+impl<IO: Read> Read for PartitionIo<IO> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let read_size = self.bytes_left(buf.len());
+        let size = self.inner_mut().read(&mut buf[..read_size])?;
+        self.pos += size as u64;
+        Ok(size)
+    }
+}
+
+impl<IO: Write> Write for PartitionIo<IO> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let write_size = self.bytes_left(buf.len());
+        if write_size == 0 {
+            return Ok(0);
+        }
+        let size = self.inner_mut().write(&buf[..write_size])?;
+        self.pos += size as u64;
+        Ok(size)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.inner_mut().flush()
+    }
+}
+
+impl<IO: Seek> Seek for PartitionIo<IO> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        let start = self.start;
+        let abs_pos = match pos {
+            SeekFrom::Start(n) => self.inner_mut().seek(SeekFrom::Start(start + n))?,
+            SeekFrom::Current(n) => self.inner_mut().seek(SeekFrom::Current(n))?,
+            SeekFrom::End(n) => match self.len {
+                Some(len) => {
+                    self.inner_mut().seek(SeekFrom::Start(start + len))?;
+                    self.inner_mut().seek(SeekFrom::Current(n))?
+                }
+                None => self.inner_mut().seek(SeekFrom::End(n))?,
+            },
+        };
+        // the file system never seeks before the partition start
+        self.pos = abs_pos.wrapping_sub(self.start);
+        Ok(self.pos)
+    }
+}
+
 fn fat_slice<S: ReadWriteSeek, B: BorrowMut<S>>(
     io: B,
     bpb: &BiosParameterBlock,
@@ -736,6 +1864,7 @@ fn fat_slice<S: ReadWriteSeek, B: BorrowMut<S>>(
     let sectors_per_fat = bpb.sectors_per_fat();
     let mirroring_enabled = bpb.mirroring_enabled();
     let (fat_first_sector, mirrors) = if mirroring_enabled {
+        // all FAT copies are placed one after another and every write is mirrored to each of them
         (bpb.reserved_sectors(), bpb.fats)
     } else {
         let active_fat = u32::from(bpb.active_fat());
@@ -778,6 +1907,16 @@ impl<B: BorrowMut<S>, S: ReadWriteSeek> DiskSlice<B, S> {
     pub(crate) fn abs_pos(&self) -> u64 {
         self.begin + self.offset
     }
+
+    pub(crate) fn extent(&self) -> Extent {
+        // safe cast: slices used for directories are much smaller than 4 GB
+        #[allow(clippy::cast_possible_truncation)]
+        Extent {
+            file_offset: 0,
+            offset: self.begin,
+            size: self.size as u32,
+        }
+    }
 }
 
 // Note: derive cannot be used because of invalid bounds. See: https://github.com/rust-lang/rust/issues/26925
@@ -900,7 +2039,8 @@ impl OemCpConverter for LossyOemCpConverter {
 }
 
 pub(crate) fn write_zeros<IO: ReadWriteSeek>(disk: &mut IO, mut len: u64) -> Result<(), IO::Error> {
-    const ZEROS: [u8; 512] = [0_u8; 512];
+    // Note: buffer size is equal to the maximal sector size so sector aligned writes are split into whole sectors
+    const ZEROS: [u8; 4096] = [0_u8; 4096];
     while len > 0 {
         let write_size = len.min(ZEROS.len() as u64) as usize;
         disk.write_all(&ZEROS[..write_size])?;
@@ -930,12 +2070,17 @@ pub struct FormatVolumeOptions {
     pub(crate) fat_type: Option<FatType>,
     pub(crate) max_root_dir_entries: u16,
     pub(crate) fats: u8,
+    pub(crate) reserved_sectors: Option<u16>,
+    pub(crate) data_alignment_sectors: Option<u32>,
+    pub(crate) sectors_per_fat: Option<u32>,
     pub(crate) media: u8,
     pub(crate) sectors_per_track: u16,
     pub(crate) heads: u16,
     pub(crate) drive_num: Option<u8>,
     pub(crate) volume_id: u32,
     pub(crate) volume_label: Option<[u8; SFN_SIZE]>,
+    pub(crate) oem_name: [u8; 8],
+    pub(crate) quick: bool,
 }
 
 impl Default for FormatVolumeOptions {
@@ -947,12 +2092,17 @@ impl Default for FormatVolumeOptions {
             fat_type: None,
             max_root_dir_entries: 512,
             fats: 2,
+            reserved_sectors: None,
+            data_alignment_sectors: None,
+            sectors_per_fat: None,
             media: 0xF8,
             sectors_per_track: 0x20,
             heads: 0x40,
             drive_num: None,
             volume_id: 0x1234_5678,
             volume_label: None,
+            oem_name: *b"MSWIN4.1",
+            quick: true,
         }
     }
 }
@@ -1019,6 +2169,8 @@ impl FormatVolumeOptions {
     /// Set total number of sectors
     ///
     /// If option is not specified total number of sectors is calculated as storage device size divided by sector size.
+    /// The size is obtained by seeking to the end of the storage so this option is required for devices that do not
+    /// support it.
     #[must_use]
     pub fn total_sectors(mut self, total_sectors: u32) -> Self {
         self.total_sectors = Some(total_sectors);
@@ -1052,6 +2204,77 @@ impl FormatVolumeOptions {
         self
     }
 
+    /// Set number of reserved sectors
+    ///
+    /// Reserved sectors are placed at the beginning of the volume and contain the Boot Sector. On FAT32 volumes they
+    /// also contain the `FSInfo` sector (sector 1) and the backup Boot Sector (sector 6) so more than 6 sectors must
+    /// be reserved, otherwise `format_volume` will not select FAT32. Many implementations use 32 reserved sectors
+    /// for FAT32 volumes.
+    /// Default is `8` for FAT32 and `1` for FAT12/FAT16.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `reserved_sectors` is `0`.
+    #[must_use]
+    pub fn reserved_sectors(mut self, reserved_sectors: u16) -> Self {
+        assert!(reserved_sectors >= 1, "Invalid number of reserved sectors");
+        self.reserved_sectors = Some(reserved_sectors);
+        self
+    }
+
+    /// Set alignment of the data region in sectors
+    ///
+    /// If set, reserved sectors are padded so the first data sector (the first sector of cluster 2) is a multiple
+    /// of `data_alignment_sectors`. It is useful for flash media, where clusters aligned to an erase block boundary
+    /// improve write performance. Alignment is relative to the beginning of the volume, so the volume itself should
+    /// be aligned on the device too. The number of reserved sectors set by `reserved_sectors` is treated as a minimum.
+    /// Padding is placed after the `FSInfo` sector and the backup Boot Sector so their placement is not changed.
+    /// Note: on FAT12/FAT16 volumes padding makes the number of reserved sectors bigger than 1, which is not supported
+    /// by some old implementations.
+    /// By default the data region is not aligned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data_alignment_sectors` is `0`.
+    #[must_use]
+    pub fn data_alignment_sectors(mut self, data_alignment_sectors: u32) -> Self {
+        assert!(data_alignment_sectors >= 1, "Invalid data alignment");
+        self.data_alignment_sectors = Some(data_alignment_sectors);
+        self
+    }
+
+    /// Set size of a single File Allocation Table in sectors
+    ///
+    /// If set, the value is used instead of a size computed from the volume geometry. It is useful for reproducing
+    /// a volume layout created by another tool. The FAT must be big enough to hold an entry for every cluster,
+    /// otherwise `format_volume` returns `Error::InvalidInput`. A bigger FAT is allowed but it reduces the data region.
+    /// By default the smallest FAT that can address all clusters is used.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sectors_per_fat` is `0`.
+    #[must_use]
+    pub fn sectors_per_fat(mut self, sectors_per_fat: u32) -> Self {
+        assert!(sectors_per_fat >= 1, "Invalid number of sectors per FAT");
+        self.sectors_per_fat = Some(sectors_per_fat);
+        self
+    }
+
+    /// Set OEM name written into the Boot Sector
+    ///
+    /// Name must be padded with spaces. Only printable ASCII characters are allowed.
+    /// Default is `MSWIN4.1`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `oem_name` contains a character outside of the printable ASCII range.
+    #[must_use]
+    pub fn oem_name(mut self, oem_name: [u8; 8]) -> Self {
+        assert!(oem_name.iter().all(|b| (0x20..=0x7E).contains(b)), "Invalid OEM name");
+        self.oem_name = oem_name;
+        self
+    }
+
     /// Set media field for Bios Parameters Block
     ///
     /// Default is `0xF8`.
@@ -1105,6 +2328,17 @@ impl FormatVolumeOptions {
         self.volume_label = Some(volume_label);
         self
     }
+
+    /// Set quick format mode
+    ///
+    /// Quick format initializes only the file system structures (FATs and root directory). If disabled the entire
+    /// data region is filled with zeros too, so no previous content of the storage is left in free clusters.
+    /// Default is `true`.
+    #[must_use]
+    pub fn quick(mut self, quick: bool) -> Self {
+        self.quick = quick;
+        self
+    }
 }
 
 /// Create FAT filesystem on a disk or partition (format a volume)
@@ -1124,6 +2358,8 @@ impl FormatVolumeOptions {
 ///   Possible reason can be requesting a fat type that is not compatible with the total number of clusters or
 ///   formatting a too big storage. If sectors/clusters related options in `options` structure were left set to
 ///   defaults this error is very unlikely to happen.
+/// * `Error::InvalidInput` will be returned if `total_sectors` option is not specified and the storage size
+///   cannot be determined because `storage` does not support seeking to its end.
 /// * `Error::Io` will be returned if the provided storage object returned an I/O error.
 ///
 /// # Panics
@@ -1137,9 +2373,16 @@ pub fn format_volume<S: ReadWriteSeek>(storage: &mut S, options: FormatVolumeOpt
     let total_sectors = if let Some(total_sectors) = options.total_sectors {
         total_sectors
     } else {
-        let total_bytes: u64 = storage.seek(SeekFrom::End(0))?;
+        let Ok(total_bytes) = storage.seek(SeekFrom::End(0)) else {
+            error!("Cannot determine storage size - total_sectors must be specified explicitly");
+            return Err(Error::InvalidInput);
+        };
         let total_sectors_64 = total_bytes / u64::from(options.bytes_per_sector);
         storage.seek(SeekFrom::Start(0))?;
+        if total_sectors_64 == 0 {
+            error!("Storage size is unknown or too small - total_sectors must be specified explicitly");
+            return Err(Error::InvalidInput);
+        }
         if total_sectors_64 > u64::from(u32::MAX) {
             error!("Volume has too many sectors: {}", total_sectors_64);
             return Err(Error::InvalidInput);
@@ -1147,8 +2390,24 @@ pub fn format_volume<S: ReadWriteSeek>(storage: &mut S, options: FormatVolumeOpt
         total_sectors_64 as u32 // safe case: possible overflow is handled above
     };
 
+    {
+        // Only whole sectors are written to the storage device so it works with devices requiring aligned writes
+        let mut sector_buf = SectorBuffer::<&mut S, S>::new(&mut *storage, options.bytes_per_sector);
+        write_volume_structures(&mut sector_buf, &options, total_sectors)?;
+        sector_buf.flush()?;
+    }
+    storage.seek(SeekFrom::Start(0))?;
+    trace!("format_volume end");
+    Ok(())
+}
+
+fn write_volume_structures<S: ReadWriteSeek>(
+    storage: &mut S,
+    options: &FormatVolumeOptions,
+    total_sectors: u32,
+) -> Result<(), Error<S::Error>> {
     // Create boot sector, validate and write to storage device
-    let (boot, fat_type) = format_boot_sector(&options, total_sectors)?;
+    let (boot, fat_type) = format_boot_sector(options, total_sectors)?;
     if boot.validate::<S::Error>().is_err() {
         return Err(Error::InvalidInput);
     }
@@ -1182,7 +2441,7 @@ pub fn format_volume<S: ReadWriteSeek>(storage: &mut S, options: FormatVolumeOpt
     storage.seek(SeekFrom::Start(fat_pos))?;
     write_zeros(storage, bpb.bytes_from_sectors(sectors_per_all_fats))?;
     {
-        let mut fat_slice = fat_slice::<S, &mut S>(storage, bpb);
+        let mut fat_slice = fat_slice::<S, _>(&mut *storage, bpb);
         let sectors_per_fat = bpb.sectors_per_fat();
         let bytes_per_fat = bpb.bytes_from_sectors(sectors_per_fat);
         format_fat(&mut fat_slice, fat_type, bpb.media, bytes_per_fat, bpb.total_clusters())?;
@@ -1194,9 +2453,17 @@ pub fn format_volume<S: ReadWriteSeek>(storage: &mut S, options: FormatVolumeOpt
     let root_dir_pos = bpb.bytes_from_sectors(root_dir_first_sector);
     storage.seek(SeekFrom::Start(root_dir_pos))?;
     write_zeros(storage, bpb.bytes_from_sectors(root_dir_sectors))?;
+
+    // zero data region if full format was requested
+    if !options.quick {
+        let data_sectors = bpb.total_sectors() - bpb.first_data_sector();
+        trace!("zeroing {} data sectors", data_sectors);
+        write_zeros(storage, bpb.bytes_from_sectors(data_sectors))?;
+    }
+
     if fat_type == FatType::Fat32 {
         let root_dir_first_cluster = {
-            let mut fat_slice = fat_slice::<S, &mut S>(storage, bpb);
+            let mut fat_slice = fat_slice::<S, _>(&mut *storage, bpb);
             alloc_cluster(&mut fat_slice, fat_type, None, None, 1)?
         };
         assert!(root_dir_first_cluster == bpb.root_dir_first_cluster);
@@ -1215,7 +2482,5 @@ pub fn format_volume<S: ReadWriteSeek>(storage: &mut S, options: FormatVolumeOpt
         volume_entry.serialize(storage)?;
     }
 
-    storage.seek(SeekFrom::Start(0))?;
-    trace!("format_volume end");
     Ok(())
 }
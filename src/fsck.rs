@@ -0,0 +1,293 @@
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::{format, string::String, vec, vec::Vec};
+
+use crate::error::Error;
+use crate::fs::{FatType, FileSystem, OemCpConverter, ReadWriteSeek};
+use crate::table::{read_fat, FatValue, RESERVED_FAT_ENTRIES};
+use crate::time::TimeProvider;
+
+/// A problem found by the file system consistency check.
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[non_exhaustive]
+pub enum FsckProblem {
+    /// The cluster chain of the entry at `path` runs into `cluster` which is already used by another entry.
+    CrossLinked { path: String, cluster: u32 },
+    /// The cluster chain of the entry at `path` is broken at `cluster`: it contains a loop or points to a free,
+    /// bad or non-existing cluster.
+    BrokenChain { path: String, cluster: u32 },
+    /// The cluster chain of the file at `path` is too short for its size.
+    ///
+    /// A chain longer than the size requires is not reported because clusters preallocated by `File::preallocate`
+    /// stay in the chain until the file is truncated.
+    SizeMismatch { path: String, size: u32, clusters: u32 },
+    /// A chain of `clusters` clusters starting at `first_cluster` is allocated in the FAT but it is not used by
+    /// any directory entry.
+    LostChain { first_cluster: u32, clusters: u32 },
+}
+
+/// A result of the file system consistency check.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct FsckReport {
+    problems: Vec<FsckProblem>,
+}
+
+impl FsckReport {
+    /// Problems found in the file system
+    #[must_use]
+    pub fn problems(&self) -> &[FsckProblem] {
+        &self.problems
+    }
+
+    /// Returns `true` if no problems were found
+    #[must_use]
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+// One bit per cluster number (including reserved FAT entries)
+struct ClusterBitmap {
+    bits: Vec<u32>,
+}
+
+impl ClusterBitmap {
+    fn new(len: u32) -> Self {
+        Self {
+            bits: vec![0; (len as usize + 31) / 32],
+        }
+    }
+
+    fn get(&self, n: u32) -> bool {
+        self.bits[n as usize / 32] & (1 << (n % 32)) != 0
+    }
+
+    fn set(&mut self, n: u32, value: bool) {
+        let mask = 1 << (n % 32);
+        if value {
+            self.bits[n as usize / 32] |= mask;
+        } else {
+            self.bits[n as usize / 32] &= !mask;
+        }
+    }
+}
+
+enum ChainEnd {
+    // chain ended with the end-of-chain marker
+    Complete,
+    // chain runs into a cluster used by another chain
+    CrossLinked(u32),
+    // chain contains a loop or an invalid link
+    Broken(u32),
+}
+
+struct Checker<'a, IO: ReadWriteSeek, TP, OCC> {
+    fs: &'a FileSystem<IO, TP, OCC>,
+    used: ClusterBitmap,
+    report: FsckReport,
+}
+
+impl<IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Checker<'_, IO, TP, OCC> {
+    fn end_cluster(&self) -> u32 {
+        self.fs.total_clusters() + RESERVED_FAT_ENTRIES
+    }
+
+    fn is_valid_cluster(&self, n: u32) -> bool {
+        (RESERVED_FAT_ENTRIES..self.end_cluster()).contains(&n)
+    }
+
+    // Marks clusters of a chain as used. Every cluster is visited at most once during the whole check so loops and
+    // cross-links cannot make it run forever.
+    fn mark_chain(&mut self, first_cluster: u32) -> Result<(ChainEnd, u32), Error<IO::Error>> {
+        let fs = self.fs;
+        let mut fat = fs.fat_slice();
+        let mut cluster = first_cluster;
+        let mut len = 0;
+        loop {
+            if !self.is_valid_cluster(cluster) {
+                return Ok((ChainEnd::Broken(cluster), len));
+            }
+            if self.used.get(cluster) {
+                // distinguish a loop from a cross-link by checking if the cluster belongs to the current chain
+                let end = if self.chain_contains(first_cluster, len, cluster)? {
+                    ChainEnd::Broken(cluster)
+                } else {
+                    ChainEnd::CrossLinked(cluster)
+                };
+                return Ok((end, len));
+            }
+            self.used.set(cluster, true);
+            len += 1;
+            match read_fat(&mut fat, self.fs.fat_type(), cluster)? {
+                FatValue::Data(n) => cluster = n,
+                FatValue::EndOfChain => return Ok((ChainEnd::Complete, len)),
+                FatValue::Free | FatValue::Bad => return Ok((ChainEnd::Broken(cluster), len)),
+            }
+        }
+    }
+
+    // Checks if one of the first `len` clusters of an already marked chain is equal to `cluster`
+    fn chain_contains(&self, first_cluster: u32, len: u32, cluster: u32) -> Result<bool, Error<IO::Error>> {
+        let mut fat = self.fs.fat_slice();
+        let mut n = first_cluster;
+        for _ in 0..len {
+            if n == cluster {
+                return Ok(true);
+            }
+            match read_fat(&mut fat, self.fs.fat_type(), n)? {
+                FatValue::Data(next) => n = next,
+                _ => break,
+            }
+        }
+        Ok(false)
+    }
+
+    // Marks an entry chain and returns `true` if it is safe to use, i.e. it ends with the end-of-chain marker and
+    // it is not shared with any other entry
+    fn check_chain(&mut self, path: &str, first_cluster: u32) -> Result<(bool, u32), Error<IO::Error>> {
+        let (end, len) = self.mark_chain(first_cluster)?;
+        let problem = match end {
+            ChainEnd::Complete => return Ok((true, len)),
+            ChainEnd::CrossLinked(cluster) => FsckProblem::CrossLinked {
+                path: path.into(),
+                cluster,
+            },
+            ChainEnd::Broken(cluster) => FsckProblem::BrokenChain {
+                path: path.into(),
+                cluster,
+            },
+        };
+        self.report.problems.push(problem);
+        Ok((false, len))
+    }
+
+    fn check_tree(&mut self) -> Result<(), Error<IO::Error>> {
+        let root_dir = self.fs.root_dir();
+        if self.fs.fat_type() == FatType::Fat32 {
+            let (ok, _) = self.check_chain("/", self.fs.root_dir_first_cluster())?;
+            if !ok {
+                // do not parse data of other entries as directory entries
+                return Ok(());
+            }
+        }
+        // explicit stack is used instead of recursion so deep trees cannot overflow the call stack
+        let mut dirs = vec![(String::new(), root_dir)];
+        while let Some((dir_path, dir)) = dirs.pop() {
+            for r in dir.iter() {
+                let e = match r {
+                    Ok(e) => e,
+                    // chain problems have already been reported
+                    Err(Error::CorruptedFileSystem) => break,
+                    Err(err) => return Err(err),
+                };
+                let name = e.file_name();
+                if name == "." || name == ".." {
+                    continue;
+                }
+                let path = format!("{}/{}", dir_path, name);
+                let (ok, clusters) = match e.first_cluster() {
+                    Some(n) => self.check_chain(&path, n)?,
+                    None => (true, 0),
+                };
+                if e.is_dir() {
+                    // a directory without a cluster would resolve to the root directory
+                    if ok && clusters > 0 {
+                        dirs.push((path, e.to_dir()));
+                    }
+                } else if ok {
+                    let size = e.data.size().unwrap_or(0);
+                    if self.fs.clusters_from_bytes(u64::from(size)) > clusters {
+                        self.report
+                            .problems
+                            .push(FsckProblem::SizeMismatch { path, size, clusters });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_lost_chains(&mut self) -> Result<(), Error<IO::Error>> {
+        let end_cluster = self.end_cluster();
+        let fs = self.fs;
+        let mut fat = fs.fat_slice();
+        // find allocated clusters not used by any entry and clusters pointed by them
+        let mut lost = ClusterBitmap::new(end_cluster);
+        let mut has_prev = ClusterBitmap::new(end_cluster);
+        for cluster in RESERVED_FAT_ENTRIES..end_cluster {
+            if self.used.get(cluster) {
+                continue;
+            }
+            match read_fat(&mut fat, self.fs.fat_type(), cluster)? {
+                FatValue::Data(n) => {
+                    lost.set(cluster, true);
+                    if self.is_valid_cluster(n) {
+                        has_prev.set(n, true);
+                    }
+                }
+                FatValue::EndOfChain => lost.set(cluster, true),
+                FatValue::Free | FatValue::Bad => {}
+            }
+        }
+        // report chains starting from their heads first - clusters left after that form loops
+        for heads_only in [true, false] {
+            for cluster in RESERVED_FAT_ENTRIES..end_cluster {
+                if lost.get(cluster) && !(heads_only && has_prev.get(cluster)) {
+                    let clusters = self.take_lost_chain(&mut lost, cluster)?;
+                    self.report.problems.push(FsckProblem::LostChain {
+                        first_cluster: cluster,
+                        clusters,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Clears lost chain clusters in the bitmap and returns the chain length
+    fn take_lost_chain(&self, lost: &mut ClusterBitmap, first_cluster: u32) -> Result<u32, Error<IO::Error>> {
+        let mut fat = self.fs.fat_slice();
+        let mut cluster = first_cluster;
+        let mut len = 0;
+        while self.is_valid_cluster(cluster) && lost.get(cluster) {
+            lost.set(cluster, false);
+            len += 1;
+            match read_fat(&mut fat, self.fs.fat_type(), cluster)? {
+                FatValue::Data(n) => cluster = n,
+                _ => break,
+            }
+        }
+        Ok(len)
+    }
+}
+
+pub(crate) fn check<IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter>(
+    fs: &FileSystem<IO, TP, OCC>,
+) -> Result<FsckReport, Error<IO::Error>> {
+    let mut checker = Checker {
+        fs,
+        used: ClusterBitmap::new(fs.total_clusters() + RESERVED_FAT_ENTRIES),
+        report: FsckReport::default(),
+    };
+    checker.check_tree()?;
+    checker.check_lost_chains()?;
+    Ok(checker.report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cluster_bitmap() {
+        let mut bitmap = ClusterBitmap::new(70);
+        assert!(!bitmap.get(0));
+        bitmap.set(0, true);
+        bitmap.set(33, true);
+        bitmap.set(69, true);
+        assert!(bitmap.get(0) && bitmap.get(33) && bitmap.get(69));
+        assert!(!bitmap.get(1) && !bitmap.get(32) && !bitmap.get(68));
+        bitmap.set(33, false);
+        assert!(!bitmap.get(33));
+        assert!(bitmap.get(0) && bitmap.get(69));
+    }
+}
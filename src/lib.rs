@@ -62,20 +62,40 @@ extern crate alloc;
 #[macro_use]
 mod log_macros;
 
+#[cfg(feature = "async")]
+mod async_fs;
+mod block;
 mod boot_sector;
+#[cfg(feature = "oem-codepages")]
+mod codepage;
 mod dir;
 mod dir_entry;
 mod error;
+mod fat_cache;
 mod file;
 mod fs;
+#[cfg(feature = "alloc")]
+mod fsck;
 mod io;
+#[cfg(feature = "mbr")]
+mod mbr;
 mod table;
 mod time;
 
+#[cfg(feature = "async")]
+pub use crate::async_fs::*;
+pub use crate::block::SectorBuffer;
+#[cfg(feature = "oem-codepages")]
+pub use crate::codepage::*;
 pub use crate::dir::*;
 pub use crate::dir_entry::*;
 pub use crate::error::*;
 pub use crate::file::*;
 pub use crate::fs::*;
+#[cfg(feature = "alloc")]
+pub use crate::fsck::*;
 pub use crate::io::*;
+#[cfg(feature = "mbr")]
+pub use crate::mbr::*;
+pub use crate::table::FatValue;
 pub use crate::time::*;
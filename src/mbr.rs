@@ -0,0 +1,148 @@
+use crate::error::Error;
+use crate::io::{Read, ReadLeExt, Seek, SeekFrom};
+
+const PARTITION_TABLE_OFFSET: u64 = 0x1BE;
+const PARTITION_COUNT: usize = 4;
+
+// Size of sectors used by partition entries
+pub(crate) const MBR_SECTOR_SIZE: u64 = 512;
+
+/// A primary partition entry from the MBR (Master Boot Record) partition table.
+///
+/// This struct is returned by the `read_mbr_partitions` function.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct PartitionEntry {
+    bootable: bool,
+    partition_type: u8,
+    start_lba: u32,
+    sector_count: u32,
+}
+
+impl PartitionEntry {
+    /// Checks if the partition is marked as active (bootable).
+    #[must_use]
+    pub fn bootable(&self) -> bool {
+        self.bootable
+    }
+
+    /// Returns the partition type (system ID), e.g. `0x0C` for FAT32 with LBA addressing.
+    #[must_use]
+    pub fn partition_type(&self) -> u8 {
+        self.partition_type
+    }
+
+    /// Returns the first sector of the partition (Logical Block Address).
+    #[must_use]
+    pub fn start_lba(&self) -> u32 {
+        self.start_lba
+    }
+
+    /// Returns the number of sectors in the partition.
+    #[must_use]
+    pub fn sector_count(&self) -> u32 {
+        self.sector_count
+    }
+
+    /// Checks if the partition table entry is unused.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.partition_type == 0 || self.sector_count == 0
+    }
+
+    /// Checks if the partition type is one of the types used for FAT volumes.
+    #[must_use]
+    pub fn is_fat(&self) -> bool {
+        matches!(
+            self.partition_type,
+            0x01 | 0x04 | 0x06 | 0x0B | 0x0C | 0x0E | 0x11 | 0x14 | 0x16 | 0x1B | 0x1C | 0x1E
+        )
+    }
+
+    fn deserialize<R: Read>(rdr: &mut R) -> Result<Self, R::Error> {
+        let boot_indicator = rdr.read_u8()?;
+        // skip CHS address of the first sector
+        let mut chs = [0_u8; 3];
+        rdr.read_exact(&mut chs)?;
+        let partition_type = rdr.read_u8()?;
+        // skip CHS address of the last sector
+        rdr.read_exact(&mut chs)?;
+        let start_lba = rdr.read_u32_le()?;
+        let sector_count = rdr.read_u32_le()?;
+        Ok(Self {
+            bootable: boot_indicator & 0x80 != 0,
+            partition_type,
+            start_lba,
+            sector_count,
+        })
+    }
+}
+
+/// Reads the four primary partition entries from the MBR (Master Boot Record) of a disk.
+///
+/// Partition start offset and size in bytes can be calculated by multiplying `start_lba` and `sector_count` by the
+/// disk sector size (usually 512). Together with `FileSystem::new_in_partition` it allows mounting a FAT volume
+/// stored in a partition of a disk image. Unused entries are returned too (see `PartitionEntry::is_empty`).
+///
+/// # Errors
+///
+/// Errors that can be returned:
+///
+/// * `Error::CorruptedFileSystem` will be returned if the MBR signature is invalid.
+/// * `Error::Io` will be returned if the provided storage object returned an I/O error.
+pub fn read_mbr_partitions<S: Read + Seek>(disk: &mut S) -> Result<[PartitionEntry; 4], Error<S::Error>> {
+    trace!("read_mbr_partitions");
+    disk.seek(SeekFrom::Start(PARTITION_TABLE_OFFSET))?;
+    let mut partitions = [PartitionEntry::default(); PARTITION_COUNT];
+    for partition in &mut partitions {
+        *partition = PartitionEntry::deserialize(disk)?;
+    }
+    let mut signature = [0_u8; 2];
+    disk.read_exact(&mut signature)?;
+    if signature != [0x55, 0xAA] {
+        error!("Invalid MBR signature");
+        return Err(Error::CorruptedFileSystem);
+    }
+    Ok(partitions)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::io::StdIoWrapper;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_mbr_partitions() {
+        let mut mbr = vec![0_u8; 512];
+        mbr[0x1BE..0x1CE].copy_from_slice(&[
+            0x80, 0x20, 0x21, 0x00, 0x0C, 0xFE, 0xFF, 0xFF, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00,
+        ]);
+        mbr[0x1CE..0x1DE].copy_from_slice(&[
+            0x00, 0xFE, 0xFF, 0xFF, 0x83, 0xFE, 0xFF, 0xFF, 0x00, 0x08, 0x10, 0x00, 0x00, 0x80, 0x00, 0x00,
+        ]);
+        mbr[0x1FE] = 0x55;
+        mbr[0x1FF] = 0xAA;
+        let mut disk = StdIoWrapper::new(Cursor::new(mbr.clone()));
+        let partitions = read_mbr_partitions(&mut disk).unwrap();
+        assert!(partitions[0].bootable());
+        assert_eq!(partitions[0].partition_type(), 0x0C);
+        assert_eq!(partitions[0].start_lba(), 0x800);
+        assert_eq!(partitions[0].sector_count(), 0x10_0000);
+        assert!(partitions[0].is_fat());
+        assert!(!partitions[1].bootable());
+        assert_eq!(partitions[1].partition_type(), 0x83);
+        assert_eq!(partitions[1].start_lba(), 0x10_0800);
+        assert_eq!(partitions[1].sector_count(), 0x8000);
+        assert!(!partitions[1].is_fat());
+        assert!(!partitions[1].is_empty());
+        assert!(partitions[2].is_empty());
+        assert!(partitions[3].is_empty());
+
+        mbr[0x1FF] = 0;
+        let mut disk = StdIoWrapper::new(Cursor::new(mbr));
+        assert!(matches!(
+            read_mbr_partitions(&mut disk),
+            Err(Error::CorruptedFileSystem)
+        ));
+    }
+}
@@ -15,11 +15,16 @@ type Fat32 = Fat<u32>;
 
 pub const RESERVED_FAT_ENTRIES: u32 = 2;
 
+/// A value of the File Allocation Table entry.
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
-enum FatValue {
+pub enum FatValue {
+    /// The cluster is free.
     Free,
+    /// The cluster is used and the next cluster in the chain has the given number.
     Data(u32),
+    /// The cluster is marked as bad.
     Bad,
+    /// The cluster is the last one in the chain.
     EndOfChain,
 }
 
@@ -48,7 +53,8 @@ trait FatTrait {
         E: IoError,
         Error<E>: From<S::Error>;
 
-    fn find_free<S, E>(fat: &mut S, start_cluster: u32, end_cluster: u32) -> Result<u32, Error<E>>
+    // Returns the first cluster in the range that is free (or used if `free` is false)
+    fn find<S, E>(fat: &mut S, start_cluster: u32, end_cluster: u32, free: bool) -> Result<Option<u32>, Error<E>>
     where
         S: Read + Seek,
         E: IoError,
@@ -61,7 +67,7 @@ trait FatTrait {
         Error<E>: From<S::Error>;
 }
 
-fn read_fat<S, E>(fat: &mut S, fat_type: FatType, cluster: u32) -> Result<FatValue, Error<E>>
+pub(crate) fn read_fat<S, E>(fat: &mut S, fat_type: FatType, cluster: u32) -> Result<FatValue, Error<E>>
 where
     S: Read + Seek,
     E: IoError,
@@ -74,7 +80,7 @@ where
     }
 }
 
-fn write_fat<S, E>(fat: &mut S, fat_type: FatType, cluster: u32, value: FatValue) -> Result<(), Error<E>>
+pub(crate) fn write_fat<S, E>(fat: &mut S, fat_type: FatType, cluster: u32, value: FatValue) -> Result<(), Error<E>>
 where
     S: Read + Write + Seek,
     E: IoError,
@@ -107,15 +113,30 @@ fn find_free_cluster<S, E>(
     start_cluster: u32,
     end_cluster: u32,
 ) -> Result<u32, Error<E>>
+where
+    S: Read + Seek,
+    E: IoError,
+    Error<E>: From<S::Error>,
+{
+    find_cluster(fat, fat_type, start_cluster, end_cluster, true)?.ok_or(Error::NotEnoughSpace)
+}
+
+fn find_cluster<S, E>(
+    fat: &mut S,
+    fat_type: FatType,
+    start_cluster: u32,
+    end_cluster: u32,
+    free: bool,
+) -> Result<Option<u32>, Error<E>>
 where
     S: Read + Seek,
     E: IoError,
     Error<E>: From<S::Error>,
 {
     match fat_type {
-        FatType::Fat12 => Fat12::find_free(fat, start_cluster, end_cluster),
-        FatType::Fat16 => Fat16::find_free(fat, start_cluster, end_cluster),
-        FatType::Fat32 => Fat32::find_free(fat, start_cluster, end_cluster),
+        FatType::Fat12 => Fat12::find(fat, start_cluster, end_cluster, free),
+        FatType::Fat16 => Fat16::find(fat, start_cluster, end_cluster, free),
+        FatType::Fat32 => Fat32::find(fat, start_cluster, end_cluster, free),
     }
 }
 
@@ -138,7 +159,7 @@ where
     };
     let new_cluster = match find_free_cluster(fat, fat_type, start_cluster, end_cluster) {
         Ok(n) => n,
-        Err(_) if start_cluster > RESERVED_FAT_ENTRIES => {
+        Err(Error::NotEnoughSpace) if start_cluster > RESERVED_FAT_ENTRIES => {
             find_free_cluster(fat, fat_type, RESERVED_FAT_ENTRIES, start_cluster)?
         }
         Err(e) => return Err(e),
@@ -151,6 +172,73 @@ where
     Ok(new_cluster)
 }
 
+fn find_free_run<S, E>(
+    fat: &mut S,
+    fat_type: FatType,
+    start_cluster: u32,
+    end_cluster: u32,
+    count: u32,
+) -> Result<Option<u32>, Error<E>>
+where
+    S: Read + Seek,
+    E: IoError,
+    Error<E>: From<S::Error>,
+{
+    // both lookups read FAT entries sequentially so every entry is read at most once
+    let mut cluster = start_cluster;
+    while let Some(run_start) = find_cluster(fat, fat_type, cluster, end_cluster, true)? {
+        let run_end = run_start.saturating_add(count);
+        if run_end > end_cluster {
+            break;
+        }
+        match find_cluster(fat, fat_type, run_start, run_end, false)? {
+            Some(used_cluster) => cluster = used_cluster + 1,
+            None => return Ok(Some(run_start)),
+        }
+    }
+    Ok(None)
+}
+
+pub(crate) fn alloc_clusters_contiguous<S, E>(
+    fat: &mut S,
+    fat_type: FatType,
+    prev_cluster: Option<u32>,
+    count: u32,
+    hint: Option<u32>,
+    total_clusters: u32,
+) -> Result<Option<u32>, Error<E>>
+where
+    S: Read + Write + Seek,
+    E: IoError,
+    Error<E>: From<S::Error>,
+{
+    debug_assert!(count > 0);
+    let end_cluster = total_clusters + RESERVED_FAT_ENTRIES;
+    let start_cluster = match hint {
+        Some(n) if (RESERVED_FAT_ENTRIES..end_cluster).contains(&n) => n,
+        _ => RESERVED_FAT_ENTRIES,
+    };
+    let mut first_cluster = find_free_run(fat, fat_type, start_cluster, end_cluster, count)?;
+    if first_cluster.is_none() && start_cluster > RESERVED_FAT_ENTRIES {
+        // a run can end right before the hint so include a part of the already scanned range
+        let end = (start_cluster + count - 1).min(end_cluster);
+        first_cluster = find_free_run(fat, fat_type, RESERVED_FAT_ENTRIES, end, count)?;
+    }
+    let Some(first_cluster) = first_cluster else {
+        return Ok(None);
+    };
+    let last_cluster = first_cluster + count - 1;
+    for cluster in first_cluster..last_cluster {
+        write_fat(fat, fat_type, cluster, FatValue::Data(cluster + 1))?;
+    }
+    write_fat(fat, fat_type, last_cluster, FatValue::EndOfChain)?;
+    if let Some(n) = prev_cluster {
+        write_fat(fat, fat_type, n, FatValue::Data(first_cluster))?;
+    }
+    trace!("allocated clusters {}-{}", first_cluster, last_cluster);
+    Ok(Some(first_cluster))
+}
+
 pub(crate) fn read_fat_flags<S, E>(fat: &mut S, fat_type: FatType) -> Result<FsStatusFlags, Error<E>>
 where
     S: Read + Seek,
@@ -176,6 +264,30 @@ where
     Ok(FsStatusFlags { dirty, io_error })
 }
 
+// Block size is a multiple of 3 so pairs of packed FAT12 entries never cross block boundaries
+const FAT_BLOCK_SIZE: usize = 3 * 512;
+
+// Reads first `len` bytes of FAT in big blocks and passes them to the callback
+fn read_fat_blocks<S, E, F>(fat: &mut S, len: u64, mut f: F) -> Result<(), Error<E>>
+where
+    S: Read + Seek,
+    E: IoError,
+    Error<E>: From<S::Error>,
+    F: FnMut(&[u8]),
+{
+    let mut buf = [0_u8; FAT_BLOCK_SIZE];
+    fat.seek(io::SeekFrom::Start(0))?;
+    let mut pos = 0;
+    while pos < len {
+        // safe cast: value is limited by the buffer size
+        let size = (len - pos).min(FAT_BLOCK_SIZE as u64) as usize;
+        fat.read_exact(&mut buf[..size])?;
+        f(&buf[..size]);
+        pos += size as u64;
+    }
+    Ok(())
+}
+
 pub(crate) fn count_free_clusters<S, E>(fat: &mut S, fat_type: FatType, total_clusters: u32) -> Result<u32, Error<E>>
 where
     S: Read + Seek,
@@ -217,7 +329,7 @@ where
             fat.write_u32_le(u32::from(media) | 0xFFF_FF00)?;
             fat.write_u32_le(0xFFFF_FFFF)?;
         }
-    };
+    }
     // mark entries at the end of FAT as used (after FAT but before sector end)
     let start_cluster = total_clusters + RESERVED_FAT_ENTRIES;
     let end_cluster = (bytes_per_fat * BITS_PER_BYTE / u64::from(fat_type.bits_per_fat_entry())) as u32;
@@ -298,12 +410,15 @@ impl FatTrait for Fat12 {
         Ok(())
     }
 
-    fn find_free<S, E>(fat: &mut S, start_cluster: u32, end_cluster: u32) -> Result<u32, Error<E>>
+    fn find<S, E>(fat: &mut S, start_cluster: u32, end_cluster: u32, free: bool) -> Result<Option<u32>, Error<E>>
     where
         S: Read + Seek,
         E: IoError,
         Error<E>: From<S::Error>,
     {
+        if start_cluster >= end_cluster {
+            return Ok(None);
+        }
         let mut cluster = start_cluster;
         let fat_offset = cluster + (cluster / 2);
         fat.seek(io::SeekFrom::Start(u64::from(fat_offset)))?;
@@ -313,12 +428,12 @@ impl FatTrait for Fat12 {
                 0 => packed_val & 0x0FFF,
                 _ => packed_val >> 4,
             };
-            if val == 0 {
-                return Ok(cluster);
+            if (val == 0) == free {
+                return Ok(Some(cluster));
             }
             cluster += 1;
             if cluster == end_cluster {
-                return Err(Error::NotEnoughSpace);
+                return Ok(None);
             }
             packed_val = if cluster & 1 == 0 {
                 fat.read_u16_le()?
@@ -336,28 +451,21 @@ impl FatTrait for Fat12 {
         Error<E>: From<S::Error>,
     {
         let mut count = 0;
-        let mut cluster = RESERVED_FAT_ENTRIES;
-        fat.seek(io::SeekFrom::Start(u64::from(cluster * 3 / 2)))?;
-        let mut prev_packed_val = 0_u16;
-        while cluster < end_cluster {
-            let res = match cluster & 1 {
-                0 => fat.read_u16_le(),
-                _ => fat.read_u8().map(u16::from),
-            };
-            let packed_val = match res {
-                Err(err) => return Err(err.into()),
-                Ok(n) => n,
-            };
-            let val = match cluster & 1 {
-                0 => packed_val & 0x0FFF,
-                _ => (packed_val << 8) | (prev_packed_val >> 12),
-            };
-            prev_packed_val = packed_val;
-            if val == 0 {
-                count += 1;
+        let mut cluster = 0;
+        // two entries are packed in three bytes - the last pair can be incomplete if `end_cluster` is odd
+        let len = (u64::from(end_cluster) * 3 + 1) / 2;
+        read_fat_blocks(fat, len, |block| {
+            for packed in block.chunks(3) {
+                let first = u16::from(packed[0]) | (u16::from(packed[1] & 0x0F) << 8);
+                let second = packed.get(2).map(|b| (u16::from(*b) << 4) | u16::from(packed[1] >> 4));
+                for val in core::iter::once(first).chain(second) {
+                    if cluster >= RESERVED_FAT_ENTRIES && val == 0 {
+                        count += 1;
+                    }
+                    cluster += 1;
+                }
             }
-            cluster += 1;
-        }
+        })?;
         Ok(count)
     }
 }
@@ -414,7 +522,7 @@ impl FatTrait for Fat16 {
         Self::set_raw(fat, cluster, raw_value)
     }
 
-    fn find_free<S, E>(fat: &mut S, start_cluster: u32, end_cluster: u32) -> Result<u32, Error<E>>
+    fn find<S, E>(fat: &mut S, start_cluster: u32, end_cluster: u32, free: bool) -> Result<Option<u32>, Error<E>>
     where
         S: Read + Seek,
         E: IoError,
@@ -424,12 +532,12 @@ impl FatTrait for Fat16 {
         fat.seek(io::SeekFrom::Start(u64::from(cluster * 2)))?;
         while cluster < end_cluster {
             let val = fat.read_u16_le()?;
-            if val == 0 {
-                return Ok(cluster);
+            if (val == 0) == free {
+                return Ok(Some(cluster));
             }
             cluster += 1;
         }
-        Err(Error::NotEnoughSpace)
+        Ok(None)
     }
 
     fn count_free<S, E>(fat: &mut S, end_cluster: u32) -> Result<u32, Error<E>>
@@ -439,15 +547,16 @@ impl FatTrait for Fat16 {
         Error<E>: From<S::Error>,
     {
         let mut count = 0;
-        let mut cluster = RESERVED_FAT_ENTRIES;
-        fat.seek(io::SeekFrom::Start(u64::from(cluster * 2)))?;
-        while cluster < end_cluster {
-            let val = fat.read_u16_le()?;
-            if val == 0 {
-                count += 1;
+        let mut cluster = 0;
+        read_fat_blocks(fat, u64::from(end_cluster) * 2, |block| {
+            for entry in block.chunks_exact(2) {
+                let val = u16::from_le_bytes([entry[0], entry[1]]);
+                if cluster >= RESERVED_FAT_ENTRIES && val == 0 {
+                    count += 1;
+                }
+                cluster += 1;
             }
-            cluster += 1;
-        }
+        })?;
         Ok(count)
     }
 }
@@ -531,7 +640,7 @@ impl FatTrait for Fat32 {
                 "cluster number {} is a special value in FAT to indicate {}; it should never be set as free",
                 cluster, tmp
             );
-        };
+        }
         let raw_val = match value {
             FatValue::Free => 0,
             FatValue::Bad => 0x0FFF_FFF7,
@@ -542,7 +651,7 @@ impl FatTrait for Fat32 {
         Self::set_raw(fat, cluster, raw_val)
     }
 
-    fn find_free<S, E>(fat: &mut S, start_cluster: u32, end_cluster: u32) -> Result<u32, Error<E>>
+    fn find<S, E>(fat: &mut S, start_cluster: u32, end_cluster: u32, free: bool) -> Result<Option<u32>, Error<E>>
     where
         S: Read + Seek,
         E: IoError,
@@ -552,12 +661,12 @@ impl FatTrait for Fat32 {
         fat.seek(io::SeekFrom::Start(u64::from(cluster * 4)))?;
         while cluster < end_cluster {
             let val = fat.read_u32_le()? & 0x0FFF_FFFF;
-            if val == 0 {
-                return Ok(cluster);
+            if (val == 0) == free {
+                return Ok(Some(cluster));
             }
             cluster += 1;
         }
-        Err(Error::NotEnoughSpace)
+        Ok(None)
     }
 
     fn count_free<S, E>(fat: &mut S, end_cluster: u32) -> Result<u32, Error<E>>
@@ -567,15 +676,16 @@ impl FatTrait for Fat32 {
         Error<E>: From<S::Error>,
     {
         let mut count = 0;
-        let mut cluster = RESERVED_FAT_ENTRIES;
-        fat.seek(io::SeekFrom::Start(u64::from(cluster * 4)))?;
-        while cluster < end_cluster {
-            let val = fat.read_u32_le()? & 0x0FFF_FFFF;
-            if val == 0 {
-                count += 1;
+        let mut cluster = 0;
+        read_fat_blocks(fat, u64::from(end_cluster) * 4, |block| {
+            for entry in block.chunks_exact(4) {
+                let val = u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]) & 0x0FFF_FFFF;
+                if cluster >= RESERVED_FAT_ENTRIES && val == 0 {
+                    count += 1;
+                }
+                cluster += 1;
             }
-            cluster += 1;
-        }
+        })?;
         Ok(count)
     }
 }
@@ -584,6 +694,11 @@ pub(crate) struct ClusterIterator<B, E, S = B> {
     fat: B,
     fat_type: FatType,
     cluster: Option<u32>,
+    total_clusters: u32,
+    // number of steps made so far - used to detect loops in the chain
+    steps: u32,
+    // if the chain is broken continue with the physically next cluster (used for recovery of deleted files)
+    assume_contiguous: bool,
     err: bool,
     // phantom is needed to add type bounds on the storage type
     phantom_s: PhantomData<S>,
@@ -597,17 +712,25 @@ where
     S: Read + Write + Seek,
     Error<E>: From<S::Error>,
 {
-    pub(crate) fn new(fat: B, fat_type: FatType, cluster: u32) -> Self {
+    pub(crate) fn new(fat: B, fat_type: FatType, cluster: u32, total_clusters: u32) -> Self {
         Self {
             fat,
             fat_type,
             cluster: Some(cluster),
+            total_clusters,
+            steps: 0,
+            assume_contiguous: false,
             err: false,
             phantom_s: PhantomData,
             phantom_e: PhantomData,
         }
     }
 
+    pub(crate) fn assume_contiguous(mut self) -> Self {
+        self.assume_contiguous = true;
+        self
+    }
+
     pub(crate) fn truncate(&mut self) -> Result<u32, Error<E>> {
         if let Some(n) = self.cluster {
             // Move to the next cluster
@@ -647,11 +770,30 @@ where
         }
         if let Some(current_cluster) = self.cluster {
             self.cluster = match get_next_cluster(self.fat.borrow_mut(), self.fat_type, current_cluster) {
+                Ok(None) if self.assume_contiguous => {
+                    Some(current_cluster + 1).filter(|n| *n < self.total_clusters + RESERVED_FAT_ENTRIES)
+                }
                 Ok(next_cluster) => next_cluster,
                 Err(err) => {
                     self.err = true;
                     return Some(Err(err));
                 }
+            };
+            if let Some(n) = self.cluster {
+                if n < RESERVED_FAT_ENTRIES || n >= self.total_clusters + RESERVED_FAT_ENTRIES {
+                    error!("Cluster {} points to invalid cluster {}", current_cluster, n);
+                    self.cluster = None;
+                    self.err = true;
+                    return Some(Err(Error::CorruptedFileSystem));
+                }
+                // a chain cannot be longer than the number of clusters so it must contain a loop
+                self.steps += 1;
+                if self.steps >= self.total_clusters {
+                    error!("Loop detected in cluster chain at cluster {}", current_cluster);
+                    self.cluster = None;
+                    self.err = true;
+                    return Some(Err(Error::CorruptedFileSystem));
+                }
             }
         }
         self.cluster.map(Ok)
@@ -700,7 +842,7 @@ mod tests {
         assert_eq!(count_free_clusters(&mut cur, fat_type, 0x1E).ok(), Some(3));
         // test reading from iterator
         {
-            let iter = ClusterIterator::<&mut S, S::Error, S>::new(&mut cur, fat_type, 0x9);
+            let iter = ClusterIterator::<&mut S, S::Error, S>::new(&mut cur, fat_type, 0x9, 0x1E);
             let actual_cluster_numbers = iter.map(Result::ok).collect::<Vec<_>>();
             let expected_cluster_numbers = [0xA_u32, 0x14_u32, 0x15_u32, 0x16_u32, 0x19_u32, 0x1A_u32]
                 .iter()
@@ -711,7 +853,7 @@ mod tests {
         }
         // test truncating a chain
         {
-            let mut iter = ClusterIterator::<&mut S, S::Error, S>::new(&mut cur, fat_type, 0x9);
+            let mut iter = ClusterIterator::<&mut S, S::Error, S>::new(&mut cur, fat_type, 0x9, 0x1E);
             assert_eq!(iter.nth(3).map(Result::ok), Some(Some(0x16)));
             assert!(iter.truncate().is_ok());
         }
@@ -720,7 +862,7 @@ mod tests {
         assert_eq!(read_fat(&mut cur, fat_type, 0x1A).ok(), Some(FatValue::Free));
         // test freeing a chain
         {
-            let mut iter = ClusterIterator::<&mut S, S::Error, S>::new(&mut cur, fat_type, 0x9);
+            let mut iter = ClusterIterator::<&mut S, S::Error, S>::new(&mut cur, fat_type, 0x9, 0x1E);
             assert!(iter.free().is_ok());
         }
         assert_eq!(read_fat(&mut cur, fat_type, 0x9).ok(), Some(FatValue::Free));
@@ -728,6 +870,90 @@ mod tests {
         assert_eq!(read_fat(&mut cur, fat_type, 0x14).ok(), Some(FatValue::Free));
         assert_eq!(read_fat(&mut cur, fat_type, 0x15).ok(), Some(FatValue::Free));
         assert_eq!(read_fat(&mut cur, fat_type, 0x16).ok(), Some(FatValue::Free));
+        // test contiguous allocation
+        assert_eq!(
+            alloc_clusters_contiguous(&mut cur, fat_type, None, 3, None, 0x1E).ok(),
+            Some(Some(0x14))
+        );
+        assert_eq!(read_fat(&mut cur, fat_type, 0x14).ok(), Some(FatValue::Data(0x15)));
+        assert_eq!(read_fat(&mut cur, fat_type, 0x15).ok(), Some(FatValue::Data(0x16)));
+        assert_eq!(read_fat(&mut cur, fat_type, 0x16).ok(), Some(FatValue::EndOfChain));
+        assert_eq!(
+            alloc_clusters_contiguous(&mut cur, fat_type, Some(0x16), 2, Some(0x17), 0x1E).ok(),
+            Some(Some(0x19))
+        );
+        assert_eq!(read_fat(&mut cur, fat_type, 0x16).ok(), Some(FatValue::Data(0x19)));
+        assert_eq!(read_fat(&mut cur, fat_type, 0x1A).ok(), Some(FatValue::EndOfChain));
+        // no matching run after the hint - search from the beginning
+        assert_eq!(
+            alloc_clusters_contiguous(&mut cur, fat_type, None, 2, Some(0x1F), 0x1E).ok(),
+            Some(Some(0x9))
+        );
+        assert_eq!(
+            alloc_clusters_contiguous(&mut cur, fat_type, None, 3, None, 0x1E).ok(),
+            Some(None)
+        );
+    }
+
+    #[test]
+    fn test_cluster_iter_out_of_range() {
+        let fat: Vec<u8> = vec![
+            0xF8, 0xFF, 0xFF, 0xFF, 0x03, 0x00, 0x50, 0x00, 0xFF, 0xFF, 0x01, 0x00, 0x00, 0x00,
+        ];
+        let mut cur = StdIoWrapper::new(Cursor::<Vec<u8>>::new(fat));
+        let mut iter =
+            ClusterIterator::<_, std::io::Error, StdIoWrapper<Cursor<Vec<u8>>>>::new(&mut cur, FatType::Fat16, 2, 5);
+        assert_eq!(iter.next().map(Result::ok), Some(Some(3)));
+        assert!(matches!(iter.next(), Some(Err(Error::CorruptedFileSystem))));
+        assert!(iter.next().is_none());
+        let mut iter =
+            ClusterIterator::<_, std::io::Error, StdIoWrapper<Cursor<Vec<u8>>>>::new(&mut cur, FatType::Fat16, 5, 5);
+        assert!(matches!(iter.next(), Some(Err(Error::CorruptedFileSystem))));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_cluster_iter_loop() {
+        // cluster 2 points to itself, cluster 3 points to 4 and cluster 4 points back to 3
+        let fat: Vec<u8> = vec![0xF8, 0xFF, 0xFF, 0xFF, 0x02, 0x00, 0x04, 0x00, 0x03, 0x00, 0x00, 0x00];
+        let mut cur = StdIoWrapper::new(Cursor::<Vec<u8>>::new(fat));
+        for first_cluster in [2, 3] {
+            let iter = ClusterIterator::<_, std::io::Error, StdIoWrapper<Cursor<Vec<u8>>>>::new(
+                &mut cur,
+                FatType::Fat16,
+                first_cluster,
+                4,
+            );
+            let result = iter.collect::<Result<Vec<_>, _>>();
+            assert!(matches!(result, Err(Error::CorruptedFileSystem)));
+        }
+        // freeing a chain with a loop terminates
+        let mut iter =
+            ClusterIterator::<_, std::io::Error, StdIoWrapper<Cursor<Vec<u8>>>>::new(&mut cur, FatType::Fat16, 3, 4);
+        assert!(iter.free().is_ok());
+        assert_eq!(read_fat(&mut cur, FatType::Fat16, 3).ok(), Some(FatValue::Free));
+        assert_eq!(read_fat(&mut cur, FatType::Fat16, 4).ok(), Some(FatValue::Free));
+    }
+
+    #[test]
+    fn test_count_free_clusters_multiple_blocks() {
+        for fat_type in [FatType::Fat12, FatType::Fat16, FatType::Fat32] {
+            // odd number of clusters spanning multiple blocks
+            let total_clusters = 3001;
+            let mut cur = StdIoWrapper::new(Cursor::new(vec![0_u8; 4 * 3100]));
+            let mut used = 0;
+            for cluster in
+                (RESERVED_FAT_ENTRIES..total_clusters + RESERVED_FAT_ENTRIES).filter(|n| n % 3 == 0 || n % 7 == 0)
+            {
+                write_fat(&mut cur, fat_type, cluster, FatValue::EndOfChain).unwrap();
+                used += 1;
+            }
+            // entries after the last cluster are zeroed and must not be counted
+            assert_eq!(
+                count_free_clusters(&mut cur, fat_type, total_clusters).ok(),
+                Some(total_clusters - used)
+            );
+        }
     }
 
     #[test]
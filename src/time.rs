@@ -14,8 +14,8 @@ const MAX_DAY: u16 = 31;
 
 /// A DOS compatible date.
 ///
-/// Used by `DirEntry` time-related methods.
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+/// Used by `DirEntry` time-related methods. Dates are ordered chronologically.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
 #[non_exhaustive]
 pub struct Date {
     /// Full year - [1980, 2107]
@@ -44,6 +44,26 @@ impl Date {
         Self { year, month, day }
     }
 
+    /// Creates a `Date` from a date packed in the DOS format.
+    ///
+    /// The same format is used by other file formats, e.g. ZIP. Decoded values are not validated so they can be out
+    /// of the range accepted by `new` if the packed value is corrupted.
+    #[must_use]
+    pub fn from_dos(dos_date: u16) -> Self {
+        Self::decode(dos_date)
+    }
+
+    /// Packs this date in the DOS format.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of fields is out of the supported range.
+    #[must_use]
+    pub fn to_dos(self) -> u16 {
+        assert!(self.is_valid(), "date out of range");
+        self.encode()
+    }
+
     pub(crate) fn decode(dos_date: u16) -> Self {
         let (year, month, day) = ((dos_date >> 9) + MIN_YEAR, (dos_date >> 5) & 0xF, dos_date & 0x1F);
         Self { year, month, day }
@@ -52,12 +72,18 @@ impl Date {
     pub(crate) fn encode(self) -> u16 {
         ((self.year - MIN_YEAR) << 9) | (self.month << 5) | self.day
     }
+
+    pub(crate) fn is_valid(self) -> bool {
+        (MIN_YEAR..=MAX_YEAR).contains(&self.year)
+            && (MIN_MONTH..=MAX_MONTH).contains(&self.month)
+            && (MIN_DAY..=MAX_DAY).contains(&self.day)
+    }
 }
 
 /// A DOS compatible time.
 ///
-/// Used by `DirEntry` time-related methods.
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+/// Used by `DirEntry` time-related methods. Times are ordered chronologically (milliseconds included).
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
 #[non_exhaustive]
 pub struct Time {
     /// Hours after midnight - [0, 23]
@@ -90,6 +116,30 @@ impl Time {
         Self { hour, min, sec, millis }
     }
 
+    /// Creates a `Time` from a time packed in the DOS format.
+    ///
+    /// `dos_time` has a 2 seconds resolution. `dos_time_hi_res` is a number of 10 ms units in range [0, 199] that are
+    /// added to it (use 0 for formats that do not store it, e.g. ZIP). Decoded values are not validated so they can
+    /// be out of the range accepted by `new` if the packed value is corrupted.
+    #[must_use]
+    pub fn from_dos(dos_time: u16, dos_time_hi_res: u8) -> Self {
+        Self::decode(dos_time, dos_time_hi_res)
+    }
+
+    /// Packs this time in the DOS format.
+    ///
+    /// Returns a time with a 2 seconds resolution and a number of 10 ms units that should be added to it.
+    /// Milliseconds are rounded down to 10 ms units.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of fields is out of the supported range.
+    #[must_use]
+    pub fn to_dos(self) -> (u16, u8) {
+        assert!(self.is_valid(), "time out of range");
+        self.encode()
+    }
+
     pub(crate) fn decode(dos_time: u16, dos_time_hi_res: u8) -> Self {
         let hour = dos_time >> 11;
         let min = (dos_time >> 5) & 0x3F;
@@ -105,12 +155,16 @@ impl Time {
         #[allow(clippy::cast_possible_truncation)]
         (dos_time, dos_time_hi_res as u8)
     }
+
+    pub(crate) fn is_valid(self) -> bool {
+        self.hour <= 23 && self.min <= 59 && self.sec <= 59 && self.millis <= 999
+    }
 }
 
 /// A DOS compatible date and time.
 ///
-/// Used by `DirEntry` time-related methods.
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+/// Used by `DirEntry` time-related methods. Date and time values are ordered chronologically.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
 #[non_exhaustive]
 pub struct DateTime {
     /// A date part
@@ -128,6 +182,10 @@ impl DateTime {
     pub(crate) fn decode(dos_date: u16, dos_time: u16, dos_time_hi_res: u8) -> Self {
         Self::new(Date::decode(dos_date), Time::decode(dos_time, dos_time_hi_res))
     }
+
+    pub(crate) fn is_valid(self) -> bool {
+        self.date.is_valid() && self.time.is_valid()
+    }
 }
 
 #[cfg(feature = "chrono")]
@@ -258,7 +316,7 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
+    #[should_panic(expected = "year out of range")]
     fn date_new_panic_year_1979() {
         let _ = Date::new(1979, 12, 31);
     }
@@ -269,7 +327,7 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
+    #[should_panic(expected = "year out of range")]
     fn date_new_panic_year_2108() {
         let _ = Date::new(2108, 1, 1);
     }
@@ -298,6 +356,109 @@ mod tests {
         assert_eq!(t3, Time::decode(x3, y3));
     }
 
+    #[test]
+    fn date_dos_format() {
+        // epoch
+        let date = Date::new(1980, 1, 1);
+        assert_eq!(date.to_dos(), 0x0021);
+        assert_eq!(Date::from_dos(0x0021), date);
+        let date = Date::new(2107, 12, 31);
+        assert_eq!(date.to_dos(), 0xFF9F);
+        assert_eq!(Date::from_dos(0xFF9F), date);
+    }
+
+    #[test]
+    #[should_panic(expected = "date out of range")]
+    fn date_to_dos_panic() {
+        let mut date = Date::new(1980, 1, 1);
+        date.year = 1979;
+        let _ = date.to_dos();
+    }
+
+    #[test]
+    fn time_dos_format() {
+        let time = Time::new(0, 0, 0, 0);
+        assert_eq!(time.to_dos(), (0, 0));
+        assert_eq!(Time::from_dos(0, 0), time);
+        let time = Time::new(23, 59, 59, 990);
+        assert_eq!(time.to_dos(), (0xBF7D, 199));
+        assert_eq!(Time::from_dos(0xBF7D, 199), time);
+        // without the high resolution part
+        assert_eq!(Time::from_dos(0xBF7D, 0), Time::new(23, 59, 58, 0));
+    }
+
+    #[test]
+    fn date_time_is_valid() {
+        let date_time = DateTime::new(Date::new(2107, 12, 31), Time::new(23, 59, 59, 999));
+        assert!(date_time.is_valid());
+        for date in [
+            Date {
+                year: 1979,
+                ..date_time.date
+            },
+            Date {
+                year: 2108,
+                ..date_time.date
+            },
+            Date {
+                month: 0,
+                ..date_time.date
+            },
+            Date {
+                month: 13,
+                ..date_time.date
+            },
+            Date {
+                day: 0,
+                ..date_time.date
+            },
+            Date {
+                day: 32,
+                ..date_time.date
+            },
+        ] {
+            assert!(!date.is_valid());
+            assert!(!DateTime { date, ..date_time }.is_valid());
+        }
+        for time in [
+            Time {
+                hour: 24,
+                ..date_time.time
+            },
+            Time {
+                min: 60,
+                ..date_time.time
+            },
+            Time {
+                sec: 60,
+                ..date_time.time
+            },
+            Time {
+                millis: 1000,
+                ..date_time.time
+            },
+        ] {
+            assert!(!time.is_valid());
+            assert!(!DateTime { time, ..date_time }.is_valid());
+        }
+    }
+
+    #[test]
+    fn date_time_ordering() {
+        // derived ordering depends on the order of fields
+        assert!(Date::new(2000, 1, 31) < Date::new(2000, 2, 1));
+        assert!(Date::new(1999, 12, 31) < Date::new(2000, 1, 1));
+        assert!(Time::new(11, 59, 59, 999) < Time::new(12, 0, 0, 0));
+        assert!(Time::new(12, 0, 1, 10) < Time::new(12, 0, 1, 20));
+        let date_time = DateTime::new(Date::new(2000, 6, 15), Time::new(12, 30, 0, 0));
+        let later = DateTime::new(Date::new(2000, 6, 15), Time::new(12, 30, 0, 10));
+        let next_day = DateTime::new(Date::new(2000, 6, 16), Time::new(0, 0, 0, 0));
+        assert!(date_time < later && later < next_day);
+        let mut date_times = [next_day, later, date_time];
+        date_times.sort();
+        assert_eq!(date_times, [date_time, later, next_day]);
+    }
+
     #[test]
     fn date_time_from_chrono_leap_second() {
         let chrono_date_time = chrono::NaiveDate::from_ymd_opt(2016, 12, 31)
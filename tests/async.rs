@@ -0,0 +1,363 @@
+#![cfg(feature = "async")]
+
+use std::fs;
+use std::future::Future;
+use std::io::Cursor;
+use std::pin::pin;
+use std::str;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+use embedded_io_async::{ErrorKind, ErrorType, Read, Seek, SeekFrom, Write};
+use fatfs::{AsyncFileSystem, FsOptions, StdIoWrapper};
+
+const TEST_TEXT: &str = "Rust is cool!\n";
+const TEST_STR: &str = "Hi there Rust programmer!\n";
+const FAT12_IMG: &str = "resources/fat12.img";
+const FAT16_IMG: &str = "resources/fat16.img";
+const FAT32_IMG: &str = "resources/fat32.img";
+
+// In-memory storage implementing embedded-io-async traits
+struct MemStorage {
+    data: Vec<u8>,
+    pos: usize,
+}
+
+impl ErrorType for MemStorage {
+    type Error = ErrorKind;
+}
+
+impl Read for MemStorage {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let src = self.data.get(self.pos..).unwrap_or_default();
+        let n = buf.len().min(src.len());
+        buf[..n].copy_from_slice(&src[..n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl Write for MemStorage {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let dst = self.data.get_mut(self.pos..).unwrap_or_default();
+        let n = buf.len().min(dst.len());
+        dst[..n].copy_from_slice(&buf[..n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl Seek for MemStorage {
+    async fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        self.pos = match pos {
+            SeekFrom::Start(n) => n as usize,
+            _ => return Err(ErrorKind::Unsupported),
+        };
+        Ok(self.pos as u64)
+    }
+}
+
+struct NoopWaker;
+
+impl Wake for NoopWaker {
+    fn wake(self: Arc<Self>) {}
+}
+
+// All futures complete without waiting because the storage is in memory
+fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = Waker::from(Arc::new(NoopWaker));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+fn open_storage(filename: &str) -> MemStorage {
+    let _ = env_logger::builder().is_test(true).try_init();
+    MemStorage {
+        data: fs::read(filename).unwrap(),
+        pos: 0,
+    }
+}
+
+async fn read_to_string<R: Read>(file: &mut R) -> String
+where
+    R::Error: core::fmt::Debug,
+{
+    let mut buf = Vec::new();
+    let mut chunk = [0_u8; 100];
+    loop {
+        let n = file.read(&mut chunk).await.unwrap();
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    String::from_utf8(buf).unwrap()
+}
+
+async fn test_read(filename: &str) {
+    let mut fs = AsyncFileSystem::new(open_storage(filename), FsOptions::new())
+        .await
+        .unwrap();
+    let mut root_dir = fs.root_dir();
+    let mut file = root_dir.open_file("short.txt").await.unwrap();
+    assert_eq!(read_to_string(&mut file).await, TEST_TEXT);
+    let mut file = root_dir.open_file("LONG.TXT").await.unwrap();
+    assert_eq!(read_to_string(&mut file).await, TEST_TEXT.repeat(1000));
+    file.seek(SeekFrom::Start(1)).await.unwrap();
+    let mut buf = [0_u8; 5];
+    file.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"ust i");
+    let mut file = root_dir.open_file("very/long/path/test.txt").await.unwrap();
+    assert_eq!(read_to_string(&mut file).await, TEST_TEXT);
+    let mut dir = root_dir.open_dir("very-long-dir-name").await.unwrap();
+    let mut file = dir.open_file("very-long-file-name.txt").await.unwrap();
+    assert_eq!(read_to_string(&mut file).await, TEST_TEXT);
+    assert!(matches!(
+        root_dir.open_file("very").await,
+        Err(fatfs::Error::InvalidInput)
+    ));
+    assert!(matches!(
+        root_dir.open_file("missing.txt").await,
+        Err(fatfs::Error::NotFound)
+    ));
+}
+
+#[test]
+fn test_read_fat12() {
+    block_on(test_read(FAT12_IMG));
+}
+
+#[test]
+fn test_read_fat16() {
+    block_on(test_read(FAT16_IMG));
+}
+
+#[test]
+fn test_read_fat32() {
+    block_on(test_read(FAT32_IMG));
+}
+
+async fn test_write(filename: &str) {
+    let mut fs = AsyncFileSystem::new(open_storage(filename), FsOptions::new())
+        .await
+        .unwrap();
+    let mut root_dir = fs.root_dir();
+    // write data spanning multiple clusters to a new file
+    let data = TEST_STR.repeat(1000);
+    let mut file = root_dir.create_file("very/long/New File.txt").await.unwrap();
+    file.write_all(data.as_bytes()).await.unwrap();
+    file.seek(SeekFrom::Start(0)).await.unwrap();
+    assert_eq!(read_to_string(&mut file).await, data);
+    // seek beyond the end of file and overwrite existing data
+    file.seek(SeekFrom::End(10)).await.unwrap();
+    file.write_all(b"end").await.unwrap();
+    file.seek(SeekFrom::Start(3)).await.unwrap();
+    file.write_all(b"HERE").await.unwrap();
+    file.flush().await.unwrap();
+    // truncate an existing file
+    let mut file = root_dir.create_file("short.txt").await.unwrap();
+    file.seek(SeekFrom::Start(4)).await.unwrap();
+    file.truncate().await.unwrap();
+    file.flush().await.unwrap();
+    let storage = fs.unmount_into_inner().await.unwrap();
+
+    // verify changes using the sync API
+    let fs = fatfs::FileSystem::new(StdIoWrapper::new(Cursor::new(storage.data)), FsOptions::new()).unwrap();
+    let mut expected = data.into_bytes();
+    expected.extend_from_slice(&[0; 10]);
+    expected.extend_from_slice(b"end");
+    expected[3..7].copy_from_slice(b"HERE");
+    let mut buf = Vec::new();
+    let mut file = fs.root_dir().open_file("very/long/new file.txt").unwrap();
+    std::io::Read::read_to_end(&mut file, &mut buf).unwrap();
+    assert_eq!(buf, expected);
+    let names = fs
+        .root_dir()
+        .open_dir("very/long")
+        .unwrap()
+        .iter()
+        .map(|r| r.unwrap().file_name())
+        .collect::<Vec<String>>();
+    assert_eq!(names, [".", "..", "path", "New File.txt"]);
+    let mut buf = Vec::new();
+    let mut file = fs.root_dir().open_file("short.txt").unwrap();
+    std::io::Read::read_to_end(&mut file, &mut buf).unwrap();
+    assert_eq!(str::from_utf8(&buf).unwrap(), &TEST_TEXT[..4]);
+    assert!(fs.check().expect("check").is_clean());
+}
+
+#[test]
+fn test_write_fat12() {
+    block_on(test_write(FAT12_IMG));
+}
+
+#[test]
+fn test_write_fat16() {
+    block_on(test_write(FAT16_IMG));
+}
+
+#[test]
+fn test_write_fat32() {
+    block_on(test_write(FAT32_IMG));
+}
+
+async fn test_create_many_files(filename: &str) {
+    let mut fs = AsyncFileSystem::new(open_storage(filename), FsOptions::new())
+        .await
+        .unwrap();
+    let mut root_dir = fs.root_dir();
+    // extend a subdirectory and generate numeric tails for colliding short names
+    for i in 0..100 {
+        let mut file = root_dir
+            .create_file(&format!("very/long-name-{}.txt", i))
+            .await
+            .unwrap();
+        file.write_all(TEST_STR.as_bytes()).await.unwrap();
+        file.flush().await.unwrap();
+    }
+    let storage = fs.unmount_into_inner().await.unwrap();
+
+    let fs = fatfs::FileSystem::new(StdIoWrapper::new(Cursor::new(storage.data)), FsOptions::new()).unwrap();
+    let dir = fs.root_dir().open_dir("very").unwrap();
+    let entries = dir.iter().map(|r| r.unwrap()).collect::<Vec<_>>();
+    assert_eq!(entries.len(), 103);
+    assert_eq!(entries[3].short_file_name(), "LONG-N~1.TXT");
+    for (i, entry) in entries[3..].iter().enumerate() {
+        assert_eq!(entry.file_name(), format!("long-name-{}.txt", i));
+        let mut buf = Vec::new();
+        std::io::Read::read_to_end(&mut entry.to_file(), &mut buf).unwrap();
+        assert_eq!(str::from_utf8(&buf).unwrap(), TEST_STR);
+    }
+    assert!(fs.check().expect("check").is_clean());
+}
+
+#[test]
+fn test_create_many_files_fat12() {
+    block_on(test_create_many_files(FAT12_IMG));
+}
+
+#[test]
+fn test_create_many_files_fat16() {
+    block_on(test_create_many_files(FAT16_IMG));
+}
+
+#[test]
+fn test_create_many_files_fat32() {
+    block_on(test_create_many_files(FAT32_IMG));
+}
+
+async fn test_read_only(filename: &str) {
+    let options = FsOptions::new().read_only(true);
+    let mut fs = AsyncFileSystem::new(open_storage(filename), options).await.unwrap();
+    let mut root_dir = fs.root_dir();
+    assert!(matches!(
+        root_dir.create_file("new.txt").await,
+        Err(fatfs::Error::ReadOnlyFileSystem)
+    ));
+    let mut file = root_dir.open_file("short.txt").await.unwrap();
+    assert!(matches!(file.write(b"x").await, Err(fatfs::Error::ReadOnlyFileSystem)));
+    assert_eq!(read_to_string(&mut file).await, TEST_TEXT);
+}
+
+#[test]
+fn test_read_only_fat12() {
+    block_on(test_read_only(FAT12_IMG));
+}
+
+async fn test_dirty_flag(filename: &str) {
+    for unmount in [false, true] {
+        let mut storage = open_storage(filename);
+        let mut fs = AsyncFileSystem::new(&mut storage, FsOptions::new()).await.unwrap();
+        let mut root_dir = fs.root_dir();
+        let mut file = root_dir.create_file("new.txt").await.unwrap();
+        file.write_all(TEST_STR.as_bytes()).await.unwrap();
+        file.flush().await.unwrap();
+        if unmount {
+            fs.unmount().await.unwrap();
+        }
+        // without unmounting the volume is left marked as dirty like after a crash
+        let sync_fs = fatfs::FileSystem::new(StdIoWrapper::new(Cursor::new(storage.data)), FsOptions::new()).unwrap();
+        assert_eq!(sync_fs.read_status_flags().unwrap().dirty(), !unmount);
+    }
+}
+
+#[test]
+fn test_dirty_flag_fat16() {
+    block_on(test_dirty_flag(FAT16_IMG));
+}
+
+#[test]
+fn test_dirty_flag_fat32() {
+    block_on(test_dirty_flag(FAT32_IMG));
+}
+
+async fn test_cluster_chain_loop(filename: &str) {
+    let mut storage = open_storage(filename);
+    let sync_fs =
+        fatfs::FileSystem::new(StdIoWrapper::new(Cursor::new(storage.data.clone())), FsOptions::new()).unwrap();
+    let fat32 = sync_fs.fat_type() == fatfs::FatType::Fat32;
+    let cluster = sync_fs
+        .root_dir()
+        .iter()
+        .map(|r| r.unwrap())
+        .find(|e| e.file_name() == "very")
+        .unwrap()
+        .first_cluster()
+        .unwrap() as usize;
+    drop(sync_fs);
+
+    // make the directory cluster point to itself and mark all its entries as deleted so there is no end marker
+    let data = &mut storage.data;
+    let bytes_per_sector = usize::from(u16::from_le_bytes([data[11], data[12]]));
+    let sectors_per_cluster = usize::from(data[13]);
+    let reserved_sectors = usize::from(u16::from_le_bytes([data[14], data[15]]));
+    let fats = usize::from(data[16]);
+    let root_dir_sectors = usize::from(u16::from_le_bytes([data[17], data[18]])) * 32 / bytes_per_sector;
+    let sectors_per_fat = if fat32 {
+        u32::from_le_bytes(data[36..40].try_into().unwrap()) as usize
+    } else {
+        usize::from(u16::from_le_bytes([data[22], data[23]]))
+    };
+    for fat in 0..fats {
+        let fat_pos = (reserved_sectors + fat * sectors_per_fat) * bytes_per_sector;
+        if fat32 {
+            let pos = fat_pos + cluster * 4;
+            data[pos..pos + 4].copy_from_slice(&(cluster as u32).to_le_bytes());
+        } else {
+            let pos = fat_pos + cluster * 2;
+            data[pos..pos + 2].copy_from_slice(&(cluster as u16).to_le_bytes());
+        }
+    }
+    let first_data_sector = reserved_sectors + fats * sectors_per_fat + root_dir_sectors;
+    let cluster_pos = (first_data_sector + (cluster - 2) * sectors_per_cluster) * bytes_per_sector;
+    for pos in (cluster_pos..cluster_pos + sectors_per_cluster * bytes_per_sector).step_by(32) {
+        data[pos] = 0xE5;
+    }
+
+    let mut fs = AsyncFileSystem::new(storage, FsOptions::new()).await.unwrap();
+    let mut root_dir = fs.root_dir();
+    let mut dir = root_dir.open_dir("very").await.unwrap();
+    assert!(matches!(
+        dir.open_file("missing.txt").await,
+        Err(fatfs::Error::CorruptedFileSystem)
+    ));
+    assert!(matches!(
+        dir.create_file("new.txt").await,
+        Err(fatfs::Error::CorruptedFileSystem)
+    ));
+}
+
+#[test]
+fn test_cluster_chain_loop_fat16() {
+    block_on(test_cluster_chain_loop(FAT16_IMG));
+}
+
+#[test]
+fn test_cluster_chain_loop_fat32() {
+    block_on(test_cluster_chain_loop(FAT32_IMG));
+}
@@ -24,7 +24,7 @@ fn format_fs(opts: fatfs::FormatVolumeOptions, total_bytes: u64) -> FileSystem {
     fatfs::FileSystem::new(buffered_stream, fatfs::FsOptions::new()).expect("open fs")
 }
 
-fn basic_fs_test(fs: &FileSystem) {
+fn basic_fs_test<IO: fatfs::ReadWriteSeek<Error = io::Error>>(fs: &fatfs::FileSystem<IO>) {
     let stats = fs.stats().expect("stats");
     if fs.fat_type() == fatfs::FatType::Fat32 {
         // On FAT32 one cluster is allocated for root directory
@@ -95,6 +95,23 @@ fn test_format_8mb_1fat() {
     assert_eq!(fs.fat_type(), fatfs::FatType::Fat16);
 }
 
+#[test]
+fn test_format_1fat_layout() {
+    for (total_bytes, fat_type) in [
+        (MB, FatType::Fat12),
+        (8 * MB, FatType::Fat16),
+        (64 * MB, FatType::Fat32),
+    ] {
+        let opts = fatfs::FormatVolumeOptions::new().fat_type(fat_type);
+        let total_clusters_2fats = format_fs(opts, total_bytes).stats().expect("stats").total_clusters();
+        let opts = fatfs::FormatVolumeOptions::new().fat_type(fat_type).fats(1);
+        let fs = test_format_fs(opts, total_bytes);
+        assert_eq!(fs.fat_type(), fat_type);
+        // space saved by skipping the second FAT is used for data clusters
+        assert!(fs.stats().expect("stats").total_clusters() > total_clusters_2fats);
+    }
+}
+
 #[test]
 fn test_format_50mb() {
     let total_bytes = 50 * MB;
@@ -143,6 +160,19 @@ fn test_format_volume_label_and_id() {
     assert_eq!(fs.volume_id(), 1234);
 }
 
+#[test]
+fn test_format_volume_label_fat12() {
+    let total_bytes = MB;
+    let opts = fatfs::FormatVolumeOptions::new().volume_label(*b"SMALL DISK ");
+    let fs = test_format_fs(opts, total_bytes);
+    assert_eq!(fs.fat_type(), fatfs::FatType::Fat12);
+    assert_eq!(fs.volume_label(), "SMALL DISK");
+    assert_eq!(
+        fs.read_volume_label_from_root_dir().unwrap(),
+        Some("SMALL DISK".to_string())
+    );
+}
+
 #[test]
 fn test_zero_root_dir_clusters() {
     init_logger();
@@ -158,3 +188,967 @@ fn test_zero_root_dir_clusters() {
     }
     assert_eq!(root_dir.iter().count(), files_to_create);
 }
+
+#[test]
+fn test_write_to_full_volume() {
+    let total_bytes = MB;
+    let opts = fatfs::FormatVolumeOptions::new();
+    let fs = format_fs(opts, total_bytes);
+    assert_eq!(fs.fat_type(), fatfs::FatType::Fat12);
+    let root_dir = fs.root_dir();
+    let mut file = root_dir.create_file("big.bin").expect("create file");
+    let buf = vec![0xAB_u8; 64 * KB as usize];
+    let err = loop {
+        if let Err(err) = fatfs::Write::write_all(&mut file, &buf) {
+            break err;
+        }
+    };
+    assert!(
+        matches!(err, fatfs::Error::NotEnoughSpace),
+        "unexpected error {:?}",
+        err
+    );
+    assert_eq!(fs.stats().expect("stats").free_clusters(), 0);
+    // volume is still full on next write
+    let err = fatfs::Write::write_all(&mut file, &buf).unwrap_err();
+    assert!(
+        matches!(err, fatfs::Error::NotEnoughSpace),
+        "unexpected error {:?}",
+        err
+    );
+    // std::io::Write interface reports the error too
+    let io_err = file.write_all(&buf).unwrap_err();
+    assert_eq!(io_err.kind(), io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn test_read_file_using_all_clusters_fat16() {
+    init_logger();
+    let opts = fatfs::FormatVolumeOptions::new()
+        .fat_type(FatType::Fat16)
+        .bytes_per_cluster(512);
+    let fs = format_fs(opts, 3 * MB);
+    assert_eq!(fs.fat_type(), FatType::Fat16);
+    let stats = fs.stats().expect("stats");
+    // the root directory is outside of the data area so the file can use every cluster
+    assert_eq!(stats.free_clusters(), stats.total_clusters());
+    let data = (0..stats.total_clusters() as usize * 512)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    let root_dir = fs.root_dir();
+    let mut file = root_dir.create_file("big.bin").expect("create file");
+    file.write_all(&data).expect("write");
+    assert_eq!(fs.stats().expect("stats").free_clusters(), 0);
+    file.rewind().expect("seek");
+    let mut content = Vec::new();
+    file.read_to_end(&mut content).expect("read");
+    assert!(content == data);
+}
+
+#[test]
+fn test_create_dir_on_full_volume() {
+    let total_bytes = MB;
+    let opts = fatfs::FormatVolumeOptions::new();
+    let fs = format_fs(opts, total_bytes);
+    assert_eq!(fs.fat_type(), fatfs::FatType::Fat12);
+    let root_dir = fs.root_dir();
+    let dir = root_dir.create_dir("dir").expect("create dir");
+    // leave only a few free clusters
+    let free_clusters = fs.stats().expect("stats").free_clusters();
+    let mut file = root_dir.create_file("big.bin").expect("create file");
+    let size = (free_clusters - 5) * fs.cluster_size();
+    file.write_all(&vec![0xAB_u8; size as usize]).expect("write file");
+    drop(file);
+    // fixed-size root directory runs out of entries
+    let err = (0..)
+        .find_map(|i| root_dir.create_file(&format!("F{}", i)).err())
+        .unwrap();
+    assert!(
+        matches!(err, fatfs::Error::NotEnoughSpace),
+        "unexpected error {:?}",
+        err
+    );
+    // volume runs out of clusters
+    let err = (0..).find_map(|i| dir.create_dir(&format!("D{}", i)).err()).unwrap();
+    assert!(
+        matches!(err, fatfs::Error::NotEnoughSpace),
+        "unexpected error {:?}",
+        err
+    );
+    // failed operations do not leak clusters
+    assert!(fs.check().expect("check").is_clean());
+}
+
+#[test]
+fn test_create_file_in_full_root_dir_fat16() {
+    let opts = fatfs::FormatVolumeOptions::new().fat_type(FatType::Fat16);
+    let fs = format_fs(opts, 8 * MB);
+    assert_eq!(fs.fat_type(), FatType::Fat16);
+    let root_dir = fs.root_dir();
+    let free_slots = root_dir.free_entry_slots().expect("free_entry_slots").unwrap();
+    // a long name needs more than one slot so it does not fit in the last free slot
+    for i in 0..free_slots - 1 {
+        root_dir.create_file(&format!("F{}", i)).expect("create file");
+    }
+    let err = root_dir.create_file("long file name.txt").err().unwrap();
+    assert!(
+        matches!(err, fatfs::Error::NotEnoughSpace),
+        "unexpected error {:?}",
+        err
+    );
+    root_dir.create_file("LAST").expect("create file");
+    assert_eq!(root_dir.free_entry_slots().expect("free_entry_slots"), Some(0));
+    // no end-of-directory marker is left in a full root directory
+    let err = root_dir.create_file("NEXT").err().unwrap();
+    assert!(
+        matches!(err, fatfs::Error::NotEnoughSpace),
+        "unexpected error {:?}",
+        err
+    );
+    let err = root_dir.create_dir("DIR").err().unwrap();
+    assert!(
+        matches!(err, fatfs::Error::NotEnoughSpace),
+        "unexpected error {:?}",
+        err
+    );
+    assert_eq!(root_dir.iter().count(), free_slots);
+    assert!(fs.check().expect("check").is_clean());
+}
+
+#[test]
+fn test_extents_contiguous_file() {
+    let total_bytes = 8 * MB;
+    let opts = fatfs::FormatVolumeOptions::new();
+    let fs = format_fs(opts, total_bytes);
+    let root_dir = fs.root_dir();
+    let mut file = root_dir.create_file("contiguous.bin").expect("create file");
+    let size = fs.cluster_size() as usize * 5 + 100;
+    file.write_all(&vec![0x55_u8; size]).expect("write file");
+    let extents = file.extents().collect::<Result<Vec<_>, _>>().expect("extents");
+    assert_eq!(extents.len(), 1);
+    assert_eq!(extents[0].file_offset, 0);
+    assert_eq!(extents[0].size as usize, size);
+}
+
+#[test]
+fn test_is_contiguous() {
+    let fs = format_fs(fatfs::FormatVolumeOptions::new(), 8 * MB);
+    let root_dir = fs.root_dir();
+    let cluster_size = fs.cluster_size() as usize;
+    let mut first = root_dir.create_file("first.bin").expect("create file");
+    assert!(first.is_contiguous().expect("is_contiguous"));
+    first.write_all(&vec![1_u8; cluster_size * 2]).expect("write file");
+    assert!(first.is_contiguous().expect("is_contiguous"));
+    let mut second = root_dir.create_file("second.bin").expect("create file");
+    second.write_all(&vec![2_u8; cluster_size]).expect("write file");
+    assert!(second.is_contiguous().expect("is_contiguous"));
+    // the cluster after the first file is used by the second file
+    first.write_all(&vec![1_u8; cluster_size]).expect("write file");
+    assert!(!first.is_contiguous().expect("is_contiguous"));
+}
+
+#[test]
+fn test_defragment() {
+    let fs = format_fs(fatfs::FormatVolumeOptions::new(), 8 * MB);
+    let root_dir = fs.root_dir();
+    let cluster_size = fs.cluster_size() as usize;
+    let data = (0..cluster_size * 3 + 100).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    let mut file = root_dir.create_file("fragmented.bin").expect("create file");
+    file.write_all(&data[..cluster_size]).expect("write file");
+    let mut other = root_dir.create_file("other.bin").expect("create file");
+    other.write_all(&vec![2_u8; cluster_size]).expect("write file");
+    file.write_all(&data[cluster_size..]).expect("write file");
+    assert!(!file.is_contiguous().expect("is_contiguous"));
+    let free_clusters = fs.stats().expect("stats").free_clusters();
+
+    file.seek(io::SeekFrom::Start(cluster_size as u64 + 10)).expect("seek");
+    assert!(file.defragment().expect("defragment"));
+    assert!(file.is_contiguous().expect("is_contiguous"));
+    assert_eq!(fs.stats().expect("stats").free_clusters(), free_clusters);
+    // the position is preserved
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf).expect("read_to_end");
+    assert_eq!(buf, &data[cluster_size + 10..]);
+    // already contiguous files are not relocated
+    assert!(!file.defragment().expect("defragment"));
+    drop(file);
+
+    let mut file = root_dir.open_file("fragmented.bin").expect("open file");
+    assert!(file.is_contiguous().expect("is_contiguous"));
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf).expect("read_to_end");
+    assert_eq!(buf, data);
+    let mut buf = Vec::new();
+    other.seek(io::SeekFrom::Start(0)).expect("seek");
+    other.read_to_end(&mut buf).expect("read_to_end");
+    assert_eq!(buf, vec![2_u8; cluster_size]);
+}
+
+#[test]
+fn test_recover_deleted() {
+    let fs = format_fs(fatfs::FormatVolumeOptions::new(), 8 * MB);
+    let root_dir = fs.root_dir();
+    let cluster_size = fs.cluster_size() as usize;
+    let data = (0..cluster_size * 3 + 100).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    root_dir
+        .create_file("deleted.bin")
+        .expect("create file")
+        .write_all(&data)
+        .expect("write file");
+    root_dir.create_file("empty.bin").expect("create file");
+    root_dir.remove("deleted.bin").expect("remove");
+    root_dir.remove("empty.bin").expect("remove");
+    let deleted = root_dir
+        .iter_deleted()
+        .collect::<Result<Vec<_>, _>>()
+        .expect("iter_deleted");
+    assert_eq!(deleted.len(), 2);
+
+    // the cluster chain has been freed so clusters are assumed to be contiguous
+    let mut file = fs.recover_deleted(&deleted[0]).expect("recover_deleted");
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf).expect("read_to_end");
+    assert_eq!(buf, data);
+    file.seek(io::SeekFrom::Start(cluster_size as u64 * 2 + 5))
+        .expect("seek");
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf).expect("read_to_end");
+    assert_eq!(buf, &data[cluster_size * 2 + 5..]);
+    assert_eq!(
+        file.write(b"x").expect_err("write").kind(),
+        io::ErrorKind::PermissionDenied
+    );
+
+    let mut file = fs.recover_deleted(&deleted[1]).expect("recover_deleted");
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf).expect("read_to_end");
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn test_read_contiguous_clusters() {
+    let total_bytes = 8 * MB;
+    let opts = fatfs::FormatVolumeOptions::new();
+    let fs = format_fs(opts, total_bytes);
+    let root_dir = fs.root_dir();
+    let mut file = root_dir.create_file("contiguous.bin").expect("create file");
+    let cluster_size = fs.cluster_size() as usize;
+    let data = (0..cluster_size * 5 + 100).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    file.write_all(&data).expect("write file");
+
+    file.seek(io::SeekFrom::Start(100)).expect("seek");
+    let mut buf = vec![0_u8; data.len()];
+    // a single read call covers all physically contiguous clusters
+    let read_bytes = fatfs::Read::read(&mut file, &mut buf).expect("read");
+    assert_eq!(read_bytes, data.len() - 100);
+    assert_eq!(&buf[..read_bytes], &data[100..]);
+    assert_eq!(fatfs::Read::read(&mut file, &mut buf).expect("read"), 0);
+
+    // reads ending on a cluster boundary leave the file in a consistent state
+    file.seek(io::SeekFrom::Start(0)).expect("seek");
+    let read_bytes = fatfs::Read::read(&mut file, &mut buf[..cluster_size * 2]).expect("read");
+    assert_eq!(read_bytes, cluster_size * 2);
+    let mut rest = Vec::new();
+    file.read_to_end(&mut rest).expect("read_to_end");
+    assert_eq!(rest, &data[cluster_size * 2..]);
+}
+
+#[test]
+fn test_format_data_alignment() {
+    let total_bytes = 64 * MB;
+    let opts = fatfs::FormatVolumeOptions::new()
+        .fat_type(FatType::Fat32)
+        .data_alignment_sectors(2048);
+    let fs = test_format_fs(opts, total_bytes);
+    let root_extents = fs.root_dir().extents().collect::<Result<Vec<_>, _>>().expect("extents");
+    // root directory starts at the beginning of the data region
+    assert_eq!(root_extents[0].offset % MB, 0);
+}
+
+#[test]
+fn test_format_oem_name() {
+    init_logger();
+    let mut storage = StdIoWrapper::new(io::Cursor::new(vec![0_u8; MB as usize]));
+    fatfs::format_volume(&mut storage, fatfs::FormatVolumeOptions::new()).expect("format volume");
+    assert_eq!(&storage.into_inner().into_inner()[3..11], b"MSWIN4.1");
+
+    let mut storage = StdIoWrapper::new(io::Cursor::new(vec![0_u8; MB as usize]));
+    let opts = fatfs::FormatVolumeOptions::new().oem_name(*b"mkfs.fat");
+    fatfs::format_volume(&mut storage, opts).expect("format volume");
+    let storage = storage.into_inner();
+    assert_eq!(&storage.get_ref()[3..11], b"mkfs.fat");
+    fatfs::FileSystem::new(StdIoWrapper::new(storage), fatfs::FsOptions::new()).expect("open fs");
+}
+
+#[test]
+fn test_format_reserved_sectors() {
+    init_logger();
+    let total_bytes = 64 * MB;
+    let opts = fatfs::FormatVolumeOptions::new()
+        .fat_type(FatType::Fat32)
+        .reserved_sectors(32);
+    let mut storage = StdIoWrapper::new(io::Cursor::new(vec![0_u8; total_bytes as usize]));
+    fatfs::format_volume(&mut storage, opts).expect("format volume");
+    let storage = storage.into_inner();
+    let image = storage.get_ref();
+    assert_eq!(&image[14..16], &32_u16.to_le_bytes());
+    // first FAT starts right after the reserved sectors
+    assert_eq!(&image[32 * 512..32 * 512 + 4], &[0xF8, 0xFF, 0xFF, 0x0F]);
+    let fs =
+        fatfs::FileSystem::new(StdIoWrapper::new(BufStream::new(storage)), fatfs::FsOptions::new()).expect("open fs");
+    assert_eq!(fs.fat_type(), FatType::Fat32);
+    basic_fs_test(&fs);
+
+    // FAT32 requires space for FSInfo and backup boot sectors
+    let opts = fatfs::FormatVolumeOptions::new()
+        .fat_type(FatType::Fat32)
+        .reserved_sectors(4);
+    let mut storage = StdIoWrapper::new(io::Cursor::new(vec![0_u8; total_bytes as usize]));
+    let result = fatfs::format_volume(&mut storage, opts);
+    assert!(matches!(result, Err(fatfs::Error::InvalidInput)));
+}
+
+#[test]
+fn test_format_sectors_per_fat() {
+    init_logger();
+    let cases = [
+        (FatType::Fat12, 8 * MB, 4096, 40_u32),
+        (FatType::Fat16, 8 * MB, 1024, 100),
+        (FatType::Fat32, 64 * MB, 512, 1100),
+    ];
+    for (fat_type, total_bytes, bytes_per_cluster, sectors_per_fat) in cases {
+        let opts = fatfs::FormatVolumeOptions::new()
+            .fat_type(fat_type)
+            .bytes_per_cluster(bytes_per_cluster)
+            .sectors_per_fat(sectors_per_fat);
+        let mut storage = StdIoWrapper::new(io::Cursor::new(vec![0_u8; total_bytes as usize]));
+        fatfs::format_volume(&mut storage, opts).expect("format volume");
+        let storage = storage.into_inner();
+        let image = storage.get_ref();
+        let bpb_sectors_per_fat = match fat_type {
+            FatType::Fat32 => u32::from_le_bytes(image[36..40].try_into().unwrap()),
+            _ => u32::from(u16::from_le_bytes([image[22], image[23]])),
+        };
+        assert_eq!(bpb_sectors_per_fat, sectors_per_fat);
+        let fs = fatfs::FileSystem::new(StdIoWrapper::new(BufStream::new(storage)), fatfs::FsOptions::new())
+            .expect("open fs");
+        assert_eq!(fs.fat_type(), fat_type);
+        basic_fs_test(&fs);
+        assert!(fs.check().expect("check").is_clean());
+    }
+
+    // FAT too small to address all clusters
+    let opts = fatfs::FormatVolumeOptions::new()
+        .fat_type(FatType::Fat16)
+        .bytes_per_cluster(1024)
+        .sectors_per_fat(1);
+    let mut storage = StdIoWrapper::new(io::Cursor::new(vec![0_u8; (8 * MB) as usize]));
+    let result = fatfs::format_volume(&mut storage, opts);
+    assert!(matches!(result, Err(fatfs::Error::InvalidInput)));
+
+    // FAT bigger than the volume
+    let opts = fatfs::FormatVolumeOptions::new().sectors_per_fat(u32::MAX);
+    let result = fatfs::format_volume(&mut storage, opts);
+    assert!(matches!(result, Err(fatfs::Error::InvalidInput)));
+}
+
+#[test]
+fn test_format_full() {
+    init_logger();
+    let total_bytes = 8 * MB;
+    for quick in [true, false] {
+        let opts = fatfs::FormatVolumeOptions::new().quick(quick);
+        let mut storage = StdIoWrapper::new(io::Cursor::new(vec![0xD1_u8; total_bytes as usize]));
+        fatfs::format_volume(&mut storage, opts).expect("format volume");
+        let storage = storage.into_inner();
+        // the end of the volume belongs to the data region
+        let data_tail = &storage.get_ref()[(total_bytes - MB) as usize..];
+        let expected = if quick { 0xD1 } else { 0 };
+        assert!(data_tail.iter().all(|b| *b == expected));
+        let fs = fatfs::FileSystem::new(StdIoWrapper::new(BufStream::new(storage)), fatfs::FsOptions::new())
+            .expect("open fs");
+        basic_fs_test(&fs);
+    }
+}
+
+/// Storage that accepts only whole-sector writes at sector-aligned offsets
+struct SectorAlignedStorage {
+    data: Vec<u8>,
+    pos: usize,
+    bytes_per_sector: usize,
+}
+
+impl fatfs::IoBase for SectorAlignedStorage {
+    type Error = io::Error;
+}
+
+impl fatfs::Read for SectorAlignedStorage {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if self.pos % self.bytes_per_sector != 0 || buf.len() % self.bytes_per_sector != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unaligned read of {} bytes at {}", buf.len(), self.pos),
+            ));
+        }
+        let len = buf.len().min(self.data.len() - self.pos);
+        buf[..len].copy_from_slice(&self.data[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+impl fatfs::Write for SectorAlignedStorage {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if self.pos % self.bytes_per_sector != 0 || buf.len() % self.bytes_per_sector != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unaligned write of {} bytes at {}", buf.len(), self.pos),
+            ));
+        }
+        let len = buf.len().min(self.data.len() - self.pos);
+        self.data[self.pos..self.pos + len].copy_from_slice(&buf[..len]);
+        self.pos += len;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl fatfs::Seek for SectorAlignedStorage {
+    fn seek(&mut self, pos: fatfs::SeekFrom) -> Result<u64, Self::Error> {
+        let new_pos = match pos {
+            fatfs::SeekFrom::Start(n) => n as i64,
+            fatfs::SeekFrom::Current(n) => self.pos as i64 + n,
+            fatfs::SeekFrom::End(n) => self.data.len() as i64 + n,
+        };
+        self.pos = new_pos as usize;
+        Ok(self.pos as u64)
+    }
+}
+
+#[test]
+fn test_format_sector_aligned_writes() {
+    init_logger();
+    for (bytes_per_sector, total_bytes) in [(512, MB), (4096, 8 * MB), (4096, 1024 * MB)] {
+        let mut storage = SectorAlignedStorage {
+            data: vec![0xD1_u8; total_bytes as usize],
+            pos: 0,
+            bytes_per_sector: bytes_per_sector.into(),
+        };
+        let opts = fatfs::FormatVolumeOptions::new()
+            .bytes_per_sector(bytes_per_sector)
+            .volume_label(*b"ALIGNED    ");
+        fatfs::format_volume(&mut storage, opts).expect("format volume");
+        let storage_cur = io::Cursor::new(storage.data);
+        let fs = fatfs::FileSystem::new(StdIoWrapper::new(BufStream::new(storage_cur)), fatfs::FsOptions::new())
+            .expect("open fs");
+        basic_fs_test(&fs);
+    }
+}
+
+/// Storage that does not support seeking relative to its end
+struct UnknownSizeStorage(io::Cursor<Vec<u8>>);
+
+impl Read for UnknownSizeStorage {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for UnknownSizeStorage {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl Seek for UnknownSizeStorage {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        if let io::SeekFrom::End(_) = pos {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "seek from end"));
+        }
+        self.0.seek(pos)
+    }
+}
+
+#[test]
+fn test_format_unknown_size() {
+    init_logger();
+    let total_bytes = MB;
+    let mut storage = StdIoWrapper::new(UnknownSizeStorage(io::Cursor::new(vec![0_u8; total_bytes as usize])));
+    let err = fatfs::format_volume(&mut storage, fatfs::FormatVolumeOptions::new()).unwrap_err();
+    assert!(matches!(err, fatfs::Error::InvalidInput), "unexpected error {:?}", err);
+    let opts = fatfs::FormatVolumeOptions::new().total_sectors((total_bytes / 512) as u32);
+    fatfs::format_volume(&mut storage, opts).expect("format volume");
+    let storage_cur = io::Cursor::new(storage.into_inner().0.into_inner());
+    let fs = fatfs::FileSystem::new(StdIoWrapper::new(BufStream::new(storage_cur)), fatfs::FsOptions::new())
+        .expect("open fs");
+    basic_fs_test(&fs);
+}
+
+#[test]
+fn test_format_empty_storage() {
+    init_logger();
+    let total_bytes = 4 * MB;
+    // storage grows when the volume is written
+    let mut storage = StdIoWrapper::new(io::Cursor::new(Vec::new()));
+    let opts = fatfs::FormatVolumeOptions::new().total_sectors((total_bytes / 512) as u32);
+    fatfs::format_volume(&mut storage, opts).expect("format volume");
+    let storage_cur = storage.into_inner();
+    assert!(storage_cur.get_ref().len() as u64 <= total_bytes);
+    let fs = fatfs::FileSystem::new(StdIoWrapper::new(BufStream::new(storage_cur)), fatfs::FsOptions::new())
+        .expect("open fs");
+    basic_fs_test(&fs);
+}
+
+#[test]
+fn test_sector_buffer_fs() {
+    init_logger();
+    for (bytes_per_sector, total_bytes) in [(512, MB), (4096, 8 * MB)] {
+        let mut storage = SectorAlignedStorage {
+            data: vec![0xD1_u8; total_bytes as usize],
+            pos: 0,
+            bytes_per_sector: bytes_per_sector.into(),
+        };
+        let opts = fatfs::FormatVolumeOptions::new().bytes_per_sector(bytes_per_sector);
+        fatfs::format_volume(&mut storage, opts).expect("format volume");
+        {
+            let sector_buf = fatfs::SectorBuffer::<_, SectorAlignedStorage>::new(&mut storage, bytes_per_sector);
+            let fs = fatfs::FileSystem::new(sector_buf, fatfs::FsOptions::new()).expect("open fs");
+            basic_fs_test(&fs);
+            let mut file = fs.root_dir().create_file("odd.txt").expect("create file");
+            file.write_all(&TEST_STR.as_bytes()[..7]).expect("write");
+            file.write_all(TEST_STR.as_bytes()).expect("write");
+        }
+        // reopen the volume to make sure buffered sectors were written back
+        let sector_buf = fatfs::SectorBuffer::<_, SectorAlignedStorage>::new(&mut storage, bytes_per_sector);
+        let fs = fatfs::FileSystem::new(sector_buf, fatfs::FsOptions::new()).expect("open fs");
+        let mut file = fs.root_dir().open_file("odd.txt").expect("open file");
+        let mut content = Vec::new();
+        file.read_to_end(&mut content).expect("read");
+        assert_eq!(&content[..7], &TEST_STR.as_bytes()[..7]);
+        assert_eq!(&content[7..], TEST_STR.as_bytes());
+    }
+}
+
+#[test]
+fn test_preallocate() {
+    let total_bytes = 8 * MB;
+    let opts = fatfs::FormatVolumeOptions::new();
+    let fs = format_fs(opts, total_bytes);
+    let cluster_size = u64::from(fs.cluster_size());
+    let root_dir = fs.root_dir();
+    // fragment free space: one cluster files with every second one removed
+    for i in 0..20 {
+        let mut file = root_dir.create_file(&format!("{}.txt", i)).expect("create file");
+        file.write_all(b"x").expect("write");
+    }
+    for i in (0..20).step_by(2) {
+        root_dir.remove(&format!("{}.txt", i)).expect("remove");
+    }
+    let free_clusters = fs.stats().expect("stats").free_clusters();
+
+    let mut file = root_dir.create_file("prealloc.bin").expect("create file");
+    file.write_all(TEST_STR.as_bytes()).expect("write");
+    file.preallocate(100 * cluster_size).expect("preallocate");
+    assert_eq!(fs.stats().expect("stats").free_clusters(), free_clusters - 100);
+    let chain = file
+        .cluster_chain()
+        .collect::<Result<Vec<_>, _>>()
+        .expect("cluster_chain");
+    assert_eq!(chain.len(), 100);
+    // new clusters form a single run
+    assert!(chain[1..].windows(2).all(|w| w[1] == w[0] + 1));
+    // preallocating a smaller size does nothing
+    file.preallocate(10).expect("preallocate");
+    assert_eq!(file.cluster_chain().count(), 100);
+    // clusters beyond the end of file are not reported by the check
+    file.flush().expect("flush");
+    assert!(fs.check().expect("check").is_clean());
+
+    // size and position are not changed and the preallocated clusters are used for writing
+    assert_eq!(file.stream_position().expect("position"), TEST_STR.len() as u64);
+    let data = TEST_STR.repeat(1000);
+    file.write_all(data.as_bytes()).expect("write");
+    let chain2 = file
+        .cluster_chain()
+        .collect::<Result<Vec<_>, _>>()
+        .expect("cluster_chain");
+    assert_eq!(chain2, chain);
+    file.rewind().expect("seek");
+    let mut content = String::new();
+    file.read_to_string(&mut content).expect("read");
+    assert_eq!(content, TEST_STR.repeat(1001));
+
+    // truncating at the end of file releases the unused clusters
+    file.truncate().expect("truncate");
+    let used_clusters = ((TEST_STR.len() as u64 * 1001 + cluster_size - 1) / cluster_size) as u32;
+    assert_eq!(
+        fs.stats().expect("stats").free_clusters(),
+        free_clusters - used_clusters
+    );
+}
+
+#[test]
+fn test_preallocate_fragmented() {
+    let total_bytes = MB;
+    let opts = fatfs::FormatVolumeOptions::new();
+    let fs = format_fs(opts, total_bytes);
+    let root_dir = fs.root_dir();
+    let cluster = vec![0xAB_u8; fs.cluster_size() as usize];
+    // fill the volume with two interleaved files and remove one of them so only single free clusters are left
+    {
+        let mut file1 = root_dir.create_file("1.bin").expect("create file");
+        let mut file2 = root_dir.create_file("2.bin").expect("create file");
+        while fatfs::Write::write_all(&mut file1, &cluster).is_ok()
+            && fatfs::Write::write_all(&mut file2, &cluster).is_ok()
+        {}
+    }
+    root_dir.remove("2.bin").expect("remove");
+    let free_clusters = fs.stats().expect("stats").free_clusters();
+    assert!(free_clusters > 10);
+    let mut file = root_dir.create_file("prealloc.bin").expect("create file");
+    file.preallocate(u64::from(fs.cluster_size()) * 10)
+        .expect("preallocate");
+    let chain = file
+        .cluster_chain()
+        .collect::<Result<Vec<_>, _>>()
+        .expect("cluster_chain");
+    assert_eq!(chain.len(), 10);
+    assert!(chain.windows(2).all(|w| w[1] > w[0] + 1));
+    assert_eq!(fs.stats().expect("stats").free_clusters(), free_clusters - 10);
+}
+
+#[test]
+fn test_alloc_clusters_contiguous() {
+    let total_bytes = MB;
+    let opts = fatfs::FormatVolumeOptions::new();
+    let fs = format_fs(opts, total_bytes);
+    let root_dir = fs.root_dir();
+    // fragment free space at the beginning of the data area
+    for i in 0..10 {
+        let mut file = root_dir.create_file(&format!("{}.txt", i)).expect("create file");
+        file.write_all(b"x").expect("write");
+    }
+    for i in (0..10).step_by(2) {
+        root_dir.remove(&format!("{}.txt", i)).expect("remove");
+    }
+    let stats = fs.stats().expect("stats");
+    let free_clusters = stats.free_clusters();
+
+    let first_cluster = fs
+        .alloc_clusters_contiguous(5, Some(2))
+        .expect("alloc_clusters_contiguous")
+        .expect("contiguous run");
+    for cluster in first_cluster..first_cluster + 4 {
+        assert_eq!(
+            fs.read_fat_entry(cluster).expect("read_fat_entry"),
+            fatfs::FatValue::Data(cluster + 1)
+        );
+    }
+    assert_eq!(
+        fs.read_fat_entry(first_cluster + 4).expect("read_fat_entry"),
+        fatfs::FatValue::EndOfChain
+    );
+    assert_eq!(fs.stats().expect("stats").free_clusters(), free_clusters - 5);
+    // the chain is not used by any entry
+    assert_eq!(
+        fs.check().expect("check").problems(),
+        [fatfs::FsckProblem::LostChain {
+            first_cluster,
+            clusters: 5
+        }]
+    );
+
+    // no run is long enough
+    assert!(fs
+        .alloc_clusters_contiguous(stats.total_clusters(), None)
+        .expect("alloc_clusters_contiguous")
+        .is_none());
+    assert_eq!(fs.stats().expect("stats").free_clusters(), free_clusters - 5);
+    assert!(matches!(
+        fs.alloc_clusters_contiguous(0, None),
+        Err(fatfs::Error::InvalidInput)
+    ));
+}
+
+#[test]
+fn test_mark_cluster_bad() {
+    let total_bytes = MB;
+    let opts = fatfs::FormatVolumeOptions::new();
+    let fs = format_fs(opts, total_bytes);
+    let free_clusters = fs.stats().expect("stats").free_clusters();
+    for cluster in 2..12 {
+        fs.mark_cluster_bad(cluster).expect("mark_cluster_bad");
+    }
+    // marking the same cluster again does not change the free clusters count
+    fs.mark_cluster_bad(2).expect("mark_cluster_bad");
+    assert_eq!(fs.stats().expect("stats").free_clusters(), free_clusters - 10);
+    assert_eq!(fs.read_fat_entry(5).expect("read_fat_entry"), fatfs::FatValue::Bad);
+
+    // bad clusters are skipped by the allocator
+    let root_dir = fs.root_dir();
+    let mut file = root_dir.create_file("test.txt").expect("create file");
+    file.write_all(TEST_STR.as_bytes()).expect("write");
+    let chain = file
+        .cluster_chain()
+        .collect::<Result<Vec<_>, _>>()
+        .expect("cluster_chain");
+    assert_eq!(chain, [12]);
+
+    // clusters used by files cannot be marked
+    assert!(matches!(fs.mark_cluster_bad(12), Err(fatfs::Error::InvalidInput)));
+    assert!(matches!(fs.mark_cluster_bad(1), Err(fatfs::Error::InvalidInput)));
+}
+
+// Formats a volume with two FATs and converts it to a volume with three FATs by moving the end of the reserved area
+// back by the size of one FAT so the data region stays in place
+fn format_3fats_volume(fat_type: FatType, total_bytes: u64) -> Vec<u8> {
+    init_logger();
+    let mut storage = vec![0_u8; total_bytes as usize];
+    let opts = fatfs::FormatVolumeOptions::new()
+        .fat_type(fat_type)
+        .reserved_sectors(1024);
+    fatfs::format_volume(&mut StdIoWrapper::from(io::Cursor::new(&mut storage)), opts).expect("format volume");
+    let bytes_per_sector = u16::from_le_bytes([storage[11], storage[12]]) as usize;
+    let reserved_sectors = u16::from_le_bytes([storage[14], storage[15]]) as usize;
+    let sectors_per_fat = match fat_type {
+        FatType::Fat32 => u32::from_le_bytes(storage[36..40].try_into().unwrap()) as usize,
+        _ => u16::from_le_bytes([storage[22], storage[23]]) as usize,
+    };
+    assert!(reserved_sectors > sectors_per_fat + 8);
+    let new_reserved_sectors = reserved_sectors - sectors_per_fat;
+    let fat_size = sectors_per_fat * bytes_per_sector;
+    let fat_offset = reserved_sectors * bytes_per_sector;
+    let new_fat_offset = new_reserved_sectors * bytes_per_sector;
+    storage.copy_within(fat_offset..fat_offset + fat_size, new_fat_offset);
+    storage[14..16].copy_from_slice(&(new_reserved_sectors as u16).to_le_bytes());
+    storage[16] = 3;
+    storage
+}
+
+fn test_3fats(fat_type: FatType, total_bytes: u64) {
+    let mut storage = format_3fats_volume(fat_type, total_bytes);
+    {
+        let fs = fatfs::FileSystem::new(
+            StdIoWrapper::from(io::Cursor::new(&mut storage)),
+            fatfs::FsOptions::new(),
+        )
+        .expect("open fs");
+        assert_eq!(fs.fat_type(), fat_type);
+        basic_fs_test(&fs);
+        assert!(fs.check().expect("check").is_clean());
+        for fat_index in 0..3 {
+            assert_eq!(
+                fs.read_fat_entry_from(fat_index, 2).expect("read_fat_entry_from"),
+                fs.read_fat_entry(2).expect("read_fat_entry")
+            );
+        }
+        fs.unmount().expect("unmount");
+    }
+    // all FAT copies are identical
+    let bytes_per_sector = u16::from_le_bytes([storage[11], storage[12]]) as usize;
+    let fat_offset = u16::from_le_bytes([storage[14], storage[15]]) as usize * bytes_per_sector;
+    let sectors_per_fat = match fat_type {
+        FatType::Fat32 => u32::from_le_bytes(storage[36..40].try_into().unwrap()) as usize,
+        _ => u16::from_le_bytes([storage[22], storage[23]]) as usize,
+    };
+    let fat_size = sectors_per_fat * bytes_per_sector;
+    let fats = storage[fat_offset..fat_offset + 3 * fat_size]
+        .chunks(fat_size)
+        .collect::<Vec<_>>();
+    assert_eq!(fats[0], fats[1]);
+    assert_eq!(fats[0], fats[2]);
+}
+
+#[test]
+fn test_3fats_fat16() {
+    test_3fats(FatType::Fat16, 8 * MB);
+}
+
+#[test]
+fn test_3fats_fat32() {
+    test_3fats(FatType::Fat32, 40 * MB);
+}
+
+#[test]
+fn test_3fats_active_fat() {
+    let mut storage = format_3fats_volume(FatType::Fat32, 40 * MB);
+    // disable mirroring and select the last FAT as the active one
+    storage[40..42].copy_from_slice(&0x82_u16.to_le_bytes());
+    {
+        let fs = fatfs::FileSystem::new(
+            StdIoWrapper::from(io::Cursor::new(&mut storage)),
+            fatfs::FsOptions::new(),
+        )
+        .expect("open fs");
+        let mut file = fs.root_dir().create_file("test.txt").expect("create file");
+        file.write_all(TEST_STR.as_bytes()).expect("write file");
+        let cluster = file.cluster_chain().next().expect("cluster").expect("cluster");
+        drop(file);
+        // only the active FAT is updated
+        assert_eq!(
+            fs.read_fat_entry_from(0, cluster).expect("read_fat_entry_from"),
+            fatfs::FatValue::Free
+        );
+        assert_eq!(
+            fs.read_fat_entry_from(2, cluster).expect("read_fat_entry_from"),
+            fatfs::FatValue::EndOfChain
+        );
+        assert!(fs.check().expect("check").is_clean());
+        fs.unmount().expect("unmount");
+    }
+    // active FAT number exceeding the number of FATs is rejected
+    storage[40..42].copy_from_slice(&0x83_u16.to_le_bytes());
+    let err = fatfs::FileSystem::new(
+        StdIoWrapper::from(io::Cursor::new(&mut storage)),
+        fatfs::FsOptions::new(),
+    )
+    .err()
+    .expect("open fs");
+    assert!(
+        matches!(err, fatfs::Error::CorruptedFileSystem),
+        "unexpected error {:?}",
+        err
+    );
+}
+
+/// Storage that counts write and flush calls and tracks the size of the biggest write
+struct CountingStorage<'a> {
+    inner: io::Cursor<Vec<u8>>,
+    writes: &'a std::cell::Cell<u32>,
+    biggest_write: &'a std::cell::Cell<usize>,
+}
+
+impl Read for CountingStorage<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Write for CountingStorage<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writes.set(self.writes.get() + 1);
+        self.biggest_write.set(self.biggest_write.get().max(buf.len()));
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writes.set(self.writes.get() + 1);
+        self.inner.flush()
+    }
+}
+
+impl Seek for CountingStorage<'_> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+#[test]
+fn test_read_only_file_access_does_not_write() {
+    init_logger();
+    let writes = std::cell::Cell::new(0);
+    let biggest_write = std::cell::Cell::new(0);
+    let mut storage = StdIoWrapper::new(CountingStorage {
+        inner: io::Cursor::new(vec![0_u8; MB as usize]),
+        writes: &writes,
+        biggest_write: &biggest_write,
+    });
+    fatfs::format_volume(&mut storage, fatfs::FormatVolumeOptions::new()).expect("format volume");
+    let fs = fatfs::FileSystem::new(storage, fatfs::FsOptions::new()).expect("open fs");
+    let mut file = fs.root_dir().create_file("test.txt").expect("create file");
+    file.write_all(TEST_STR.as_bytes()).expect("write file");
+    drop(file);
+    let writes_before = writes.get();
+
+    let mut file = fs.root_dir().open_file("test.txt").expect("open file");
+    let mut buf = String::new();
+    file.read_to_string(&mut buf).expect("read file");
+    assert_eq!(buf, TEST_STR);
+    file.flush().expect("flush");
+    drop(file);
+    assert_eq!(writes.get(), writes_before);
+
+    let mut file = fs.root_dir().open_file("test.txt").expect("open file");
+    file.write_all(TEST_STR.as_bytes()).expect("write file");
+    drop(file);
+    assert!(writes.get() > writes_before);
+    fs.unmount().expect("unmount");
+}
+
+#[test]
+fn test_write_contiguous_clusters_at_once() {
+    init_logger();
+    let writes = std::cell::Cell::new(0);
+    let biggest_write = std::cell::Cell::new(0);
+    let mut storage = StdIoWrapper::new(CountingStorage {
+        inner: io::Cursor::new(vec![0_u8; MB as usize]),
+        writes: &writes,
+        biggest_write: &biggest_write,
+    });
+    let opts = fatfs::FormatVolumeOptions::new().bytes_per_cluster(512);
+    fatfs::format_volume(&mut storage, opts).expect("format volume");
+    let fs = fatfs::FileSystem::new(storage, fatfs::FsOptions::new()).expect("open fs");
+    let root_dir = fs.root_dir();
+    // make a hole of one cluster so the big file is fragmented
+    root_dir
+        .create_file("a.txt")
+        .expect("create file")
+        .write_all(TEST_STR.as_bytes())
+        .expect("write");
+    root_dir
+        .create_file("b.txt")
+        .expect("create file")
+        .write_all(TEST_STR.as_bytes())
+        .expect("write");
+    let hole = root_dir
+        .open_file("a.txt")
+        .expect("open file")
+        .cluster_chain()
+        .next()
+        .unwrap()
+        .unwrap();
+    root_dir.remove("a.txt").expect("remove");
+    fs.set_next_free_cluster_hint(hole).expect("set hint");
+
+    let data = (0..20 * 512 + 100).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    let mut file = root_dir.create_file("big.bin").expect("create file");
+    // the first write starts in the middle of a cluster
+    file.write_all(&data[..100]).expect("write");
+    biggest_write.set(0);
+    file.write_all(&data[100..]).expect("write");
+    drop(file);
+    // the hole is used and the rest of the file is contiguous so most of the data is written at once
+    assert!(biggest_write.get() >= 18 * 512, "biggest write {}", biggest_write.get());
+    let chain = root_dir
+        .open_file("big.bin")
+        .expect("open file")
+        .cluster_chain()
+        .collect::<Result<Vec<_>, _>>()
+        .expect("cluster chain");
+    assert_eq!(chain.len(), 21);
+    assert_eq!(chain[0], hole);
+
+    let mut content = Vec::new();
+    root_dir
+        .open_file("big.bin")
+        .expect("open file")
+        .read_to_end(&mut content)
+        .expect("read");
+    assert_eq!(content, data);
+    assert!(fs.check().expect("check").is_clean());
+    // overwriting the existing contiguous clusters is done in one write too
+    let mut file = root_dir.open_file("big.bin").expect("open file");
+    file.seek(io::SeekFrom::Start(512)).expect("seek");
+    biggest_write.set(0);
+    file.write_all(&data[512..]).expect("write");
+    assert_eq!(biggest_write.get(), data.len() - 512);
+    drop(file);
+    assert!(fs.check().expect("check").is_clean());
+}
@@ -3,7 +3,7 @@ use std::io::prelude::*;
 use std::io::SeekFrom;
 use std::str;
 
-use fatfs::{FatType, FsOptions, StdIoWrapper};
+use fatfs::{FatType, FatValue, FsOptions, StdIoWrapper};
 use fscommon::BufStream;
 
 const TEST_TEXT: &str = "Rust is cool!\n";
@@ -48,6 +48,203 @@ fn test_root_dir_fat32() {
     call_with_fs(test_root_dir, FAT32_IMG)
 }
 
+fn test_filtered_iter(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let files = root_dir
+        .iter_files()
+        .map(|r| r.unwrap().file_name())
+        .collect::<Vec<String>>();
+    assert_eq!(files, ["long.txt", "short.txt"]);
+    let dirs = root_dir
+        .iter_dirs()
+        .map(|r| r.unwrap().file_name())
+        .collect::<Vec<String>>();
+    assert_eq!(dirs, ["very", "very-long-dir-name"]);
+    let dir = root_dir.open_dir("very").unwrap();
+    let dirs = dir.iter_dirs().map(|r| r.unwrap().file_name()).collect::<Vec<String>>();
+    assert_eq!(dirs, [".", "..", "long"]);
+    assert_eq!(dir.iter_files().count(), 0);
+}
+
+#[test]
+fn test_filtered_iter_fat12() {
+    call_with_fs(test_filtered_iter, FAT12_IMG)
+}
+
+#[test]
+fn test_filtered_iter_fat16() {
+    call_with_fs(test_filtered_iter, FAT16_IMG)
+}
+
+#[test]
+fn test_filtered_iter_fat32() {
+    call_with_fs(test_filtered_iter, FAT32_IMG)
+}
+
+fn test_iter_seek(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let mut iter = root_dir.iter();
+    assert_eq!(iter.position(), 0);
+    let entries = iter
+        .by_ref()
+        .map(|r| r.map(|e| (e.offset(), e.file_name())).unwrap())
+        .collect::<Vec<_>>();
+    let end = iter.position();
+    assert!(iter.next().is_none());
+    assert_eq!(iter.position(), end);
+    // page through the directory backward
+    for (offset, name) in entries.iter().rev() {
+        iter.seek_to(*offset).unwrap();
+        assert_eq!(iter.position(), *offset);
+        assert_eq!(&iter.next().unwrap().unwrap().file_name(), name);
+        let next_pos = iter.position();
+        assert!(next_pos > *offset && next_pos <= end);
+    }
+    // resume from a stored position
+    iter.seek_to(entries[1].0).unwrap();
+    let names = iter.map(|r| r.unwrap().file_name()).collect::<Vec<_>>();
+    let expected = entries[1..].iter().map(|(_, name)| name.clone()).collect::<Vec<_>>();
+    assert_eq!(names, expected);
+    assert!(matches!(root_dir.iter().seek_to(1), Err(fatfs::Error::InvalidInput)));
+}
+
+#[test]
+fn test_iter_seek_fat12() {
+    call_with_fs(test_iter_seek, FAT12_IMG)
+}
+
+#[test]
+fn test_iter_seek_fat16() {
+    call_with_fs(test_iter_seek, FAT16_IMG)
+}
+
+#[test]
+fn test_iter_seek_fat32() {
+    call_with_fs(test_iter_seek, FAT32_IMG)
+}
+
+fn test_walk(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let entries = root_dir
+        .walk()
+        .map(|r| r.map(|(path, e)| (path, e.is_dir())).unwrap())
+        .collect::<Vec<_>>();
+    let expected = [
+        ("long.txt", false),
+        ("short.txt", false),
+        ("very", true),
+        ("very/long", true),
+        ("very/long/path", true),
+        ("very/long/path/test.txt", false),
+        ("very-long-dir-name", true),
+        ("very-long-dir-name/very-long-file-name.txt", false),
+    ];
+    assert_eq!(entries, expected.map(|(p, d)| (p.to_string(), d)));
+
+    let dir = root_dir.open_dir("very/long").unwrap();
+    let paths = dir.walk().map(|r| r.unwrap().0).collect::<Vec<_>>();
+    assert_eq!(paths, ["path", "path/test.txt"]);
+}
+
+#[test]
+fn test_walk_fat12() {
+    call_with_fs(test_walk, FAT12_IMG)
+}
+
+#[test]
+fn test_walk_fat16() {
+    call_with_fs(test_walk, FAT16_IMG)
+}
+
+#[test]
+fn test_walk_fat32() {
+    call_with_fs(test_walk, FAT32_IMG)
+}
+
+fn test_entry_path(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let paths = |dir: &fatfs::Dir<_, _, _>| dir.iter().map(|r| r.unwrap().path()).collect::<Vec<_>>();
+    assert_eq!(
+        paths(&root_dir),
+        ["/long.txt", "/short.txt", "/very", "/very-long-dir-name"]
+    );
+    let dir = root_dir.open_dir("VERY/long").unwrap();
+    assert_eq!(paths(&dir), ["/very/long", "/very", "/very/long/path"]);
+    // paths of directories opened through `.` and `..` entries are normalized
+    let dir = root_dir.open_dir("very/long/path/./..").unwrap();
+    assert_eq!(paths(&dir), ["/very/long", "/very", "/very/long/path"]);
+    let dir = root_dir.open_dir("very/..").unwrap();
+    assert_eq!(paths(&dir), paths(&root_dir));
+    let paths_from_walk = root_dir
+        .walk()
+        .map(|r| r.map(|(path, e)| (format!("/{}", path), e.path())).unwrap());
+    for (expected, path) in paths_from_walk {
+        assert_eq!(path, expected);
+    }
+}
+
+#[test]
+fn test_entry_path_fat12() {
+    call_with_fs(test_entry_path, FAT12_IMG)
+}
+
+#[test]
+fn test_entry_path_fat16() {
+    call_with_fs(test_entry_path, FAT16_IMG)
+}
+
+#[test]
+fn test_entry_path_fat32() {
+    call_with_fs(test_entry_path, FAT32_IMG)
+}
+
+fn test_exists_and_metadata(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    assert!(root_dir.exists("long.txt").unwrap());
+    assert!(root_dir.exists("VERY/LONG/path/test.txt").unwrap());
+    assert!(root_dir.exists("very/long").unwrap());
+    assert!(root_dir.exists("\\very\\long/path\\test.txt").unwrap());
+    assert!(!root_dir.exists("missing.txt").unwrap());
+    assert!(!root_dir.exists("missing/test.txt").unwrap());
+    // intermediate component is a file
+    assert!(!root_dir.exists("short.txt/test.txt").unwrap());
+
+    let metadata = root_dir.metadata("very/long/path/test.txt").unwrap();
+    let entry = root_dir
+        .open_dir("very/long/path")
+        .unwrap()
+        .iter()
+        .map(Result::unwrap)
+        .find(|e| e.file_name() == "test.txt")
+        .unwrap();
+    assert_eq!(metadata, entry.metadata());
+    assert!(metadata.is_file());
+    assert_eq!(metadata.len(), TEST_TEXT.len() as u64);
+    assert_eq!(metadata.modified(), entry.modified());
+    assert_eq!(metadata.created(), entry.created());
+    assert_eq!(metadata.accessed(), entry.accessed());
+
+    let metadata = root_dir.metadata("very-long-dir-name").unwrap();
+    assert!(metadata.is_dir());
+    assert_eq!(metadata.len(), 0);
+    assert!(matches!(root_dir.metadata("very/missing"), Err(fatfs::Error::NotFound)));
+}
+
+#[test]
+fn test_exists_and_metadata_fat12() {
+    call_with_fs(test_exists_and_metadata, FAT12_IMG)
+}
+
+#[test]
+fn test_exists_and_metadata_fat16() {
+    call_with_fs(test_exists_and_metadata, FAT16_IMG)
+}
+
+#[test]
+fn test_exists_and_metadata_fat32() {
+    call_with_fs(test_exists_and_metadata, FAT32_IMG)
+}
+
 fn test_read_seek_short_file(fs: FileSystem) {
     let root_dir = fs.root_dir();
     let mut short_file = root_dir.open_file("short.txt").unwrap();
@@ -60,9 +257,11 @@ fn test_read_seek_short_file(fs: FileSystem) {
     short_file.read_exact(&mut buf2).unwrap();
     assert_eq!(str::from_utf8(&buf2).unwrap(), &TEST_TEXT[5..10]);
 
-    assert_eq!(short_file.seek(SeekFrom::Start(1000)).unwrap(), TEST_TEXT.len() as u64);
+    // seeking beyond the end of the file is allowed but there is nothing to read
+    assert_eq!(short_file.seek(SeekFrom::Start(1000)).unwrap(), 1000);
     let mut buf2 = [0; 5];
     assert_eq!(short_file.read(&mut buf2).unwrap(), 0);
+    assert_eq!(short_file.seek(SeekFrom::Current(-1)).unwrap(), 999);
 }
 
 #[test]
@@ -109,6 +308,331 @@ fn test_read_long_file_fat32() {
     call_with_fs(test_read_long_file, FAT32_IMG)
 }
 
+fn test_open_file_from_cluster(fs: FileSystem) {
+    let expected = TEST_TEXT.repeat(1000);
+    let first_cluster = {
+        let long_file = fs.root_dir().open_file("long.txt").unwrap();
+        long_file.cluster_chain().next().unwrap().unwrap()
+    };
+    // with known size reading stops at the end of file
+    let mut file = fs
+        .open_file_from_cluster(first_cluster, Some(expected.len() as u32))
+        .unwrap();
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf).unwrap();
+    assert_eq!(str::from_utf8(&buf).unwrap(), expected);
+    // without size the whole cluster chain is read
+    let mut file = fs.open_file_from_cluster(first_cluster, None).unwrap();
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf).unwrap();
+    assert_eq!(buf.len() % fs.cluster_size() as usize, 0);
+    assert!(buf.len() >= expected.len());
+    assert_eq!(&buf[..expected.len()], expected.as_bytes());
+    // detached files cannot be modified
+    let err = file.write(b"x").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+    // invalid cluster numbers are rejected
+    assert!(matches!(
+        fs.open_file_from_cluster(0, None),
+        Err(fatfs::Error::InvalidInput)
+    ));
+    assert!(matches!(
+        fs.open_file_from_cluster(u32::MAX, None),
+        Err(fatfs::Error::InvalidInput)
+    ));
+}
+
+#[test]
+fn test_open_file_from_cluster_fat12() {
+    call_with_fs(test_open_file_from_cluster, FAT12_IMG)
+}
+
+#[test]
+fn test_open_file_from_cluster_fat16() {
+    call_with_fs(test_open_file_from_cluster, FAT16_IMG)
+}
+
+#[test]
+fn test_open_file_from_cluster_fat32() {
+    call_with_fs(test_open_file_from_cluster, FAT32_IMG)
+}
+
+fn test_fat_entries(fs: FileSystem) {
+    let entries = fs.fat_entries().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(entries.len(), fs.stats().unwrap().total_clusters() as usize);
+    for (i, (cluster, value)) in entries.iter().enumerate() {
+        assert_eq!(*cluster, i as u32 + 2);
+        assert_eq!(*value, fs.read_fat_entry(*cluster).unwrap());
+    }
+    // follow the long file cluster chain using the dumped table
+    let file = fs.root_dir().open_file("long.txt").unwrap();
+    let chain = file.cluster_chain().collect::<Result<Vec<_>, _>>().unwrap();
+    for pair in chain.windows(2) {
+        assert_eq!(entries[pair[0] as usize - 2].1, FatValue::Data(pair[1]));
+    }
+    let last = *chain.last().unwrap();
+    assert_eq!(entries[last as usize - 2].1, FatValue::EndOfChain);
+}
+
+#[test]
+fn test_fat_entries_fat12() {
+    call_with_fs(test_fat_entries, FAT12_IMG)
+}
+
+#[test]
+fn test_fat_entries_fat16() {
+    call_with_fs(test_fat_entries, FAT16_IMG)
+}
+
+#[test]
+fn test_fat_entries_fat32() {
+    call_with_fs(test_fat_entries, FAT32_IMG)
+}
+
+fn test_read_fat_entry_from(fs: FileSystem) {
+    let total_clusters = fs.stats().unwrap().total_clusters();
+    for cluster in 2..total_clusters + 2 {
+        let value = fs.read_fat_entry(cluster).unwrap();
+        assert_eq!(fs.read_fat_entry_from(0, cluster).unwrap(), value);
+        assert_eq!(fs.read_fat_entry_from(1, cluster).unwrap(), value);
+    }
+    assert!(matches!(fs.read_fat_entry_from(2, 2), Err(fatfs::Error::InvalidInput)));
+    assert!(matches!(fs.read_fat_entry_from(0, 1), Err(fatfs::Error::InvalidInput)));
+}
+
+#[test]
+fn test_read_fat_entry_from_fat12() {
+    call_with_fs(test_read_fat_entry_from, FAT12_IMG)
+}
+
+#[test]
+fn test_read_fat_entry_from_fat16() {
+    call_with_fs(test_read_fat_entry_from, FAT16_IMG)
+}
+
+#[test]
+fn test_read_fat_entry_from_fat32() {
+    call_with_fs(test_read_fat_entry_from, FAT32_IMG)
+}
+
+fn test_dir_entry_display(fs: FileSystem) {
+    let lines = fs
+        .root_dir()
+        .iter()
+        .map(|r| r.unwrap().to_string())
+        .collect::<Vec<String>>();
+    assert_eq!(
+        lines,
+        [
+            "-rw--a       14000  2017-09-24 19:59  long.txt",
+            "-rw--a          14  2017-09-24 19:59  short.txt",
+            "drw---           0  2017-09-24 19:59  very",
+            "drw---           0  2017-09-24 19:59  very-long-dir-name",
+        ]
+    );
+}
+
+#[test]
+fn test_dir_entry_display_fat12() {
+    call_with_fs(test_dir_entry_display, FAT12_IMG)
+}
+
+#[test]
+fn test_dir_entry_display_fat16() {
+    call_with_fs(test_dir_entry_display, FAT16_IMG)
+}
+
+#[test]
+fn test_dir_entry_display_fat32() {
+    call_with_fs(test_dir_entry_display, FAT32_IMG)
+}
+
+fn test_dir_entry_offset_on_disk(fs: FileSystem, img: &str) {
+    let mut raw = fs::File::open(img).unwrap();
+    let mut prev_end = 0;
+    for e in fs.root_dir().iter().map(Result::unwrap) {
+        let mut buf = [0_u8; 32];
+        raw.seek(SeekFrom::Start(e.offset_on_disk())).unwrap();
+        raw.read_exact(&mut buf).unwrap();
+        assert_eq!(buf[11], e.attributes().bits());
+        assert_eq!(u64::from(u32::from_le_bytes(buf[28..32].try_into().unwrap())), e.len());
+        let (start, end) = e.entry_span();
+        assert_eq!(start, e.offset());
+        assert!(start >= prev_end);
+        assert!(end - start >= 32 && (end - start) % 32 == 0);
+        prev_end = end;
+    }
+}
+
+#[test]
+fn test_dir_entry_offset_on_disk_fat12() {
+    call_with_fs(|fs| test_dir_entry_offset_on_disk(fs, FAT12_IMG), FAT12_IMG)
+}
+
+#[test]
+fn test_dir_entry_offset_on_disk_fat16() {
+    call_with_fs(|fs| test_dir_entry_offset_on_disk(fs, FAT16_IMG), FAT16_IMG)
+}
+
+#[test]
+fn test_dir_entry_offset_on_disk_fat32() {
+    call_with_fs(|fs| test_dir_entry_offset_on_disk(fs, FAT32_IMG), FAT32_IMG)
+}
+
+fn test_read_all_into(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let expected = TEST_TEXT.repeat(1000);
+    let mut long_file = root_dir.open_file("long.txt").unwrap();
+    let mut buf = Vec::new();
+    assert_eq!(long_file.read_all_into(&mut buf).unwrap(), expected.len());
+    assert_eq!(buf, expected.as_bytes());
+    assert_eq!(buf.capacity(), expected.len());
+    // data is appended to the buffer and reading starts at the current position
+    let mut buf = b"abc".to_vec();
+    long_file.seek(SeekFrom::Start(2017)).unwrap();
+    assert_eq!(long_file.read_all_into(&mut buf).unwrap(), expected.len() - 2017);
+    assert_eq!(&buf[..3], b"abc");
+    assert_eq!(&buf[3..], &expected.as_bytes()[2017..]);
+    // end of file
+    assert_eq!(long_file.read_all_into(&mut buf).unwrap(), 0);
+    let mut buf = Vec::new();
+    let mut short_file = root_dir.open_file("short.txt").unwrap();
+    assert_eq!(short_file.read_all_into(&mut buf).unwrap(), TEST_TEXT.len());
+    assert_eq!(buf, TEST_TEXT.as_bytes());
+}
+
+#[test]
+fn test_read_all_into_fat12() {
+    call_with_fs(test_read_all_into, FAT12_IMG)
+}
+
+#[test]
+fn test_read_all_into_fat16() {
+    call_with_fs(test_read_all_into, FAT16_IMG)
+}
+
+#[test]
+fn test_read_all_into_fat32() {
+    call_with_fs(test_read_all_into, FAT32_IMG)
+}
+
+fn test_seek_forward_long_file(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let mut long_file = root_dir.open_file("long.txt").unwrap();
+    let expected = TEST_TEXT.repeat(1000);
+    let cluster_size = fs.cluster_size() as usize;
+    // short forward seeks, cluster boundaries and a backward seek
+    let offsets = (0..expected.len() - 10).step_by(777).chain([
+        cluster_size,
+        2 * cluster_size,
+        2 * cluster_size - 1,
+        100,
+        cluster_size * 3 + 5,
+    ]);
+    for offset in offsets {
+        assert_eq!(long_file.seek(SeekFrom::Start(offset as u64)).unwrap(), offset as u64);
+        let mut buf = [0; 10];
+        long_file.read_exact(&mut buf).unwrap();
+        assert_eq!(str::from_utf8(&buf).unwrap(), &expected[offset..offset + 10]);
+        // small relative seek forward
+        long_file.seek(SeekFrom::Current(3)).unwrap();
+    }
+    assert_eq!(long_file.seek(SeekFrom::End(-5)).unwrap(), expected.len() as u64 - 5);
+    let mut buf = Vec::new();
+    long_file.read_to_end(&mut buf).unwrap();
+    assert_eq!(str::from_utf8(&buf).unwrap(), &expected[expected.len() - 5..]);
+}
+
+#[test]
+fn test_seek_forward_long_file_fat12() {
+    call_with_fs(test_seek_forward_long_file, FAT12_IMG)
+}
+
+#[test]
+fn test_seek_forward_long_file_fat16() {
+    call_with_fs(test_seek_forward_long_file, FAT16_IMG)
+}
+
+#[test]
+fn test_seek_forward_long_file_fat32() {
+    call_with_fs(test_seek_forward_long_file, FAT32_IMG)
+}
+
+fn test_cluster_chain(fs: FileSystem, filename: &str) {
+    let image = fs::read(filename).unwrap();
+    let root_dir = fs.root_dir();
+    let long_file = root_dir.open_file("long.txt").unwrap();
+    let clusters = long_file.cluster_chain().collect::<Result<Vec<_>, _>>().unwrap();
+    let cluster_size = fs.cluster_size() as usize;
+    let text = TEST_TEXT.repeat(1000);
+    assert_eq!(clusters.len(), (text.len() + cluster_size - 1) / cluster_size);
+    let mut data = Vec::new();
+    for cluster in clusters {
+        let offset = fs.offset_from_cluster(cluster) as usize;
+        data.extend_from_slice(&image[offset..offset + cluster_size]);
+    }
+    assert_eq!(str::from_utf8(&data[..text.len()]).unwrap(), text);
+}
+
+#[test]
+fn test_cluster_chain_fat12() {
+    call_with_fs(|fs| test_cluster_chain(fs, FAT12_IMG), FAT12_IMG)
+}
+
+#[test]
+fn test_cluster_chain_fat16() {
+    call_with_fs(|fs| test_cluster_chain(fs, FAT16_IMG), FAT16_IMG)
+}
+
+#[test]
+fn test_cluster_chain_fat32() {
+    call_with_fs(|fs| test_cluster_chain(fs, FAT32_IMG), FAT32_IMG)
+}
+
+fn test_extents(fs: FileSystem, filename: &str) {
+    let image = fs::read(filename).unwrap();
+    let root_dir = fs.root_dir();
+    let long_file = root_dir.open_file("long.txt").unwrap();
+    let text = TEST_TEXT.repeat(1000);
+    let mut data = Vec::new();
+    for extent in long_file.extents() {
+        let extent = extent.unwrap();
+        assert_eq!(extent.file_offset as usize, data.len());
+        assert_eq!(extent.offset % u64::from(fs.bytes_per_sector()), 0);
+        let offset = extent.offset as usize;
+        data.extend_from_slice(&image[offset..offset + extent.size as usize]);
+    }
+    assert_eq!(str::from_utf8(&data).unwrap(), text);
+
+    let root_extents = root_dir.extents().collect::<Result<Vec<_>, _>>().unwrap();
+    if fs.fat_type() == FatType::Fat32 {
+        assert!(!root_extents.is_empty());
+        assert!(root_extents.iter().all(|e| e.size % fs.cluster_size() == 0));
+    } else {
+        assert_eq!(root_extents.len(), 1);
+        assert_eq!(root_extents[0].file_offset, 0);
+        assert_eq!(root_extents[0].size % 32, 0);
+    }
+    // first root directory entry is the volume label
+    let root_offset = root_extents[0].offset as usize;
+    assert_eq!(image[root_offset + 11] & 0x08, 0x08);
+}
+
+#[test]
+fn test_extents_fat12() {
+    call_with_fs(|fs| test_extents(fs, FAT12_IMG), FAT12_IMG)
+}
+
+#[test]
+fn test_extents_fat16() {
+    call_with_fs(|fs| test_extents(fs, FAT16_IMG), FAT16_IMG)
+}
+
+#[test]
+fn test_extents_fat32() {
+    call_with_fs(|fs| test_extents(fs, FAT32_IMG), FAT32_IMG)
+}
+
 fn test_get_dir_by_path(fs: FileSystem) {
     let root_dir = fs.root_dir();
     let dir = root_dir.open_dir("very/long/path/").unwrap();
@@ -228,6 +752,175 @@ fn test_status_flags_fat32() {
     call_with_fs(test_status_flags, FAT32_IMG)
 }
 
+fn test_file_name_to_buf(fs: FileSystem) {
+    let dir = fs.root_dir().open_dir("very-long-dir-name").unwrap();
+    for e in fs.root_dir().iter().chain(dir.iter()) {
+        let e = e.unwrap();
+        let mut buf = [0_u8; 765];
+        assert_eq!(e.file_name_to_buf(&mut buf), e.file_name());
+    }
+    // names are truncated if the buffer is too small
+    let e = dir.iter().map(Result::unwrap).find(|e| !e.is_dir()).unwrap();
+    let mut buf = [0_u8; 9];
+    assert_eq!(e.file_name_to_buf(&mut buf), "very-long");
+}
+
+#[test]
+fn test_file_name_to_buf_fat12() {
+    call_with_fs(test_file_name_to_buf, FAT12_IMG)
+}
+
+#[test]
+fn test_file_name_to_buf_fat16() {
+    call_with_fs(test_file_name_to_buf, FAT16_IMG)
+}
+
+#[test]
+fn test_file_name_to_buf_fat32() {
+    call_with_fs(test_file_name_to_buf, FAT32_IMG)
+}
+
+fn test_stream_position(fs: FileSystem) {
+    let mut file = fs.root_dir().open_file("short.txt").unwrap();
+    assert_eq!(file.stream_position().unwrap(), 0);
+    let mut buf = [0_u8; 5];
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(file.stream_position().unwrap(), 5);
+    file.seek_relative(-3).unwrap();
+    assert_eq!(file.stream_position().unwrap(), 2);
+    file.read_exact(&mut buf[..2]).unwrap();
+    assert_eq!(&buf[..2], &TEST_TEXT.as_bytes()[2..4]);
+    file.seek_relative(10).unwrap();
+    assert_eq!(file.stream_position().unwrap(), 14);
+    assert!(matches!(file.seek_relative(-15), Err(fatfs::Error::InvalidInput)));
+    assert_eq!(file.stream_position().unwrap(), 14);
+    // position beyond the end of the file is kept
+    file.seek_relative(100).unwrap();
+    assert_eq!(file.stream_position().unwrap(), 114);
+}
+
+#[test]
+fn test_stream_position_fat12() {
+    call_with_fs(test_stream_position, FAT12_IMG)
+}
+
+#[test]
+fn test_stream_position_fat32() {
+    call_with_fs(test_stream_position, FAT32_IMG)
+}
+
+fn test_first_cluster(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    for e in root_dir.iter().map(|r| r.unwrap()) {
+        if e.is_file() {
+            let chain_start = e.to_file().cluster_chain().next().map(|r| r.unwrap());
+            assert_eq!(e.first_cluster(), chain_start);
+        }
+    }
+    let very = root_dir
+        .iter()
+        .map(|r| r.unwrap())
+        .find(|e| e.file_name() == "very")
+        .unwrap();
+    let cluster = very.first_cluster().unwrap();
+    assert_ne!(fs.read_fat_entry(cluster).unwrap(), fatfs::FatValue::Free);
+    let entries = very.to_dir().iter().map(|r| r.unwrap()).collect::<Vec<_>>();
+    assert_eq!(entries[0].file_name(), ".");
+    assert_eq!(entries[0].first_cluster(), Some(cluster));
+    // root directory is referenced by cluster 0
+    assert_eq!(entries[1].file_name(), "..");
+    assert_eq!(entries[1].first_cluster(), None);
+}
+
+#[test]
+fn test_first_cluster_fat12() {
+    call_with_fs(test_first_cluster, FAT12_IMG)
+}
+
+#[test]
+fn test_first_cluster_fat32() {
+    call_with_fs(test_first_cluster, FAT32_IMG)
+}
+
+fn test_cluster_sectors(fs: FileSystem) {
+    let bytes_per_sector = u32::from(fs.bytes_per_sector());
+    let sectors_per_cluster = u32::from(fs.sectors_per_cluster());
+    assert_eq!(sectors_per_cluster * bytes_per_sector, fs.cluster_size());
+    let file = fs.root_dir().open_file("long.txt").unwrap();
+    let cluster = file.cluster_chain().next().unwrap().unwrap();
+    let sector = fs.first_sector_of_cluster(cluster);
+    assert_eq!(
+        u64::from(sector) * u64::from(bytes_per_sector),
+        fs.offset_from_cluster(cluster)
+    );
+    assert_eq!(fs.cluster_containing_sector(sector), Some(cluster));
+    assert_eq!(
+        fs.cluster_containing_sector(sector + sectors_per_cluster - 1),
+        Some(cluster)
+    );
+    assert_eq!(
+        fs.cluster_containing_sector(sector + sectors_per_cluster),
+        Some(cluster + 1)
+    );
+    // reserved sectors, FATs and the root directory of FAT12/FAT16 are not a part of any cluster
+    let first_data_sector = fs.first_sector_of_cluster(2);
+    assert_eq!(fs.cluster_containing_sector(0), None);
+    assert_eq!(fs.cluster_containing_sector(first_data_sector - 1), None);
+    assert_eq!(fs.cluster_containing_sector(first_data_sector), Some(2));
+    let last_cluster = fs.stats().unwrap().total_clusters() + 1;
+    let last_sector = fs.first_sector_of_cluster(last_cluster) + sectors_per_cluster - 1;
+    assert_eq!(fs.cluster_containing_sector(last_sector), Some(last_cluster));
+    assert_eq!(fs.cluster_containing_sector(last_sector + 1), None);
+}
+
+#[test]
+fn test_cluster_sectors_fat12() {
+    call_with_fs(test_cluster_sectors, FAT12_IMG)
+}
+
+#[test]
+fn test_cluster_sectors_fat16() {
+    call_with_fs(test_cluster_sectors, FAT16_IMG)
+}
+
+#[test]
+fn test_cluster_sectors_fat32() {
+    call_with_fs(test_cluster_sectors, FAT32_IMG)
+}
+
+fn test_free_entry_slots(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let free_slots = root_dir.free_entry_slots().unwrap();
+    if fs.fat_type() == FatType::Fat32 {
+        assert_eq!(free_slots, None);
+    } else {
+        let used_slots: u64 = root_dir
+            .iter()
+            .map(|e| e.unwrap().entry_span())
+            .map(|(start, end)| (end - start) / 32)
+            .sum();
+        // one more slot is used by the volume label entry which is skipped by the iterator
+        assert_eq!(free_slots, Some(512 - used_slots as usize - 1));
+    }
+    let dir = root_dir.open_dir("very/long/path").unwrap();
+    assert_eq!(dir.free_entry_slots().unwrap(), None);
+}
+
+#[test]
+fn test_free_entry_slots_fat12() {
+    call_with_fs(test_free_entry_slots, FAT12_IMG)
+}
+
+#[test]
+fn test_free_entry_slots_fat16() {
+    call_with_fs(test_free_entry_slots, FAT16_IMG)
+}
+
+#[test]
+fn test_free_entry_slots_fat32() {
+    call_with_fs(test_free_entry_slots, FAT32_IMG)
+}
+
 #[test]
 fn test_stats_fat12() {
     call_with_fs(
@@ -236,6 +929,9 @@ fn test_stats_fat12() {
             assert_eq!(stats.cluster_size(), 512);
             assert_eq!(stats.total_clusters(), 1955); // 1000 * 1024 / 512 = 2000
             assert_eq!(stats.free_clusters(), 1920);
+            assert_eq!(stats.total_space(), 1955 * 512);
+            assert_eq!(stats.free_space(), 1920 * 512);
+            assert_eq!(stats.used_space(), 35 * 512);
         },
         FAT12_IMG,
     )
@@ -249,6 +945,9 @@ fn test_stats_fat16() {
             assert_eq!(stats.cluster_size(), 512);
             assert_eq!(stats.total_clusters(), 4927); // 2500 * 1024 / 512 = 5000
             assert_eq!(stats.free_clusters(), 4892);
+            assert_eq!(stats.total_space(), 4927 * 512);
+            assert_eq!(stats.free_space(), 4892 * 512);
+            assert_eq!(stats.used_space(), 35 * 512);
         },
         FAT16_IMG,
     )
@@ -262,6 +961,9 @@ fn test_stats_fat32() {
             assert_eq!(stats.cluster_size(), 512);
             assert_eq!(stats.total_clusters(), 66922); // 34000 * 1024 / 512 = 68000
             assert_eq!(stats.free_clusters(), 66886);
+            assert_eq!(stats.total_space(), 66922 * 512);
+            assert_eq!(stats.free_space(), 66886 * 512);
+            assert_eq!(stats.used_space(), 36 * 512);
         },
         FAT32_IMG,
     )
@@ -290,3 +992,120 @@ fn test_multi_thread() {
         FAT32_IMG,
     )
 }
+
+#[cfg(feature = "mbr")]
+fn test_mount_mbr_partition(filename: &str) {
+    let _ = env_logger::builder().is_test(true).try_init();
+    const START_LBA: u32 = 8;
+    let volume = fs::read(filename).unwrap();
+    let sector_count = (volume.len() / 512) as u32;
+    let mut disk = vec![0_u8; START_LBA as usize * 512];
+    disk[0x1BE] = 0x80;
+    disk[0x1C2] = 0x06;
+    disk[0x1C6..0x1CA].copy_from_slice(&START_LBA.to_le_bytes());
+    disk[0x1CA..0x1CE].copy_from_slice(&sector_count.to_le_bytes());
+    disk[0x1FE] = 0x55;
+    disk[0x1FF] = 0xAA;
+    disk.extend_from_slice(&volume);
+
+    let mut storage = StdIoWrapper::new(std::io::Cursor::new(disk));
+    let partitions = fatfs::read_mbr_partitions(&mut storage).unwrap();
+    let partition = partitions.iter().find(|p| p.is_fat()).unwrap();
+    assert!(partitions[1..].iter().all(fatfs::PartitionEntry::is_empty));
+    let start = u64::from(partition.start_lba()) * 512;
+    let len = u64::from(partition.sector_count()) * 512;
+    let fs = fatfs::FileSystem::new_in_partition(storage, start, len, FsOptions::new()).unwrap();
+    let mut content = String::new();
+    fs.root_dir()
+        .open_file("short.txt")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, TEST_TEXT);
+}
+
+#[test]
+#[cfg(feature = "mbr")]
+fn test_mount_mbr_partition_fat12() {
+    test_mount_mbr_partition(FAT12_IMG)
+}
+
+#[test]
+#[cfg(feature = "mbr")]
+fn test_mount_mbr_partition_fat16() {
+    test_mount_mbr_partition(FAT16_IMG)
+}
+
+#[test]
+#[cfg(feature = "mbr")]
+fn test_mount_mbr_partition_fat32() {
+    test_mount_mbr_partition(FAT32_IMG)
+}
+
+type MemFileSystem = fatfs::FileSystem<StdIoWrapper<std::io::Cursor<Vec<u8>>>>;
+
+fn mount_in_memory(
+    image: Vec<u8>,
+    options: FsOptions<fatfs::DefaultTimeProvider, fatfs::LossyOemCpConverter>,
+) -> Result<MemFileSystem, fatfs::Error<std::io::Error>> {
+    MemFileSystem::new(std::io::Cursor::new(image), options)
+}
+
+#[test]
+fn test_backup_boot_sector() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let mut image = fs::read(FAT32_IMG).unwrap();
+    // corrupt primary boot sector signature
+    image[510] = 0;
+    assert!(matches!(
+        mount_in_memory(image.clone(), FsOptions::new()),
+        Err(fatfs::Error::CorruptedFileSystem)
+    ));
+    let options = FsOptions::new().use_backup_boot_sector_on_error(true);
+    let fs = mount_in_memory(image.clone(), options).unwrap();
+    assert_eq!(fs.fat_type(), FatType::Fat32);
+    let mut content = String::new();
+    fs.root_dir()
+        .open_file("short.txt")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, TEST_TEXT);
+    drop(fs);
+    // BPB is corrupted too - backup is found in the default location
+    image[11..13].copy_from_slice(&[0, 0]);
+    assert!(mount_in_memory(image.clone(), options).is_ok());
+    // both boot sectors are corrupted
+    image[6 * 512 + 510] = 0;
+    assert!(matches!(
+        mount_in_memory(image, options),
+        Err(fatfs::Error::CorruptedFileSystem)
+    ));
+    // there is no backup boot sector on FAT16
+    let mut image = fs::read(FAT16_IMG).unwrap();
+    image[510] = 0;
+    assert!(matches!(
+        mount_in_memory(image, options),
+        Err(fatfs::Error::CorruptedFileSystem)
+    ));
+}
+
+fn test_check_clean(fs: FileSystem) {
+    let report = fs.check().unwrap();
+    assert!(report.is_clean(), "{:?}", report.problems());
+}
+
+#[test]
+fn test_check_clean_fat12() {
+    call_with_fs(test_check_clean, FAT12_IMG)
+}
+
+#[test]
+fn test_check_clean_fat16() {
+    call_with_fs(test_check_clean, FAT16_IMG)
+}
+
+#[test]
+fn test_check_clean_fat32() {
+    call_with_fs(test_check_clean, FAT32_IMG)
+}
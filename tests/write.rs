@@ -184,6 +184,39 @@ fn test_create_file(fs: FileSystem) {
     }
     // check using create_file with existing directory fails
     assert!(root_dir.create_file("very").is_err());
+    // check creating new file fails if an entry exists
+    assert!(matches!(
+        root_dir.create_new_file("very/long/path/new-file-with-long-name.txt"),
+        Err(fatfs::Error::AlreadyExists)
+    ));
+    assert!(matches!(
+        root_dir.create_new_file("very/long/path/NEW-FI~1.TXT"),
+        Err(fatfs::Error::AlreadyExists)
+    ));
+    assert!(matches!(
+        root_dir.create_new_file("very"),
+        Err(fatfs::Error::AlreadyExists)
+    ));
+    assert!(matches!(
+        root_dir.create_new_file("missing/new.txt"),
+        Err(fatfs::Error::NotFound)
+    ));
+    // check creating new file
+    {
+        let mut file = root_dir.create_new_file("very/long/path/lock").unwrap();
+        file.write_all(TEST_STR2.as_bytes()).unwrap();
+    }
+    assert!(matches!(
+        root_dir.create_new_file("very/long/path/lock"),
+        Err(fatfs::Error::AlreadyExists)
+    ));
+    let mut content = String::new();
+    root_dir
+        .open_file("very/long/path/lock")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, TEST_STR2);
 }
 
 #[test]
@@ -213,6 +246,9 @@ fn test_create_dir(fs: FileSystem) {
         let subdir = root_dir.create_dir("very/long/path/new-dir-with-long-name").unwrap();
         names = subdir.iter().map(|r| r.unwrap().file_name()).collect::<Vec<String>>();
         assert_eq!(names, [".", ".."]);
+        // directory containing only "." and ".." is empty
+        assert!(subdir.is_empty().unwrap());
+        assert!(!parent_dir.is_empty().unwrap());
     }
     // check if new entry is visible in parent
     names = parent_dir
@@ -238,12 +274,35 @@ fn test_create_dir(fs: FileSystem) {
         names = subdir.iter().map(|r| r.unwrap().file_name()).collect::<Vec<String>>();
         assert_eq!(names, [".", "..", "test.txt", "new-dir-with-long-name"]);
     }
-    // check if creating existing directory returns it
+    // check if creating existing directory fails
+    assert!(matches!(root_dir.create_dir("very"), Err(fatfs::Error::AlreadyExists)));
+    assert!(matches!(
+        root_dir.create_dir("very/long/path/new-dir-with-long-name"),
+        Err(fatfs::Error::AlreadyExists)
+    ));
+    // check if opening or creating existing directory returns it
     {
-        let subdir = root_dir.create_dir("very").unwrap();
+        let subdir = root_dir.open_or_create_dir("very").unwrap();
         names = subdir.iter().map(|r| r.unwrap().file_name()).collect::<Vec<String>>();
         assert_eq!(names, [".", "..", "long"]);
     }
+    // check if opening or creating a directory is idempotent
+    {
+        let subdir = root_dir.open_or_create_dir("very/long/new-dir").unwrap();
+        assert!(subdir.is_empty().unwrap());
+        subdir.create_file("file.txt").unwrap();
+        assert!(!subdir.is_empty().unwrap());
+        let subdir = root_dir.open_or_create_dir("very/long/new-dir").unwrap();
+        names = subdir.iter().map(|r| r.unwrap().file_name()).collect::<Vec<String>>();
+        assert_eq!(names, [".", "..", "file.txt"]);
+        names = root_dir
+            .open_dir("very/long")
+            .unwrap()
+            .iter()
+            .map(|r| r.unwrap().file_name())
+            .collect::<Vec<String>>();
+        assert_eq!(names, [".", "..", "path", "new-dir"]);
+    }
     // check short names validity after create_dir
     {
         let subdir = root_dir.create_dir("test").unwrap();
@@ -255,7 +314,23 @@ fn test_create_dir(fs: FileSystem) {
     }
 
     // check using create_dir with existing file fails
-    assert!(root_dir.create_dir("very/long/path/test.txt").is_err());
+    assert!(matches!(
+        root_dir.create_dir("very/long/path/test.txt"),
+        Err(fatfs::Error::AlreadyExists)
+    ));
+    assert!(matches!(
+        root_dir.open_or_create_dir("very/long/path/test.txt"),
+        Err(fatfs::Error::InvalidInput)
+    ));
+    // check intermediate directories are not created
+    assert!(matches!(
+        root_dir.create_dir("missing/dir"),
+        Err(fatfs::Error::NotFound)
+    ));
+    assert!(matches!(
+        root_dir.open_or_create_dir("missing/dir"),
+        Err(fatfs::Error::NotFound)
+    ));
 }
 
 #[test]
@@ -400,3 +475,2158 @@ fn test_multiple_files_in_directory_fat16() {
 fn test_multiple_files_in_directory_fat32() {
     call_with_fs(test_multiple_files_in_directory, FAT32_IMG, 8)
 }
+
+fn test_remove_dir_all(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let stats = fs.stats().unwrap();
+    assert!(root_dir.remove_dir_all("short.txt").is_err());
+    assert!(root_dir.remove_dir_all("very/..").is_err());
+    root_dir.remove_dir_all("very").unwrap();
+    let names = root_dir.iter().map(|r| r.unwrap().file_name()).collect::<Vec<String>>();
+    assert_eq!(names, ["long.txt", "short.txt", "very-long-dir-name"]);
+    // "very", "long", "path" directories and "test.txt" file use one cluster each
+    let new_stats = fs.stats().unwrap();
+    assert_eq!(new_stats.free_clusters(), stats.free_clusters() + 4);
+}
+
+#[test]
+fn test_remove_dir_all_fat12() {
+    call_with_fs(test_remove_dir_all, FAT12_IMG, 9)
+}
+
+#[test]
+fn test_remove_dir_all_fat16() {
+    call_with_fs(test_remove_dir_all, FAT16_IMG, 9)
+}
+
+#[test]
+fn test_remove_dir_all_fat32() {
+    call_with_fs(test_remove_dir_all, FAT32_IMG, 9)
+}
+
+fn test_copy_file(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let stats = fs.stats().unwrap();
+    let src_entry = root_dir
+        .iter()
+        .map(|r| r.unwrap())
+        .find(|e| e.file_name() == "long.txt")
+        .unwrap();
+    root_dir.copy_file("long.txt", &root_dir, "very/long/copy.txt").unwrap();
+    let dst_dir = root_dir.open_dir("very/long").unwrap();
+    let dst_entry = dst_dir
+        .iter()
+        .map(|r| r.unwrap())
+        .find(|e| e.file_name() == "copy.txt")
+        .unwrap();
+    assert_eq!(dst_entry.len(), src_entry.len());
+    assert_eq!(dst_entry.attributes(), src_entry.attributes());
+    assert_eq!(dst_entry.created(), src_entry.created());
+    assert_eq!(dst_entry.modified(), src_entry.modified());
+    let mut src_buf = Vec::new();
+    root_dir
+        .open_file("long.txt")
+        .unwrap()
+        .read_to_end(&mut src_buf)
+        .unwrap();
+    let mut dst_buf = Vec::new();
+    dst_dir
+        .open_file("copy.txt")
+        .unwrap()
+        .read_to_end(&mut dst_buf)
+        .unwrap();
+    assert_eq!(src_buf, dst_buf);
+    let new_stats = fs.stats().unwrap();
+    let used_clusters = (src_entry.len() + u64::from(stats.cluster_size()) - 1) / u64::from(stats.cluster_size());
+    assert_eq!(
+        u64::from(new_stats.free_clusters()),
+        u64::from(stats.free_clusters()) - used_clusters
+    );
+
+    // copying over an existing file or copying a directory fails
+    assert!(root_dir.copy_file("long.txt", &root_dir, "short.txt").is_err());
+    assert!(root_dir.copy_file("very", &root_dir, "very-copy").is_err());
+    assert_eq!(fs.stats().unwrap().free_clusters(), new_stats.free_clusters());
+}
+
+#[test]
+fn test_copy_file_fat12() {
+    call_with_fs(test_copy_file, FAT12_IMG, 10)
+}
+
+#[test]
+fn test_copy_file_fat16() {
+    call_with_fs(test_copy_file, FAT16_IMG, 10)
+}
+
+#[test]
+fn test_copy_file_fat32() {
+    call_with_fs(test_copy_file, FAT32_IMG, 10)
+}
+
+fn test_flush(tmp_path: &str) {
+    // Open filesystem, make change, flush and forget it - changes should be persisted
+    let fs = open_filesystem_rw(tmp_path);
+    {
+        let mut file = fs.root_dir().create_file("flushed.txt").unwrap();
+        file.write_all(TEST_STR.repeat(100).as_bytes()).unwrap();
+    }
+    let free_clusters = fs.stats().unwrap().free_clusters();
+    fs.flush().unwrap();
+    mem::forget(fs);
+    // Check if the file exists and volume is still dirty
+    let fs = open_filesystem_rw(tmp_path);
+    let status_flags = fs.read_status_flags().unwrap();
+    assert!(status_flags.dirty());
+    assert_eq!(fs.stats().unwrap().free_clusters(), free_clusters);
+    let mut content = String::new();
+    fs.root_dir()
+        .open_file("flushed.txt")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, TEST_STR.repeat(100));
+}
+
+#[test]
+fn test_flush_fat12() {
+    call_with_tmp_img(test_flush, FAT12_IMG, 11)
+}
+
+#[test]
+fn test_flush_fat16() {
+    call_with_tmp_img(test_flush, FAT16_IMG, 11)
+}
+
+#[test]
+fn test_flush_fat32() {
+    call_with_tmp_img(test_flush, FAT32_IMG, 11)
+}
+
+fn test_read_only(tmp_path: &str) {
+    let image = fs::read(tmp_path).unwrap();
+    {
+        // storage is opened without write access so any write attempt fails with an I/O error
+        let file = fs::File::open(tmp_path).unwrap();
+        let options = FsOptions::new().read_only(true).update_accessed_date(true);
+        let fs = FileSystem::new(BufStream::new(file), options).unwrap();
+        assert!(fs.is_read_only());
+        let root_dir = fs.root_dir();
+        let mut content = String::new();
+        let mut file = root_dir.open_file("short.txt").unwrap();
+        file.read_to_string(&mut content).unwrap();
+        assert_eq!(content, TEST_STR2);
+
+        let is_read_only_err =
+            |r: Result<(), fatfs::Error<io::Error>>| matches!(r, Err(fatfs::Error::ReadOnlyFileSystem));
+        assert!(is_read_only_err(fatfs::Write::write_all(&mut file, b"x")));
+        assert!(is_read_only_err(file.truncate()));
+        assert!(is_read_only_err(root_dir.create_file("new.txt").map(|_| ())));
+        assert!(is_read_only_err(root_dir.create_dir("new-dir").map(|_| ())));
+        assert!(is_read_only_err(root_dir.remove("short.txt")));
+        assert!(is_read_only_err(root_dir.remove_dir_all("very")));
+        assert!(is_read_only_err(root_dir.rename("short.txt", &root_dir, "moved.txt")));
+        assert!(is_read_only_err(root_dir.copy_file("short.txt", &root_dir, "copy.txt")));
+        drop(file);
+        drop(root_dir);
+        fs.flush().unwrap();
+        fs.unmount().unwrap();
+    }
+    assert!(fs::read(tmp_path).unwrap() == image);
+}
+
+#[test]
+fn test_read_only_fat12() {
+    call_with_tmp_img(test_read_only, FAT12_IMG, 12)
+}
+
+#[test]
+fn test_read_only_fat16() {
+    call_with_tmp_img(test_read_only, FAT16_IMG, 12)
+}
+
+#[test]
+fn test_read_only_fat32() {
+    call_with_tmp_img(test_read_only, FAT32_IMG, 12)
+}
+
+fn test_set_volume_label(tmp_path: &str) {
+    let mut fs = open_filesystem_rw(tmp_path);
+    assert_eq!(fs.volume_label(), "Test!");
+    assert!(matches!(
+        fs.set_volume_label("LABEL TOO LONG"),
+        Err(fatfs::Error::InvalidInput)
+    ));
+    assert_eq!(fs.volume_label(), "Test!");
+    fs.set_volume_label("NEW LABEL").unwrap();
+    assert_eq!(fs.volume_label(), "NEW LABEL");
+    assert_eq!(&fs.read_volume_label_from_root_dir().unwrap().unwrap(), "NEW LABEL");
+    let is_fat32 = fs.fat_type() == fatfs::FatType::Fat32;
+    fs.unmount().unwrap();
+
+    let fs = open_filesystem_rw(tmp_path);
+    assert_eq!(fs.volume_label(), "NEW LABEL");
+    assert_eq!(&fs.read_volume_label_from_root_dir().unwrap().unwrap(), "NEW LABEL");
+    fs.unmount().unwrap();
+    if is_fat32 {
+        // backup boot sector is located in sector 6
+        let image = fs::read(tmp_path).unwrap();
+        assert_eq!(&image[6 * 512 + 0x47..6 * 512 + 0x52], b"NEW LABEL  ");
+    }
+}
+
+#[test]
+fn test_set_volume_label_fat12() {
+    call_with_tmp_img(test_set_volume_label, FAT12_IMG, 13)
+}
+
+#[test]
+fn test_set_volume_label_fat16() {
+    call_with_tmp_img(test_set_volume_label, FAT16_IMG, 13)
+}
+
+#[test]
+fn test_set_volume_label_fat32() {
+    call_with_tmp_img(test_set_volume_label, FAT32_IMG, 13)
+}
+
+fn test_set_volume_id(tmp_path: &str) {
+    let boot_sectors = || {
+        let image = fs::read(tmp_path).unwrap();
+        (image[..512].to_vec(), image[6 * 512..7 * 512].to_vec())
+    };
+    let (old_primary, old_backup) = boot_sectors();
+    let mut fs = open_filesystem_rw(tmp_path);
+    let old_volume_id = fs.volume_id();
+    fs.set_volume_id(0xCAFE_BABE).unwrap();
+    assert_eq!(fs.volume_id(), 0xCAFE_BABE);
+    let is_fat32 = fs.fat_type() == fatfs::FatType::Fat32;
+    fs.unmount().unwrap();
+
+    let fs = open_filesystem_rw(tmp_path);
+    assert_eq!(fs.volume_id(), 0xCAFE_BABE);
+    fs.unmount().unwrap();
+    // only the volume ID field has changed
+    let offset = if is_fat32 { 0x43 } else { 0x27 };
+    let (mut primary, backup) = boot_sectors();
+    assert_eq!(&primary[offset..offset + 4], &0xCAFE_BABE_u32.to_le_bytes());
+    if is_fat32 {
+        assert_eq!(backup, primary);
+    } else {
+        assert_eq!(backup, old_backup);
+    }
+    primary[offset..offset + 4].copy_from_slice(&old_volume_id.to_le_bytes());
+    assert_eq!(primary, old_primary);
+}
+
+#[test]
+fn test_set_volume_id_fat12() {
+    call_with_tmp_img(test_set_volume_id, FAT12_IMG, 29)
+}
+
+#[test]
+fn test_set_volume_id_fat32() {
+    call_with_tmp_img(test_set_volume_id, FAT32_IMG, 29)
+}
+
+fn test_fat_cache(tmp_path: &str) {
+    let (free_clusters, chain) = {
+        let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+        let fs = FileSystem::new(BufStream::new(file), FsOptions::new().fat_cache_sectors(8)).unwrap();
+        let root_dir = fs.root_dir();
+        let mut file1 = root_dir.create_file("frag1.txt").unwrap();
+        let mut file2 = root_dir.create_file("frag2.txt").unwrap();
+        // interleave writes so both files end up fragmented
+        for _ in 0..100 {
+            file1.write_all(TEST_STR.repeat(20).as_bytes()).unwrap();
+            file2.write_all(TEST_STR2.repeat(20).as_bytes()).unwrap();
+        }
+        file2.seek(io::SeekFrom::Start(1000)).unwrap();
+        file2.truncate().unwrap();
+        file1.write_all(TEST_STR.repeat(100).as_bytes()).unwrap();
+        let chain = file1.cluster_chain().collect::<Result<Vec<_>, _>>().unwrap();
+        (fs.stats().unwrap().free_clusters(), chain)
+    };
+    // FAT changes must have been written through to the storage
+    let fs = open_filesystem_rw(tmp_path);
+    let root_dir = fs.root_dir();
+    let mut file1 = root_dir.open_file("frag1.txt").unwrap();
+    assert_eq!(file1.cluster_chain().collect::<Result<Vec<_>, _>>().unwrap(), chain);
+    let mut content = String::new();
+    file1.read_to_string(&mut content).unwrap();
+    assert_eq!(content, TEST_STR.repeat(2100));
+    let mut file2 = root_dir.open_file("frag2.txt").unwrap();
+    let mut content = String::new();
+    file2.read_to_string(&mut content).unwrap();
+    assert_eq!(content, TEST_STR2.repeat(2000)[..1000]);
+    assert_eq!(fs.stats().unwrap().free_clusters(), free_clusters);
+}
+
+#[test]
+fn test_fat_cache_fat12() {
+    call_with_tmp_img(test_fat_cache, FAT12_IMG, 14)
+}
+
+#[test]
+fn test_fat_cache_fat16() {
+    call_with_tmp_img(test_fat_cache, FAT16_IMG, 14)
+}
+
+#[test]
+fn test_fat_cache_fat32() {
+    call_with_tmp_img(test_fat_cache, FAT32_IMG, 14)
+}
+
+fn test_fat_entry(tmp_path: &str) {
+    let free_cluster = {
+        let fs = open_filesystem_rw(tmp_path);
+        let file = fs.root_dir().open_file("long.txt").unwrap();
+        let chain = file.cluster_chain().collect::<Result<Vec<_>, _>>().unwrap();
+        for w in chain.windows(2) {
+            assert_eq!(fs.read_fat_entry(w[0]).unwrap(), fatfs::FatValue::Data(w[1]));
+        }
+        assert_eq!(
+            fs.read_fat_entry(*chain.last().unwrap()).unwrap(),
+            fatfs::FatValue::EndOfChain
+        );
+        assert!(matches!(fs.read_fat_entry(0), Err(fatfs::Error::InvalidInput)));
+        let total_clusters = fs.stats().unwrap().total_clusters();
+        assert!(matches!(
+            fs.read_fat_entry(total_clusters + 2),
+            Err(fatfs::Error::InvalidInput)
+        ));
+        assert!(matches!(
+            fs.write_fat_entry(chain[0], fatfs::FatValue::Data(1)),
+            Err(fatfs::Error::InvalidInput)
+        ));
+
+        let free_cluster = (2..total_clusters + 2)
+            .find(|n| fs.read_fat_entry(*n).unwrap() == fatfs::FatValue::Free)
+            .unwrap();
+        let free_clusters = fs.stats().unwrap().free_clusters();
+        fs.write_fat_entry(free_cluster, fatfs::FatValue::Bad).unwrap();
+        assert_eq!(fs.stats().unwrap().free_clusters(), free_clusters - 1);
+        free_cluster
+    };
+    let fs = open_filesystem_rw(tmp_path);
+    assert_eq!(fs.read_fat_entry(free_cluster).unwrap(), fatfs::FatValue::Bad);
+    let free_clusters = fs.stats().unwrap().free_clusters();
+    fs.write_fat_entry(free_cluster, fatfs::FatValue::Free).unwrap();
+    assert_eq!(fs.stats().unwrap().free_clusters(), free_clusters + 1);
+}
+
+#[test]
+fn test_fat_entry_fat12() {
+    call_with_tmp_img(test_fat_entry, FAT12_IMG, 15)
+}
+
+#[test]
+fn test_fat_entry_fat16() {
+    call_with_tmp_img(test_fat_entry, FAT16_IMG, 15)
+}
+
+#[test]
+fn test_fat_entry_fat32() {
+    call_with_tmp_img(test_fat_entry, FAT32_IMG, 15)
+}
+
+fn test_cluster_chain_loop(tmp_path: &str) {
+    let fs = open_filesystem_rw(tmp_path);
+    let root_dir = fs.root_dir();
+    let dir = root_dir.create_dir("loop").unwrap();
+    // create entries until the first two clusters of the directory are full
+    let mut i = 0;
+    while dir.extents().map(|e| e.unwrap().size).sum::<u32>() < 3 * fs.cluster_size() {
+        dir.create_file(&format!("file with a long name {}", i)).unwrap();
+        i += 1;
+    }
+    let first_cluster_offset = dir.extents().next().unwrap().unwrap().offset;
+    let first_cluster = ((first_cluster_offset - fs.offset_from_cluster(2)) / u64::from(fs.cluster_size())) as u32 + 2;
+    let fatfs::FatValue::Data(second_cluster) = fs.read_fat_entry(first_cluster).unwrap() else {
+        panic!("directory has only one cluster");
+    };
+    // make the second cluster of the directory point to the first one
+    fs.write_fat_entry(second_cluster, fatfs::FatValue::Data(first_cluster))
+        .unwrap();
+    let err = dir.iter().find_map(Result::err).expect("iteration should fail");
+    assert!(matches!(err, fatfs::Error::CorruptedFileSystem));
+}
+
+#[test]
+fn test_cluster_chain_loop_fat12() {
+    call_with_tmp_img(test_cluster_chain_loop, FAT12_IMG, 16)
+}
+
+#[test]
+fn test_cluster_chain_loop_fat16() {
+    call_with_tmp_img(test_cluster_chain_loop, FAT16_IMG, 16)
+}
+
+fn find_entry<'a>(
+    fs: &'a FileSystem,
+    name: &str,
+) -> fatfs::DirEntry<'a, StdIoWrapper<BufStream<fs::File>>, fatfs::DefaultTimeProvider, fatfs::LossyOemCpConverter> {
+    fs.root_dir()
+        .iter()
+        .map(Result::unwrap)
+        .find(|e| e.file_name() == name)
+        .unwrap()
+}
+
+fn test_set_attributes(tmp_path: &str) {
+    use fatfs::FileAttributes;
+    {
+        let fs = open_filesystem_rw(tmp_path);
+        let mut entry = find_entry(&fs, "short.txt");
+        let attrs = entry.attributes() | FileAttributes::READ_ONLY | FileAttributes::HIDDEN;
+        entry.set_attributes(attrs).unwrap();
+        assert_eq!(entry.attributes(), attrs);
+        assert!(matches!(
+            entry.set_attributes(attrs | FileAttributes::DIRECTORY),
+            Err(fatfs::Error::InvalidInput)
+        ));
+        assert!(matches!(
+            entry.set_attributes(attrs | FileAttributes::VOLUME_ID),
+            Err(fatfs::Error::InvalidInput)
+        ));
+
+        let mut entry = find_entry(&fs, "very");
+        entry
+            .set_attributes(FileAttributes::DIRECTORY | FileAttributes::SYSTEM)
+            .unwrap();
+        assert!(matches!(
+            entry.set_attributes(FileAttributes::SYSTEM),
+            Err(fatfs::Error::InvalidInput)
+        ));
+    }
+    let fs = open_filesystem_rw(tmp_path);
+    let mut entry = find_entry(&fs, "short.txt");
+    assert!(entry
+        .attributes()
+        .contains(FileAttributes::READ_ONLY | FileAttributes::HIDDEN));
+    assert_eq!(
+        find_entry(&fs, "very").attributes(),
+        FileAttributes::DIRECTORY | FileAttributes::SYSTEM
+    );
+    entry
+        .set_attributes(entry.attributes() - FileAttributes::READ_ONLY - FileAttributes::HIDDEN)
+        .unwrap();
+    assert!(!find_entry(&fs, "short.txt")
+        .attributes()
+        .intersects(FileAttributes::READ_ONLY | FileAttributes::HIDDEN));
+    // content is not affected
+    let mut content = String::new();
+    entry.to_file().read_to_string(&mut content).unwrap();
+    assert_eq!(content, TEST_STR2);
+}
+
+#[test]
+fn test_set_attributes_fat12() {
+    call_with_tmp_img(test_set_attributes, FAT12_IMG, 17)
+}
+
+#[test]
+fn test_set_attributes_fat16() {
+    call_with_tmp_img(test_set_attributes, FAT16_IMG, 17)
+}
+
+#[test]
+fn test_set_attributes_fat32() {
+    call_with_tmp_img(test_set_attributes, FAT32_IMG, 17)
+}
+
+fn test_read_only_file(fs: FileSystem) {
+    use fatfs::FileAttributes;
+    let mut entry = find_entry(&fs, "short.txt");
+    entry
+        .set_attributes(entry.attributes() | FileAttributes::READ_ONLY)
+        .unwrap();
+    let root_dir = fs.root_dir();
+    let read_content = || {
+        let mut content = String::new();
+        root_dir
+            .open_file("short.txt")
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        content
+    };
+    assert_eq!(read_content(), TEST_STR2);
+    let mut file = root_dir.open_file("short.txt").unwrap();
+    let err = file.write(TEST_STR.as_bytes()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    file.seek(io::SeekFrom::Start(0)).unwrap();
+    assert!(matches!(file.truncate(), Err(fatfs::Error::PermissionDenied)));
+    // opening with create_file does not bypass the check either
+    let mut file = root_dir.create_file("short.txt").unwrap();
+    assert!(matches!(file.truncate(), Err(fatfs::Error::PermissionDenied)));
+    assert_eq!(read_content(), TEST_STR2);
+
+    let mut file = root_dir.open_file_ignore_readonly("short.txt").unwrap();
+    file.truncate().unwrap();
+    file.write_all(TEST_STR.as_bytes()).unwrap();
+    drop(file);
+    assert_eq!(read_content(), TEST_STR);
+    assert!(find_entry(&fs, "short.txt")
+        .attributes()
+        .contains(FileAttributes::READ_ONLY));
+}
+
+#[test]
+fn test_read_only_file_fat12() {
+    call_with_fs(test_read_only_file, FAT12_IMG, 18)
+}
+
+#[test]
+fn test_read_only_file_fat16() {
+    call_with_fs(test_read_only_file, FAT16_IMG, 18)
+}
+
+#[test]
+fn test_read_only_file_fat32() {
+    call_with_fs(test_read_only_file, FAT32_IMG, 18)
+}
+
+fn test_rename_overwrite(fs: FileSystem) {
+    use fatfs::FileAttributes;
+    let root_dir = fs.root_dir();
+    let free_clusters = fs.stats().unwrap().free_clusters();
+    let mut file = root_dir.create_file("new.txt").unwrap();
+    file.write_all(TEST_STR.as_bytes()).unwrap();
+    drop(file);
+
+    // file replaces file
+    root_dir.rename_overwrite("new.txt", &root_dir, "short.txt").unwrap();
+    let names = root_dir.iter().map(|r| r.unwrap().file_name()).collect::<Vec<_>>();
+    assert_eq!(names, ["long.txt", "short.txt", "very", "very-long-dir-name"]);
+    let mut content = String::new();
+    root_dir
+        .open_file("short.txt")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, TEST_STR);
+    // old destination data has been freed
+    assert_eq!(fs.stats().unwrap().free_clusters(), free_clusters);
+
+    // destination does not exist
+    root_dir
+        .rename_overwrite("short.txt", &root_dir, "very/renamed.txt")
+        .unwrap();
+    assert!(!root_dir.exists("short.txt").unwrap());
+    assert!(root_dir.exists("very/renamed.txt").unwrap());
+
+    // type mismatch
+    assert!(matches!(
+        root_dir.rename_overwrite("long.txt", &root_dir, "very"),
+        Err(fatfs::Error::InvalidInput)
+    ));
+    assert!(matches!(
+        root_dir.rename_overwrite("very", &root_dir, "long.txt"),
+        Err(fatfs::Error::InvalidInput)
+    ));
+
+    // read-only destination
+    let mut entry = find_entry(&fs, "long.txt");
+    entry
+        .set_attributes(entry.attributes() | FileAttributes::READ_ONLY)
+        .unwrap();
+    assert!(matches!(
+        root_dir.rename_overwrite("very/renamed.txt", &root_dir, "long.txt"),
+        Err(fatfs::Error::PermissionDenied)
+    ));
+
+    // directory replaces empty directory only
+    assert!(matches!(
+        root_dir.rename_overwrite("very", &root_dir, "very-long-dir-name"),
+        Err(fatfs::Error::DirectoryIsNotEmpty)
+    ));
+    root_dir.create_dir("empty").unwrap();
+    root_dir.rename_overwrite("very", &root_dir, "empty").unwrap();
+    let names = root_dir.iter().map(|r| r.unwrap().file_name()).collect::<Vec<_>>();
+    assert_eq!(names, ["long.txt", "empty", "very-long-dir-name"]);
+    assert!(root_dir.exists("empty/long/path/test.txt").unwrap());
+    assert!(root_dir.exists("empty/renamed.txt").unwrap());
+    assert_eq!(fs.stats().unwrap().free_clusters(), free_clusters);
+}
+
+#[test]
+fn test_rename_overwrite_fat12() {
+    call_with_fs(test_rename_overwrite, FAT12_IMG, 19)
+}
+
+#[test]
+fn test_rename_overwrite_fat16() {
+    call_with_fs(test_rename_overwrite, FAT16_IMG, 19)
+}
+
+#[test]
+fn test_rename_overwrite_fat32() {
+    call_with_fs(test_rename_overwrite, FAT32_IMG, 19)
+}
+
+fn test_create_file_with_short_name(fs: FileSystem) {
+    use fatfs::FileAttributes;
+    let root_dir = fs.root_dir();
+    let mut file = root_dir
+        .create_file_with_short_name("very/Some Long Name.txt", *b"CUSTOM  TXT", FileAttributes::ARCHIVE)
+        .unwrap();
+    file.write_all(TEST_STR.as_bytes()).unwrap();
+    drop(file);
+    let dir = root_dir.open_dir("very").unwrap();
+    let entry = dir
+        .iter()
+        .map(Result::unwrap)
+        .find(|e| e.file_name() == "Some Long Name.txt")
+        .unwrap();
+    assert_eq!(entry.short_file_name(), "CUSTOM.TXT");
+    assert_eq!(entry.attributes(), FileAttributes::ARCHIVE);
+    let mut content = String::new();
+    dir.open_file("custom.txt")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, TEST_STR);
+
+    // short or long name collision
+    assert!(matches!(
+        dir.create_file_with_short_name("other.txt", *b"CUSTOM  TXT", FileAttributes::empty()),
+        Err(fatfs::Error::AlreadyExists)
+    ));
+    assert!(matches!(
+        dir.create_file_with_short_name("some long name.TXT", *b"OTHER   TXT", FileAttributes::empty()),
+        Err(fatfs::Error::AlreadyExists)
+    ));
+    // invalid arguments
+    assert!(matches!(
+        dir.create_file_with_short_name("other.txt", *b"other   txt", FileAttributes::empty()),
+        Err(fatfs::Error::UnsupportedFileNameCharacter)
+    ));
+    assert!(matches!(
+        dir.create_file_with_short_name("other", *b"OTHER      ", FileAttributes::DIRECTORY),
+        Err(fatfs::Error::InvalidInput)
+    ));
+    let names = dir.iter().map(|r| r.unwrap().file_name()).collect::<Vec<_>>();
+    assert_eq!(names, [".", "..", "long", "Some Long Name.txt"]);
+}
+
+#[test]
+fn test_create_file_with_short_name_fat12() {
+    call_with_fs(test_create_file_with_short_name, FAT12_IMG, 20)
+}
+
+#[test]
+fn test_create_file_with_short_name_fat16() {
+    call_with_fs(test_create_file_with_short_name, FAT16_IMG, 20)
+}
+
+#[test]
+fn test_create_file_with_short_name_fat32() {
+    call_with_fs(test_create_file_with_short_name, FAT32_IMG, 20)
+}
+
+fn test_create_lowercase_short_name(tmp_path: &str) {
+    {
+        let fs = open_filesystem_rw(tmp_path);
+        let root_dir = fs.root_dir();
+        root_dir.create_file("readme.txt").unwrap();
+        root_dir.create_file("MAKEFILE").unwrap();
+        root_dir.create_dir("src").unwrap();
+        root_dir.create_file("Mixed.txt").unwrap();
+    }
+    let fs = open_filesystem_rw(tmp_path);
+    let root_dir = fs.root_dir();
+    let entries = root_dir.iter().map(Result::unwrap).collect::<Vec<_>>();
+    let names = entries.iter().map(fatfs::DirEntry::file_name).collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            "long.txt",
+            "short.txt",
+            "very",
+            "very-long-dir-name",
+            "readme.txt",
+            "MAKEFILE",
+            "src",
+            "Mixed.txt"
+        ]
+    );
+    let short_names = entries[4..]
+        .iter()
+        .map(fatfs::DirEntry::short_file_name)
+        .collect::<Vec<_>>();
+    assert_eq!(short_names, ["README.TXT", "MAKEFILE", "SRC", "MIXED.TXT"]);
+    // names that fit 8.3 format except for letter case are stored without LFN entries
+    let has_lfn = entries[4..]
+        .iter()
+        .map(|e| e.long_file_name_as_ucs2_units().is_some())
+        .collect::<Vec<_>>();
+    assert_eq!(has_lfn, [false, false, false, true]);
+    assert!(root_dir.exists("README.TXT").unwrap());
+    assert!(root_dir.exists("src").unwrap());
+}
+
+#[test]
+fn test_create_lowercase_short_name_fat12() {
+    call_with_tmp_img(test_create_lowercase_short_name, FAT12_IMG, 21)
+}
+
+#[test]
+fn test_create_lowercase_short_name_fat16() {
+    call_with_tmp_img(test_create_lowercase_short_name, FAT16_IMG, 21)
+}
+
+#[test]
+fn test_create_lowercase_short_name_fat32() {
+    call_with_tmp_img(test_create_lowercase_short_name, FAT32_IMG, 21)
+}
+
+fn test_create_many_similar_files(fs: FileSystem) {
+    let dir = fs.root_dir().create_dir("reports").unwrap();
+    for i in 0..300 {
+        dir.create_file(&format!("report-{}-final.txt", i)).unwrap();
+    }
+    let entries = dir.iter().map(Result::unwrap).skip(2).collect::<Vec<_>>();
+    assert_eq!(entries.len(), 300);
+    let mut short_names = entries.iter().map(fatfs::DirEntry::short_file_name).collect::<Vec<_>>();
+    short_names.sort();
+    short_names.dedup();
+    assert_eq!(short_names.len(), 300);
+    for i in 0..300 {
+        assert!(dir.exists(&format!("report-{}-final.txt", i)).unwrap());
+    }
+}
+
+#[test]
+fn test_create_many_similar_files_fat12() {
+    call_with_fs(test_create_many_similar_files, FAT12_IMG, 22)
+}
+
+#[test]
+fn test_create_many_similar_files_fat16() {
+    call_with_fs(test_create_many_similar_files, FAT16_IMG, 22)
+}
+
+#[test]
+fn test_create_many_similar_files_fat32() {
+    call_with_fs(test_create_many_similar_files, FAT32_IMG, 22)
+}
+
+fn test_new_in_partition(filename: &str) {
+    let _ = env_logger::builder().is_test(true).try_init();
+    const PREFIX_LEN: usize = 3 * 512;
+    const SUFFIX_LEN: usize = 4096;
+    let mut volume = fs::read(format!("{}/{}", IMG_DIR, filename)).unwrap();
+    // number of sectors preceding the volume
+    volume[28..32].copy_from_slice(&3_u32.to_le_bytes());
+    let mut disk = vec![0xAA_u8; PREFIX_LEN];
+    disk.extend_from_slice(&volume);
+    disk.extend_from_slice(&[0xBB_u8; SUFFIX_LEN]);
+    let (start, len) = (PREFIX_LEN as u64, volume.len() as u64);
+    {
+        let mut cur = io::Cursor::new(&mut disk);
+        // caller does not have to seek the storage
+        cur.seek(io::SeekFrom::End(0)).unwrap();
+        let fs = fatfs::FileSystem::new_in_partition(cur, start, len, FsOptions::new()).unwrap();
+        assert_eq!(fs.volume_label(), "Test!");
+        assert_eq!(u64::from(fs.hidden_sectors()) * 512, start);
+        let root_dir = fs.root_dir();
+        let mut content = String::new();
+        root_dir
+            .open_file("short.txt")
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, TEST_STR2);
+        let mut file = root_dir.create_file("new.txt").unwrap();
+        file.write_all(TEST_STR.as_bytes()).unwrap();
+        drop(file);
+        drop(root_dir);
+        fs.unmount().unwrap();
+    }
+    // data outside of the partition is not modified
+    assert!(disk[..PREFIX_LEN].iter().all(|b| *b == 0xAA));
+    assert!(disk[PREFIX_LEN + volume.len()..].iter().all(|b| *b == 0xBB));
+    let fs = fatfs::FileSystem::new_in_partition(io::Cursor::new(&mut disk), start, len, FsOptions::new()).unwrap();
+    let mut content = String::new();
+    fs.root_dir()
+        .open_file("new.txt")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, TEST_STR);
+    drop(fs);
+    // volume bigger than the partition is rejected
+    let result = fatfs::FileSystem::new_in_partition(io::Cursor::new(&mut disk), start, len - 512, FsOptions::new());
+    assert!(matches!(result, Err(fatfs::Error::CorruptedFileSystem)));
+}
+
+#[test]
+fn test_new_in_partition_fat12() {
+    test_new_in_partition(FAT12_IMG)
+}
+
+#[test]
+fn test_new_in_partition_fat16() {
+    test_new_in_partition(FAT16_IMG)
+}
+
+#[test]
+fn test_new_in_partition_fat32() {
+    test_new_in_partition(FAT32_IMG)
+}
+
+#[cfg(feature = "mbr")]
+fn test_new_in_mbr_partition(filename: &str) {
+    let _ = env_logger::builder().is_test(true).try_init();
+    const START_LBA: u32 = 8;
+    const SUFFIX_LEN: usize = 4096;
+    let mut volume = fs::read(format!("{}/{}", IMG_DIR, filename)).unwrap();
+    // number of sectors preceding the volume
+    volume[28..32].copy_from_slice(&START_LBA.to_le_bytes());
+    let sector_count = (volume.len() / 512) as u32;
+    // MBR with a non-FAT partition placed after the volume in the first entry and the volume in the second one
+    let mut disk = vec![0_u8; START_LBA as usize * 512];
+    disk[0x1C2] = 0x83;
+    disk[0x1C6..0x1CA].copy_from_slice(&(START_LBA + sector_count).to_le_bytes());
+    disk[0x1CA..0x1CE].copy_from_slice(&((SUFFIX_LEN / 512) as u32).to_le_bytes());
+    disk[0x1CE] = 0x80;
+    disk[0x1D2] = 0x06;
+    disk[0x1D6..0x1DA].copy_from_slice(&START_LBA.to_le_bytes());
+    disk[0x1DA..0x1DE].copy_from_slice(&sector_count.to_le_bytes());
+    disk[0x1FE] = 0x55;
+    disk[0x1FF] = 0xAA;
+    let mbr = disk.clone();
+    disk.extend_from_slice(&volume);
+    disk.extend_from_slice(&[0xBB_u8; SUFFIX_LEN]);
+    type DiskFs<'a> = fatfs::FileSystem<StdIoWrapper<io::Cursor<&'a mut Vec<u8>>>>;
+    fn mount(disk: &mut Vec<u8>, index: usize) -> Result<DiskFs<'_>, fatfs::Error<io::Error>> {
+        fatfs::FileSystem::new_in_mbr_partition(io::Cursor::new(disk), index, FsOptions::new())
+    }
+
+    let fs = mount(&mut disk, 1).unwrap();
+    assert_eq!(fs.volume_label(), "Test!");
+    assert_eq!(fs.hidden_sectors(), START_LBA);
+    let root_dir = fs.root_dir();
+    let mut content = String::new();
+    root_dir
+        .open_file("short.txt")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, TEST_STR2);
+    let mut file = root_dir.create_file("new.txt").unwrap();
+    file.write_all(TEST_STR.as_bytes()).unwrap();
+    drop(file);
+    drop(root_dir);
+    fs.unmount().unwrap();
+    // data outside of the partition is not modified
+    assert_eq!(disk[..mbr.len()], mbr);
+    assert!(disk[mbr.len() + volume.len()..].iter().all(|b| *b == 0xBB));
+    let fs = mount(&mut disk, 1).unwrap();
+    let mut content = String::new();
+    fs.root_dir()
+        .open_file("new.txt")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, TEST_STR);
+    drop(fs);
+
+    // partition that does not contain a FAT volume and unused entries are rejected
+    assert!(matches!(mount(&mut disk, 0), Err(fatfs::Error::CorruptedFileSystem)));
+    assert!(matches!(mount(&mut disk, 2), Err(fatfs::Error::InvalidInput)));
+    assert!(matches!(mount(&mut disk, 4), Err(fatfs::Error::InvalidInput)));
+    // hidden sectors not matching the partition start are rejected unless they are not set
+    let hidden_sectors_pos = mbr.len() + 28;
+    disk[hidden_sectors_pos..hidden_sectors_pos + 4].copy_from_slice(&(START_LBA + 1).to_le_bytes());
+    assert!(matches!(mount(&mut disk, 1), Err(fatfs::Error::CorruptedFileSystem)));
+    disk[hidden_sectors_pos..hidden_sectors_pos + 4].copy_from_slice(&0_u32.to_le_bytes());
+    assert!(mount(&mut disk, 1).unwrap().root_dir().exists("new.txt").unwrap());
+}
+
+#[test]
+#[cfg(feature = "mbr")]
+fn test_new_in_mbr_partition_fat12() {
+    test_new_in_mbr_partition(FAT12_IMG)
+}
+
+#[test]
+#[cfg(feature = "mbr")]
+fn test_new_in_mbr_partition_fat16() {
+    test_new_in_mbr_partition(FAT16_IMG)
+}
+
+#[test]
+#[cfg(feature = "mbr")]
+fn test_new_in_mbr_partition_fat32() {
+    test_new_in_mbr_partition(FAT32_IMG)
+}
+
+fn test_recalc_free_clusters(tmp_path: &str) {
+    let free_clusters = {
+        let fs = open_filesystem_rw(tmp_path);
+        fs.stats().unwrap().free_clusters()
+    };
+    // corrupt free cluster count in FSInfo sector (sector 1)
+    {
+        let mut file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+        file.seek(io::SeekFrom::Start(512 + 488)).unwrap();
+        file.write_all(&5_u32.to_le_bytes()).unwrap();
+    }
+    {
+        let fs = open_filesystem_rw(tmp_path);
+        assert_eq!(fs.stats().unwrap().free_clusters(), 5);
+        assert_eq!(fs.recalc_free_clusters().unwrap(), free_clusters);
+        assert_eq!(fs.stats().unwrap().free_clusters(), free_clusters);
+        // FSInfo sector is updated without unmounting
+        mem::forget(fs);
+    }
+    let fs = open_filesystem_rw(tmp_path);
+    assert_eq!(fs.stats().unwrap().free_clusters(), free_clusters);
+}
+
+#[test]
+fn test_recalc_free_clusters_fat32() {
+    call_with_tmp_img(test_recalc_free_clusters, FAT32_IMG, 23)
+}
+
+fn test_distrust_fsinfo(tmp_path: &str) {
+    let free_clusters = {
+        let fs = open_filesystem_rw(tmp_path);
+        fs.stats().unwrap().free_clusters()
+    };
+    // corrupt free cluster count in FSInfo sector (sector 1)
+    {
+        let mut file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+        file.seek(io::SeekFrom::Start(512 + 488)).unwrap();
+        file.write_all(&5_u32.to_le_bytes()).unwrap();
+    }
+    let open_fs = |options| {
+        let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+        FileSystem::new(BufStream::new(file), options).unwrap()
+    };
+    {
+        let fs = open_fs(FsOptions::new().read_only(true));
+        assert_eq!(fs.stats().unwrap().free_clusters(), 5);
+    }
+    {
+        let fs = open_fs(FsOptions::new().read_only(true).distrust_fsinfo(true));
+        assert_eq!(fs.stats().unwrap().free_clusters(), free_clusters);
+    }
+    {
+        // the corrected value is written back when unmounting
+        let fs = open_fs(FsOptions::new().distrust_fsinfo(true));
+        assert_eq!(fs.stats().unwrap().free_clusters(), free_clusters);
+        fs.unmount().unwrap();
+    }
+    let fs = open_fs(FsOptions::new());
+    assert_eq!(fs.stats().unwrap().free_clusters(), free_clusters);
+}
+
+#[test]
+fn test_distrust_fsinfo_fat32() {
+    call_with_tmp_img(test_distrust_fsinfo, FAT32_IMG, 52)
+}
+
+fn test_backup_boot_sector_in_sync(tmp_path: &str) {
+    let read_boot_sectors = || {
+        let image = fs::read(tmp_path).unwrap();
+        (image[..512].to_vec(), image[6 * 512..7 * 512].to_vec())
+    };
+    let mut fs = open_filesystem_rw(tmp_path);
+    fs.root_dir().create_file("abc.txt").unwrap();
+    fs.set_volume_label("NEW LABEL").unwrap();
+    // flush without unmounting so the dirty flag stays set
+    fs.flush().unwrap();
+    mem::forget(fs);
+    let (primary, backup) = read_boot_sectors();
+    // dirty flag is set in both boot sectors
+    assert_eq!(primary[0x41] & 1, 1);
+    assert_eq!(primary, backup);
+    // backup-based recovery sees the same state
+    let mut image = fs::read(tmp_path).unwrap();
+    image[510] = 0;
+    let options = FsOptions::new().use_backup_boot_sector_on_error(true);
+    let fs = fatfs::FileSystem::new(io::Cursor::new(image), options).unwrap();
+    assert!(fs.read_status_flags().unwrap().dirty());
+    assert_eq!(fs.volume_label(), "NEW LABEL");
+}
+
+#[test]
+fn test_backup_boot_sector_in_sync_fat32() {
+    call_with_tmp_img(test_backup_boot_sector_in_sync, FAT32_IMG, 24)
+}
+
+fn test_check(fs: FileSystem) {
+    use fatfs::{FatValue, FsckProblem};
+
+    let dir = fs.root_dir().create_dir("check").unwrap();
+    let size = 3 * fs.cluster_size();
+    let create_file = |name: &str, len: usize| {
+        let mut file = dir.create_file(name).unwrap();
+        file.write_all(&vec![b'a'; len]).unwrap();
+        let chain = file.cluster_chain().collect::<Result<Vec<_>, _>>().unwrap();
+        // file is dropped here so its directory entry is updated
+        chain
+    };
+    let a = create_file("a.txt", size as usize);
+    let b = create_file("b.txt", TEST_STR.len());
+    assert_eq!((a.len(), b.len()), (3, 1));
+    assert_eq!(fs.check().unwrap().problems(), []);
+
+    // allocated cluster without an owner
+    let total_clusters = fs.stats().unwrap().total_clusters();
+    let free_cluster = (2..total_clusters + 2)
+        .find(|n| fs.read_fat_entry(*n).unwrap() == FatValue::Free)
+        .unwrap();
+    fs.write_fat_entry(free_cluster, FatValue::EndOfChain).unwrap();
+    // writes are mirrored to all FAT copies
+    assert_eq!(fs.read_fat_entry_from(1, free_cluster).unwrap(), FatValue::EndOfChain);
+    assert_eq!(
+        fs.check().unwrap().problems(),
+        [FsckProblem::LostChain {
+            first_cluster: free_cluster,
+            clusters: 1
+        }]
+    );
+    fs.write_fat_entry(free_cluster, FatValue::Free).unwrap();
+
+    // chain shorter than the file size - its tail is lost
+    fs.write_fat_entry(a[0], FatValue::EndOfChain).unwrap();
+    assert_eq!(
+        fs.check().unwrap().problems(),
+        [
+            FsckProblem::SizeMismatch {
+                path: "/check/a.txt".into(),
+                size,
+                clusters: 1
+            },
+            FsckProblem::LostChain {
+                first_cluster: a[1],
+                clusters: 2
+            },
+        ]
+    );
+    fs.write_fat_entry(a[0], FatValue::Data(a[1])).unwrap();
+
+    // two files sharing a cluster
+    fs.write_fat_entry(b[0], FatValue::Data(a[1])).unwrap();
+    let report = fs.check().unwrap();
+    assert!(report
+        .problems()
+        .iter()
+        .any(|p| matches!(p, FsckProblem::CrossLinked { cluster, .. } if *cluster == a[1])));
+    fs.write_fat_entry(b[0], FatValue::EndOfChain).unwrap();
+
+    // loop in a chain
+    fs.write_fat_entry(a[2], FatValue::Data(a[0])).unwrap();
+    assert_eq!(
+        fs.check().unwrap().problems(),
+        [FsckProblem::BrokenChain {
+            path: "/check/a.txt".into(),
+            cluster: a[0]
+        }]
+    );
+    fs.write_fat_entry(a[2], FatValue::EndOfChain).unwrap();
+    assert!(fs.check().unwrap().is_clean());
+}
+
+#[test]
+fn test_check_fat12() {
+    call_with_fs(test_check, FAT12_IMG, 25)
+}
+
+#[test]
+fn test_check_fat16() {
+    call_with_fs(test_check, FAT16_IMG, 25)
+}
+
+#[test]
+fn test_check_fat32() {
+    call_with_fs(test_check, FAT32_IMG, 25)
+}
+
+fn test_std_io_copy(fs: FileSystem) {
+    // `File` implements `std::io` traits so it works with generic std code
+    let root_dir = fs.root_dir();
+    let data = TEST_STR.repeat(1000);
+    let mut file = root_dir.create_file("copy.txt").unwrap();
+    let copied = io::copy(&mut io::Cursor::new(data.as_bytes()), &mut file).unwrap();
+    assert_eq!(copied, data.len() as u64);
+    file.seek(io::SeekFrom::Start(0)).unwrap();
+    let mut buf = Vec::new();
+    assert_eq!(io::copy(&mut file, &mut buf).unwrap(), data.len() as u64);
+    assert_eq!(buf, data.as_bytes());
+}
+
+#[test]
+fn test_std_io_copy_fat32() {
+    call_with_fs(test_std_io_copy, FAT32_IMG, 26)
+}
+
+fn test_write_beyond_end(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let cluster_size = fs.cluster_size() as usize;
+    // fill some clusters with data and free them so the gap is written over old data
+    let mut file = root_dir.create_file("old.txt").unwrap();
+    file.write_all(&vec![0xAA; 4 * cluster_size]).unwrap();
+    drop(file);
+    root_dir.remove("old.txt").unwrap();
+
+    let mut file = root_dir.create_file("sparse.txt").unwrap();
+    file.write_all(b"abc").unwrap();
+    file.preallocate(4 * cluster_size as u64).unwrap();
+    // seeking alone does not change the file size
+    assert_eq!(file.seek(io::SeekFrom::Start(10000)).unwrap(), 10000);
+    assert_eq!(file.seek(io::SeekFrom::Current(-1000)).unwrap(), 9000);
+    assert_eq!(file.write(b"").unwrap(), 0);
+    file.flush().unwrap();
+    assert_eq!(root_dir.metadata("sparse.txt").unwrap().len(), 3);
+
+    let gap_end = 2 * cluster_size + 5;
+    assert_eq!(
+        file.seek(io::SeekFrom::End(gap_end as i64 - 3)).unwrap(),
+        gap_end as u64
+    );
+    file.write_all(b"xyz").unwrap();
+    assert_eq!(file.stream_position().unwrap(), gap_end as u64 + 3);
+    file.flush().unwrap();
+    assert_eq!(root_dir.metadata("sparse.txt").unwrap().len(), gap_end as u64 + 3);
+
+    let mut expected = b"abc".to_vec();
+    expected.resize(gap_end, 0);
+    expected.extend_from_slice(b"xyz");
+    let mut buf = Vec::new();
+    file.seek(io::SeekFrom::Start(0)).unwrap();
+    file.read_to_end(&mut buf).unwrap();
+    assert_eq!(buf, expected);
+}
+
+#[test]
+fn test_write_beyond_end_fat12() {
+    call_with_fs(test_write_beyond_end, FAT12_IMG, 27)
+}
+
+#[test]
+fn test_write_beyond_end_fat32() {
+    call_with_fs(test_write_beyond_end, FAT32_IMG, 27)
+}
+
+#[allow(deprecated)]
+fn test_set_invalid_date_time(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let mut file = root_dir.create_file("dates.txt").unwrap();
+    let date_time = fatfs::DateTime::new(fatfs::Date::new(2020, 2, 29), fatfs::Time::new(13, 14, 16, 0));
+    file.set_modified(date_time).unwrap();
+    let mut invalid = date_time;
+    invalid.date.month = 13;
+    assert!(matches!(file.set_modified(invalid), Err(fatfs::Error::InvalidInput)));
+    assert!(matches!(file.set_created(invalid), Err(fatfs::Error::InvalidInput)));
+    assert!(matches!(
+        file.set_accessed(invalid.date),
+        Err(fatfs::Error::InvalidInput)
+    ));
+    invalid = date_time;
+    invalid.time.hour = 25;
+    assert!(matches!(file.set_modified(invalid), Err(fatfs::Error::InvalidInput)));
+    drop(file);
+    // rejected values are not stored
+    assert_eq!(root_dir.metadata("dates.txt").unwrap().modified(), date_time);
+}
+
+#[test]
+fn test_set_invalid_date_time_fat16() {
+    call_with_fs(test_set_invalid_date_time, FAT16_IMG, 28)
+}
+
+fn test_set_times(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let created = fatfs::DateTime::new(fatfs::Date::new(1999, 12, 31), fatfs::Time::new(23, 59, 58, 990));
+    let accessed = fatfs::Date::new(2001, 2, 3);
+    let modified = fatfs::DateTime::new(fatfs::Date::new(2000, 1, 1), fatfs::Time::new(0, 0, 2, 0));
+    let mut file = root_dir.create_file("times.txt").unwrap();
+    file.set_times(Some(created), Some(accessed), Some(modified)).unwrap();
+    // writing and reading does not overwrite timestamps set explicitly
+    file.write_all(TEST_STR.as_bytes()).unwrap();
+    file.rewind().unwrap();
+    file.read_to_end(&mut Vec::new()).unwrap();
+    drop(file);
+    let metadata = root_dir.metadata("times.txt").unwrap();
+    assert_eq!(metadata.created(), created);
+    assert_eq!(metadata.accessed(), accessed);
+    assert_eq!(metadata.modified(), modified);
+
+    // only provided timestamps are changed
+    let mut file = root_dir.open_file("times.txt").unwrap();
+    let new_created = fatfs::DateTime::new(fatfs::Date::new(2010, 5, 6), fatfs::Time::new(7, 8, 10, 0));
+    file.set_times(Some(new_created), None, None).unwrap();
+    let mut invalid = modified;
+    invalid.date.day = 32;
+    assert!(matches!(
+        file.set_times(None, Some(accessed), Some(invalid)),
+        Err(fatfs::Error::InvalidInput)
+    ));
+    drop(file);
+    let metadata = root_dir.metadata("times.txt").unwrap();
+    assert_eq!(metadata.created(), new_created);
+    assert_eq!(metadata.accessed(), accessed);
+    assert_eq!(metadata.modified(), modified);
+}
+
+#[test]
+fn test_set_times_fat16() {
+    call_with_fs(test_set_times, FAT16_IMG, 30)
+}
+
+fn test_disabled_timestamp_updates(tmp_path: &str) {
+    let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+    let options = FsOptions::new().update_timestamps(false).update_accessed_date(true);
+    let fs = FileSystem::new(BufStream::new(file), options).unwrap();
+    let root_dir = fs.root_dir();
+    let before = root_dir.metadata("short.txt").unwrap();
+    let mut file = root_dir.open_file("short.txt").unwrap();
+    file.read_to_end(&mut Vec::new()).unwrap();
+    file.rewind().unwrap();
+    file.truncate().unwrap();
+    file.write_all(TEST_STR.as_bytes()).unwrap();
+    drop(file);
+    let after = root_dir.metadata("short.txt").unwrap();
+    assert_eq!(after.len(), TEST_STR.len() as u64);
+    assert_eq!(after.created(), before.created());
+    assert_eq!(after.accessed(), before.accessed());
+    assert_eq!(after.modified(), before.modified());
+
+    // timestamps can be set explicitly after writing
+    let modified = fatfs::DateTime::new(fatfs::Date::new(2003, 4, 5), fatfs::Time::new(6, 7, 8, 0));
+    let mut file = root_dir.open_file("short.txt").unwrap();
+    file.write_all(TEST_STR2.as_bytes()).unwrap();
+    file.set_times(None, None, Some(modified)).unwrap();
+    drop(file);
+    assert_eq!(root_dir.metadata("short.txt").unwrap().modified(), modified);
+}
+
+#[test]
+fn test_disabled_timestamp_updates_fat16() {
+    call_with_tmp_img(test_disabled_timestamp_updates, FAT16_IMG, 31)
+}
+
+fn test_fat_write_back(tmp_path: &str) {
+    let test_str = TEST_STR.repeat(100);
+    {
+        let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+        let options = FsOptions::new().fat_write_back(true).fat_cache_sectors(2);
+        let fs = FileSystem::new(BufStream::new(file), options).unwrap();
+        let root_dir = fs.root_dir();
+        for i in 0..10 {
+            let mut file = root_dir.create_file(&format!("file{}.txt", i)).unwrap();
+            file.write_all(test_str.as_bytes()).unwrap();
+        }
+        root_dir.remove("file3.txt").unwrap();
+        drop(root_dir);
+        fs.unmount().unwrap();
+    }
+    let fs = open_filesystem_rw(tmp_path);
+    assert!(fs.check().unwrap().is_clean());
+    let root_dir = fs.root_dir();
+    for i in (0..10).filter(|i| *i != 3) {
+        let mut content = String::new();
+        let mut file = root_dir.open_file(&format!("file{}.txt", i)).unwrap();
+        file.read_to_string(&mut content).unwrap();
+        assert_eq!(content, test_str);
+    }
+    assert!(!root_dir.exists("file3.txt").unwrap());
+    drop(root_dir);
+    drop(fs);
+    // both FAT copies are written
+    let image = fs::read(tmp_path).unwrap();
+    let bytes_per_sector = usize::from(u16::from_le_bytes([image[11], image[12]]));
+    let reserved_sectors = usize::from(u16::from_le_bytes([image[14], image[15]]));
+    let sectors_per_fat = usize::from(u16::from_le_bytes([image[22], image[23]]));
+    let fat_size = sectors_per_fat * bytes_per_sector;
+    let fat_start = reserved_sectors * bytes_per_sector;
+    assert_eq!(
+        image[fat_start..fat_start + fat_size],
+        image[fat_start + fat_size..fat_start + 2 * fat_size]
+    );
+}
+
+#[test]
+fn test_fat_write_back_fat16() {
+    call_with_tmp_img(test_fat_write_back, FAT16_IMG, 32)
+}
+
+fn test_short_name_cased(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    for name in ["foo.rs", "bar.RS", "BAZ.rs", "QUX.RS", "Mixed.txt"] {
+        root_dir.create_file(name).unwrap();
+    }
+    let entries = root_dir
+        .iter()
+        .map(|r| r.unwrap())
+        .filter(|e| !e.is_dir())
+        .map(|e| (e.file_name(), e.short_file_name(), e.short_name_cased()))
+        .collect::<Vec<_>>();
+    let expected = [
+        ("foo.rs", "FOO.RS", "foo.rs"),
+        ("bar.RS", "BAR.RS", "bar.RS"),
+        ("BAZ.rs", "BAZ.RS", "BAZ.rs"),
+        ("QUX.RS", "QUX.RS", "QUX.RS"),
+        // mixed case name needs a LFN and the short name has no case flags
+        ("Mixed.txt", "MIXED.TXT", "MIXED.TXT"),
+    ];
+    for (file_name, short_name, cased) in expected {
+        assert!(entries.contains(&(file_name.to_string(), short_name.to_string(), cased.to_string())));
+    }
+}
+
+#[test]
+fn test_short_name_cased_fat16() {
+    call_with_fs(test_short_name_cased, FAT16_IMG, 33)
+}
+
+fn test_iter_modified_since(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let dir = root_dir.create_dir("backup").unwrap();
+    let cutoff = fatfs::DateTime::new(fatfs::Date::new(2020, 6, 15), fatfs::Time::new(12, 0, 0, 0));
+    let older = fatfs::DateTime::new(fatfs::Date::new(2019, 1, 1), fatfs::Time::new(8, 30, 0, 0));
+    let newer = fatfs::DateTime::new(fatfs::Date::new(2020, 6, 15), fatfs::Time::new(12, 0, 2, 0));
+    for (name, modified) in [
+        ("old.txt", older),
+        ("same-as-cutoff.txt", cutoff),
+        ("new-file-with-long-name.txt", newer),
+    ] {
+        let mut file = dir.create_file(name).unwrap();
+        file.set_times(None, None, Some(modified)).unwrap();
+    }
+    dir.create_dir("new-dir").unwrap();
+    let names = dir
+        .iter()
+        .modified_since(cutoff)
+        .map(|r| r.unwrap().file_name())
+        .collect::<Vec<String>>();
+    assert_eq!(names, ["new-file-with-long-name.txt"]);
+    let names = dir
+        .iter()
+        .modified_since(older)
+        .map(|r| r.unwrap().file_name())
+        .collect::<Vec<String>>();
+    assert_eq!(names, ["same-as-cutoff.txt", "new-file-with-long-name.txt"]);
+}
+
+#[test]
+fn test_iter_modified_since_fat32() {
+    call_with_fs(test_iter_modified_since, FAT32_IMG, 34)
+}
+
+fn test_iter_deleted(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let dir = root_dir.create_dir("undelete").unwrap();
+    assert_eq!(dir.iter_deleted().count(), 0);
+    {
+        let mut file = dir.create_file("removed.txt").unwrap();
+        file.write_all(TEST_STR.as_bytes()).unwrap();
+    }
+    dir.create_file("kept.txt").unwrap();
+    dir.remove("removed.txt").unwrap();
+    let deleted = dir.iter_deleted().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(deleted.len(), 1);
+    let entry = &deleted[0];
+    assert_eq!(entry.short_file_name_as_bytes(), b"\xE5EMOVED.TXT");
+    assert_eq!(entry.len(), TEST_STR.len() as u64);
+    assert!(!entry.is_dir());
+    assert!(entry.metadata().is_file());
+    // data of the deleted file is still there
+    let mut file = fs
+        .open_file_from_cluster(entry.first_cluster().unwrap(), Some(entry.len() as u32))
+        .unwrap();
+    let mut buf = String::new();
+    file.read_to_string(&mut buf).unwrap();
+    assert_eq!(buf, TEST_STR);
+}
+
+#[test]
+fn test_iter_deleted_fat16() {
+    call_with_fs(test_iter_deleted, FAT16_IMG, 35)
+}
+
+fn test_open_options(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    // invalid combinations of options
+    assert!(matches!(
+        root_dir.open_options().open("new.txt"),
+        Err(fatfs::Error::InvalidInput)
+    ));
+    assert!(matches!(
+        root_dir.open_options().read(true).create(true).open("new.txt"),
+        Err(fatfs::Error::InvalidInput)
+    ));
+    assert!(matches!(
+        root_dir.open_options().append(true).truncate(true).open("new.txt"),
+        Err(fatfs::Error::InvalidInput)
+    ));
+    assert!(matches!(
+        root_dir.open_options().write(true).open("new.txt"),
+        Err(fatfs::Error::NotFound)
+    ));
+    // create a new file
+    let mut file = root_dir
+        .open_options()
+        .write(true)
+        .create_new(true)
+        .open("new.txt")
+        .unwrap();
+    file.write_all(TEST_STR.as_bytes()).unwrap();
+    assert!(file.read(&mut [0_u8; 1]).is_err());
+    drop(file);
+    assert!(matches!(
+        root_dir.open_options().write(true).create_new(true).open("new.txt"),
+        Err(fatfs::Error::AlreadyExists)
+    ));
+    // read only access
+    let mut file = root_dir.open_options().read(true).open("new.txt").unwrap();
+    let mut buf = String::new();
+    file.read_to_string(&mut buf).unwrap();
+    assert_eq!(buf, TEST_STR);
+    assert_eq!(file.write(b"x").unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+    drop(file);
+    // append mode writes at the end of file regardless of the position
+    let mut file = root_dir.open_options().read(true).append(true).open("new.txt").unwrap();
+    file.write_all(TEST_STR.as_bytes()).unwrap();
+    file.rewind().unwrap();
+    file.write_all(TEST_STR.as_bytes()).unwrap();
+    file.rewind().unwrap();
+    let mut buf = String::new();
+    file.read_to_string(&mut buf).unwrap();
+    assert_eq!(buf, TEST_STR.repeat(3));
+    drop(file);
+    // truncate an existing file
+    let file = root_dir
+        .open_options()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open("new.txt")
+        .unwrap();
+    drop(file);
+    assert_eq!(root_dir.metadata("new.txt").unwrap().len(), 0);
+}
+
+#[test]
+fn test_open_options_fat16() {
+    call_with_fs(test_open_options, FAT16_IMG, 36)
+}
+
+fn test_exclusive_mount(tmp_path: &str) {
+    let open_exclusive = |read_only: bool| {
+        let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+        let options = FsOptions::new().exclusive(true).read_only(read_only);
+        FileSystem::new(BufStream::new(file), options)
+    };
+    let fs = open_exclusive(false).unwrap();
+    // the volume is in use by the first instance
+    assert!(matches!(open_exclusive(false), Err(fatfs::Error::VolumeInUse)));
+    assert!(matches!(open_exclusive(true), Err(fatfs::Error::VolumeInUse)));
+    // non-exclusive mount is still possible
+    let fs2 = open_filesystem_rw(tmp_path);
+    drop(fs2);
+    fs.unmount().unwrap();
+    // read-only exclusive mount does not mark the volume
+    let fs = open_exclusive(true).unwrap();
+    let fs2 = open_exclusive(false).unwrap();
+    fs2.unmount().unwrap();
+    drop(fs);
+}
+
+#[test]
+fn test_exclusive_mount_fat32() {
+    call_with_tmp_img(test_exclusive_mount, FAT32_IMG, 37)
+}
+
+fn test_exclusive_mount_after_crash(tmp_path: &str) {
+    let open_exclusive = || {
+        let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+        FileSystem::new(BufStream::new(file), FsOptions::new().exclusive(true))
+    };
+    // simulate a crash by leaking the mounted file system
+    let fs = open_exclusive().unwrap();
+    fs.root_dir().create_file("new.txt").unwrap();
+    fs.flush().unwrap();
+    mem::forget(fs);
+    // a non-exclusive mount and a clean unmount do not clear the dirty flag
+    let fs = open_filesystem_rw(tmp_path);
+    assert!(fs.read_status_flags().unwrap().dirty());
+    fs.unmount().unwrap();
+    assert!(matches!(open_exclusive(), Err(fatfs::Error::VolumeInUse)));
+    // the flag is cleared explicitly after checking the volume
+    let fs = open_filesystem_rw(tmp_path);
+    assert!(fs.check().expect("check").is_clean());
+    fs.clear_dirty_flag().unwrap();
+    assert!(!fs.read_status_flags().unwrap().dirty());
+    fs.unmount().unwrap();
+    let fs = open_exclusive().unwrap();
+    fs.unmount().unwrap();
+    let fs = open_filesystem_rw(tmp_path);
+    assert!(!fs.read_status_flags().unwrap().dirty());
+}
+
+#[test]
+fn test_exclusive_mount_after_crash_fat16() {
+    call_with_tmp_img(test_exclusive_mount_after_crash, FAT16_IMG, 55)
+}
+
+#[test]
+fn test_exclusive_mount_after_crash_fat32() {
+    call_with_tmp_img(test_exclusive_mount_after_crash, FAT32_IMG, 56)
+}
+
+fn test_zero_freed_clusters(tmp_path: &str) {
+    let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+    let options = FsOptions::new().zero_freed_clusters(true);
+    let fs = FileSystem::new(BufStream::new(file), options).unwrap();
+    let cluster_size = fs.cluster_size() as usize;
+    let read_cluster = |cluster: u32| {
+        let mut buf = Vec::new();
+        fs.open_file_from_cluster(cluster, Some(cluster_size as u32))
+            .unwrap()
+            .read_to_end(&mut buf)
+            .unwrap();
+        buf
+    };
+    let root_dir = fs.root_dir();
+    let mut file = root_dir.create_file("secret.bin").unwrap();
+    file.write_all(&vec![0xAB_u8; cluster_size * 3]).unwrap();
+    let chain = file.cluster_chain().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(chain.len(), 3);
+    // truncating zeroes clusters after the new end of file
+    file.seek(io::SeekFrom::Start(cluster_size as u64 + 10)).unwrap();
+    file.truncate().unwrap();
+    assert_eq!(read_cluster(chain[0]), vec![0xAB_u8; cluster_size]);
+    assert_eq!(read_cluster(chain[1]), vec![0xAB_u8; cluster_size]);
+    assert_eq!(read_cluster(chain[2]), vec![0_u8; cluster_size]);
+    drop(file);
+    // removing zeroes all clusters
+    root_dir.remove("secret.bin").unwrap();
+    assert_eq!(read_cluster(chain[0]), vec![0_u8; cluster_size]);
+    assert_eq!(read_cluster(chain[1]), vec![0_u8; cluster_size]);
+}
+
+#[test]
+fn test_zero_freed_clusters_fat16() {
+    call_with_tmp_img(test_zero_freed_clusters, FAT16_IMG, 38)
+}
+
+fn test_short_name_starting_with_e5(tmp_path: &str) {
+    use fatfs::FileAttributes;
+    let short_name = *b"\xE5IGMA   TXT";
+    {
+        let fs = open_filesystem_rw(tmp_path);
+        let root_dir = fs.root_dir();
+        let mut file = root_dir
+            .create_file_with_short_name("sigma.txt", short_name, FileAttributes::ARCHIVE)
+            .unwrap();
+        file.write_all(TEST_STR.as_bytes()).unwrap();
+        assert!(matches!(
+            root_dir.create_file_with_short_name("other.txt", short_name, FileAttributes::empty()),
+            Err(fatfs::Error::AlreadyExists)
+        ));
+    }
+    let fs = open_filesystem_rw(tmp_path);
+    let root_dir = fs.root_dir();
+    // the entry is not treated as deleted
+    let entry = root_dir
+        .iter()
+        .map(Result::unwrap)
+        .find(|e| e.file_name() == "sigma.txt")
+        .unwrap();
+    assert_eq!(entry.short_file_name_as_bytes(), b"\xE5IGMA.TXT");
+    assert_eq!(entry.len(), TEST_STR.len() as u64);
+    assert_eq!(root_dir.iter_deleted().count(), 0);
+    // 0xE5 is stored as 0x05 on disk
+    let mut raw = fs::File::open(tmp_path).unwrap();
+    let mut first_byte = [0_u8; 1];
+    raw.seek(io::SeekFrom::Start(entry.offset_on_disk())).unwrap();
+    raw.read_exact(&mut first_byte).unwrap();
+    assert_eq!(first_byte, [0x05]);
+    let mut content = String::new();
+    root_dir
+        .open_file("sigma.txt")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, TEST_STR);
+}
+
+#[test]
+fn test_short_name_starting_with_e5_fat32() {
+    call_with_tmp_img(test_short_name_starting_with_e5, FAT32_IMG, 39)
+}
+
+fn test_next_free_cluster_hint(tmp_path: &str) {
+    let hint = {
+        let fs = open_filesystem_rw(tmp_path);
+        let total_clusters = fs.stats().unwrap().total_clusters();
+        let hint = total_clusters / 2;
+        for invalid in [0, 1, total_clusters + 2] {
+            assert!(matches!(
+                fs.set_next_free_cluster_hint(invalid),
+                Err(fatfs::Error::InvalidInput)
+            ));
+        }
+        fs.set_next_free_cluster_hint(hint).unwrap();
+        assert_eq!(fs.next_free_cluster_hint(), Some(hint));
+        let mut file = fs.root_dir().create_file("hinted.txt").unwrap();
+        file.write_all(TEST_STR.as_bytes()).unwrap();
+        assert_eq!(file.cluster_chain().next().unwrap().unwrap(), hint);
+        assert_eq!(fs.next_free_cluster_hint(), Some(hint + 1));
+        drop(file);
+        fs.unmount().unwrap();
+        hint
+    };
+    let fs = open_filesystem_rw(tmp_path);
+    if fs.fat_type() == fatfs::FatType::Fat32 {
+        // the hint is persisted in the FS Information Sector
+        assert_eq!(fs.next_free_cluster_hint(), Some(hint + 1));
+    }
+}
+
+#[test]
+fn test_next_free_cluster_hint_fat16() {
+    call_with_tmp_img(test_next_free_cluster_hint, FAT16_IMG, 40)
+}
+
+#[test]
+fn test_next_free_cluster_hint_fat32() {
+    call_with_tmp_img(test_next_free_cluster_hint, FAT32_IMG, 41)
+}
+
+fn test_iter_fragmented_dir(tmp_path: &str) {
+    let names = (0..100)
+        .map(|i| format!("long file name {}.txt", i))
+        .collect::<Vec<_>>();
+    let positions = {
+        let fs = open_filesystem_rw(tmp_path);
+        let total_clusters = fs.stats().unwrap().total_clusters();
+        let dir = fs.root_dir().create_dir("fragmented").unwrap();
+        for (i, name) in names.iter().enumerate() {
+            // allocate every directory cluster far from the previous one
+            fs.set_next_free_cluster_hint(total_clusters / 2 + (i as u32 % 8) * 16)
+                .unwrap();
+            dir.create_file(name).unwrap();
+        }
+        assert!(dir.extents().count() > 1);
+        let entries = dir
+            .iter()
+            .map(Result::unwrap)
+            .filter(|e| !e.is_dir())
+            .collect::<Vec<_>>();
+        assert_eq!(entries.iter().map(|e| e.file_name()).collect::<Vec<_>>(), names);
+        // an iterator can be cloned in the middle of the read-ahead buffer
+        let mut iter = dir.iter();
+        iter.nth(4).unwrap().unwrap();
+        let rest = iter.clone().map(|e| e.unwrap().file_name()).collect::<Vec<_>>();
+        assert_eq!(rest, iter.map(|e| e.unwrap().file_name()).collect::<Vec<_>>());
+        entries
+            .iter()
+            .map(|e| (e.offset_on_disk(), e.short_file_name_as_bytes().to_vec()))
+            .collect::<Vec<_>>()
+    };
+    let image = fs::read(tmp_path).unwrap();
+    for (pos, short_name) in positions {
+        let raw_name = &image[pos as usize..pos as usize + 11];
+        let (base, ext) = short_name.split_at(short_name.iter().position(|&c| c == b'.').unwrap());
+        assert_eq!(&raw_name[..base.len()], base);
+        assert_eq!(&raw_name[8..8 + ext.len() - 1], &ext[1..]);
+    }
+}
+
+#[test]
+fn test_iter_fragmented_dir_fat16() {
+    call_with_tmp_img(test_iter_fragmented_dir, FAT16_IMG, 42)
+}
+
+#[test]
+fn test_iter_fragmented_dir_fat32() {
+    call_with_tmp_img(test_iter_fragmented_dir, FAT32_IMG, 43)
+}
+
+fn test_rename_within(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let entry = root_dir
+        .iter()
+        .map(Result::unwrap)
+        .find(|e| e.file_name() == "long.txt")
+        .unwrap();
+    let stats = fs.stats().unwrap();
+
+    root_dir
+        .rename_within("long.txt", "renamed long file name.txt")
+        .unwrap();
+    assert!(!root_dir.exists("long.txt").unwrap());
+    let renamed = root_dir
+        .iter()
+        .map(Result::unwrap)
+        .find(|e| e.file_name() == "renamed long file name.txt")
+        .unwrap();
+    let first_cluster = |e: &fatfs::DirEntry<_, _, _>| e.to_file().cluster_chain().next().map(Result::unwrap);
+    assert_eq!(first_cluster(&renamed), first_cluster(&entry));
+    assert_eq!(renamed.len(), entry.len());
+    assert_eq!(renamed.attributes(), entry.attributes());
+    assert_eq!(renamed.created(), entry.created());
+    assert_eq!(renamed.modified(), entry.modified());
+    assert_eq!(renamed.short_file_name_as_bytes(), b"RENAME~1.TXT");
+
+    // short name collision is resolved by generating a different short name
+    root_dir
+        .rename_within("short.txt", "renamed long file name 2.txt")
+        .unwrap();
+    let renamed2 = root_dir
+        .iter()
+        .map(Result::unwrap)
+        .find(|e| e.file_name() == "renamed long file name 2.txt")
+        .unwrap();
+    assert_eq!(renamed2.short_file_name_as_bytes(), b"RENAME~2.TXT");
+    let mut buf = Vec::new();
+    renamed2.to_file().read_to_end(&mut buf).unwrap();
+    assert_eq!(str::from_utf8(&buf).unwrap(), TEST_STR2);
+
+    // directories keep their content
+    root_dir.rename_within("very", "not so very").unwrap();
+    assert!(root_dir.exists("not so very/long/path/test.txt").unwrap());
+
+    assert!(matches!(
+        root_dir.rename_within("not so very", "renamed long file name.txt"),
+        Err(fatfs::Error::AlreadyExists)
+    ));
+    assert!(matches!(
+        root_dir.rename_within("missing.txt", "other.txt"),
+        Err(fatfs::Error::NotFound)
+    ));
+    assert!(matches!(
+        root_dir.rename_within("not so very", "very/moved"),
+        Err(fatfs::Error::UnsupportedFileNameCharacter)
+    ));
+    assert_eq!(fs.stats().unwrap().free_clusters(), stats.free_clusters());
+    let report = fs.check().unwrap();
+    assert!(report.is_clean(), "{:?}", report);
+}
+
+#[test]
+fn test_rename_within_fat12() {
+    call_with_fs(test_rename_within, FAT12_IMG, 44)
+}
+
+#[test]
+fn test_rename_within_fat32() {
+    call_with_fs(test_rename_within, FAT32_IMG, 44)
+}
+
+fn test_timestamp_resolution(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let date_time = fatfs::DateTime::new(fatfs::Date::new(2021, 6, 7), fatfs::Time::new(8, 9, 11, 570));
+    let mut file = root_dir.create_file("resolution.txt").unwrap();
+    file.set_times(Some(date_time), None, Some(date_time)).unwrap();
+    drop(file);
+    let metadata = root_dir.metadata("resolution.txt").unwrap();
+    // creation time keeps hundredths of a second
+    assert_eq!(metadata.created(), date_time);
+    // modification time has no hundredths field so it is rounded down to even seconds
+    assert_eq!(
+        metadata.modified(),
+        fatfs::DateTime::new(fatfs::Date::new(2021, 6, 7), fatfs::Time::new(8, 9, 10, 0))
+    );
+
+    // updating the modification time on write does not change the creation time
+    let mut file = root_dir.open_file("resolution.txt").unwrap();
+    file.write_all(TEST_STR.as_bytes()).unwrap();
+    drop(file);
+    let metadata = root_dir.metadata("resolution.txt").unwrap();
+    assert_eq!(metadata.created(), date_time);
+    assert_eq!(metadata.modified().time.millis, 0);
+    assert_eq!(metadata.modified().time.sec % 2, 0);
+}
+
+#[test]
+fn test_timestamp_resolution_fat16() {
+    call_with_fs(test_timestamp_resolution, FAT16_IMG, 45)
+}
+
+fn test_unmount_into_inner(tmp_path: &str) {
+    let fs = open_filesystem_rw(tmp_path);
+    fs.root_dir()
+        .create_file("first.txt")
+        .unwrap()
+        .write_all(TEST_STR.as_bytes())
+        .unwrap();
+    let storage = fs.unmount_into_inner().unwrap();
+
+    // the storage can be mounted again and the volume has been cleanly unmounted
+    let fs = FileSystem::new(storage, FsOptions::new()).unwrap();
+    assert!(!fs.read_status_flags().unwrap().dirty());
+    let mut buf = Vec::new();
+    fs.root_dir()
+        .open_file("first.txt")
+        .unwrap()
+        .read_to_end(&mut buf)
+        .unwrap();
+    assert_eq!(str::from_utf8(&buf).unwrap(), TEST_STR);
+    fs.root_dir().create_file("second.txt").unwrap();
+    let buf_file = fs.unmount_into_inner().unwrap().into_inner();
+    drop(buf_file);
+
+    let fs = open_filesystem_rw(tmp_path);
+    assert!(fs.root_dir().exists("second.txt").unwrap());
+}
+
+#[test]
+fn test_unmount_into_inner_fat32() {
+    call_with_tmp_img(test_unmount_into_inner, FAT32_IMG, 46)
+}
+
+fn test_raw_sectors(tmp_path: &str) {
+    let sectors = {
+        let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+        let options = FsOptions::new().fat_cache_sectors(8).fat_write_back(true);
+        let fs = FileSystem::new(BufStream::new(file), options).unwrap();
+        let sector_size = usize::from(fs.bytes_per_sector());
+        let mut file = fs.root_dir().create_file("raw.txt").unwrap();
+        file.write_all(TEST_STR.as_bytes()).unwrap();
+        let data_sector = (file.extents().next().unwrap().unwrap().offset / sector_size as u64) as u32;
+        drop(file);
+
+        // reading does not write cached FAT changes back to the storage
+        let mut buf = vec![0_u8; sector_size];
+        fs.read_sector(0, &mut buf).unwrap();
+        let fat_sector = u32::from(u16::from_le_bytes([buf[14], buf[15]]));
+        fs.read_sector(fat_sector, &mut buf).unwrap();
+        let offset = fat_sector as usize * sector_size;
+        assert_ne!(buf, fs::read(tmp_path).unwrap()[offset..offset + sector_size]);
+
+        // data written directly to a sector is visible through the file
+        fs.read_sector(data_sector, &mut buf).unwrap();
+        assert_eq!(&buf[..TEST_STR.len()], TEST_STR.as_bytes());
+        buf[..TEST_STR2.len()].copy_from_slice(TEST_STR2.as_bytes());
+        fs.write_sector(data_sector, &buf).unwrap();
+        let mut content = vec![0_u8; TEST_STR2.len()];
+        fs.root_dir()
+            .open_file("raw.txt")
+            .unwrap()
+            .read_exact(&mut content)
+            .unwrap();
+        assert_eq!(content, TEST_STR2.as_bytes());
+
+        let total_sectors = (fs::metadata(tmp_path).unwrap().len() / sector_size as u64) as u32;
+        assert!(matches!(
+            fs.read_sector(total_sectors, &mut buf),
+            Err(fatfs::Error::InvalidInput)
+        ));
+        assert!(matches!(
+            fs.read_sector(0, &mut buf[..sector_size - 1]),
+            Err(fatfs::Error::InvalidInput)
+        ));
+        assert!(matches!(
+            fs.write_sector(0, &buf[..sector_size - 1]),
+            Err(fatfs::Error::InvalidInput)
+        ));
+
+        // cached FAT changes are visible in sectors read before unmounting (the reserved area is skipped because
+        // the volume dirty flag is cleared when unmounting)
+        fs.read_sector(0, &mut buf).unwrap();
+        let reserved_sectors = u32::from(u16::from_le_bytes([buf[14], buf[15]]));
+        let mut sector_numbers = (reserved_sectors..reserved_sectors + 64).collect::<Vec<_>>();
+        if fs.fat_type() == fatfs::FatType::Fat32 {
+            // FS Information Sector
+            sector_numbers.push(u32::from(u16::from_le_bytes([buf[48], buf[49]])));
+        }
+        sector_numbers
+            .into_iter()
+            .map(|sector| {
+                fs.read_sector(sector, &mut buf).unwrap();
+                (sector as usize, buf.clone())
+            })
+            .collect::<Vec<_>>()
+    };
+    let image = fs::read(tmp_path).unwrap();
+    let sector_size = sectors[0].1.len();
+    for (sector, data) in &sectors {
+        let offset = sector * sector_size;
+        assert_eq!(data, &image[offset..offset + sector_size]);
+    }
+
+    let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+    let fs = FileSystem::new(BufStream::new(file), FsOptions::new().read_only(true)).unwrap();
+    let mut buf = vec![0_u8; sector_size];
+    fs.read_sector(0, &mut buf).unwrap();
+    assert_eq!(buf, &image[..sector_size]);
+    assert!(matches!(
+        fs.write_sector(0, &buf),
+        Err(fatfs::Error::ReadOnlyFileSystem)
+    ));
+}
+
+#[test]
+fn test_raw_sectors_fat16() {
+    call_with_tmp_img(test_raw_sectors, FAT16_IMG, 47)
+}
+
+#[test]
+fn test_raw_sectors_fat32() {
+    call_with_tmp_img(test_raw_sectors, FAT32_IMG, 48)
+}
+
+fn find_short_entry(image: &[u8], name: &[u8; 11]) -> usize {
+    (0..image.len())
+        .step_by(32)
+        .find(|&pos| &image[pos..pos + 11] == name)
+        .expect("short entry")
+}
+
+fn test_total_size(tmp_path: &str) {
+    let fs = open_filesystem_rw(tmp_path);
+    let root_dir = fs.root_dir();
+    let expected = root_dir
+        .walk()
+        .map(|r| r.unwrap().1)
+        .filter(|e| e.is_file())
+        .map(|e| e.len())
+        .sum::<u64>();
+    assert_eq!(root_dir.total_size().unwrap(), expected);
+
+    let outer = root_dir.create_dir("outer").unwrap();
+    outer
+        .create_file("a.txt")
+        .unwrap()
+        .write_all(TEST_STR.as_bytes())
+        .unwrap();
+    let inner = outer.create_dir("inner").unwrap();
+    inner
+        .create_file("b.txt")
+        .unwrap()
+        .write_all(TEST_STR2.as_bytes())
+        .unwrap();
+    inner.create_file("empty.txt").unwrap();
+    let outer_size = (TEST_STR.len() + TEST_STR2.len()) as u64;
+    assert_eq!(inner.total_size().unwrap(), TEST_STR2.len() as u64);
+    assert_eq!(outer.total_size().unwrap(), outer_size);
+    assert_eq!(root_dir.total_size().unwrap(), expected + outer_size);
+    drop((root_dir, outer, inner));
+    fs.unmount().unwrap();
+
+    // make the inner directory entry point to the outer directory so the tree contains a loop
+    let mut image = fs::read(tmp_path).unwrap();
+    let outer_pos = find_short_entry(&image, b"OUTER      ");
+    let inner_pos = find_short_entry(&image, b"INNER      ");
+    image.copy_within(outer_pos + 20..outer_pos + 22, inner_pos + 20);
+    image.copy_within(outer_pos + 26..outer_pos + 28, inner_pos + 26);
+    fs::write(tmp_path, image).unwrap();
+
+    let fs = open_filesystem_rw(tmp_path);
+    let root_dir = fs.root_dir();
+    assert_eq!(root_dir.total_size().unwrap(), expected + TEST_STR.len() as u64);
+    assert_eq!(
+        root_dir.open_dir("outer").unwrap().total_size().unwrap(),
+        TEST_STR.len() as u64
+    );
+}
+
+#[test]
+fn test_total_size_fat12() {
+    call_with_tmp_img(test_total_size, FAT12_IMG, 49)
+}
+
+#[test]
+fn test_total_size_fat32() {
+    call_with_tmp_img(test_total_size, FAT32_IMG, 50)
+}
+
+fn test_walk_corrupted(tmp_path: &str) {
+    let fs = open_filesystem_rw(tmp_path);
+    let outer = fs.root_dir().create_dir("outer").unwrap();
+    outer.create_file("a.txt").unwrap();
+    outer.create_dir("inner").unwrap();
+    drop(outer);
+    fs.unmount().unwrap();
+
+    // make the inner directory entry point to the outer directory so the tree contains a loop
+    let mut image = fs::read(tmp_path).unwrap();
+    let outer_pos = find_short_entry(&image, b"OUTER      ");
+    let inner_pos = find_short_entry(&image, b"INNER      ");
+    image.copy_within(outer_pos + 20..outer_pos + 22, inner_pos + 20);
+    image.copy_within(outer_pos + 26..outer_pos + 28, inner_pos + 26);
+    fs::write(tmp_path, &image).unwrap();
+
+    let fs = open_filesystem_rw(tmp_path);
+    let paths = fs
+        .root_dir()
+        .open_dir("outer")
+        .unwrap()
+        .walk()
+        .map(|r| r.unwrap().0)
+        .collect::<Vec<String>>();
+    assert_eq!(paths, ["a.txt", "inner"]);
+    drop(fs);
+
+    // clear the cluster of the inner directory entry so it would resolve to the root directory
+    for offset in [20, 26] {
+        image[inner_pos + offset..inner_pos + offset + 2].fill(0);
+    }
+    fs::write(tmp_path, &image).unwrap();
+
+    let fs = open_filesystem_rw(tmp_path);
+    let results = fs.root_dir().open_dir("outer").unwrap().walk().collect::<Vec<_>>();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].as_ref().unwrap().0, "a.txt");
+    assert!(matches!(results[1], Err(fatfs::Error::CorruptedFileSystem)));
+}
+
+#[test]
+fn test_walk_corrupted_fat16() {
+    call_with_tmp_img(test_walk_corrupted, FAT16_IMG, 57)
+}
+
+#[test]
+fn test_walk_corrupted_fat32() {
+    call_with_tmp_img(test_walk_corrupted, FAT32_IMG, 58)
+}
+
+#[test]
+fn test_max_file_size() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let max_size = u64::from(u32::MAX);
+    fs::create_dir(TMP_DIR).ok();
+    let tmp_path = format!("{}/{}-big.img", TMP_DIR, 51);
+    {
+        // sparse file - only written sectors take space
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp_path)
+            .unwrap();
+        file.set_len(5 * 1024 * 1024 * 1024).unwrap();
+        let mut storage = StdIoWrapper::from(BufStream::new(file));
+        let opts = fatfs::FormatVolumeOptions::new()
+            .fat_type(fatfs::FatType::Fat32)
+            .bytes_per_cluster(32 * 1024);
+        fatfs::format_volume(&mut storage, opts).unwrap();
+    }
+    let fs = open_filesystem_rw(&tmp_path);
+    // allocate clusters for the maximal file size without writing its data
+    let mut file = fs.root_dir().create_file("big.bin").unwrap();
+    file.preallocate(max_size).unwrap();
+    assert!(matches!(
+        file.preallocate(max_size + 1),
+        Err(fatfs::Error::InvalidInput)
+    ));
+    drop(file);
+
+    // set the file size close to the limit directly in the directory entry
+    let bytes_per_sector = fs.bytes_per_sector();
+    let root_dir_sector = fs.root_dir().extents().next().unwrap().unwrap().offset / u64::from(bytes_per_sector);
+    let mut sector = vec![0_u8; bytes_per_sector.into()];
+    fs.read_sector(root_dir_sector as u32, &mut sector).unwrap();
+    let pos = (0..sector.len())
+        .step_by(32)
+        .find(|&pos| &sector[pos..pos + 11] == b"BIG     BIN")
+        .unwrap();
+    sector[pos + 28..pos + 32].copy_from_slice(&(u32::MAX - 4).to_le_bytes());
+    fs.write_sector(root_dir_sector as u32, &sector).unwrap();
+
+    let mut file = fs.root_dir().open_file("big.bin").unwrap();
+    assert_eq!(file.seek(io::SeekFrom::End(0)).unwrap(), max_size - 4);
+    let err = fatfs::Write::write_all(&mut file, TEST_STR.as_bytes()).unwrap_err();
+    assert!(matches!(err, fatfs::Error::FileTooLarge), "unexpected error {:?}", err);
+    assert_eq!(file.stream_position().unwrap(), max_size);
+    assert_eq!(fatfs::Write::write(&mut file, &[]).unwrap(), 0);
+    assert!(matches!(
+        fatfs::Write::write(&mut file, b"x"),
+        Err(fatfs::Error::FileTooLarge)
+    ));
+    assert!(matches!(
+        fatfs::Seek::seek(&mut file, fatfs::SeekFrom::Current(1)),
+        Err(fatfs::Error::InvalidInput)
+    ));
+    assert!(matches!(
+        fatfs::Seek::seek(&mut file, fatfs::SeekFrom::Start(max_size + 1)),
+        Err(fatfs::Error::InvalidInput)
+    ));
+    assert_eq!(file.seek(io::SeekFrom::Start(max_size)).unwrap(), max_size);
+    drop(file);
+
+    assert_eq!(fs.root_dir().metadata("big.bin").unwrap().len(), max_size);
+    assert!(fs.check().unwrap().is_clean());
+    fs.unmount().unwrap();
+    fs::remove_file(tmp_path).unwrap();
+}
+
+fn test_case_sensitive(tmp_path: &str) {
+    let open_fs = |options| {
+        let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+        FileSystem::new(BufStream::new(file), options).unwrap()
+    };
+    let read_to_string = |mut file: fatfs::File<_, _, _>| {
+        let mut content = String::new();
+        file.read_to_string(&mut content).unwrap();
+        content
+    };
+    {
+        let fs = open_fs(FsOptions::new().case_sensitive(true));
+        let root_dir = fs.root_dir();
+        assert!(matches!(
+            root_dir.open_file("LONG.TXT").err().unwrap(),
+            fatfs::Error::NotFound
+        ));
+        let long_txt = read_to_string(root_dir.open_file("long.txt").unwrap());
+        assert!(!root_dir.exists("VERY/long/path").unwrap());
+        assert!(root_dir.exists("very/long/path").unwrap());
+        // entry differing only by letter case can be created
+        let mut file = root_dir.create_file("LONG.TXT").unwrap();
+        fatfs::Write::write_all(&mut file, TEST_STR2.as_bytes()).unwrap();
+        drop(file);
+        assert_eq!(read_to_string(root_dir.open_file("long.txt").unwrap()), long_txt);
+        assert_eq!(read_to_string(root_dir.open_file("LONG.TXT").unwrap()), TEST_STR2);
+        // short name of an entry with a long name does not match
+        assert!(matches!(
+            root_dir.open_file("LONG~1.TXT").err().unwrap(),
+            fatfs::Error::NotFound
+        ));
+    }
+    // by default letter case is ignored and the first matching entry is used
+    let fs = open_fs(FsOptions::new());
+    let root_dir = fs.root_dir();
+    assert_ne!(read_to_string(root_dir.open_file("LONG.TXT").unwrap()), TEST_STR2);
+    assert_eq!(read_to_string(root_dir.open_file("long~1.txt").unwrap()), TEST_STR2);
+}
+
+#[test]
+fn test_case_sensitive_fat16() {
+    call_with_tmp_img(test_case_sensitive, FAT16_IMG, 53)
+}
+
+fn test_unicode_case_insensitive_lookup(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let mut file = root_dir.create_file("Über.txt").unwrap();
+    fatfs::Write::write_all(&mut file, TEST_STR.as_bytes()).unwrap();
+    drop(file);
+    root_dir.create_dir("Café").unwrap().create_file("Ärger.txt").unwrap();
+    // letter case of non-ASCII letters is ignored too
+    for name in ["ÜBER.TXT", "über.txt", "üBeR.TxT"] {
+        let mut content = String::new();
+        root_dir.open_file(name).unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, TEST_STR);
+    }
+    assert!(root_dir.exists("CAFÉ/ärger.TXT").unwrap());
+    assert_eq!(root_dir.open_dir("café").unwrap().iter().count(), 3);
+    // existing file is opened instead of creating a new entry
+    root_dir.create_file("ÜBER.TXT").unwrap();
+    assert!(matches!(root_dir.create_dir("CAFÉ"), Err(fatfs::Error::AlreadyExists)));
+    let names = root_dir.iter().map(|r| r.unwrap().file_name()).collect::<Vec<String>>();
+    assert_eq!(names.iter().filter(|n| n.as_str() == "Über.txt").count(), 1);
+    assert!(!names.iter().any(|n| n == "ÜBER.TXT"));
+}
+
+#[test]
+fn test_unicode_case_insensitive_lookup_fat32() {
+    call_with_fs(test_unicode_case_insensitive_lookup, FAT32_IMG, 54)
+}